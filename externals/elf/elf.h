@@ -252,7 +252,9 @@ typedef struct
 #define EM_AARCH64	183		/* ARM AARCH64 */
 #define EM_TILEPRO	188		/* Tilera TILEPro */
 #define EM_TILEGX	191		/* Tilera TILE-Gx */
-#define EM_NUM		192
+#define EM_RISCV	243		/* RISC-V */
+#define EM_LOONGARCH	258		/* LoongArch */
+#define EM_NUM		259
 
 /* If it is necessary to assign new unofficial EM_* values, please
    pick large random numbers (0x8523, 0xa7f2, etc.) to minimize the
@@ -2324,6 +2326,8 @@ typedef Elf32_Addr Elf32_Conflict;
 #define R_AARCH64_NONE            0	/* No relocation.  */
 #define R_AARCH64_ABS64         257	/* Direct 64 bit. */
 #define R_AARCH64_ABS32         258	/* Direct 32 bit.  */
+#define R_AARCH64_JUMP26        282	/* Likewise for B instruction.  */
+#define R_AARCH64_CALL26        283	/* Likewise for CALL.  */
 #define R_AARCH64_COPY         1024	/* Copy symbol at runtime.  */
 #define R_AARCH64_GLOB_DAT     1025	/* Create GOT entry.  */
 #define R_AARCH64_JUMP_SLOT    1026	/* Create PLT entry.  */
@@ -2332,6 +2336,7 @@ typedef Elf32_Addr Elf32_Conflict;
 #define R_AARCH64_TLS_DTPREL64 1029	/* Module-relative offset, 64 bit.  */
 #define R_AARCH64_TLS_TPREL64  1030	/* TP-relative offset, 64 bit.  */
 #define R_AARCH64_TLSDESC      1031	/* TLS Descriptor.  */
+#define R_AARCH64_IRELATIVE    1032	/* STT_GNU_IFUNC relocation.  */
 
 /* ARM relocs.  */
 
@@ -2364,6 +2369,9 @@ typedef Elf32_Addr Elf32_Conflict;
 #define R_ARM_GOTPC		25	/* 32 bit PC relative offset to GOT */
 #define R_ARM_GOT32		26	/* 32 bit GOT entry */
 #define R_ARM_PLT32		27	/* 32 bit PLT address */
+#define R_ARM_CALL		28	/* PC relative 24 bit (BL, BLX).  */
+#define R_ARM_JUMP24		29	/* PC relative 24 bit (B, BL<cond>).  */
+#define R_ARM_THM_JUMP24	30	/* PC relative 24 bit (Thumb32 B.W).  */
 #define R_ARM_ALU_PCREL_7_0	32
 #define R_ARM_ALU_PCREL_15_8	33
 #define R_ARM_ALU_PCREL_23_15	34
@@ -2744,8 +2752,10 @@ typedef Elf32_Addr Elf32_Conflict;
 #define R_X86_64_TLSDESC        36	/* TLS descriptor.  */
 #define R_X86_64_IRELATIVE	37	/* Adjust indirectly by program base */
 #define R_X86_64_RELATIVE64	38	/* 64-bit adjust by program base */
+#define R_X86_64_GOTPCRELX	41	/* Relaxable GOTPCREL.  */
+#define R_X86_64_REX_GOTPCRELX	42	/* Relaxable GOTPCREL with REX.  */
 
-#define R_X86_64_NUM		39
+#define R_X86_64_NUM		43
 
 
 /* AM33 relocations.  */
@@ -3074,6 +3084,83 @@ typedef Elf32_Addr Elf32_Conflict;
 #define R_TILEGX_NUM		130
 
 
+/* RISC-V relocations.  */
+#define R_RISCV_NONE			0
+#define R_RISCV_32			1
+#define R_RISCV_64			2
+#define R_RISCV_RELATIVE		3
+#define R_RISCV_COPY			4
+#define R_RISCV_JUMP_SLOT		5
+#define R_RISCV_TLS_DTPMOD32		6
+#define R_RISCV_TLS_DTPMOD64		7
+#define R_RISCV_TLS_DTPREL32		8
+#define R_RISCV_TLS_DTPREL64		9
+#define R_RISCV_TLS_TPREL32		10
+#define R_RISCV_TLS_TPREL64		11
+#define R_RISCV_BRANCH			16
+#define R_RISCV_JAL			17
+#define R_RISCV_CALL			18
+#define R_RISCV_CALL_PLT		19
+#define R_RISCV_GOT_HI20		20
+#define R_RISCV_TLS_GOT_HI20		21
+#define R_RISCV_TLS_GD_HI20		22
+#define R_RISCV_PCREL_HI20		23
+#define R_RISCV_PCREL_LO12_I		24
+#define R_RISCV_PCREL_LO12_S		25
+#define R_RISCV_HI20			26
+#define R_RISCV_LO12_I			27
+#define R_RISCV_LO12_S			28
+#define R_RISCV_TPREL_HI20		29
+#define R_RISCV_TPREL_LO12_I		30
+#define R_RISCV_TPREL_LO12_S		31
+#define R_RISCV_TPREL_ADD		32
+#define R_RISCV_ADD8			33
+#define R_RISCV_ADD16			34
+#define R_RISCV_ADD32			35
+#define R_RISCV_ADD64			36
+#define R_RISCV_SUB8			37
+#define R_RISCV_SUB16			38
+#define R_RISCV_SUB32			39
+#define R_RISCV_SUB64			40
+#define R_RISCV_GNU_VTINHERIT		41
+#define R_RISCV_GNU_VTENTRY		42
+#define R_RISCV_ALIGN			43
+#define R_RISCV_RVC_BRANCH		44
+#define R_RISCV_RVC_JUMP		45
+#define R_RISCV_RVC_LUI			46
+#define R_RISCV_GPREL_I			47
+#define R_RISCV_GPREL_S			48
+#define R_RISCV_TPREL_I			49
+#define R_RISCV_TPREL_S			50
+#define R_RISCV_RELAX			51
+#define R_RISCV_SUB6			52
+#define R_RISCV_SET6			53
+#define R_RISCV_SET8			54
+#define R_RISCV_SET16			55
+#define R_RISCV_SET32			56
+#define R_RISCV_32_PCREL		57
+#define R_RISCV_IRELATIVE		58
+#define R_RISCV_NUM			59
+
+/* LoongArch relocations.  */
+#define R_LARCH_NONE			0
+#define R_LARCH_32			1
+#define R_LARCH_64			2
+#define R_LARCH_RELATIVE		3
+#define R_LARCH_COPY			4
+#define R_LARCH_JUMP_SLOT		5
+#define R_LARCH_TLS_DTPMOD32		6
+#define R_LARCH_TLS_DTPMOD64		7
+#define R_LARCH_TLS_DTPREL32		8
+#define R_LARCH_TLS_DTPREL64		9
+#define R_LARCH_TLS_TPREL32		10
+#define R_LARCH_TLS_TPREL64		11
+#define R_LARCH_IRELATIVE		12
+#define R_LARCH_B16			64
+#define R_LARCH_B21			65
+#define R_LARCH_B26			66
+
+
 //__END_DECLS
 
 #endif	/* elf.h */
@@ -32,8 +32,8 @@ struct dyld_cache_header
 	char		magic[16];				// e.g. "dyld_v0    i386"
 	uint32_t	mappingOffset;			// file offset to first dyld_cache_mapping_info
 	uint32_t	mappingCount;			// number of dyld_cache_mapping_info entries
-	uint32_t	imagesOffset;			// file offset to first dyld_cache_image_info
-	uint32_t	imagesCount;			// number of dyld_cache_image_info entries
+	uint32_t	imagesOffsetOld;		// UNUSED: moved to imagesOffset to prevent older dsc_extarctors from crashing
+	uint32_t	imagesCountOld;			// UNUSED: moved to imagesCount to prevent older dsc_extarctors from crashing
 	uint64_t	dyldBaseAddress;		// base address of dyld when cache was built
 	uint64_t	codeSignatureOffset;	// file offset of code signature blob
 	uint64_t	codeSignatureSize;		// size of code signature blob (zero means to end of file)
@@ -49,6 +49,57 @@ struct dyld_cache_header
 	uint64_t	accelerateInfoSize;		// size of optimization info
 	uint64_t	imagesTextOffset;		// file offset to first dyld_cache_image_text_info
 	uint64_t	imagesTextCount;		// number of dyld_cache_image_text_info entries
+	uint64_t	patchInfoAddr;			// (unslid) address of dyld_cache_patch_info
+	uint64_t	patchInfoSize;			// Size of all of the patch information pointed to via the dyld_cache_patch_info
+	uint64_t	otherImageGroupAddrUnused;	// unused
+	uint64_t	otherImageGroupSizeUnused;	// unused
+	uint64_t	progClosuresAddr;		// (unslid) address of list of program launch closures
+	uint64_t	progClosuresSize;		// size of list of program launch closures
+	uint64_t	progClosuresTrieAddr;	// (unslid) address of trie of indexes into program launch closures
+	uint64_t	progClosuresTrieSize;	// size of trie of indexes into program launch closures
+	uint32_t	platform;				// platform number (macOS=1, etc)
+	uint32_t	formatVersionEtc;		// formatVersion:8, dylibsExpectedOnDisk:1, simulator:1, locallyBuiltCache:1, builtFromChainedFixups:1, padding:20
+	uint64_t	sharedRegionStart;		// base load address of cache if not slid
+	uint64_t	sharedRegionSize;		// overall size required to map the cache and all subCaches, if any
+	uint64_t	maxSlide;				// runtime slide of cache can be between zero and this value
+	uint64_t	dylibsImageArrayAddr;	// (unslid) address of ImageArray for dylibs in this cache
+	uint64_t	dylibsImageArraySize;	// size of ImageArray for dylibs in this cache
+	uint64_t	dylibsTrieAddr;			// (unslid) address of trie of indexes of all cached dylibs
+	uint64_t	dylibsTrieSize;			// size of trie of cached dylib paths
+	uint64_t	otherImageArrayAddr;	// (unslid) address of ImageArray for dylibs and bundles with dlopen closures
+	uint64_t	otherImageArraySize;	// size of ImageArray for dylibs and bundles with dlopen closures
+	uint64_t	otherTrieAddr;			// (unslid) address of trie of indexes of all dylibs and bundles with dlopen closures
+	uint64_t	otherTrieSize;			// size of trie of dylibs and bundles with dlopen closures
+	uint32_t	mappingWithSlideOffset;	// file offset to first dyld_cache_mapping_and_slide_info
+	uint32_t	mappingWithSlideCount;	// number of dyld_cache_mapping_and_slide_info entries
+	uint64_t	dylibsPBLStateArrayAddrUnused;	// unused
+	uint64_t	dylibsPBLSetAddr;		// (unslid) address of PrebuiltLoaderSet of all cached dylibs
+	uint64_t	programsPBLSetPoolAddr;	// (unslid) address of pool of PrebuiltLoaderSet for each program
+	uint64_t	programsPBLSetPoolSize;	// size of pool of PrebuiltLoaderSet for each program
+	uint64_t	programTrieAddr;		// (unslid) address of trie mapping program path to PrebuiltLoaderSet
+	uint32_t	programTrieSize;
+	uint32_t	osVersion;				// OS Version of dylibs in this cache for the main platform
+	uint32_t	altPlatform;			// e.g. iOSMac on macOS
+	uint32_t	altOsVersion;			// e.g. 14.0 for iOSMac
+	uint64_t	swiftOptsOffset;		// VM offset from cache_header* to Swift optimizations header
+	uint64_t	swiftOptsSize;			// size of Swift optimizations header
+	uint32_t	subCacheArrayOffset;	// file offset to first dyld_subcache_entry
+	uint32_t	subCacheArrayCount;		// number of subCache entries
+	uint8_t		symbolFileUUID[16];		// unique value for the shared cache file containing unmapped local symbols
+	uint64_t	rosettaReadOnlyAddr;	// (unslid) address of the start of where Rosetta can add read-only/executable data
+	uint64_t	rosettaReadOnlySize;	// maximum size of the Rosetta read-only/executable region
+	uint64_t	rosettaReadWriteAddr;	// (unslid) address of the start of where Rosetta can add read-write data
+	uint64_t	rosettaReadWriteSize;	// maximum size of the Rosetta read-write region
+	uint32_t	imagesOffset;			// file offset to first dyld_cache_image_info
+	uint32_t	imagesCount;			// number of dyld_cache_image_info entries
+	uint32_t	cacheSubType;			// 0 for development, 1 for production, when cacheType is multi-cache(2)
+	uint32_t	padding2;
+	uint64_t	objcOptsOffset;			// VM offset from cache_header* to ObjC optimizations header
+	uint64_t	objcOptsSize;			// size of ObjC optimizations header
+	uint64_t	cacheAtlasOffset;		// VM offset from cache_header* to embedded cache atlas for process introspection
+	uint64_t	cacheAtlasSize;			// size of embedded cache atlas
+	uint64_t	dynamicDataOffset;		// VM offset from cache_header* to the location of dyld_cache_dynamic_data_header
+	uint64_t	dynamicDataMaxSize;		// maximum size of space reserved from dynamic data
 };
 
 struct dyld_cache_mapping_info {
@@ -59,6 +110,19 @@ struct dyld_cache_mapping_info {
 	uint32_t	initProt;
 };
 
+// Contains the slide info for a given mapping; the mappings are in the same order
+// as the dyld_cache_mapping_info array
+struct dyld_cache_mapping_and_slide_info {
+	uint64_t	address;
+	uint64_t	size;
+	uint64_t	fileOffset;
+	uint64_t	slideInfoFileOffset;
+	uint64_t	slideInfoFileSize;
+	uint64_t	flags;
+	uint32_t	maxProt;
+	uint32_t	initProt;
+};
+
 struct dyld_cache_image_info
 {
 	uint64_t	address;
@@ -227,6 +291,110 @@ struct dyld_cache_slide_info2
 #define DYLD_CACHE_SLIDE_PAGE_ATTR_END			0x8000  // last chain entry for page
 
 
+// The version 3 of the slide info uses a different compression scheme. Since
+// only interior pointers (pointers that point within the cache) are rebased
+// (slid), we know the possible range of the pointers and thus know there are
+// unused bits in each pointer.  We use those bits to form a linked list of
+// locations needing rebasing in each page.
+//
+// Definitions:
+//
+//  pageIndex = (pageAddress - startOfAllDataAddress)/info->page_size
+//  pageStarts[] = info->page_starts (right after the header)
+//
+// There are two cases:
+//
+// 1) pageStarts[pageIndex] == DYLD_CACHE_SLIDE_V3_PAGE_ATTR_NO_REBASE
+//    The page contains no values that need rebasing.
+//
+// 2) otherwise...
+//    All rebase locations are in one linked list. The offset of the first
+//    rebase location in the page is pageStarts[pageIndex].
+//
+// A pointer is one of :
+// {
+//	 uint64_t pointerValue : 51;
+//	 uint64_t offsetToNextPointer : 11;
+//	 uint64_t unused : 1;
+//	 uint64_t authenticated : 1; // = 0;
+// }
+// {
+//	 uint64_t offsetFromSharedCacheBase : 32;
+//	 uint64_t diversityData : 16;
+//	 uint64_t hasAddressDiversity : 1;
+//	 uint64_t key : 2;
+//	 uint64_t offsetToNextPointer : 11;
+//	 uint64_t unused : 1;
+//	 uint64_t authenticated : 1; // = 1;
+// }
+//
+// offsetToNextPointer is in units of 8 bytes.  For unauthenticated pointers,
+// the top 8 bits of the target are stored in bits 43..50 of pointerValue.
+// Authenticated pointers are offsetFromSharedCacheBase + info->auth_value_add.
+//
+struct dyld_cache_slide_info3
+{
+	uint32_t	version;		// currently 3
+	uint32_t	page_size;		// currently 4096 (may also be 16384)
+	uint32_t	page_starts_count;
+	uint64_t	auth_value_add;
+	//uint16_t	page_starts[page_starts_count];
+};
+#define DYLD_CACHE_SLIDE_V3_PAGE_ATTR_NO_REBASE	0xFFFF	// page has no rebasing
+
+
+// The version 4 of the slide info is optimized for 32-bit caches up to 1GB.
+// Since only interior pointers (pointers that point within the cache) are rebased
+// (slid), we know the possible range of the pointers takes 30 bits.  That
+// gives us two bits to use to chain to the next rebase.
+//
+// Definitions:
+//
+//  pageIndex = (pageAddress - startOfAllDataAddress)/info->page_size
+//  pageStarts[] = info + info->page_starts_offset
+//  pageExtras[] = info + info->page_extras_offset
+//  valueMask = ~(info->delta_mask)
+//  deltaShift = __builtin_ctzll(info->delta_mask) - 2
+//
+// There are three cases:
+//
+// 1) pageStarts[pageIndex] == DYLD_CACHE_SLIDE4_PAGE_NO_REBASE
+//    The page contains no values that need rebasing.
+//
+// 2) (pageStarts[pageIndex] & DYLD_CACHE_SLIDE4_PAGE_USE_EXTRA) == 0
+//    All rebase locations are in one linked list. The offset of the first
+//    rebase location in the page is pageStarts[pageIndex] * 4.
+//
+// 3) pageStarts[pageIndex] & DYLD_CACHE_SLIDE4_PAGE_USE_EXTRA
+//	  Multiple chains are needed for all rebase locations in a page.
+//    The pagesExtras array contains 2 or more entries each of which is the
+//    start of a new chain in the page. The first is at:
+//       extrasStartIndex = (pageStarts[pageIndex] & DYLD_CACHE_SLIDE4_PAGE_INDEX)
+//	  The next is at extrasStartIndex+1.  The last is denoted by
+//    having the high bit (DYLD_CACHE_SLIDE4_PAGE_EXTRA_END) of the pageExtras[].
+//
+// Values in the chain which are not pointers (small positive and negative
+// integers) are left alone apart from having the delta masked off.
+//
+struct dyld_cache_slide_info4
+{
+	uint32_t	version;		// currently 4
+	uint32_t	page_size;		// currently 4096 (may also be 16384)
+	uint32_t	page_starts_offset;
+	uint32_t	page_starts_count;
+	uint32_t	page_extras_offset;
+	uint32_t	page_extras_count;
+	uint64_t	delta_mask;		// which (contiguous) set of bits contains the delta to the next rebase location (0xC0000000)
+	uint64_t	value_add;		// base address of cache
+	//uint16_t	page_starts[page_starts_count];
+	//uint16_t	page_extras[page_extras_count];
+};
+#define DYLD_CACHE_SLIDE4_PAGE_NO_REBASE		0xFFFF	// page has no rebasing
+#define DYLD_CACHE_SLIDE4_PAGE_INDEX			0x7FFF	// mask of page_starts[] values
+#define DYLD_CACHE_SLIDE4_PAGE_USE_EXTRA		0x8000	// index is into extras array (not a chain start offset)
+#define DYLD_CACHE_SLIDE4_PAGE_EXTRA_END		0x8000	// last chain entry for page
+
+
 struct dyld_cache_local_symbols_info
 {
 	uint32_t	nlistOffset;		// offset into this chunk of nlist entries
@@ -244,6 +412,26 @@ struct dyld_cache_local_symbols_entry
 	uint32_t	nlistCount;			// number of local symbols for this dylib
 };
 
+struct dyld_cache_local_symbols_entry_64
+{
+	uint64_t	dylibOffset;		// offset in cache buffer of start of dylib
+	uint32_t	nlistStartIndex;	// start index of locals for this dylib
+	uint32_t	nlistCount;			// number of local symbols for this dylib
+};
+
+struct dyld_subcache_entry_v1
+{
+	uint8_t		uuid[16];			// The UUID of the subCache file
+	uint64_t	cacheVMOffset;		// The offset of this subcache from the main cache base address
+};
+
+struct dyld_subcache_entry
+{
+	uint8_t		uuid[16];			// The UUID of the subCache file
+	uint64_t	cacheVMOffset;		// The offset of this subcache from the main cache base address
+	char		fileSuffix[32];		// The file name suffix of the subCache file e.g. ".25.data", ".03.development"
+};
+
 
 
 #define MACOSX_DYLD_SHARED_CACHE_DIR	"/private/var/db/dyld/"
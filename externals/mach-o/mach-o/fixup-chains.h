@@ -0,0 +1,102 @@
+/*
+ * Copyright (c) 2018 Apple Inc. All rights reserved.
+ *
+ * @APPLE_LICENSE_HEADER_START@
+ *
+ * This file contains Original Code and/or Modifications of Original Code
+ * as defined in and that are subject to the Apple Public Source License
+ * Version 2.0 (the 'License'). You may not use this file except in
+ * compliance with the License. Please obtain a copy of the License at
+ * http://www.opensource.apple.com/apsl/ and read it before using this
+ * file.
+ *
+ * The Original Code and all software distributed under the License are
+ * distributed on an 'AS IS' basis, WITHOUT WARRANTY OF ANY KIND, EITHER
+ * EXPRESS OR IMPLIED, AND APPLE HEREBY DISCLAIMS ALL SUCH WARRANTIES,
+ * INCLUDING WITHOUT LIMITATION, ANY WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE, QUIET ENJOYMENT OR NON-INFRINGEMENT.
+ * Please see the License for the specific language governing rights and
+ * limitations under the License.
+ *
+ * @APPLE_LICENSE_HEADER_END@
+ */
+
+#ifndef __MACH_O_FIXUP_CHAINS__
+#define __MACH_O_FIXUP_CHAINS__
+
+#include <stdint.h>
+
+/*
+ * Abridged: the enums are rewritten as #defines, and the bitfield pointer and
+ * import structs are left out (they're decoded by hand).
+ */
+
+// header of the LC_DYLD_CHAINED_FIXUPS payload
+struct dyld_chained_fixups_header
+{
+    uint32_t    fixups_version;    // 0
+    uint32_t    starts_offset;     // offset of dyld_chained_starts_in_image in chain_data
+    uint32_t    imports_offset;    // offset of imports table in chain_data
+    uint32_t    symbols_offset;    // offset of symbol strings in chain_data
+    uint32_t    imports_count;     // number of imported symbol names
+    uint32_t    imports_format;    // DYLD_CHAINED_IMPORT*
+    uint32_t    symbols_format;    // 0 => uncompressed, 1 => zlib compressed
+};
+
+// This struct is embedded in LC_DYLD_CHAINED_FIXUPS payload
+struct dyld_chained_starts_in_image
+{
+    uint32_t    seg_count;
+    uint32_t    seg_info_offset[1];  // each entry is offset into this struct for that segment
+    // followed by pool of dyld_chain_starts_in_segment data
+};
+
+// This struct is embedded in dyld_chain_starts_in_image
+// and passed down to the kernel for page-in linking
+struct dyld_chained_starts_in_segment
+{
+    uint32_t    size;               // size of this (amount kernel needs to copy)
+    uint16_t    page_size;          // 0x1000 or 0x4000
+    uint16_t    pointer_format;     // DYLD_CHAINED_PTR_*
+    uint64_t    segment_offset;     // offset in memory to start of segment
+    uint32_t    max_valid_pointer;  // for 32-bit OS, any value beyond this is not a pointer
+    uint16_t    page_count;         // how many pages are in array
+    uint16_t    page_start[1];      // each entry is offset in each page of first element in chain
+                                    // or DYLD_CHAINED_PTR_START_NONE if no fixups on page
+ // uint16_t    chain_starts[1];    // some 32-bit formats may require multiple starts per page.
+                                    // for those, if high bit is set in page_starts[], then it
+                                    // is index into chain_starts[] which is a list of starts
+                                    // the last of which has the high bit set
+};
+
+#define DYLD_CHAINED_PTR_START_NONE   0xFFFF // used in page_start[] to denote a page with no fixups
+#define DYLD_CHAINED_PTR_START_MULTI  0x8000 // used in page_start[] to denote a page which has multiple starts
+#define DYLD_CHAINED_PTR_START_LAST   0x8000 // used in chain_starts[] to denote last start in list for page
+
+// values for dyld_chained_starts_in_segment.pointer_format
+#define DYLD_CHAINED_PTR_ARM64E                  1 // stride 8, unauth target is vmaddr
+#define DYLD_CHAINED_PTR_64                      2 // target is vmaddr
+#define DYLD_CHAINED_PTR_32                      3
+#define DYLD_CHAINED_PTR_32_CACHE                4
+#define DYLD_CHAINED_PTR_32_FIRMWARE             5
+#define DYLD_CHAINED_PTR_64_OFFSET               6 // target is vm offset
+#define DYLD_CHAINED_PTR_ARM64E_OFFSET           7 // old name
+#define DYLD_CHAINED_PTR_ARM64E_KERNEL           7 // stride 4, unauth target is vm offset
+#define DYLD_CHAINED_PTR_64_KERNEL_CACHE         8
+#define DYLD_CHAINED_PTR_ARM64E_USERLAND         9 // stride 8, unauth target is vm offset
+#define DYLD_CHAINED_PTR_ARM64E_FIRMWARE        10 // stride 4, unauth target is vmaddr
+#define DYLD_CHAINED_PTR_X86_64_KERNEL_CACHE    11 // stride 1, x86_64 kernel caches
+#define DYLD_CHAINED_PTR_ARM64E_USERLAND24      12 // stride 8, unauth target is vm offset, 24-bit bind
+#define DYLD_CHAINED_PTR_ARM64E_SHARED_CACHE    13 // stride 8, regular/auth targets both vm offsets.  Only A keys supported
+#define DYLD_CHAINED_PTR_ARM64E_SEGMENTED       14 // stride 4, rebase offsets use segIndex and segOffset
+
+// values for dyld_chained_fixups_header.imports_format
+#define DYLD_CHAINED_IMPORT           1
+#define DYLD_CHAINED_IMPORT_ADDEND    2
+#define DYLD_CHAINED_IMPORT_ADDEND64  3
+
+// values for dyld_chained_fixups_header.symbols_format
+#define DYLD_CHAINED_SYMBOL_UNCOMPRESSED  0
+#define DYLD_CHAINED_SYMBOL_ZLIB          1
+
+#endif // __MACH_O_FIXUP_CHAINS__
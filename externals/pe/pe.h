@@ -0,0 +1,247 @@
+/*
+ * PE/COFF structures and constants, following the names in the Microsoft PE
+ * format specification (and winnt.h).  Unions are flattened to their most
+ * common member.
+ */
+#ifndef _PE_H
+#define _PE_H
+
+#include "../stdint.h"
+
+#define IMAGE_DOS_SIGNATURE	0x5A4D		/* MZ */
+#define IMAGE_NT_SIGNATURE	0x00004550	/* PE00 */
+
+struct IMAGE_DOS_HEADER {
+	uint16_t	e_magic;
+	uint16_t	e_cblp;
+	uint16_t	e_cp;
+	uint16_t	e_crlc;
+	uint16_t	e_cparhdr;
+	uint16_t	e_minalloc;
+	uint16_t	e_maxalloc;
+	uint16_t	e_ss;
+	uint16_t	e_sp;
+	uint16_t	e_csum;
+	uint16_t	e_ip;
+	uint16_t	e_cs;
+	uint16_t	e_lfarlc;
+	uint16_t	e_ovno;
+	uint16_t	e_res[4];
+	uint16_t	e_oemid;
+	uint16_t	e_oeminfo;
+	uint16_t	e_res2[10];
+	uint32_t	e_lfanew;	/* file offset of the PE signature */
+};
+
+struct IMAGE_FILE_HEADER {
+	uint16_t	Machine;
+	uint16_t	NumberOfSections;
+	uint32_t	TimeDateStamp;
+	uint32_t	PointerToSymbolTable;
+	uint32_t	NumberOfSymbols;
+	uint16_t	SizeOfOptionalHeader;
+	uint16_t	Characteristics;
+};
+
+#define IMAGE_FILE_MACHINE_UNKNOWN	0
+#define IMAGE_FILE_MACHINE_I386		0x014c
+#define IMAGE_FILE_MACHINE_R3000	0x0162
+#define IMAGE_FILE_MACHINE_R4000	0x0166
+#define IMAGE_FILE_MACHINE_R10000	0x0168
+#define IMAGE_FILE_MACHINE_WCEMIPSV2	0x0169
+#define IMAGE_FILE_MACHINE_SH3		0x01a2
+#define IMAGE_FILE_MACHINE_SH4		0x01a6
+#define IMAGE_FILE_MACHINE_ARM		0x01c0
+#define IMAGE_FILE_MACHINE_THUMB	0x01c2
+#define IMAGE_FILE_MACHINE_ARMNT	0x01c4
+#define IMAGE_FILE_MACHINE_POWERPC	0x01f0
+#define IMAGE_FILE_MACHINE_POWERPCFP	0x01f1
+#define IMAGE_FILE_MACHINE_IA64		0x0200
+#define IMAGE_FILE_MACHINE_MIPS16	0x0266
+#define IMAGE_FILE_MACHINE_MIPSFPU	0x0366
+#define IMAGE_FILE_MACHINE_MIPSFPU16	0x0466
+#define IMAGE_FILE_MACHINE_EBC		0x0ebc
+#define IMAGE_FILE_MACHINE_RISCV32	0x5032
+#define IMAGE_FILE_MACHINE_RISCV64	0x5064
+#define IMAGE_FILE_MACHINE_AMD64	0x8664
+#define IMAGE_FILE_MACHINE_ARM64	0xaa64
+
+#define IMAGE_FILE_RELOCS_STRIPPED	0x0001
+#define IMAGE_FILE_EXECUTABLE_IMAGE	0x0002
+#define IMAGE_FILE_LARGE_ADDRESS_AWARE	0x0020
+#define IMAGE_FILE_32BIT_MACHINE	0x0100
+#define IMAGE_FILE_DEBUG_STRIPPED	0x0200
+#define IMAGE_FILE_SYSTEM		0x1000
+#define IMAGE_FILE_DLL			0x2000
+
+struct IMAGE_DATA_DIRECTORY {
+	uint32_t	VirtualAddress;
+	uint32_t	Size;
+};
+
+#define IMAGE_NUMBEROF_DIRECTORY_ENTRIES	16
+
+#define IMAGE_DIRECTORY_ENTRY_EXPORT		0
+#define IMAGE_DIRECTORY_ENTRY_IMPORT		1
+#define IMAGE_DIRECTORY_ENTRY_RESOURCE		2
+#define IMAGE_DIRECTORY_ENTRY_EXCEPTION		3
+#define IMAGE_DIRECTORY_ENTRY_SECURITY		4
+#define IMAGE_DIRECTORY_ENTRY_BASERELOC		5
+#define IMAGE_DIRECTORY_ENTRY_DEBUG		6
+#define IMAGE_DIRECTORY_ENTRY_ARCHITECTURE	7
+#define IMAGE_DIRECTORY_ENTRY_GLOBALPTR		8
+#define IMAGE_DIRECTORY_ENTRY_TLS		9
+#define IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG	10
+#define IMAGE_DIRECTORY_ENTRY_BOUND_IMPORT	11
+#define IMAGE_DIRECTORY_ENTRY_IAT		12
+#define IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT	13
+#define IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR	14
+
+#define IMAGE_NT_OPTIONAL_HDR32_MAGIC	0x10b
+#define IMAGE_NT_OPTIONAL_HDR64_MAGIC	0x20b
+
+/* the data directories follow each of these; there are NumberOfRvaAndSizes of them */
+struct IMAGE_OPTIONAL_HEADER32 {
+	uint16_t	Magic;
+	uint8_t		MajorLinkerVersion;
+	uint8_t		MinorLinkerVersion;
+	uint32_t	SizeOfCode;
+	uint32_t	SizeOfInitializedData;
+	uint32_t	SizeOfUninitializedData;
+	uint32_t	AddressOfEntryPoint;
+	uint32_t	BaseOfCode;
+	uint32_t	BaseOfData;
+	uint32_t	ImageBase;
+	uint32_t	SectionAlignment;
+	uint32_t	FileAlignment;
+	uint16_t	MajorOperatingSystemVersion;
+	uint16_t	MinorOperatingSystemVersion;
+	uint16_t	MajorImageVersion;
+	uint16_t	MinorImageVersion;
+	uint16_t	MajorSubsystemVersion;
+	uint16_t	MinorSubsystemVersion;
+	uint32_t	Win32VersionValue;
+	uint32_t	SizeOfImage;
+	uint32_t	SizeOfHeaders;
+	uint32_t	CheckSum;
+	uint16_t	Subsystem;
+	uint16_t	DllCharacteristics;
+	uint32_t	SizeOfStackReserve;
+	uint32_t	SizeOfStackCommit;
+	uint32_t	SizeOfHeapReserve;
+	uint32_t	SizeOfHeapCommit;
+	uint32_t	LoaderFlags;
+	uint32_t	NumberOfRvaAndSizes;
+};
+
+struct IMAGE_OPTIONAL_HEADER64 {
+	uint16_t	Magic;
+	uint8_t		MajorLinkerVersion;
+	uint8_t		MinorLinkerVersion;
+	uint32_t	SizeOfCode;
+	uint32_t	SizeOfInitializedData;
+	uint32_t	SizeOfUninitializedData;
+	uint32_t	AddressOfEntryPoint;
+	uint32_t	BaseOfCode;
+	uint64_t	ImageBase;
+	uint32_t	SectionAlignment;
+	uint32_t	FileAlignment;
+	uint16_t	MajorOperatingSystemVersion;
+	uint16_t	MinorOperatingSystemVersion;
+	uint16_t	MajorImageVersion;
+	uint16_t	MinorImageVersion;
+	uint16_t	MajorSubsystemVersion;
+	uint16_t	MinorSubsystemVersion;
+	uint32_t	Win32VersionValue;
+	uint32_t	SizeOfImage;
+	uint32_t	SizeOfHeaders;
+	uint32_t	CheckSum;
+	uint16_t	Subsystem;
+	uint16_t	DllCharacteristics;
+	uint64_t	SizeOfStackReserve;
+	uint64_t	SizeOfStackCommit;
+	uint64_t	SizeOfHeapReserve;
+	uint64_t	SizeOfHeapCommit;
+	uint32_t	LoaderFlags;
+	uint32_t	NumberOfRvaAndSizes;
+};
+
+#define IMAGE_SIZEOF_SHORT_NAME	8
+
+struct IMAGE_SECTION_HEADER {
+	uint8_t		Name[IMAGE_SIZEOF_SHORT_NAME];
+	uint32_t	VirtualSize;	/* Misc.VirtualSize */
+	uint32_t	VirtualAddress;
+	uint32_t	SizeOfRawData;
+	uint32_t	PointerToRawData;
+	uint32_t	PointerToRelocations;
+	uint32_t	PointerToLinenumbers;
+	uint16_t	NumberOfRelocations;
+	uint16_t	NumberOfLinenumbers;
+	uint32_t	Characteristics;
+};
+
+#define IMAGE_SCN_CNT_CODE			0x00000020
+#define IMAGE_SCN_CNT_INITIALIZED_DATA		0x00000040
+#define IMAGE_SCN_CNT_UNINITIALIZED_DATA	0x00000080
+#define IMAGE_SCN_MEM_DISCARDABLE		0x02000000
+#define IMAGE_SCN_MEM_SHARED			0x10000000
+#define IMAGE_SCN_MEM_EXECUTE			0x20000000
+#define IMAGE_SCN_MEM_READ			0x40000000
+#define IMAGE_SCN_MEM_WRITE			0x80000000
+
+struct IMAGE_EXPORT_DIRECTORY {
+	uint32_t	Characteristics;
+	uint32_t	TimeDateStamp;
+	uint16_t	MajorVersion;
+	uint16_t	MinorVersion;
+	uint32_t	Name;
+	uint32_t	Base;
+	uint32_t	NumberOfFunctions;
+	uint32_t	NumberOfNames;
+	uint32_t	AddressOfFunctions;	/* RVA from base of image */
+	uint32_t	AddressOfNames;		/* RVA from base of image */
+	uint32_t	AddressOfNameOrdinals;	/* RVA from base of image */
+};
+
+struct IMAGE_IMPORT_DESCRIPTOR {
+	uint32_t	OriginalFirstThunk;	/* RVA to original unbound IAT (the INT) */
+	uint32_t	TimeDateStamp;
+	uint32_t	ForwarderChain;
+	uint32_t	Name;
+	uint32_t	FirstThunk;		/* RVA to IAT */
+};
+
+/* the top bit of a thunk; otherwise it's the RVA of an IMAGE_IMPORT_BY_NAME (a 16-bit hint followed by the name) */
+#define IMAGE_ORDINAL_FLAG32	0x80000000
+
+struct IMAGE_DELAYLOAD_DESCRIPTOR {
+	uint32_t	Attributes;		/* bit 0: RvaBased; otherwise the rest are VAs */
+	uint32_t	DllNameRVA;
+	uint32_t	ModuleHandleRVA;
+	uint32_t	ImportAddressTableRVA;
+	uint32_t	ImportNameTableRVA;
+	uint32_t	BoundImportAddressTableRVA;
+	uint32_t	UnloadInformationTableRVA;
+	uint32_t	TimeDateStamp;
+};
+
+#define IMAGE_DELAYLOAD_RVA_BASED	0x1
+
+struct IMAGE_BASE_RELOCATION {
+	uint32_t	VirtualAddress;
+	uint32_t	SizeOfBlock;
+	/* uint16_t TypeOffset[1]; */
+};
+
+#define IMAGE_REL_BASED_ABSOLUTE	0
+#define IMAGE_REL_BASED_HIGH		1
+#define IMAGE_REL_BASED_LOW		2
+#define IMAGE_REL_BASED_HIGHLOW		3
+#define IMAGE_REL_BASED_HIGHADJ		4
+#define IMAGE_REL_BASED_MIPS_JMPADDR	5
+#define IMAGE_REL_BASED_ARM_MOV32	5
+#define IMAGE_REL_BASED_THUMB_MOV32	7
+#define IMAGE_REL_BASED_DIR64		10
+
+#endif
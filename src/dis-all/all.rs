@@ -3,6 +3,7 @@ extern crate dis;
 extern crate dis_llvmdis as llvmdis;
 #[cfg(feature = "use_llvm")]
 extern crate dis_gendis as gendis;
+extern crate dis_simpledis as simpledis;
 use std::marker::PhantomData;
 use dis::{DisassemblerFamily, DisassemblerFamilyImpl};
 
@@ -11,4 +12,5 @@ pub static ALL_FAMILIES: &'static [&'static dis::DisassemblerFamily] = &[
     (&DisassemblerFamilyImpl::<llvmdis::LLVMDisassembler>(PhantomData) as &DisassemblerFamily),
     #[cfg(feature = "use_llvm")]
     (&DisassemblerFamilyImpl::<gendis::GenDisassembler>(PhantomData) as &DisassemblerFamily),
+    (&DisassemblerFamilyImpl::<simpledis::SimpleDisassembler>(PhantomData) as &DisassemblerFamily),
 ];
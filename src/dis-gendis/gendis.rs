@@ -1,7 +1,7 @@
 extern crate dis_generated_debug_dis;
 use dis_generated_debug_dis::{Operand, MAX_OPS, DebugDisFn};
-//extern crate dis_generated_jump_dis;
-//use dis_generated_jump_dis::{JumpDisCtx, JumpDisFn};
+extern crate dis_generated_jump_dis;
+use dis_generated_jump_dis::{GenericHandler, AArch64Handler, TargetAddr, InsnKind};
 
 #[macro_use]
 extern crate macros;
@@ -10,11 +10,9 @@ extern crate dis;
 extern crate exec;
 extern crate util;
 
-use std::fmt::Write;
-
 use exec::VMA;
 use exec::arch::{ArchAndOptions, ARMMode, OtherMode};
-use dis::{Disassembler, DisassemblerStatics, DisassemblerInput, TrawlLead, TrawlLeadKind};
+use dis::{Disassembler, DisassemblerStatics, DisassemblerInput, TrawlLead, TrawlLeadKind, Insn, InsnOperand, OperandKind, FlowKind};
 use util::{Endian, LittleEndian, copy_from_slice};
 
 pub struct GenDisassembler { arch: ArchAndOptions }
@@ -23,22 +21,59 @@ impl Disassembler for GenDisassembler {
     fn arch(&self) -> &ArchAndOptions { &self.arch }
     fn can_disassemble_to_str(&self) -> bool { true }
     fn disassemble_insn_to_str(&self, input: &DisassemblerInput) -> Option<(Option<String>, u32)> {
+        let (val, insn) = some_or!(self.debug(input), { return None; });
+        Some((Some(format!("0x{:0.*x}: {}", 2 * insn.len as usize, val, insn)), insn.len))
+    }
+    fn can_disassemble_insn(&self) -> bool { true }
+    fn disassemble_insn(&self, input: &DisassemblerInput) -> Option<Insn> {
+        self.debug(input).map(|(_, insn)| insn)
+    }
+
+    fn can_trawl(&self) -> bool {
+        match self.arch {
+            ArchAndOptions::ARM(_) | ArchAndOptions::AArch64(_) => true,
+            _ => false,
+        }
+    }
+    fn trawl(&self, input: &DisassemblerInput, leads: &mut Vec<TrawlLead>) -> Option<()> {
         match (self.arch, input.mode) {
-            (ArchAndOptions::ARM(opts), ARMMode { thumb }) => debug_arm(input, thumb, opts.endian),
-            (ArchAndOptions::AArch64(_), _) => debug_common(input, LittleEndian, 4, dis_generated_debug_dis::debug_dis_AArch64),
+            (ArchAndOptions::ARM(opts), ARMMode { thumb: false }) => trawl_arm(input, leads, opts.endian),
+            (ArchAndOptions::ARM(opts), ARMMode { thumb: true }) => trawl_thumb(input, leads, opts.endian),
+            (ArchAndOptions::AArch64(_), _) => trawl_generic(input, leads, &mut AArch64Handler::new()),
             _ => panic!("unsupported arch"),
         }
     }
+}
 
-/*
-    fn can_trawl(&self) -> bool { true }
-    fn trawl(&self, input: &DisassemblerInput, leads: &mut Vec<TrawlLead>) -> Option<()> {
+impl GenDisassembler {
+    // The raw instruction word and what we make of it.  The trawl code
+    // already knows about branches and PC-relative references, so reuse it
+    // for flow and targets.
+    fn debug(&self, input: &DisassemblerInput) -> Option<(u32, Insn)> {
+        let mut leads = Vec::new();
         match (self.arch, input.mode) {
-            (ArchAndOptions::AArch64(_), _) => trawl_common(input, leads, LittleEndian, 4, dis_generated_jump_dis::jump_dis_AArch64),
+            (ArchAndOptions::ARM(opts), ARMMode { thumb: false }) => {
+                try_opt!(trawl_arm(input, &mut leads, opts.endian));
+                debug_common(input, opts.endian, 4, dis_generated_debug_dis::debug_dis_ARM, &leads)
+            },
+            (ArchAndOptions::ARM(opts), ARMMode { thumb: true }) => {
+                let (size, falls_through) = try_opt!(trawl_thumb_one(input.data, input.pc, opts.endian, &mut leads));
+                if falls_through {
+                    leads.push(TrawlLead { addr: input.pc.wrapping_add(size), kind: TrawlLeadKind::NextInsn });
+                }
+                if size == 4 {
+                    debug_common(input, opts.endian, 4, dis_generated_debug_dis::debug_dis_Thumb2, &leads)
+                } else {
+                    debug_common(input, opts.endian, 2, dis_generated_debug_dis::debug_dis_Thumb, &leads)
+                }
+            },
+            (ArchAndOptions::AArch64(_), _) => {
+                try_opt!(trawl_generic(input, &mut leads, &mut AArch64Handler::new()));
+                debug_common(input, LittleEndian, 4, dis_generated_debug_dis::debug_dis_AArch64, &leads)
+            },
             _ => panic!("unsupported arch"),
         }
     }
-    */
 }
 
 impl DisassemblerStatics for GenDisassembler {
@@ -52,21 +87,6 @@ impl DisassemblerStatics for GenDisassembler {
     fn name() -> &'static str { "gen" }
 }
 
-fn debug_arm(input: &DisassemblerInput, thumb: bool, endian: Endian) -> Option<(Option<String>, u32)> {
-    if thumb {
-        if input.data.len() < 2 { return None; }
-        let x: u16 = copy_from_slice(&input.data[..2], endian);
-        let is32 =  (x >> 13 & 7) == 7 && (x >> 11 & 3) != 0;
-        if is32 {
-            debug_common(input, endian, 4, dis_generated_debug_dis::debug_dis_Thumb2)
-        } else {
-            debug_common(input, endian, 2, dis_generated_debug_dis::debug_dis_Thumb)
-        }
-    } else {
-        debug_common(input, endian, 4, dis_generated_debug_dis::debug_dis_ARM)
-    }
-}
-
 #[inline]
 fn get_word_common(input: &DisassemblerInput, endian: Endian, size: usize) -> Option<u32> {
     if input.data.len() < size { return None; }
@@ -80,38 +100,271 @@ fn get_word_common(input: &DisassemblerInput, endian: Endian, size: usize) -> Op
     }
 }
 
-fn debug_common(input: &DisassemblerInput, endian: Endian, size: usize, debug_dis: DebugDisFn) -> Option<(Option<String>, u32)> {
+// Operand names come from the LLVM tablegen encoding fields.
+fn is_reg_field(name: &str) -> bool {
+    let b = name.as_bytes();
+    b.len() >= 2 && b.len() <= 4 && b"RXWVQDSHB".contains(&b[0]) && (b[1] as char).is_lowercase()
+}
+
+fn is_label_field(name: &str) -> bool {
+    match name {
+        "label" | "addr" | "target" | "func" => true,
+        _ => false,
+    }
+}
+
+fn gen_flow_kind(name: &str, code_target: Option<VMA>, falls_through: bool) -> FlowKind {
+    let base = name.trim_left_matches("t2").trim_left_matches('t');
+    if base.starts_with("BL") {
+        FlowKind::Call { target: code_target }
+    } else if !falls_through {
+        if base.contains("RET") {
+            FlowKind::Return
+        } else if code_target.is_some() {
+            FlowKind::Jump { target: code_target, conditional: false }
+        } else if base.is_empty() || base.starts_with("UDF") || base.starts_with("TRAP") || base.starts_with("BRK") || base.starts_with("HLT") {
+            FlowKind::Stop
+        } else {
+            FlowKind::Jump { target: None, conditional: false }
+        }
+    } else if code_target.is_some() {
+        FlowKind::Jump { target: code_target, conditional: true }
+    } else {
+        FlowKind::Normal
+    }
+}
+
+fn debug_common(input: &DisassemblerInput, endian: Endian, size: usize, debug_dis: DebugDisFn, leads: &[TrawlLead]) -> Option<(u32, Insn)> {
     let val = some_or!(get_word_common(input, endian, size), { return None; });
     let mut ops = [Operand { name: "", val: 0 }; MAX_OPS];
     let mut name = "";
     unsafe { debug_dis(val, &mut name, &mut ops); }
-    let mut out: String = format!("0x{:0.*x}: {}", 2 * size, val, name);
+    let mut code_target = None;
+    let mut ref_target = None;
+    let mut falls_through = false;
+    for lead in leads {
+        match lead.kind {
+            TrawlLeadKind::NextInsn => falls_through = true,
+            TrawlLeadKind::JumpRef { .. } => { code_target = code_target.or(Some(lead.addr)); },
+            TrawlLeadKind::OtherRef => { ref_target = ref_target.or(Some(lead.addr)); },
+        }
+    }
+    let target = code_target.or(ref_target);
+    let mut operands = Vec::new();
+    let mut have_target = false;
     for op in &ops {
         if op.name.len() == 0 { break; }
-        write!(&mut out, " {}=0x{:x}", op.name, op.val).unwrap();
+        let kind = if is_reg_field(op.name) {
+            OperandKind::Reg
+        } else if target.is_some() && is_label_field(op.name) {
+            have_target = true;
+            OperandKind::PcRel(target.unwrap())
+        } else {
+            OperandKind::Imm(op.val as i64)
+        };
+        operands.push(InsnOperand { text: format!("{}=0x{:x}", op.name, op.val), kind: kind });
+    }
+    if let (Some(target), false) = (target, have_target) {
+        operands.push(InsnOperand { text: format!("0x{:x}", target.0), kind: OperandKind::PcRel(target) });
     }
-    Some((Some(out), size as u32))
+    Some((val, Insn {
+        mnemonic: name.to_owned(),
+        operands: operands,
+        flow: gen_flow_kind(name, code_target, falls_through),
+        len: size as u32,
+        comment: None,
+    }))
 }
 
-/*
-fn trawl_common(input: &DisassemblerInput, leads: &mut Vec<TrawlLead>, endian: Endian, size: usize, jump_dis: JumpDisFn) -> Option<()> {
-    let val = some_or!(get_word_common(input, endian, size), { return None; });
-    let mut ctx = JumpDisCtx::default();
-    ctx.base_addr = input.pc.0;
-    unsafe { jump_dis(&mut ctx, val); }
-    if ctx.have_target_addr {
-        leads.push(TrawlLead {
-            addr: VMA(ctx.target_addr),
-            kind: if ctx.target_addr_is_data {
-                TrawlLeadKind::OtherRef
-            } else {
-                TrawlLeadKind::JumpRef { mode: OtherMode }
+fn trawl_generic<H: GenericHandler>(input: &DisassemblerInput, leads: &mut Vec<TrawlLead>, handler: &mut H) -> Option<()> {
+    let (size, info) = handler.decode(input.pc, util::as_read_cells(input.data));
+    if size == 0 { return None; }
+    match info.target_addr {
+        TargetAddr::Code(addr) => leads.push(TrawlLead { addr: addr, kind: TrawlLeadKind::JumpRef { mode: OtherMode } }),
+        TargetAddr::Data(addr) => leads.push(TrawlLead { addr: addr, kind: TrawlLeadKind::OtherRef }),
+        TargetAddr::None => (),
+    }
+    match info.kind {
+        InsnKind::Tail | InsnKind::Unidentified | InsnKind::Br(_) => (),
+        _ => leads.push(TrawlLead { addr: input.pc.wrapping_add(size as u64), kind: TrawlLeadKind::NextInsn }),
+    }
+    Some(())
+}
+
+#[inline]
+fn sext(val: u32, bits: u32) -> u64 {
+    (((val << (32 - bits)) as i32) >> (32 - bits)) as i64 as u64
+}
+
+fn push_jump(leads: &mut Vec<TrawlLead>, addr: VMA, thumb: bool) {
+    leads.push(TrawlLead { addr: addr, kind: TrawlLeadKind::JumpRef { mode: ARMMode { thumb: thumb } } });
+}
+
+fn push_ref(leads: &mut Vec<TrawlLead>, addr: VMA) {
+    leads.push(TrawlLead { addr: addr, kind: TrawlLeadKind::OtherRef });
+}
+
+fn arm_expand_imm(imm12: u32) -> u32 {
+    (imm12 & 0xff).rotate_right(2 * (imm12 >> 8))
+}
+
+fn trawl_arm(input: &DisassemblerInput, leads: &mut Vec<TrawlLead>, endian: Endian) -> Option<()> {
+    let insn = some_or!(get_word_common(input, endian, 4), { return None; });
+    let pc = input.pc;
+    let always = insn >> 28 == 0b1110;
+    let rn = (insn >> 16) & 0xf;
+    let rd = (insn >> 12) & 0xf;
+    let mut falls_through = true;
+    if (insn >> 25) & 7 == 0b101 {
+        let off = sext((insn & 0xffffff) << 2, 26);
+        if insn >> 28 == 0b1111 {
+            // blx imm
+            let h = ((insn >> 24) & 1) << 1;
+            push_jump(leads, pc.wrapping_add(8).wrapping_add(off | h as u64), true);
+        } else {
+            push_jump(leads, pc.wrapping_add(8).wrapping_add(off), false);
+            if insn & (1 << 24) == 0 && always { falls_through = false; }
+        }
+    } else if insn & 0x0ffffff0 == 0x012fff10 {
+        // bx
+        if always { falls_through = false; }
+    } else if (insn >> 25) & 7 == 0b100 && insn & (1 << 20) != 0 {
+        // ldm, including pop
+        if insn & (1 << 15) != 0 && always { falls_through = false; }
+    } else if (insn >> 26) & 3 == 0b01 && insn & (1 << 20) != 0 {
+        // ldr
+        if insn & (1 << 25) == 0 && rn == 15 {
+            let imm = (insn & 0xfff) as u64;
+            let base = pc.wrapping_add(8);
+            push_ref(leads, if insn & (1 << 23) != 0 { base.wrapping_add(imm) } else { base.wrapping_add(imm.wrapping_neg()) });
+        }
+        if rd == 15 && always { falls_through = false; }
+    } else if insn & 0x0fef0000 == 0x028f0000 || insn & 0x0fef0000 == 0x024f0000 {
+        // adr (add/sub rd, pc, #imm)
+        let imm = arm_expand_imm(insn & 0xfff) as u64;
+        let base = pc.wrapping_add(8);
+        push_ref(leads, if insn & (1 << 23) != 0 { base.wrapping_add(imm) } else { base.wrapping_add(imm.wrapping_neg()) });
+        if rd == 15 && always { falls_through = false; }
+    } else if (insn >> 26) & 3 == 0 && rd == 15 {
+        // data processing writing pc, e.g. mov pc, lr or add pc, pc, rN, lsl #2;
+        // skip the misc/multiply encodings and the compares, which don't
+        let misc = (insn >> 23) & 0x1f == 0b00010 && insn & (1 << 20) == 0;
+        let mul = insn & (1 << 25) == 0 && insn & 0x90 == 0x90;
+        let cmp = (insn >> 23) & 3 == 0b10 && insn & (1 << 20) != 0;
+        if !misc && !mul && !cmp && always { falls_through = false; }
+    }
+    if falls_through {
+        leads.push(TrawlLead { addr: pc.wrapping_add(4), kind: TrawlLeadKind::NextInsn });
+    }
+    Some(())
+}
+
+// Returns the size and whether the instruction can fall through, pushing any
+// references to leads.
+fn trawl_thumb_one(data: &[u8], pc: VMA, endian: Endian, leads: &mut Vec<TrawlLead>) -> Option<(u64, bool)> {
+    if data.len() < 2 { return None; }
+    let hw1: u16 = copy_from_slice(&data[..2], endian);
+    let hw1 = hw1 as u32;
+    let aligned_pc = VMA(pc.wrapping_add(4).0 & !3);
+    let is32 = (hw1 >> 13 & 7) == 7 && (hw1 >> 11 & 3) != 0;
+    if !is32 {
+        let mut falls_through = true;
+        if hw1 & 0xf000 == 0xd000 && (hw1 >> 8) & 0xf < 0b1110 {
+            // b<cond>
+            push_jump(leads, pc.wrapping_add(4).wrapping_add(sext((hw1 & 0xff) << 1, 9)), true);
+        } else if hw1 & 0xf800 == 0xe000 {
+            push_jump(leads, pc.wrapping_add(4).wrapping_add(sext((hw1 & 0x7ff) << 1, 12)), true);
+            falls_through = false;
+        } else if hw1 & 0xf500 == 0xb100 {
+            // cbz/cbnz
+            let off = ((hw1 >> 9) & 1) << 6 | ((hw1 >> 3) & 0x1f) << 1;
+            push_jump(leads, pc.wrapping_add(4).wrapping_add(off as u64), true);
+        } else if hw1 & 0xff87 == 0x4700 {
+            // bx
+            falls_through = false;
+        } else if hw1 & 0xff00 == 0xbd00 {
+            // pop {..., pc}
+            falls_through = false;
+        } else if hw1 & 0xff87 == 0x4687 || hw1 & 0xff87 == 0x4487 {
+            // mov pc, rM / add pc, rM
+            falls_through = false;
+        } else if hw1 & 0xf800 == 0x4800 || hw1 & 0xf800 == 0xa000 {
+            // ldr rT, [pc, #imm] / adr
+            push_ref(leads, aligned_pc.wrapping_add(((hw1 & 0xff) << 2) as u64));
+        } else if hw1 & 0xff00 == 0xde00 {
+            // udf
+            falls_through = false;
+        }
+        return Some((2, falls_through));
+    }
+    if data.len() < 4 { return None; }
+    let hw2: u16 = copy_from_slice(&data[2..4], endian);
+    let hw2 = hw2 as u32;
+    let mut falls_through = true;
+    if hw1 & 0xf800 == 0xf000 && hw2 & 0x8000 != 0 {
+        let s = (hw1 >> 10) & 1;
+        let j1 = (hw2 >> 13) & 1;
+        let j2 = (hw2 >> 11) & 1;
+        match (hw2 >> 12) & 5 {
+            0b000 => {
+                let cond = (hw1 >> 6) & 0xf;
+                if cond >> 1 != 0b111 {
+                    let imm = s << 20 | j2 << 19 | j1 << 18 | (hw1 & 0x3f) << 12 | (hw2 & 0x7ff) << 1;
+                    push_jump(leads, pc.wrapping_add(4).wrapping_add(sext(imm, 21)), true);
+                }
+            },
+            _ => {
+                let i1 = !(j1 ^ s) & 1;
+                let i2 = !(j2 ^ s) & 1;
+                let imm = sext(s << 24 | i1 << 23 | i2 << 22 | (hw1 & 0x3ff) << 12 | (hw2 & 0x7ff) << 1, 25);
+                match (hw2 >> 12) & 5 {
+                    0b001 => { // b.w
+                        push_jump(leads, pc.wrapping_add(4).wrapping_add(imm), true);
+                        falls_through = false;
+                    },
+                    0b101 => push_jump(leads, pc.wrapping_add(4).wrapping_add(imm), true), // bl
+                    _ => push_jump(leads, aligned_pc.wrapping_add(imm & !3), false), // blx
+                }
             },
-        });
+        }
+    } else if hw1 & 0xfe50 == 0xe810 && (hw1 >> 7) & 3 != 0 && (hw1 >> 7) & 3 != 3 {
+        // ldm.w, including pop.w
+        if hw2 & 0x8000 != 0 { falls_through = false; }
+    } else if hw1 & 0xfff0 == 0xe8d0 && hw2 & 0xffe0 == 0xf000 {
+        // tbb/tbh; the table normally follows immediately
+        if hw1 & 0xf == 15 { push_ref(leads, pc.wrapping_add(4)); }
+        falls_through = false;
+    } else if hw1 & 0xff70 == 0xf850 {
+        // ldr.w
+        if hw1 & 0xf == 15 {
+            let imm = (hw2 & 0xfff) as u64;
+            push_ref(leads, if hw1 & 0x80 != 0 { aligned_pc.wrapping_add(imm) } else { aligned_pc.wrapping_add(imm.wrapping_neg()) });
+        }
+        if hw2 >> 12 == 15 { falls_through = false; }
+    }
+    Some((4, falls_through))
+}
+
+fn trawl_thumb(input: &DisassemblerInput, leads: &mut Vec<TrawlLead>, endian: Endian) -> Option<()> {
+    let (size, mut falls_through) = some_or!(trawl_thumb_one(input.data, input.pc, endian, leads), { return None; });
+    let mut off = size;
+    let hw1: u16 = copy_from_slice(&input.data[..2], endian);
+    if hw1 & 0xff00 == 0xbf00 && hw1 & 0xf != 0 {
+        // An IT block: treat it and the instructions it covers as a unit,
+        // since a pop {pc} or b inside is conditional and doesn't end flow.
+        let firstcond = (hw1 >> 4) & 0xf;
+        let count = 4 - (hw1 & 0xf).trailing_zeros();
+        for _ in 0..count {
+            let (size, ft) = some_or!(trawl_thumb_one(&input.data[off as usize..], input.pc.wrapping_add(off), endian, leads), { return None; });
+            off += size;
+            if !ft && firstcond == 0b1110 {
+                falls_through = false;
+                break;
+            }
+        }
     }
-    if !ctx.is_tail {
-        leads.push(TrawlLead { addr: input.pc + (size as u64), kind: TrawlLeadKind::NextInsn });
+    if falls_through {
+        leads.push(TrawlLead { addr: input.pc.wrapping_add(off), kind: TrawlLeadKind::NextInsn });
     }
     Some(())
 }
-*/
@@ -55,6 +55,7 @@ impl Default for TargetAddr { fn default() -> Self { TargetAddr::None } }
 pub enum InsnKind {
     Other,
     Tail,
+    Call,
     Unidentified,
     Load(Reg, Addrish, Size, Signedness),
     Set(Reg, Addrish),
@@ -258,6 +259,7 @@ impl aarch64::Handler for AArch64Handler {
     }
     #[inline]
     fn addr_1_BL(&mut self, addr: u32) -> Self::Res {
+        self.info.kind = InsnKind::Call;
         self.info.target_addr = TargetAddr::Code(self.addr.wrapping_add((addr << 2).sign_extend(28)));
     }
     #[inline]
@@ -5,20 +5,154 @@ use llvm_sys::target::*;
 use llvm_sys::disassembler::*;
 extern crate dis;
 extern crate libc;
+#[macro_use]
+extern crate macros;
 extern crate bsdlike_getopts as getopts;
 use exec::arch;
-use exec::arch::ArchAndOptions;
-use libc::c_char;
+use exec::arch::{ArchAndOptions, CodeMode, MipsISA, ARMMode, X86Mode, MipsMode, PowerPCMode};
+use exec::VMA;
+use util::{BigEndian, LittleEndian};
+use libc::{c_char, c_int, c_void};
 
 use std::ffi::CString;
 use std::mem::transmute;
+use std::cell::{Cell, RefCell};
+
+mod parse;
 
 use std::sync::{Once, ONCE_INIT};
 static LLVM_INIT_ONCE: Once = ONCE_INIT;
 
+// from llvm-c/Disassembler.h
+const REFERENCE_TYPE_INOUT_NONE: u64 = 0;
+const REFERENCE_TYPE_IN_BRANCH: u64 = 1;
+const REFERENCE_TYPE_IN_ARM64_ADRP: u64 = 0x100000001;
+const REFERENCE_TYPE_OUT_LITPOOL_SYMADDR: u64 = 2;
+
+// What LLVM gets as DisInfo.  The resolver is only set for the duration of
+// a disassemble_text call; names handed to LLVM live until the next.
+struct SymbolizerCtx {
+    resolver: Cell<Option<*const (dis::SymbolResolver + 'static)>>,
+    names: RefCell<Vec<CString>>,
+    // operand names and the addresses they replaced, so parse can still
+    // tell where a branch goes
+    substituted: RefCell<Vec<(String, u64)>>,
+}
+
+impl SymbolizerCtx {
+    fn resolve(&self, addr: u64) -> Option<(String, u64)> {
+        let resolver = some_or!(self.resolver.get(), { return None; });
+        let resolver = unsafe { &*resolver };
+        resolver.resolve(VMA(addr)).map(|(name, off)| (name.to_owned(), off))
+    }
+    fn intern(&self, s: String) -> *const c_char {
+        let cs = some_or!(CString::new(s).ok(), { return std::ptr::null(); });
+        let ptr = cs.as_ptr();
+        self.names.borrow_mut().push(cs);
+        ptr
+    }
+}
+
+// Called for operands LLVM thinks might be symbolic (branch targets,
+// immediates); we render them as symbol+off if they land in a symbol.
+extern "C" fn get_op_info(dis_info: *mut c_void, _pc: u64, _offset: u64, _size: u64, tag_type: c_int, tag_buf: *mut c_void) -> c_int {
+    if tag_type != 1 { return 0; }
+    let ctx = unsafe { &*(dis_info as *const SymbolizerCtx) };
+    let op_info = unsafe { &mut *(tag_buf as *mut LLVMOpInfo1) };
+    let (name, off) = some_or!(ctx.resolve(op_info.Value), { return 0; });
+    ctx.substituted.borrow_mut().push((name.clone(), op_info.Value));
+    op_info.AddSymbol.Present = 1;
+    op_info.AddSymbol.Name = ctx.intern(name);
+    op_info.Value = off;
+    1
+}
+
+// Called for PC-relative loads and the like, which end up as comments.
+extern "C" fn symbol_lookup(dis_info: *mut c_void, ref_value: u64, ref_type: *mut u64, _ref_pc: u64, ref_name: *mut *const c_char) -> *const c_char {
+    let ctx = unsafe { &*(dis_info as *const SymbolizerCtx) };
+    let in_type = unsafe { *ref_type };
+    unsafe {
+        *ref_type = REFERENCE_TYPE_INOUT_NONE;
+        *ref_name = std::ptr::null();
+    }
+    // just a page address; not worth naming
+    if in_type == REFERENCE_TYPE_IN_ARM64_ADRP { return std::ptr::null(); }
+    let (name, off) = some_or!(ctx.resolve(ref_value), { return std::ptr::null(); });
+    if in_type == REFERENCE_TYPE_IN_BRANCH || in_type == REFERENCE_TYPE_INOUT_NONE {
+        if off == 0 { ctx.intern(name) } else { std::ptr::null() }
+    } else {
+        let desc = if off == 0 { name } else { format!("{}+0x{:x}", name, off) };
+        unsafe {
+            *ref_type = REFERENCE_TYPE_OUT_LITPOOL_SYMADDR;
+            *ref_name = ctx.intern(desc);
+        }
+        std::ptr::null()
+    }
+}
+
+// The default triple and any extra features for a given mode.
+fn target_for(arch: &ArchAndOptions, mode: CodeMode) -> Result<(&'static str, &'static str), String> {
+    Ok(match (*arch, mode) {
+        (ArchAndOptions::X86(..), X86Mode { bits: 16 }) |
+        (ArchAndOptions::X86_64(..), X86Mode { bits: 16 }) => ("i386-unknown-unknown-code16", ""),
+        (ArchAndOptions::X86(..), X86Mode { bits: 64 }) |
+        (ArchAndOptions::X86_64(..), X86Mode { bits: 64 }) => ("x86_64", ""),
+        (ArchAndOptions::X86(..), _) | (ArchAndOptions::X86_64(..), X86Mode { .. }) => ("i386", ""),
+        (ArchAndOptions::X86_64(..), _) => ("x86_64", ""),
+        (ArchAndOptions::ARM(opts), ARMMode { thumb: true }) =>
+            (if opts.endian == BigEndian { "thumbebv7" } else { "thumbv7" }, ""),
+        (ArchAndOptions::ARM(opts), _) =>
+            (if opts.endian == BigEndian { "armebv7" } else { "armv7" }, ""),
+        (ArchAndOptions::AArch64(..), _) => ("aarch64", ""),
+        (ArchAndOptions::Mips(opts), _) => {
+            let triple = match (opts.is64, opts.endian) {
+                (false, BigEndian) => "mips",
+                (false, LittleEndian) => "mipsel",
+                (true, BigEndian) => "mips64",
+                (true, LittleEndian) => "mips64el",
+            };
+            let features = match mode {
+                MipsMode { isa: MipsISA::Mips16 } => "+mips16",
+                MipsMode { isa: MipsISA::MicroMips } => "+micromips",
+                _ => "",
+            };
+            (triple, features)
+        },
+        (ArchAndOptions::PowerPC(_), PowerPCMode { vle: true }) =>
+            return Err("LLVM doesn't support PowerPC VLE".to_owned()),
+        (ArchAndOptions::PowerPC(opts), _) => {
+            (match (opts.is64, opts.endian) {
+                (false, BigEndian) => "powerpc",
+                (false, LittleEndian) => "powerpcle",
+                (true, BigEndian) => "powerpc64",
+                (true, LittleEndian) => "powerpc64le",
+            }, "")
+        },
+        (ArchAndOptions::Sparc(..), _) => ("sparc", ""),
+        (ArchAndOptions::RiscV(opts), _) => {
+            // assume G, since LLVM won't decode anything beyond I otherwise
+            (if opts.is64 { "riscv64" } else { "riscv32" },
+             if opts.compressed { "+m,+a,+f,+d,+c" } else { "+m,+a,+f,+d" })
+        },
+        (ArchAndOptions::S390x(..), _) => ("s390x", ""),
+        (ArchAndOptions::LoongArch(..), _) => ("loongarch64", ""),
+        (ArchAndOptions::M68k(..), _) => ("m68k", ""),
+        (ArchAndOptions::UnknownArch(..), _) => return Err("can't create disassembler for unknown arch".to_owned()),
+    })
+}
+
 pub struct LLVMDisassembler {
     arch: arch::ArchAndOptions,
-    dcr: LLVMDisasmContextRef,
+    default_mode: CodeMode,
+    // replaces the default mode's triple only; other modes still need theirs
+    triple: Option<String>,
+    cpu: Option<String>,
+    features: Option<String>,
+    // LLVM wants a separate context per mode (thumb, code16, ...); these are
+    // made on first use.  None means creation failed, so don't retry.
+    dcrs: RefCell<Vec<(CodeMode, Option<LLVMDisasmContextRef>)>>,
+    // boxed so the address LLVM holds stays put
+    ctx: Box<SymbolizerCtx>,
 }
 
 impl LLVMDisassembler {
@@ -31,60 +165,115 @@ impl LLVMDisassembler {
             }
         });
 
-        let triple = if let Some(t) = triple { t } else {
-            match arch {
-                ArchAndOptions::ARM(..) => "armv7",
-                ArchAndOptions::X86_64(..) => "x86_64",
-                ArchAndOptions::X86(..) => "x86",
-                ArchAndOptions::UnknownArch(..) => return Err(util::GenericError("can't create disassembler for unknown arch".to_owned())),
-                _ => panic!("todo"),
-            }
+        let default_mode = try!(CodeMode::new(&arch, &[]).map_err(util::GenericError));
+        let dis = LLVMDisassembler {
+            arch: arch,
+            default_mode: default_mode,
+            triple: triple.map(|s| s.to_owned()),
+            cpu: cpu.map(|s| s.to_owned()),
+            features: features.map(|s| s.to_owned()),
+            dcrs: RefCell::new(Vec::new()),
+            ctx: Box::new(SymbolizerCtx {
+                resolver: Cell::new(None),
+                names: RefCell::new(Vec::new()),
+                substituted: RefCell::new(Vec::new()),
+            }),
+        };
+        // make sure at least the default mode works
+        let dcr = try!(dis.create_dcr(default_mode).map_err(util::GenericError));
+        dis.dcrs.borrow_mut().push((default_mode, Some(dcr)));
+        Ok(dis)
+    }
+
+    fn create_dcr(&self, mode: CodeMode) -> Result<LLVMDisasmContextRef, String> {
+        let (default_triple, mode_features) = try!(target_for(&self.arch, mode));
+        let triple = match self.triple {
+            Some(ref triple) if mode == self.default_mode => &**triple,
+            _ => default_triple,
         };
+        let mut features = self.features.clone().unwrap_or(String::new());
+        if !mode_features.is_empty() {
+            if !features.is_empty() { features.push(','); }
+            features.push_str(mode_features);
+        }
         let triple_cs = CString::new(triple).unwrap();
-        let cpu_cs = CString::new(cpu.unwrap_or("")).unwrap();
-        let features_cs = CString::new(features.unwrap_or("")).unwrap();
+        let cpu_cs = CString::new(self.cpu.as_ref().map_or("", |s| &**s)).unwrap();
+        let features_cs = CString::new(features).unwrap();
 
         let dcr = unsafe {
             LLVMCreateDisasmCPUFeatures(
                 triple_cs.as_ptr(),
                 cpu_cs.as_ptr(),
                 features_cs.as_ptr(),
-                std::ptr::null_mut(),
-                0,
-                // XXX llvm-sys should be using Option; this is UB
-                transmute(0usize),
-                transmute(0usize),
+                &*self.ctx as *const SymbolizerCtx as *mut c_void,
+                1,
+                get_op_info,
+                symbol_lookup,
             )
         };
 
         if dcr == std::ptr::null_mut() {
-            return Err(util::GenericError("LLVMCreateDisasmCPUFeatures failed".to_owned()));
+            return Err(format!("LLVMCreateDisasmCPUFeatures failed for triple {}", triple));
         }
+        Ok(dcr)
+    }
 
-        Ok(LLVMDisassembler { arch: arch, dcr: dcr })
+    fn dcr_for(&self, mode: CodeMode) -> Option<LLVMDisasmContextRef> {
+        if let Some(&(_, dcr)) = self.dcrs.borrow().iter().find(|&&(m, _)| m == mode) {
+            return dcr;
+        }
+        let dcr = match self.create_dcr(mode) {
+            Ok(dcr) => Some(dcr),
+            Err(e) => {
+                errln!("warning: llvm: can't disassemble {:?}: {}", mode, e);
+                None
+            },
+        };
+        self.dcrs.borrow_mut().push((mode, dcr));
+        dcr
     }
 }
 
 impl Drop for LLVMDisassembler {
     fn drop(&mut self) {
-        unsafe { LLVMDisasmDispose(self.dcr); }
+        for &(_, dcr) in self.dcrs.borrow().iter() {
+            if let Some(dcr) = dcr {
+                unsafe { LLVMDisasmDispose(dcr); }
+            }
+        }
     }
 }
 
 
-impl dis::Disassembler for LLVMDisassembler {
-    fn arch(&self) -> &arch::ArchAndOptions { &self.arch }
-    fn can_disassemble_to_str(&self) -> bool { true }
-    fn disassemble_insn_to_str(&self, input: &dis::DisassemblerInput) -> Option<(Option<String>, u32)> {
+impl LLVMDisassembler {
+    fn disassemble_text(&self, input: &dis::DisassemblerInput) -> Option<(String, u32)> {
+        let dcr = try_opt!(self.dcr_for(input.mode));
         let mut tmp: [u8; 256] = unsafe { std::mem::uninitialized() };
-        let res = unsafe { LLVMDisasmInstruction(self.dcr, input.data.as_ptr() as *mut u8, input.data.len() as u64, input.pc.0, &mut tmp[0] as *mut u8 as *mut c_char, 256) };
+        self.ctx.names.borrow_mut().clear();
+        self.ctx.substituted.borrow_mut().clear();
+        self.ctx.resolver.set(input.resolver.map(|r| unsafe { transmute(r as *const dis::SymbolResolver) }));
+        let res = unsafe { LLVMDisasmInstruction(dcr, input.data.as_ptr() as *mut u8, input.data.len() as u64, input.pc.0, &mut tmp[0] as *mut u8 as *mut c_char, 256) };
+        self.ctx.resolver.set(None);
         if res == 0 {
             None
         } else {
-            Some((Some(util::from_cstr(&tmp as &[u8]).lossy().into_owned()), res as u32))
+            Some((util::from_cstr(&tmp as &[u8]).lossy().into_owned(), res as u32))
         }
     }
 }
+
+impl dis::Disassembler for LLVMDisassembler {
+    fn arch(&self) -> &arch::ArchAndOptions { &self.arch }
+    fn can_disassemble_to_str(&self) -> bool { true }
+    fn disassemble_insn_to_str(&self, input: &dis::DisassemblerInput) -> Option<(Option<String>, u32)> {
+        self.disassemble_insn(input).map(|insn| (Some(insn.to_string()), insn.len))
+    }
+    fn can_disassemble_insn(&self) -> bool { true }
+    fn disassemble_insn(&self, input: &dis::DisassemblerInput) -> Option<dis::Insn> {
+        let (text, len) = try_opt!(self.disassemble_text(input));
+        Some(parse::parse_insn(&self.arch, input.mode, &text, input.pc, len, &self.ctx.substituted.borrow()))
+    }
+}
 impl dis::DisassemblerStatics for LLVMDisassembler {
     fn new_with_args(arch: arch::ArchAndOptions, args: &[String]) -> Result<LLVMDisassembler, dis::CreateDisError> {
         let mut optgrps = vec![
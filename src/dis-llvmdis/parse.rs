@@ -0,0 +1,324 @@
+// The C API only gives us text, not the MCInst, so recover mnemonic,
+// operands and flow from the syntax LLVM prints for each arch.  This is
+// heuristic; anything we don't understand ends up as OperandKind::Other.
+use dis::{Insn, InsnOperand, OperandKind, FlowKind};
+use exec::VMA;
+use exec::arch::{ArchAndOptions, CodeMode, ARMMode};
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Syntax { X86, ARM, AArch64, Other }
+
+fn syntax_for(arch: &ArchAndOptions) -> Syntax {
+    match *arch {
+        ArchAndOptions::X86(..) | ArchAndOptions::X86_64(..) => Syntax::X86,
+        ArchAndOptions::ARM(..) => Syntax::ARM,
+        ArchAndOptions::AArch64(..) => Syntax::AArch64,
+        _ => Syntax::Other,
+    }
+}
+
+const X86_PREFIXES: &'static [&'static str] = &[
+    "lock", "rep", "repe", "repne", "repz", "repnz", "data16", "data32", "addr32",
+    "rex64", "notrack", "bnd", "xacquire", "xrelease",
+];
+
+const ARM_CCS: &'static [&'static str] = &[
+    "eq", "ne", "cs", "hs", "cc", "lo", "mi", "pl", "vs", "vc", "hi", "ls", "ge", "lt", "gt", "le", "al",
+];
+
+fn split_operands(s: &str) -> Vec<String> {
+    let mut res = Vec::new();
+    let mut depth = 0;
+    let mut cur = String::new();
+    for c in s.chars() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                res.push(cur.trim().to_owned());
+                cur.clear();
+                continue;
+            },
+            _ => (),
+        }
+        cur.push(c);
+    }
+    if !cur.trim().is_empty() {
+        res.push(cur.trim().to_owned());
+    }
+    res
+}
+
+// -> (mnemonic, operands, comment)
+fn split_text(arch: &ArchAndOptions, text: &str) -> (String, Vec<String>, Option<String>) {
+    let syntax = syntax_for(arch);
+    let marker = match syntax {
+        Syntax::X86 => Some("#"),
+        Syntax::ARM => Some("@"),
+        Syntax::AArch64 => Some("//"),
+        Syntax::Other => None,
+    };
+    let (mut text, mut comment) = (text, None);
+    if let Some(pos) = marker.and_then(|marker| text.find(marker)) {
+        let c = text[pos..].trim_left_matches(|c: char| c == '#' || c == '@' || c == '/').trim();
+        if !c.is_empty() { comment = Some(c.to_owned()); }
+        text = &text[..pos];
+    }
+    let mut rest = text.trim();
+    let mut mnemonic = String::new();
+    loop {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = &rest[..end];
+        if !mnemonic.is_empty() { mnemonic.push(' '); }
+        mnemonic.push_str(word);
+        rest = rest[end..].trim_left();
+        if !(syntax == Syntax::X86 && X86_PREFIXES.iter().any(|&p| p == word) && !rest.is_empty()) { break; }
+    }
+    (mnemonic, split_operands(rest), comment)
+}
+
+fn parse_num(s: &str) -> Option<i64> {
+    let s = s.trim().trim_left_matches(|c: char| c == '$' || c == '#');
+    let (neg, s) = if s.starts_with('-') { (true, &s[1..]) } else { (false, s) };
+    let val: u64 = if s.starts_with("0x") {
+        try_opt!(u64::from_str_radix(&s[2..], 16).ok())
+    } else {
+        try_opt!(s.parse::<u64>().ok())
+    };
+    let val = val as i64;
+    Some(if neg { val.wrapping_neg() } else { val })
+}
+
+// syms are the names LLVM substituted for addresses (see get_op_info in
+// llvmdis), which it prints as name or name+off.
+fn symbol_value(op: &str, syms: &[(String, u64)]) -> Option<u64> {
+    let op = op.trim_left_matches(|c: char| c == '$' || c == '#' || c == '*');
+    syms.iter().find(|&&(ref name, _)| {
+        op.starts_with(&name[..]) && (op.len() == name.len() || op[name.len()..].starts_with('+'))
+    }).map(|&(_, addr)| addr)
+}
+
+fn classify_x86(m: &str) -> FlowKind {
+    let m = m.rsplit(' ').next().unwrap();
+    if m.starts_with("call") || m.starts_with("lcall") {
+        FlowKind::Call { target: None }
+    } else if m.starts_with("jmp") || m.starts_with("ljmp") {
+        FlowKind::Jump { target: None, conditional: false }
+    } else if m.starts_with('j') || m.starts_with("loop") {
+        FlowKind::Jump { target: None, conditional: true }
+    } else if m.starts_with("ret") || m.starts_with("lret") || m.starts_with("iret") {
+        FlowKind::Return
+    } else if m == "ud2" || m == "ud1" || m == "ud0" || m == "hlt" {
+        FlowKind::Stop
+    } else {
+        FlowKind::Normal
+    }
+}
+
+fn classify_arm(m: &str, ops: &[String]) -> FlowKind {
+    let m = m.trim_right_matches(".w").trim_right_matches(".n");
+    let is_cc = |s: &str| ARM_CCS.iter().any(|&cc| cc == s);
+    let writes_pc = ops.first().map_or(false, |op| op == "pc");
+    let has_pc = ops.iter().any(|op| op.starts_with('{') && op.contains("pc"));
+    if m == "b" || (m.starts_with('b') && is_cc(&m[1..])) {
+        FlowKind::Jump { target: None, conditional: m != "b" && m != "bal" }
+    } else if m.starts_with("bx") && (m.len() == 2 || is_cc(&m[2..])) {
+        if ops.first().map_or(false, |op| op == "lr") { FlowKind::Return } else { FlowKind::Jump { target: None, conditional: m.len() > 2 } }
+    } else if m.starts_with("blx") && (m.len() == 3 || is_cc(&m[3..])) || m.starts_with("bl") && (m.len() == 2 || is_cc(&m[2..])) {
+        FlowKind::Call { target: None }
+    } else if m == "cbz" || m == "cbnz" {
+        FlowKind::Jump { target: None, conditional: true }
+    } else if m.starts_with("pop") || m.starts_with("ldm") {
+        if has_pc { FlowKind::Return } else { FlowKind::Normal }
+    } else if m == "mov" && writes_pc && ops.get(1).map_or(false, |op| op == "lr") {
+        FlowKind::Return
+    } else if writes_pc && (m.starts_with("ldr") || m == "mov" || m == "add") || m == "tbb" || m == "tbh" {
+        FlowKind::Jump { target: None, conditional: false }
+    } else if m == "udf" {
+        FlowKind::Stop
+    } else {
+        FlowKind::Normal
+    }
+}
+
+fn classify_aarch64(m: &str) -> FlowKind {
+    if m.starts_with("ret") || m.starts_with("eret") {
+        FlowKind::Return
+    } else if m == "b" {
+        FlowKind::Jump { target: None, conditional: false }
+    } else if m.starts_with("b.") || m == "cbz" || m == "cbnz" || m == "tbz" || m == "tbnz" {
+        FlowKind::Jump { target: None, conditional: true }
+    } else if m.starts_with("bl") {
+        FlowKind::Call { target: None }
+    } else if m == "brk" || m == "udf" || m == "hlt" {
+        FlowKind::Stop
+    } else if m.starts_with("br") {
+        FlowKind::Jump { target: None, conditional: false }
+    } else {
+        FlowKind::Normal
+    }
+}
+
+fn x86_mem(op: &str, pc: VMA, len: u32, syms: &[(String, u64)]) -> OperandKind {
+    let paren = op.find('(').unwrap();
+    let disp = parse_num(&op[..paren]).or_else(|| symbol_value(&op[..paren], syms).map(|addr| addr as i64)).unwrap_or(0);
+    let inner = op[paren+1..].trim_right_matches(')');
+    let mut parts = inner.split(',').map(|s| s.trim());
+    let base = parts.next().and_then(|s| if s.is_empty() { None } else { Some(s.to_owned()) });
+    let index = parts.next().map(|s| s.to_owned());
+    let scale = parts.next().and_then(|s| s.parse::<u8>().ok()).unwrap_or(1);
+    if base.as_ref().map_or(false, |b| b == "%rip" || b == "%eip") {
+        return OperandKind::PcRel((pc + len as u64).wrapping_add(disp as u64));
+    }
+    OperandKind::Mem { base: base, index: index, scale: scale, disp: disp }
+}
+
+fn arm_mem(op: &str, pc_base: VMA) -> OperandKind {
+    let inner = op.trim_right_matches('!').trim_left_matches('[').trim_right_matches(']');
+    let parts = split_operands(inner);
+    let base = parts.get(0).cloned();
+    let (mut index, mut disp) = (None, 0);
+    if let Some(second) = parts.get(1) {
+        if second.starts_with('#') {
+            disp = parse_num(second).unwrap_or(0);
+        } else {
+            index = Some(second.clone());
+        }
+    }
+    if base.as_ref().map_or(false, |b| b == "pc") && index.is_none() {
+        return OperandKind::PcRel(VMA(pc_base.0 & !3).wrapping_add(disp as u64));
+    }
+    OperandKind::Mem { base: base, index: index, scale: 1, disp: disp }
+}
+
+fn operand_kind(syntax: Syntax, op: &str, is_target: bool, pc: VMA, len: u32, pc_base: VMA, syms: &[(String, u64)]) -> OperandKind {
+    if let Some(addr) = symbol_value(op, syms) {
+        if !op.contains('(') && !op.starts_with('[') {
+            return if is_target { OperandKind::PcRel(VMA(addr)) } else { OperandKind::Imm(addr as i64) };
+        }
+    }
+    let is_ident = !op.is_empty() && op.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.');
+    match syntax {
+        Syntax::X86 => {
+            let op = op.trim_left_matches('*');
+            if op.contains('(') {
+                x86_mem(op, pc, len, syms)
+            } else if op.starts_with('%') {
+                if op.contains(':') { OperandKind::Mem { base: None, index: None, scale: 1, disp: 0 } } else { OperandKind::Reg }
+            } else if op.starts_with('$') {
+                parse_num(op).map_or(OperandKind::Other, OperandKind::Imm)
+            } else if let Some(val) = parse_num(op) {
+                // branch targets are printed relative to the next instruction
+                if is_target { OperandKind::PcRel((pc + len as u64).wrapping_add(val as u64)) } else { OperandKind::Mem { base: None, index: None, scale: 1, disp: val } }
+            } else {
+                OperandKind::Other
+            }
+        },
+        Syntax::ARM | Syntax::AArch64 => {
+            if op.starts_with('[') {
+                arm_mem(op, pc_base)
+            } else if op.starts_with('#') {
+                let val = some_or!(parse_num(op), { return OperandKind::Other; });
+                if is_target { OperandKind::PcRel(pc_base.wrapping_add(val as u64)) } else { OperandKind::Imm(val) }
+            } else if let Some(val) = parse_num(op) {
+                if is_target { OperandKind::PcRel(pc_base.wrapping_add(val as u64)) } else { OperandKind::Imm(val) }
+            } else if is_ident && !ARM_CCS.iter().any(|&cc| cc == op) {
+                OperandKind::Reg
+            } else {
+                OperandKind::Other
+            }
+        },
+        Syntax::Other => {
+            match parse_num(op) {
+                Some(val) => OperandKind::Imm(val),
+                None => if is_ident { OperandKind::Reg } else { OperandKind::Other },
+            }
+        },
+    }
+}
+
+pub fn parse_insn(arch: &ArchAndOptions, mode: CodeMode, text: &str, pc: VMA, len: u32, syms: &[(String, u64)]) -> Insn {
+    let syntax = syntax_for(arch);
+    let (mnemonic, ops, comment) = split_text(arch, text);
+    let mut flow = match syntax {
+        Syntax::X86 => classify_x86(&mnemonic),
+        Syntax::ARM => classify_arm(&mnemonic, &ops),
+        Syntax::AArch64 => classify_aarch64(&mnemonic),
+        Syntax::Other => FlowKind::Normal,
+    };
+    // what a relative immediate is relative to
+    let pc_base = match (syntax, mode) {
+        (Syntax::ARM, ARMMode { thumb: true }) => pc + 4,
+        (Syntax::ARM, _) => pc + 8,
+        (Syntax::AArch64, _) if mnemonic == "adrp" => VMA(pc.0 & !0xfff),
+        _ => pc,
+    };
+    let pcrel_data = match syntax {
+        Syntax::ARM => mnemonic == "adr",
+        Syntax::AArch64 => mnemonic == "adr" || mnemonic == "adrp" || mnemonic.starts_with("ldr") || mnemonic == "prfm",
+        _ => false,
+    };
+    let has_target = pcrel_data || match flow {
+        FlowKind::Jump { .. } | FlowKind::Call { .. } => true,
+        _ => false,
+    };
+    let nops = ops.len();
+    let operands: Vec<InsnOperand> = ops.into_iter().enumerate().map(|(i, op)| {
+        let kind = operand_kind(syntax, &op, has_target && i == nops - 1, pc, len, pc_base, syms);
+        InsnOperand { text: op, kind: kind }
+    }).collect();
+    let direct_target = operands.last().and_then(|op| match op.kind {
+        OperandKind::PcRel(addr) if !op.text.starts_with('*') && !op.text.starts_with('[') => Some(addr),
+        _ => None,
+    });
+    match flow {
+        FlowKind::Jump { ref mut target, .. } | FlowKind::Call { ref mut target } => *target = direct_target,
+        _ => (),
+    }
+    Insn {
+        mnemonic: mnemonic,
+        operands: operands,
+        flow: flow,
+        len: len,
+        comment: comment,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exec::arch::X86Mode;
+
+    fn x86_64_insn(text: &str, pc: u64, len: u32) -> Insn {
+        parse_insn(&ArchAndOptions::X86_64(Default::default()), X86Mode { bits: 64 }, text, VMA(pc), len, &[])
+    }
+
+    #[test]
+    fn test_x86_rel32_targets() {
+        // e8 fb 0f 00 00
+        assert_eq!(x86_64_insn("\tcallq\t4091", 0x1000, 5).flow,
+                   FlowKind::Call { target: Some(VMA(0x2000)) });
+        // e9 f6 ff ff ff
+        assert_eq!(x86_64_insn("\tjmp\t-10", 0x1000, 5).flow,
+                   FlowKind::Jump { target: Some(VMA(0xffb)), conditional: false });
+        // 0f 84 00 01 00 00
+        assert_eq!(x86_64_insn("\tje\t256", 0x1000, 6).flow,
+                   FlowKind::Jump { target: Some(VMA(0x1106)), conditional: true });
+    }
+
+    #[test]
+    fn test_x86_symbolized() {
+        let syms = vec![("_foo".to_owned(), 0x2000)];
+        let insn = parse_insn(&ArchAndOptions::X86_64(Default::default()), X86Mode { bits: 64 },
+                              "\tcallq\t_foo", VMA(0x1000), 5, &syms);
+        assert_eq!(insn.flow, FlowKind::Call { target: Some(VMA(0x2000)) });
+        assert_eq!(insn.operands[0].text, "_foo");
+    }
+
+    #[test]
+    fn test_x86_indirect() {
+        assert_eq!(x86_64_insn("\tcallq\t*%rax", 0x1000, 2).flow, FlowKind::Call { target: None });
+        assert_eq!(x86_64_insn("\tjmpq\t*16(%rip)", 0x1000, 6).flow,
+                   FlowKind::Jump { target: None, conditional: false });
+    }
+}
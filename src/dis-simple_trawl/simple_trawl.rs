@@ -0,0 +1,528 @@
+// Finds code by following control flow from a set of roots (usually symbols),
+// recording basic blocks, calls, data references and jump tables along the
+// way.  Unlike the old version in attic/ this doesn't try to propagate
+// register values along every edge; jump tables are recovered by walking
+// backwards from the indirect branch through straight-line code, which is
+// enough for what compilers actually emit.
+#[macro_use]
+extern crate macros;
+extern crate dis_generated_jump_dis;
+extern crate exec;
+extern crate util;
+
+use dis_generated_jump_dis::{Reg, GenericHandler, TargetAddr, InsnInfo, InsnKind, Addrish, CC};
+use exec::VMA;
+use util::{Endian, ReadCell, Fnv};
+use std::collections::HashSet;
+use std::mem::replace;
+
+simple_bitflags! {
+    GrainFlags: u8 {
+        insn/set_insn: bool << 0, // an instruction starts here
+        insn_tail/set_insn_tail: bool << 1, // inside an instruction
+        block/set_block: bool << 2,
+        func/set_func: bool << 3,
+        ends_block/set_ends_block: bool << 4,
+        seen/set_seen: bool << 5, // has been queued
+    }
+}
+
+// how far back to look for the pieces of a jump table sequence
+const MAX_SCAN_BACK: usize = 64;
+const MAX_TABLE_LEN: u64 = 100000;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BasicBlock {
+    pub start: VMA,
+    pub end: VMA, // exclusive
+}
+
+#[derive(Clone, Debug)]
+pub struct JumpTable {
+    pub br_addr: VMA,
+    pub table_addr: VMA,
+    pub entry_size: u8,
+    pub targets: Vec<VMA>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum GrokSwitchFail {
+    GettingSetterOfBrAddr,
+    BrAddrNotAdd,
+    ShiftMismatch,
+    NeitherAddendLooksLikeTable,
+    GettingTableBaseValue,
+    GettingTableAddrValue,
+    FindingCmp,
+    CmpWeirdCond,
+    CmpWrongReg,
+    TableTooBig,
+    TableReadError,
+}
+
+pub struct CodeMap<'a> {
+    region_start: VMA,
+    grain_shift: u8,
+    insn_data: &'a [ReadCell<u8>],
+    endian: Endian,
+    flags: Vec<GrainFlags>,
+    queue: Vec<usize>,
+    pending_brs: Vec<usize>,
+    noreturn_addrs: HashSet<VMA, Fnv>,
+    // everything below is keyed by the idx of the referencing instruction
+    pub calls: Vec<(usize, VMA)>,
+    pub jumps: Vec<(usize, VMA)>,
+    pub data_refs: Vec<(usize, VMA)>,
+    // direct branches and calls whose target is outside the region
+    pub out_of_range_idxs: Vec<usize>,
+    pub jump_tables: Vec<JumpTable>,
+    pub switch_failures: Vec<(usize, GrokSwitchFail)>,
+}
+
+impl<'a> CodeMap<'a> {
+    pub fn new(region_start: VMA, grain_shift: u8, insn_data: &'a [ReadCell<u8>], endian: Endian) -> Self {
+        let grain = 1usize << grain_shift;
+        let num_grains = (insn_data.len() + grain - 1) / grain;
+        CodeMap {
+            region_start: region_start,
+            grain_shift: grain_shift,
+            insn_data: insn_data,
+            endian: endian,
+            flags: vec![GrainFlags::default(); num_grains],
+            queue: Vec::new(),
+            pending_brs: Vec::new(),
+            noreturn_addrs: util::new_fnv_hashset(),
+            calls: Vec::new(),
+            jumps: Vec::new(),
+            data_refs: Vec::new(),
+            out_of_range_idxs: Vec::new(),
+            jump_tables: Vec::new(),
+            switch_failures: Vec::new(),
+        }
+    }
+    #[inline]
+    pub fn addr_to_idx(&self, addr: VMA) -> Option<usize> {
+        let off = addr.wrapping_sub(self.region_start);
+        if off < (self.insn_data.len() as u64) && off & ((1 << self.grain_shift) - 1) == 0 {
+            Some((off >> self.grain_shift) as usize)
+        } else {
+            None
+        }
+    }
+    #[inline]
+    pub fn idx_to_addr(&self, idx: usize) -> VMA {
+        assert!(idx <= self.flags.len());
+        self.region_start + ((idx as u64) << self.grain_shift)
+    }
+    pub fn mark_noreturn_addr(&mut self, addr: VMA) {
+        self.noreturn_addrs.insert(addr);
+    }
+    pub fn mark_root(&mut self, idx: usize) {
+        self.flags[idx].set_func(true);
+        self.enqueue(idx);
+    }
+    fn enqueue(&mut self, idx: usize) {
+        let flags = &mut self.flags[idx];
+        flags.set_block(true);
+        if !flags.seen() {
+            flags.set_seen(true);
+            self.queue.push(idx);
+        }
+    }
+    pub fn go<'x, H: GenericHandler + ?Sized>(&mut self, handler: &mut H, read: &mut FnMut(VMA, u64) -> Option<&'x [ReadCell<u8>]>) {
+        loop {
+            while let Some(idx) = self.queue.pop() {
+                self.scan_block(handler, idx);
+            }
+            if self.pending_brs.is_empty() { break; }
+            for br_idx in replace(&mut self.pending_brs, Vec::new()) {
+                match self.grok_switch(handler, br_idx, read) {
+                    Ok(jt) => {
+                        for &target in &jt.targets {
+                            if let Some(target_idx) = self.addr_to_idx(target) {
+                                self.enqueue(target_idx);
+                            }
+                        }
+                        self.jump_tables.push(jt);
+                    },
+                    Err(e) => self.switch_failures.push((br_idx, e)),
+                }
+            }
+        }
+    }
+    #[inline]
+    fn decode<H: GenericHandler + ?Sized>(&self, handler: &mut H, idx: usize) -> (usize, InsnInfo) {
+        let off = idx << self.grain_shift;
+        let (size, info) = handler.decode(self.idx_to_addr(idx), &self.insn_data[off..]);
+        (size, *info)
+    }
+    fn scan_block<H: GenericHandler + ?Sized>(&mut self, handler: &mut H, start: usize) {
+        let mut idx = start;
+        // if we've already been here, this is just a new block boundary
+        while !self.flags[idx].insn() {
+            let (size, info) = self.decode(handler, idx);
+            let grains = size >> self.grain_shift;
+            if grains == 0 { break; }
+            self.flags[idx].set_insn(true);
+            for tail in idx + 1..std::cmp::min(idx + grains, self.flags.len()) {
+                self.flags[tail].set_insn_tail(true);
+            }
+            let mut cont = true;
+            match info.target_addr {
+                TargetAddr::Code(target) => {
+                    if self.noreturn_addrs.contains(&target) { cont = false; }
+                    let is_call = if let InsnKind::Call = info.kind { true } else { false };
+                    if is_call {
+                        self.calls.push((idx, target));
+                    } else {
+                        self.jumps.push((idx, target));
+                        self.flags[idx].set_ends_block(true);
+                    }
+                    match self.addr_to_idx(target) {
+                        Some(target_idx) => {
+                            if is_call { self.flags[target_idx].set_func(true); }
+                            self.enqueue(target_idx);
+                        },
+                        None => match info.kind {
+                            InsnKind::Call | InsnKind::Tail => self.out_of_range_idxs.push(idx),
+                            _ => (),
+                        },
+                    }
+                },
+                TargetAddr::Data(target) => self.data_refs.push((idx, target)),
+                TargetAddr::None => (),
+            }
+            match info.kind {
+                InsnKind::Tail | InsnKind::Unidentified => cont = false,
+                InsnKind::Br(_) => {
+                    self.pending_brs.push(idx);
+                    cont = false;
+                },
+                _ => (),
+            }
+            let next = idx + grains;
+            if !cont {
+                self.flags[idx].set_ends_block(true);
+                break;
+            }
+            if next >= self.flags.len() { break; }
+            if self.flags[idx].ends_block() {
+                // conditional branch; the fallthrough is its own block
+                self.enqueue(next);
+                break;
+            }
+            idx = next;
+        }
+    }
+
+    // Walk backwards from `idx` through code that can fall through to it,
+    // returning the first instruction that satisfies `pred`.  This is purely
+    // linear, so it may wander across a branch target, but that's fine for
+    // the compiler-generated sequences we care about.
+    fn scan_back<H: GenericHandler + ?Sized, F: FnMut(&InsnInfo) -> bool>(&self, handler: &mut H, mut idx: usize, mut pred: F) -> Option<(usize, InsnInfo)> {
+        for _ in 0..MAX_SCAN_BACK {
+            if idx == 0 { return None; }
+            idx -= 1;
+            while self.flags[idx].insn_tail() {
+                if idx == 0 { return None; }
+                idx -= 1;
+            }
+            if !self.flags[idx].insn() { return None; }
+            let (_, info) = self.decode(handler, idx);
+            match info.kind {
+                InsnKind::Tail | InsnKind::Unidentified | InsnKind::Br(_) => return None,
+                _ => (),
+            }
+            if let TargetAddr::Code(target) = info.target_addr {
+                if self.noreturn_addrs.contains(&target) { return None; }
+            }
+            if pred(&info) { return Some((idx, info)); }
+        }
+        None
+    }
+    fn last_setter<H: GenericHandler + ?Sized>(&self, handler: &mut H, before_idx: usize, reg: Reg) -> Option<(usize, InsnInfo)> {
+        self.scan_back(handler, before_idx, |info| {
+            match info.kind {
+                InsnKind::Set(r, _) | InsnKind::Load(r, ..) if r == reg => true,
+                _ => info.kills_reg(reg),
+            }
+        })
+    }
+    fn value_of<H: GenericHandler + ?Sized>(&self, handler: &mut H, before_idx: usize, reg: Reg, depth: usize) -> Option<u64> {
+        if depth > 8 { return None; }
+        let (idx, info) = try_opt!(self.last_setter(handler, before_idx, reg));
+        match info.kind {
+            InsnKind::Set(r, Addrish::Imm(val)) if r == reg => Some(val),
+            InsnKind::Set(r, Addrish::AddImm(base, addend)) if r == reg =>
+                self.value_of(handler, idx, base, depth + 1).map(|val| val.wrapping_add(addend)),
+            _ => None,
+        }
+    }
+    fn regs_look_equiv<H: GenericHandler + ?Sized>(&self, handler: &mut H, idx1: usize, r1: Reg, idx2: usize, r2: Reg) -> bool {
+        // a true result is NOT guaranteed to be accurate; doing this properly
+        // would require tracking phis and stuff
+        let setter1 = self.last_setter(handler, idx1, r1);
+        let setter2 = self.last_setter(handler, idx2, r2);
+        if let Some((_, info)) = setter1 {
+            if let InsnKind::Set(_, Addrish::AddImm(base, 0)) = info.kind {
+                if base == r2 { return true; }
+            }
+        }
+        if let Some((_, info)) = setter2 {
+            if let InsnKind::Set(_, Addrish::AddImm(base, 0)) = info.kind {
+                if base == r1 { return true; }
+            }
+        }
+        if let (Some((i1, _)), Some((i2, _))) = (setter1, setter2) {
+            if i1 == i2 { return true; }
+        }
+        false
+    }
+
+    // Recognizes
+    //     cmp idx, #n; b.hi default
+    //     ...
+    //     ldr{b,h,sw} entry, [table, idx{, lsl #s}]
+    //     add target, base, entry{, lsl #s}
+    //     br target
+    // where table and base are known constants (adrp/add/adr).
+    fn grok_switch<'x, H: GenericHandler + ?Sized>(&mut self, handler: &mut H, br_idx: usize, read: &mut FnMut(VMA, u64) -> Option<&'x [ReadCell<u8>]>) -> Result<JumpTable, GrokSwitchFail> {
+        let br_reg = match self.decode(handler, br_idx).1.kind {
+            InsnKind::Br(r) => r,
+            _ => panic!("grok_switch: not a br"),
+        };
+        let (add_idx, add_info) = try!(self.last_setter(handler, br_idx, br_reg).ok_or(GrokSwitchFail::GettingSetterOfBrAddr));
+        let (r1, r2, shift) = match add_info.kind {
+            InsnKind::Set(_, Addrish::AddReg(r1, r2, shift)) => (r1, r2, shift),
+            _ => return Err(GrokSwitchFail::BrAddrNotAdd),
+        };
+        // one addend should be a table entry, the other the base it's relative to
+        let mut found = None;
+        for &(base_reg, entry_reg) in &[(r1, r2), (r2, r1)] {
+            if let Some((load_idx, load_info)) = self.last_setter(handler, add_idx, entry_reg) {
+                if let InsnKind::Load(_, Addrish::AddReg(table_reg, idx_reg, load_shift), size, signedness) = load_info.kind {
+                    if load_shift != size.log2_bytes() {
+                        return Err(GrokSwitchFail::ShiftMismatch);
+                    }
+                    found = Some((base_reg, load_idx, table_reg, idx_reg, size, signedness));
+                    break;
+                }
+            }
+        }
+        let (base_reg, load_idx, table_reg, idx_reg, size, signedness) = try!(found.ok_or(GrokSwitchFail::NeitherAddendLooksLikeTable));
+        let table_base = try!(self.value_of(handler, add_idx, base_reg, 0).ok_or(GrokSwitchFail::GettingTableBaseValue));
+        let table_addr = try!(self.value_of(handler, load_idx, table_reg, 0).ok_or(GrokSwitchFail::GettingTableAddrValue));
+
+        // find the bounds check to get the table size
+        let mut cond = None;
+        let (cmp_idx, cmp_info) = try!(self.scan_back(handler, br_idx, |info| {
+            match info.kind {
+                InsnKind::Bcc(cc) => {
+                    if cond.is_none() { cond = Some(cc); }
+                    false
+                },
+                InsnKind::CmpImm(..) => true,
+                _ => false,
+            }
+        }).ok_or(GrokSwitchFail::FindingCmp));
+        let (cmp_reg, limit) = match cmp_info.kind {
+            InsnKind::CmpImm(r, limit) => (r, limit),
+            _ => unreachable!(),
+        };
+        if cmp_reg != idx_reg && !self.regs_look_equiv(handler, load_idx, idx_reg, cmp_idx, cmp_reg) {
+            return Err(GrokSwitchFail::CmpWrongReg);
+        }
+        let table_len = match cond {
+            // branch away if out of range
+            Some(CC::Gtu) => limit + 1,
+            Some(CC::Geu) => limit,
+            // branch to the table if in range
+            Some(CC::Leu) => limit + 1,
+            Some(CC::Ltu) => limit,
+            _ => return Err(GrokSwitchFail::CmpWeirdCond),
+        };
+        if table_len > MAX_TABLE_LEN {
+            return Err(GrokSwitchFail::TableTooBig);
+        }
+
+        let entry_size = size.bytes();
+        let table = try!(read(VMA(table_addr), table_len * entry_size).ok_or(GrokSwitchFail::TableReadError));
+        let mut targets = Vec::new();
+        for chunk in table.chunks(entry_size as usize) {
+            let val = exec::dynsized_integer_from_slice(chunk, signedness, self.endian);
+            targets.push(VMA(table_base).wrapping_add(val << shift));
+        }
+        Ok(JumpTable {
+            br_addr: self.idx_to_addr(br_idx),
+            table_addr: VMA(table_addr),
+            entry_size: entry_size as u8,
+            targets: targets,
+        })
+    }
+
+    pub fn is_func_start(&self, idx: usize) -> bool {
+        self.flags[idx].func()
+    }
+    pub fn func_starts(&self) -> Vec<VMA> {
+        (0..self.flags.len()).filter(|&idx| self.flags[idx].func()).map(|idx| self.idx_to_addr(idx)).collect()
+    }
+    pub fn basic_blocks(&self) -> Vec<BasicBlock> {
+        let mut result = Vec::new();
+        let mut cur: Option<usize> = None;
+        let mut close_pending = false;
+        for idx in 0..self.flags.len() {
+            let flags = self.flags[idx];
+            if flags.insn_tail() { continue; }
+            if !flags.insn() || flags.block() || close_pending {
+                if let Some(start) = cur.take() {
+                    result.push(BasicBlock { start: self.idx_to_addr(start), end: self.idx_to_addr(idx) });
+                }
+                close_pending = false;
+            }
+            if !flags.insn() { continue; }
+            if cur.is_none() { cur = Some(idx); }
+            close_pending = flags.ends_block();
+        }
+        if let Some(start) = cur {
+            result.push(BasicBlock { start: self.idx_to_addr(start), end: self.idx_to_addr(self.flags.len()) });
+        }
+        result
+    }
+    // Only approximate: this assumes functions are contiguous and takes the
+    // closest preceding root or call target.
+    pub fn containing_func(&self, idx: usize) -> Option<VMA> {
+        (0..idx + 1).rev().find(|&i| self.flags[i].func()).map(|i| self.idx_to_addr(i))
+    }
+    pub fn funcs_referencing(&self, addr: VMA) -> Vec<VMA> {
+        let mut idxs: Vec<usize> = Vec::new();
+        for list in &[&self.calls, &self.jumps, &self.data_refs] {
+            idxs.extend(list.iter().filter(|&&(_, target)| target == addr).map(|&(idx, _)| idx));
+        }
+        for jt in &self.jump_tables {
+            if jt.table_addr == addr || jt.targets.contains(&addr) {
+                idxs.extend(self.addr_to_idx(jt.br_addr));
+            }
+        }
+        let mut funcs: Vec<VMA> = idxs.into_iter().filter_map(|idx| self.containing_func(idx)).collect();
+        funcs.sort();
+        funcs.dedup();
+        funcs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dis_generated_jump_dis::Size8;
+    use util::{Unsigned, LittleEndian};
+
+    // Hands out pre-decoded instructions, one per 4 bytes, so this doesn't
+    // depend on the generated AArch64 decoder.
+    struct ListHandler {
+        insns: Vec<(u64, InsnInfo)>,
+        cur: InsnInfo,
+    }
+    impl GenericHandler for ListHandler {
+        fn decode<'a>(&'a mut self, addr: VMA, _data: &[ReadCell<u8>]) -> (usize, &'a InsnInfo) {
+            self.cur = self.insns.iter().find(|&&(a, _)| a == addr.0).map(|&(_, info)| info)
+                .unwrap_or(InsnInfo { kind: InsnKind::Unidentified, ..Default::default() });
+            (4, &self.cur)
+        }
+    }
+
+    fn x(r: i8) -> Reg { Reg(r) }
+    fn insn(addr: u64, kind: InsnKind, target_addr: TargetAddr, kills: Option<i8>) -> (u64, InsnInfo) {
+        let mut info = InsnInfo { kind: kind, target_addr: target_addr, ..Default::default() };
+        if let Some(r) = kills { info.kills_reg[0] = x(r); }
+        (addr, info)
+    }
+
+    fn switch_func(cmp_reg: i8) -> Vec<(u64, InsnInfo)> {
+        use dis_generated_jump_dis::InsnKind::*;
+        use dis_generated_jump_dis::Addrish::*;
+        use dis_generated_jump_dis::TargetAddr::{Code, Data};
+        let none = TargetAddr::None;
+        vec![
+            insn(0x1000, Set(x(8), Imm(0x3000)), Data(VMA(0x3000)), Some(8)),     // adrp x8, 0x3000
+            insn(0x1004, Set(x(8), AddImm(x(8), 0x40)), none, Some(8)),           // add x8, x8, #0x40
+            insn(0x1008, CmpImm(x(cmp_reg), 3), none, None),                      // cmp w0, #3
+            insn(0x100c, Bcc(CC::Gtu), Code(VMA(0x1040)), None),                  // b.hi 0x1040
+            insn(0x1010, Set(x(9), Imm(0x1020)), Data(VMA(0x1020)), Some(9)),     // adr x9, 0x1020
+            insn(0x1014, Load(x(10), AddReg(x(8), x(0), 0), Size8, Unsigned), none, Some(10)), // ldrb w10, [x8, x0]
+            insn(0x1018, Set(x(9), AddReg(x(9), x(10), 2)), none, Some(9)),       // add x9, x9, x10, lsl #2
+            insn(0x101c, Br(x(9)), none, None),                                   // br x9
+            insn(0x1020, Call, Code(VMA(0x1100)), None),                          // bl 0x1100
+            insn(0x1024, Tail, none, None),                                       // ret
+            insn(0x1028, Tail, Code(VMA(0x1048)), None),                          // b 0x1048
+            insn(0x102c, Other, none, None),                                      // nop
+            insn(0x1030, Tail, none, None),                                       // ret
+            insn(0x1034, Tail, none, None),                                       // ret
+            insn(0x1040, Tail, none, None),                                       // ret
+            insn(0x1048, Tail, none, None),                                       // ret
+            insn(0x1100, Set(x(1), Imm(0x3000)), Data(VMA(0x3000)), Some(1)),     // adrp x1, 0x3000
+            insn(0x1104, Tail, none, None),                                       // ret
+        ]
+    }
+
+    fn run(insns: Vec<(u64, InsnInfo)>, cb: &Fn(&CodeMap)) {
+        let code: Vec<ReadCell<u8>> = (0..0x108).map(|_| ReadCell::new(0)).collect();
+        // ldrb offsets to cases 0..3
+        let table: Vec<ReadCell<u8>> = [0u8, 2, 3, 5].iter().map(|&b| ReadCell::new(b)).collect();
+        let mut handler = ListHandler { insns: insns, cur: Default::default() };
+        let mut codemap = CodeMap::new(VMA(0x1000), 2, &code, LittleEndian);
+        codemap.mark_root(0);
+        codemap.go(&mut handler, &mut |addr, len| {
+            if addr == VMA(0x3040) && len <= table.len() as u64 { Some(&table[..len as usize]) } else { None }
+        });
+        cb(&codemap);
+    }
+
+    #[test]
+    fn test_jump_table() {
+        run(switch_func(0), &|codemap| {
+            assert_eq!(codemap.jump_tables.len(), 1);
+            let jt = &codemap.jump_tables[0];
+            assert_eq!((jt.br_addr, jt.table_addr, jt.entry_size), (VMA(0x101c), VMA(0x3040), 1));
+            assert_eq!(jt.targets, vec![VMA(0x1020), VMA(0x1028), VMA(0x102c), VMA(0x1034)]);
+            assert!(codemap.switch_failures.is_empty());
+
+            let blocks: Vec<(u64, u64)> = codemap.basic_blocks().iter().map(|bb| (bb.start.0, bb.end.0)).collect();
+            assert_eq!(blocks, vec![
+                (0x1000, 0x1010), // up to the bounds check
+                (0x1010, 0x1020), // up to the br
+                (0x1020, 0x1028), (0x1028, 0x102c), (0x102c, 0x1034), (0x1034, 0x1038), // cases
+                (0x1040, 0x1044), // default
+                (0x1048, 0x104c), // tail call target
+                (0x1100, 0x1108), // called function
+            ]);
+            assert_eq!(codemap.func_starts(), vec![VMA(0x1000), VMA(0x1100)]);
+        });
+    }
+
+    #[test]
+    fn test_funcs_referencing() {
+        run(switch_func(0), &|codemap| {
+            assert_eq!(codemap.funcs_referencing(VMA(0x1100)), vec![VMA(0x1000)]);
+            assert_eq!(codemap.funcs_referencing(VMA(0x3000)), vec![VMA(0x1000), VMA(0x1100)]);
+            // via the jump table
+            assert_eq!(codemap.funcs_referencing(VMA(0x3040)), vec![VMA(0x1000)]);
+            assert_eq!(codemap.funcs_referencing(VMA(0x102c)), vec![VMA(0x1000)]);
+            assert_eq!(codemap.funcs_referencing(VMA(0x1038)), vec![]);
+        });
+    }
+
+    #[test]
+    fn test_switch_wrong_cmp_reg() {
+        // the bounds check is on some other register
+        run(switch_func(1), &|codemap| {
+            assert!(codemap.jump_tables.is_empty());
+            assert_eq!(codemap.switch_failures.len(), 1);
+            match codemap.switch_failures[0] {
+                (7, GrokSwitchFail::CmpWrongReg) => (),
+                other => panic!("{:?}", other),
+            }
+            // the cases were never found
+            assert!(!codemap.basic_blocks().iter().any(|bb| bb.start == VMA(0x102c)));
+        });
+    }
+}
@@ -0,0 +1,818 @@
+// AArch64 decoder for the base integer ISA, scalar FP and the common system
+// instructions, printing preferred aliases the way LLVM does.  Advanced SIMD
+// is mostly missing and decodes as None.
+
+use std::mem::transmute;
+
+static COND: [&'static str; 16] = ["eq", "ne", "hs", "lo", "mi", "pl", "vs", "vc", "hi", "ls", "ge", "lt", "gt", "le", "al", "nv"];
+static SHIFT: [&'static str; 4] = ["lsl", "lsr", "asr", "ror"];
+static EXTEND: [&'static str; 8] = ["uxtb", "uxth", "uxtw", "uxtx", "sxtb", "sxth", "sxtw", "sxtx"];
+
+#[inline]
+fn bits(x: u32, hi: u32, lo: u32) -> u32 {
+    (x >> lo) & ((1u32 << (hi - lo + 1)) - 1)
+}
+#[inline]
+fn bit(x: u32, n: u32) -> bool {
+    (x >> n) & 1 != 0
+}
+fn sext(x: u64, nbits: u32) -> i64 {
+    let shift = 64 - nbits;
+    ((x << shift) as i64) >> shift
+}
+
+// general purpose register; 31 is either sp or zr depending on the operand
+fn r(n: u32, is64: bool, sp: bool) -> String {
+    match (n, is64, sp) {
+        (31, true, true) => "sp".to_owned(),
+        (31, false, true) => "wsp".to_owned(),
+        (31, true, false) => "xzr".to_owned(),
+        (31, false, false) => "wzr".to_owned(),
+        (_, true, _) => format!("x{}", n),
+        (_, false, _) => format!("w{}", n),
+    }
+}
+fn x(n: u32) -> String { r(n, true, false) }
+fn xsp(n: u32) -> String { r(n, true, true) }
+
+fn imm(val: i64) -> String {
+    if val < 0 { format!("#-0x{:x}", (val as u64).wrapping_neg()) } else { format!("#0x{:x}", val) }
+}
+fn uimm(val: u64) -> String {
+    format!("#0x{:x}", val)
+}
+fn target(pc: u64, off: i64) -> String {
+    format!("0x{:x}", pc.wrapping_add(off as u64))
+}
+
+fn fp_reg(ty: u32, n: u32) -> Option<String> {
+    let prefix = match ty { 0 => "s", 1 => "d", 3 => "h", _ => return None };
+    Some(format!("{}{}", prefix, n))
+}
+
+// DecodeBitMasks from the ARM ARM (the wmask half)
+fn decode_bit_masks(n: u32, imms: u32, immr: u32, is64: bool) -> Option<u64> {
+    let combined = (n << 6) | (!imms & 0x3f);
+    if combined == 0 { return None; }
+    let len = 31 - combined.leading_zeros();
+    if len < 1 || (!is64 && n != 0) { return None; }
+    let levels = (1u32 << len) - 1;
+    if imms & levels == levels { return None; }
+    let s = imms & levels;
+    let r = immr & levels;
+    let esize = 1u32 << len;
+    let welem: u64 = if s + 1 == 64 { !0 } else { (1u64 << (s + 1)) - 1 };
+    let emask: u64 = if esize == 64 { !0 } else { (1u64 << esize) - 1 };
+    let rotated = if r == 0 { welem } else { ((welem >> r) | (welem << (esize - r))) & emask };
+    let mut result = 0u64;
+    let mut i = 0;
+    while i < 64 {
+        result |= rotated << i;
+        i += esize;
+    }
+    Some(if is64 { result } else { result & 0xffffffff })
+}
+
+fn sysreg_name(op0: u32, op1: u32, crn: u32, crm: u32, op2: u32) -> String {
+    let name = match (op0, op1, crn, crm, op2) {
+        (3, 3, 13, 0, 2) => "tpidr_el0",
+        (3, 3, 13, 0, 3) => "tpidrro_el0",
+        (3, 0, 13, 0, 4) => "tpidr_el1",
+        (3, 3, 4, 2, 0) => "nzcv",
+        (3, 3, 4, 2, 1) => "daif",
+        (3, 3, 4, 4, 0) => "fpcr",
+        (3, 3, 4, 4, 1) => "fpsr",
+        (3, 3, 0, 0, 1) => "ctr_el0",
+        (3, 3, 0, 0, 7) => "dczid_el0",
+        (3, 3, 14, 0, 0) => "cntfrq_el0",
+        (3, 3, 14, 0, 1) => "cntpct_el0",
+        (3, 3, 14, 0, 2) => "cntvct_el0",
+        (3, 0, 0, 0, 0) => "midr_el1",
+        (3, 0, 0, 0, 5) => "mpidr_el1",
+        (3, 0, 1, 0, 0) => "sctlr_el1",
+        (3, 0, 2, 0, 0) => "ttbr0_el1",
+        (3, 0, 2, 0, 1) => "ttbr1_el1",
+        (3, 0, 2, 0, 2) => "tcr_el1",
+        (3, 0, 4, 0, 0) => "spsr_el1",
+        (3, 0, 4, 0, 1) => "elr_el1",
+        (3, 0, 4, 1, 0) => "sp_el0",
+        (3, 0, 5, 2, 0) => "esr_el1",
+        (3, 0, 6, 0, 0) => "far_el1",
+        (3, 0, 10, 2, 0) => "mair_el1",
+        (3, 0, 12, 0, 0) => "vbar_el1",
+        (3, 4, 4, 0, 1) => "elr_el2",
+        _ => return format!("s{}_{}_c{}_c{}_{}", op0, op1, crn, crm, op2),
+    };
+    name.to_owned()
+}
+
+fn barrier_option(crm: u32) -> String {
+    match crm {
+        15 => "sy".to_owned(), 14 => "st".to_owned(), 13 => "ld".to_owned(),
+        11 => "ish".to_owned(), 10 => "ishst".to_owned(), 9 => "ishld".to_owned(),
+        7 => "nsh".to_owned(), 6 => "nshst".to_owned(), 5 => "nshld".to_owned(),
+        3 => "osh".to_owned(), 2 => "oshst".to_owned(), 1 => "oshld".to_owned(),
+        _ => uimm(crm as u64),
+    }
+}
+
+fn prfop(rt: u32) -> String {
+    let ty = match rt >> 3 { 0 => "pld", 1 => "pli", 2 => "pst", _ => return uimm(rt as u64) };
+    let target = match (rt >> 1) & 3 { 0 => "l1", 1 => "l2", 2 => "l3", _ => return uimm(rt as u64) };
+    format!("{}{}{}", ty, target, if rt & 1 == 0 { "keep" } else { "strm" })
+}
+
+// (text, branch or adr/adrp/literal target if any)
+pub fn disassemble(insn: u32, pc: u64) -> Option<(String, Option<u64>)> {
+    let (mnem, ops) = try_opt!(decode(insn, pc));
+    let mut out = mnem;
+    let mut tgt = None;
+    for (i, op) in ops.iter().enumerate() {
+        out.push_str(if i == 0 { " " } else { ", " });
+        out.push_str(op);
+        // immediates are printed with '#', so a bare hex operand is always a target
+        if op.starts_with("0x") {
+            tgt = u64::from_str_radix(&op[2..], 16).ok();
+        }
+    }
+    Some((out, tgt))
+}
+
+fn decode(insn: u32, pc: u64) -> Option<(String, Vec<String>)> {
+    match bits(insn, 28, 25) {
+        0b1000 | 0b1001 => dp_imm(insn, pc),
+        0b1010 | 0b1011 => branch_sys(insn, pc),
+        0b0100 | 0b0110 | 0b1100 | 0b1110 => ld_st(insn, pc),
+        0b0101 | 0b1101 => dp_reg(insn),
+        0b0111 | 0b1111 => fp(insn),
+        _ => None,
+    }
+}
+
+fn dp_imm(insn: u32, pc: u64) -> Option<(String, Vec<String>)> {
+    let is64 = bit(insn, 31);
+    let rd = bits(insn, 4, 0);
+    let rn = bits(insn, 9, 5);
+    let res = match bits(insn, 25, 23) {
+        0b000 | 0b001 => {
+            let immlo = bits(insn, 30, 29) as u64;
+            let immhi = bits(insn, 23, 5) as u64;
+            let off = sext((immhi << 2) | immlo, 21);
+            if bit(insn, 31) {
+                ("adrp".to_owned(), vec![x(rd), format!("0x{:x}", (pc & !0xfff).wrapping_add((off << 12) as u64))])
+            } else {
+                ("adr".to_owned(), vec![x(rd), target(pc, off)])
+            }
+        },
+        0b010 => {
+            let (sub, setflags) = (bit(insn, 30), bit(insn, 29));
+            let shift = bit(insn, 22);
+            let imm12 = bits(insn, 21, 10) as u64;
+            let rdn = if setflags { r(rd, is64, false) } else { r(rd, is64, true) };
+            let rn_s = r(rn, is64, true);
+            let mut ops = vec![rdn, rn_s, uimm(imm12)];
+            if shift { ops.push("lsl #12".to_owned()); }
+            if !sub && !setflags && !shift && imm12 == 0 && (rd == 31 || rn == 31) {
+                ops.truncate(2);
+                ("mov".to_owned(), ops)
+            } else if setflags && rd == 31 {
+                ops.remove(0);
+                ((if sub { "cmp" } else { "cmn" }).to_owned(), ops)
+            } else {
+                let mnem = match (sub, setflags) { (false, false) => "add", (false, true) => "adds", (true, false) => "sub", (true, true) => "subs" };
+                (mnem.to_owned(), ops)
+            }
+        },
+        0b100 => {
+            let opc = bits(insn, 30, 29);
+            let val = try_opt!(decode_bit_masks(bit(insn, 22) as u32, bits(insn, 15, 10), bits(insn, 21, 16), is64));
+            let rdn = if opc == 3 { r(rd, is64, false) } else { r(rd, is64, true) };
+            let rn_s = r(rn, is64, false);
+            match opc {
+                1 if rn == 31 => ("mov".to_owned(), vec![rdn, uimm(val)]),
+                3 if rd == 31 => ("tst".to_owned(), vec![rn_s, uimm(val)]),
+                _ => (["and", "orr", "eor", "ands"][opc as usize].to_owned(), vec![rdn, rn_s, uimm(val)]),
+            }
+        },
+        0b101 => {
+            let opc = bits(insn, 30, 29);
+            let hw = bits(insn, 22, 21);
+            if !is64 && hw >= 2 { return None; }
+            let imm16 = bits(insn, 20, 5) as u64;
+            let shift = hw * 16;
+            let rd_s = r(rd, is64, false);
+            match opc {
+                0 => {
+                    let mut val = !(imm16 << shift);
+                    if !is64 { val &= 0xffffffff; }
+                    if imm16 == 0 && hw != 0 {
+                        ("movn".to_owned(), vec![rd_s, uimm(imm16), format!("lsl #{}", shift)])
+                    } else {
+                        ("mov".to_owned(), vec![rd_s, if is64 { imm(val as i64) } else { imm(val as u32 as i32 as i64) }])
+                    }
+                },
+                2 => {
+                    if imm16 == 0 && hw != 0 {
+                        ("movz".to_owned(), vec![rd_s, uimm(imm16), format!("lsl #{}", shift)])
+                    } else {
+                        ("mov".to_owned(), vec![rd_s, uimm(imm16 << shift)])
+                    }
+                },
+                3 => {
+                    let mut ops = vec![rd_s, uimm(imm16)];
+                    if shift != 0 { ops.push(format!("lsl #{}", shift)); }
+                    ("movk".to_owned(), ops)
+                },
+                _ => return None,
+            }
+        },
+        0b110 => {
+            let opc = bits(insn, 30, 29);
+            if bit(insn, 22) != is64 { return None; }
+            let immr = bits(insn, 21, 16);
+            let imms = bits(insn, 15, 10);
+            let size = if is64 { 64 } else { 32 };
+            let rd_s = r(rd, is64, false);
+            let rn_s = r(rn, is64, false);
+            let n = |v: u32| uimm(v as u64);
+            match opc {
+                0 => {
+                    if imms == size - 1 {
+                        ("asr".to_owned(), vec![rd_s, rn_s, n(immr)])
+                    } else if immr == 0 && (imms == 7 || imms == 15 || (imms == 31 && is64)) {
+                        let mnem = match imms { 7 => "sxtb", 15 => "sxth", _ => "sxtw" };
+                        (mnem.to_owned(), vec![rd_s, r(rn, false, false)])
+                    } else if imms < immr {
+                        ("sbfiz".to_owned(), vec![rd_s, rn_s, n(size - immr), n(imms + 1)])
+                    } else {
+                        ("sbfx".to_owned(), vec![rd_s, rn_s, n(immr), n(imms - immr + 1)])
+                    }
+                },
+                1 => {
+                    if imms < immr {
+                        if rn == 31 {
+                            ("bfc".to_owned(), vec![rd_s, n(size - immr), n(imms + 1)])
+                        } else {
+                            ("bfi".to_owned(), vec![rd_s, rn_s, n(size - immr), n(imms + 1)])
+                        }
+                    } else {
+                        ("bfxil".to_owned(), vec![rd_s, rn_s, n(immr), n(imms - immr + 1)])
+                    }
+                },
+                2 => {
+                    if imms == size - 1 {
+                        ("lsr".to_owned(), vec![rd_s, rn_s, n(immr)])
+                    } else if imms + 1 == immr {
+                        ("lsl".to_owned(), vec![rd_s, rn_s, n(size - 1 - imms)])
+                    } else if immr == 0 && !is64 && (imms == 7 || imms == 15) {
+                        ((if imms == 7 { "uxtb" } else { "uxth" }).to_owned(), vec![rd_s, rn_s])
+                    } else if imms < immr {
+                        ("ubfiz".to_owned(), vec![rd_s, rn_s, n(size - immr), n(imms + 1)])
+                    } else {
+                        ("ubfx".to_owned(), vec![rd_s, rn_s, n(immr), n(imms - immr + 1)])
+                    }
+                },
+                _ => return None,
+            }
+        },
+        0b111 => {
+            if bits(insn, 30, 29) != 0 || bit(insn, 21) || bit(insn, 22) != is64 { return None; }
+            let rm = bits(insn, 20, 16);
+            let lsb = uimm(bits(insn, 15, 10) as u64);
+            if rn == rm {
+                ("ror".to_owned(), vec![r(rd, is64, false), r(rn, is64, false), lsb])
+            } else {
+                ("extr".to_owned(), vec![r(rd, is64, false), r(rn, is64, false), r(rm, is64, false), lsb])
+            }
+        },
+        _ => return None,
+    };
+    Some(res)
+}
+
+fn branch_sys(insn: u32, pc: u64) -> Option<(String, Vec<String>)> {
+    if bits(insn, 30, 26) == 0b00101 {
+        let off = sext(bits(insn, 25, 0) as u64, 26) << 2;
+        let mnem = if bit(insn, 31) { "bl" } else { "b" };
+        return Some((mnem.to_owned(), vec![target(pc, off)]));
+    }
+    if bits(insn, 31, 24) == 0b01010100 && !bit(insn, 4) {
+        let off = sext(bits(insn, 23, 5) as u64, 19) << 2;
+        return Some((format!("b.{}", COND[bits(insn, 3, 0) as usize]), vec![target(pc, off)]));
+    }
+    if bits(insn, 30, 25) == 0b011010 {
+        let off = sext(bits(insn, 23, 5) as u64, 19) << 2;
+        let mnem = if bit(insn, 24) { "cbnz" } else { "cbz" };
+        return Some((mnem.to_owned(), vec![r(bits(insn, 4, 0), bit(insn, 31), false), target(pc, off)]));
+    }
+    if bits(insn, 30, 25) == 0b011011 {
+        let off = sext(bits(insn, 18, 5) as u64, 14) << 2;
+        let bitno = (bits(insn, 31, 31) << 5) | bits(insn, 23, 19);
+        let mnem = if bit(insn, 24) { "tbnz" } else { "tbz" };
+        return Some((mnem.to_owned(), vec![r(bits(insn, 4, 0), bit(insn, 31), false), format!("#{}", bitno), target(pc, off)]));
+    }
+    if bits(insn, 31, 24) == 0b11010100 {
+        let imm16 = uimm(bits(insn, 20, 5) as u64);
+        let mnem = match (bits(insn, 23, 21), bits(insn, 4, 0)) {
+            (0b000, 1) => "svc", (0b000, 2) => "hvc", (0b000, 3) => "smc",
+            (0b001, 0) => "brk", (0b010, 0) => "hlt",
+            _ => return None,
+        };
+        return Some((mnem.to_owned(), vec![imm16]));
+    }
+    if bits(insn, 31, 22) == 0b1101010100 {
+        return system(insn);
+    }
+    if bits(insn, 31, 25) == 0b1101011 {
+        let opc = bits(insn, 24, 21);
+        let op2 = bits(insn, 20, 16);
+        let op3 = bits(insn, 15, 10);
+        let rn = bits(insn, 9, 5);
+        let op4 = bits(insn, 4, 0);
+        if op2 != 0b11111 { return None; }
+        let res = match (opc, op3, op4) {
+            (0b0000, 0, 0) => ("br", vec![x(rn)]),
+            (0b0001, 0, 0) => ("blr", vec![x(rn)]),
+            (0b0010, 0, 0) => ("ret", if rn == 30 { vec![] } else { vec![x(rn)] }),
+            (0b0010, 2, 31) if rn == 31 => ("retaa", vec![]),
+            (0b0010, 3, 31) if rn == 31 => ("retab", vec![]),
+            (0b0100, 0, 0) if rn == 31 => ("eret", vec![]),
+            (0b0101, 0, 0) if rn == 31 => ("drps", vec![]),
+            (0b0000, 2, 31) => ("braaz", vec![x(rn)]),
+            (0b0000, 3, 31) => ("brabz", vec![x(rn)]),
+            (0b0001, 2, 31) => ("blraaz", vec![x(rn)]),
+            (0b0001, 3, 31) => ("blrabz", vec![x(rn)]),
+            (0b1000, 2, _) => ("braa", vec![x(rn), xsp(op4)]),
+            (0b1000, 3, _) => ("brab", vec![x(rn), xsp(op4)]),
+            (0b1001, 2, _) => ("blraa", vec![x(rn), xsp(op4)]),
+            (0b1001, 3, _) => ("blrab", vec![x(rn), xsp(op4)]),
+            _ => return None,
+        };
+        return Some((res.0.to_owned(), res.1));
+    }
+    None
+}
+
+fn system(insn: u32) -> Option<(String, Vec<String>)> {
+    let l = bit(insn, 21);
+    let op0 = bits(insn, 20, 19);
+    let op1 = bits(insn, 18, 16);
+    let crn = bits(insn, 15, 12);
+    let crm = bits(insn, 11, 8);
+    let op2 = bits(insn, 7, 5);
+    let rt = bits(insn, 4, 0);
+    if !l && op0 == 0 && op1 == 3 && crn == 2 && rt == 31 {
+        let hint = (crm << 3) | op2;
+        let mnem = match hint {
+            0 => "nop", 1 => "yield", 2 => "wfe", 3 => "wfi", 4 => "sev", 5 => "sevl",
+            7 => "xpaclri", 8 => "pacia1716", 12 => "autia1716",
+            16 => "esb", 17 => "psb csync", 20 => "csdb",
+            24 => "paciaz", 25 => "paciasp", 26 => "pacibz", 27 => "pacibsp",
+            28 => "autiaz", 29 => "autiasp", 30 => "autibz", 31 => "autibsp",
+            32 => "bti", 34 => "bti c", 36 => "bti j", 38 => "bti jc",
+            _ => return Some(("hint".to_owned(), vec![format!("#{}", hint)])),
+        };
+        return Some((mnem.to_owned(), vec![]));
+    }
+    if !l && op0 == 0 && op1 == 3 && crn == 3 && rt == 31 {
+        return match op2 {
+            2 => Some(("clrex".to_owned(), if crm == 15 { vec![] } else { vec![uimm(crm as u64)] })),
+            4 => Some(("dsb".to_owned(), vec![barrier_option(crm)])),
+            5 => Some(("dmb".to_owned(), vec![barrier_option(crm)])),
+            6 => Some(("isb".to_owned(), if crm == 15 { vec![] } else { vec![uimm(crm as u64)] })),
+            _ => None,
+        };
+    }
+    if op0 == 1 {
+        let mut ops = vec![format!("#{}", op1), format!("c{}", crn), format!("c{}", crm), format!("#{}", op2)];
+        if l {
+            ops.insert(0, x(rt));
+            return Some(("sysl".to_owned(), ops));
+        }
+        if rt != 31 { ops.push(x(rt)); }
+        return Some(("sys".to_owned(), ops));
+    }
+    if op0 >= 2 {
+        let reg = sysreg_name(op0, op1, crn, crm, op2);
+        return Some(if l { ("mrs".to_owned(), vec![x(rt), reg]) } else { ("msr".to_owned(), vec![reg, x(rt)]) });
+    }
+    None
+}
+
+fn mem_imm(rn: u32, off: i64) -> String {
+    if off == 0 { format!("[{}]", xsp(rn)) } else { format!("[{}, {}]", xsp(rn), imm(off)) }
+}
+
+fn ld_st(insn: u32, pc: u64) -> Option<(String, Vec<String>)> {
+    let rt = bits(insn, 4, 0);
+    let rn = bits(insn, 9, 5);
+    let v = bit(insn, 26);
+    // exclusives, acquire/release, compare-and-swap
+    if bits(insn, 29, 24) == 0b001000 {
+        if v { return None; }
+        let size = bits(insn, 31, 30);
+        let (o2, l, o1, o0) = (bit(insn, 23), bit(insn, 22), bit(insn, 21), bit(insn, 15));
+        let rs = bits(insn, 20, 16);
+        let rt2 = bits(insn, 14, 10);
+        let suffix = ["b", "h", "", ""][size as usize];
+        let is64 = size == 3;
+        let mem = format!("[{}]", xsp(rn));
+        let res = match (o2, l, o1) {
+            (false, _, false) => {
+                let base = if l { if o0 { "ldaxr" } else { "ldxr" } } else { if o0 { "stlxr" } else { "stxr" } };
+                let ops = if l { vec![r(rt, is64, false), mem] } else { vec![r(rs, false, false), r(rt, is64, false), mem] };
+                (format!("{}{}", base, suffix), ops)
+            },
+            (false, _, true) => {
+                if size < 2 { return None; } // casp
+                let is64 = size == 3;
+                let base = if l { if o0 { "ldaxp" } else { "ldxp" } } else { if o0 { "stlxp" } else { "stxp" } };
+                let mut ops = vec![r(rt, is64, false), r(rt2, is64, false), mem];
+                if !l { ops.insert(0, r(rs, false, false)); }
+                (base.to_owned(), ops)
+            },
+            (true, _, false) => {
+                let base = match (l, o0) { (true, true) => "ldar", (true, false) => "ldlar", (false, true) => "stlr", (false, false) => "stllr" };
+                (format!("{}{}", base, suffix), vec![r(rt, is64, false), mem])
+            },
+            (true, _, true) => {
+                let name = format!("cas{}{}{}", if l { "a" } else { "" }, if o0 { "l" } else { "" }, suffix);
+                (name, vec![r(rs, is64, false), r(rt, is64, false), mem])
+            },
+        };
+        return Some(res);
+    }
+    // load literal
+    if bits(insn, 29, 27) == 0b011 && bits(insn, 25, 24) == 0 {
+        let opc = bits(insn, 31, 30);
+        let off = sext(bits(insn, 23, 5) as u64, 19) << 2;
+        let tgt = target(pc, off);
+        let res = match (v, opc) {
+            (false, 0) => ("ldr", r(rt, false, false)),
+            (false, 1) => ("ldr", x(rt)),
+            (false, 2) => ("ldrsw", x(rt)),
+            (false, _) => ("prfm", prfop(rt)),
+            (true, 0) => ("ldr", format!("s{}", rt)),
+            (true, 1) => ("ldr", format!("d{}", rt)),
+            (true, 2) => ("ldr", format!("q{}", rt)),
+            _ => return None,
+        };
+        return Some((res.0.to_owned(), vec![res.1, tgt]));
+    }
+    // load/store pair
+    if bits(insn, 29, 27) == 0b101 {
+        let opc = bits(insn, 31, 30);
+        let ty = bits(insn, 24, 23);
+        let l = bit(insn, 22);
+        let rt2 = bits(insn, 14, 10);
+        let (scale, kind) = match (v, opc) {
+            (false, 0) => (2, "w"),
+            (false, 1) if l && ty != 0 => (2, "x"),
+            (false, 2) => (3, "x"),
+            (true, 0) => (2, "s"),
+            (true, 1) => (3, "d"),
+            (true, 2) => (4, "q"),
+            _ => return None,
+        };
+        let regs = |n: u32| match kind {
+            "w" => r(n, false, false),
+            "x" => x(n),
+            _ => format!("{}{}", kind, n),
+        };
+        let off = sext(bits(insn, 21, 15) as u64, 7) << scale;
+        let mnem = match (ty, l, !v && opc == 1) {
+            (0, true, _) => "ldnp", (0, false, _) => "stnp",
+            (_, true, true) => "ldpsw",
+            (_, true, false) => "ldp", (_, false, _) => "stp",
+        };
+        let mut ops = vec![regs(rt), regs(rt2)];
+        match ty {
+            1 => { ops.push(format!("[{}]", xsp(rn))); ops.push(imm(off)); },
+            3 => ops.push(format!("[{}, {}]!", xsp(rn), imm(off))),
+            _ => ops.push(mem_imm(rn, off)),
+        }
+        return Some((mnem.to_owned(), ops));
+    }
+    if bits(insn, 29, 27) != 0b111 { return None; }
+    let size = bits(insn, 31, 30);
+    let opc = bits(insn, 23, 22);
+    // figure out the mnemonic stem and register for the plain forms
+    let (stem, reg, scale) = if v {
+        let scale = if opc >= 2 { if size != 0 { return None; } 4 } else { size };
+        let prefix = ["b", "h", "s", "d", "q"][scale as usize];
+        (if opc & 1 == 0 { "str" } else { "ldr" }, format!("{}{}", prefix, rt), scale)
+    } else {
+        let suffix = ["b", "h", "", ""][size as usize];
+        match opc {
+            0 => (["strb", "strh", "str", "str"][size as usize], r(rt, size == 3, false), size),
+            1 => (["ldrb", "ldrh", "ldr", "ldr"][size as usize], r(rt, size == 3, false), size),
+            2 if size == 3 => ("prfm", prfop(rt), size),
+            2 => (["ldrsb", "ldrsh", "ldrsw", ""][size as usize], x(rt), size),
+            _ if size >= 2 => return None,
+            _ => (if suffix == "b" { "ldrsb" } else { "ldrsh" }, r(rt, false, false), size),
+        }
+    };
+    if bit(insn, 24) {
+        // unsigned offset
+        let off = (bits(insn, 21, 10) as i64) << scale;
+        return Some((stem.to_owned(), vec![reg, mem_imm(rn, off)]));
+    }
+    if !bit(insn, 21) {
+        let off = sext(bits(insn, 20, 12) as u64, 9);
+        let res = match bits(insn, 11, 10) {
+            0 => (stem.replace("ldr", "ldur").replace("str", "stur").replace("prfm", "prfum"), vec![reg, mem_imm(rn, off)]),
+            1 => (stem.to_owned(), vec![reg, format!("[{}]", xsp(rn)), imm(off)]),
+            2 => {
+                if v || stem == "prfm" { return None; }
+                (stem.replace("ldr", "ldtr").replace("str", "sttr"), vec![reg, mem_imm(rn, off)])
+            },
+            _ => (stem.to_owned(), vec![reg, format!("[{}, {}]!", xsp(rn), imm(off))]),
+        };
+        return Some(res);
+    }
+    match bits(insn, 11, 10) {
+        0b10 => {
+            // register offset
+            let rm = bits(insn, 20, 16);
+            let option = bits(insn, 15, 13);
+            if option & 2 == 0 { return None; }
+            let s = bit(insn, 12);
+            let rm_s = r(rm, option & 1 != 0, false);
+            let amount = if s { scale } else { 0 };
+            let index = if option == 3 {
+                if s { format!("{}, lsl #{}", rm_s, amount) } else { rm_s }
+            } else if s {
+                format!("{}, {} #{}", rm_s, EXTEND[option as usize], amount)
+            } else {
+                format!("{}, {}", rm_s, EXTEND[option as usize])
+            };
+            Some((stem.to_owned(), vec![reg, format!("[{}, {}]", xsp(rn), index)]))
+        },
+        0b00 if !v => {
+            // atomic memory operations
+            let (a, rl) = (bit(insn, 23), bit(insn, 22));
+            let rs = bits(insn, 20, 16);
+            let base = match (bit(insn, 15), bits(insn, 14, 12)) {
+                (false, o) => ["ldadd", "ldclr", "ldeor", "ldset", "ldsmax", "ldsmin", "ldumax", "ldumin"][o as usize],
+                (true, 0) => "swp",
+                _ => return None,
+            };
+            let suffix = ["b", "h", "", ""][size as usize];
+            let is64 = size == 3;
+            let name = format!("{}{}{}{}", base, if a { "a" } else { "" }, if rl { "l" } else { "" }, suffix);
+            Some((name, vec![r(rs, is64, false), r(rt, is64, false), format!("[{}]", xsp(rn))]))
+        },
+        _ => None,
+    }
+}
+
+fn dp_reg(insn: u32) -> Option<(String, Vec<String>)> {
+    let is64 = bit(insn, 31);
+    let rd = bits(insn, 4, 0);
+    let rn = bits(insn, 9, 5);
+    let rm = bits(insn, 20, 16);
+    let rr = |n: u32| r(n, is64, false);
+    let op1 = bit(insn, 28);
+    let op2 = bits(insn, 24, 21);
+    if !op1 {
+        if op2 & 8 == 0 {
+            // logical (shifted register)
+            let opc = bits(insn, 30, 29);
+            let n = bit(insn, 21);
+            let shift = bits(insn, 23, 22);
+            let amount = bits(insn, 15, 10);
+            if !is64 && amount >= 32 { return None; }
+            let mut ops = vec![rr(rd), rr(rn), rr(rm)];
+            if amount != 0 || shift != 0 { ops.push(format!("{} #{}", SHIFT[shift as usize], amount)); }
+            let mnem = match (opc, n) {
+                (1, false) if rn == 31 && amount == 0 && shift == 0 => { ops.remove(1); "mov" },
+                (1, true) if rn == 31 => { ops.remove(1); "mvn" },
+                (3, false) if rd == 31 => { ops.remove(0); "tst" },
+                _ => ["and", "bic", "orr", "orn", "eor", "eon", "ands", "bics"][((opc << 1) | n as u32) as usize],
+            };
+            return Some((mnem.to_owned(), ops));
+        }
+        let (sub, setflags) = (bit(insn, 30), bit(insn, 29));
+        let base = match (sub, setflags) { (false, false) => "add", (false, true) => "adds", (true, false) => "sub", (true, true) => "subs" };
+        if op2 & 1 == 0 {
+            // add/sub (shifted register)
+            let shift = bits(insn, 23, 22);
+            let amount = bits(insn, 15, 10);
+            if shift == 3 { return None; }
+            let mut ops = vec![rr(rd), rr(rn), rr(rm)];
+            if amount != 0 { ops.push(format!("{} #{}", SHIFT[shift as usize], amount)); }
+            let mnem = if setflags && rd == 31 {
+                ops.remove(0);
+                if sub { "cmp" } else { "cmn" }
+            } else if sub && rn == 31 {
+                ops.remove(1);
+                if setflags { "negs" } else { "neg" }
+            } else {
+                base
+            };
+            return Some((mnem.to_owned(), ops));
+        }
+        // add/sub (extended register)
+        if bits(insn, 23, 22) != 0 { return None; }
+        let option = bits(insn, 15, 13);
+        let amount = bits(insn, 12, 10);
+        if amount > 4 { return None; }
+        let rd_s = if setflags { rr(rd) } else { r(rd, is64, true) };
+        let rn_s = r(rn, is64, true);
+        let rm_s = r(rm, is64 && option & 3 == 3, false);
+        let ext = if (rd == 31 && !setflags || rn == 31) && option == if is64 { 3 } else { 2 } {
+            if amount == 0 { None } else { Some(format!("lsl #{}", amount)) }
+        } else if amount == 0 {
+            Some(EXTEND[option as usize].to_owned())
+        } else {
+            Some(format!("{} #{}", EXTEND[option as usize], amount))
+        };
+        let mut ops = vec![rd_s, rn_s, rm_s];
+        if let Some(ext) = ext { ops.push(ext); }
+        let mnem = if setflags && rd == 31 {
+            ops.remove(0);
+            if sub { "cmp" } else { "cmn" }
+        } else {
+            base
+        };
+        return Some((mnem.to_owned(), ops));
+    }
+    match op2 {
+        0b0000 => {
+            if bits(insn, 15, 10) != 0 { return None; }
+            let (sub, setflags) = (bit(insn, 30), bit(insn, 29));
+            if sub && rn == 31 {
+                return Some(((if setflags { "ngcs" } else { "ngc" }).to_owned(), vec![rr(rd), rr(rm)]));
+            }
+            let mnem = match (sub, setflags) { (false, false) => "adc", (false, true) => "adcs", (true, false) => "sbc", (true, true) => "sbcs" };
+            Some((mnem.to_owned(), vec![rr(rd), rr(rn), rr(rm)]))
+        },
+        0b0010 => {
+            if !bit(insn, 29) || bit(insn, 10) || bit(insn, 4) { return None; }
+            let mnem = if bit(insn, 30) { "ccmp" } else { "ccmn" };
+            let second = if bit(insn, 11) { uimm(rm as u64) } else { rr(rm) };
+            let nzcv = uimm(bits(insn, 3, 0) as u64);
+            Some((mnem.to_owned(), vec![rr(rn), second, nzcv, COND[bits(insn, 15, 12) as usize].to_owned()]))
+        },
+        0b0100 => {
+            if bit(insn, 29) { return None; }
+            let cond = bits(insn, 15, 12);
+            let inv = COND[(cond ^ 1) as usize].to_owned();
+            let cond_s = COND[cond as usize].to_owned();
+            let res = match (bit(insn, 30), bits(insn, 11, 10)) {
+                (false, 0) => ("csel", vec![rr(rd), rr(rn), rr(rm), cond_s]),
+                (false, 1) if rn == 31 && rm == 31 && cond < 14 => ("cset", vec![rr(rd), inv]),
+                (false, 1) if rn == rm && cond < 14 => ("cinc", vec![rr(rd), rr(rn), inv]),
+                (false, 1) => ("csinc", vec![rr(rd), rr(rn), rr(rm), cond_s]),
+                (true, 0) if rn == 31 && rm == 31 && cond < 14 => ("csetm", vec![rr(rd), inv]),
+                (true, 0) if rn == rm && cond < 14 => ("cinv", vec![rr(rd), rr(rn), inv]),
+                (true, 0) => ("csinv", vec![rr(rd), rr(rn), rr(rm), cond_s]),
+                (true, 1) if rn == rm && cond < 14 => ("cneg", vec![rr(rd), rr(rn), inv]),
+                (true, 1) => ("csneg", vec![rr(rd), rr(rn), rr(rm), cond_s]),
+                _ => return None,
+            };
+            Some((res.0.to_owned(), res.1))
+        },
+        0b0110 => {
+            let opcode = bits(insn, 15, 10);
+            if bit(insn, 30) {
+                // 1 source
+                if rm != 0 || bit(insn, 29) { return None; }
+                let mnem = match (opcode, is64) {
+                    (0, _) => "rbit", (1, _) => "rev16",
+                    (2, false) => "rev", (2, true) => "rev32",
+                    (3, true) => "rev",
+                    (4, _) => "clz", (5, _) => "cls",
+                    _ => return None,
+                };
+                Some((mnem.to_owned(), vec![rr(rd), rr(rn)]))
+            } else {
+                if bit(insn, 29) { return None; }
+                let res = match opcode {
+                    2 => ("udiv", vec![rr(rd), rr(rn), rr(rm)]),
+                    3 => ("sdiv", vec![rr(rd), rr(rn), rr(rm)]),
+                    8 => ("lsl", vec![rr(rd), rr(rn), rr(rm)]),
+                    9 => ("lsr", vec![rr(rd), rr(rn), rr(rm)]),
+                    10 => ("asr", vec![rr(rd), rr(rn), rr(rm)]),
+                    11 => ("ror", vec![rr(rd), rr(rn), rr(rm)]),
+                    0b010000...0b010111 => {
+                        let sz = opcode & 3;
+                        if (sz == 3) != is64 { return None; }
+                        let name = ["crc32b", "crc32h", "crc32w", "crc32x"][sz as usize];
+                        let name = if opcode & 4 != 0 { name.replace("crc32", "crc32c") } else { name.to_owned() };
+                        return Some((name, vec![r(rd, false, false), r(rn, false, false), r(rm, sz == 3, false)]));
+                    },
+                    _ => return None,
+                };
+                Some((res.0.to_owned(), res.1))
+            }
+        },
+        0b1000...0b1111 => {
+            let ra = bits(insn, 14, 10);
+            let o0 = bit(insn, 15);
+            let w = |n: u32| r(n, false, false);
+            let res = match (bits(insn, 30, 29), bits(insn, 23, 21), o0) {
+                (0, 0, false) if ra == 31 => ("mul", vec![rr(rd), rr(rn), rr(rm)]),
+                (0, 0, false) => ("madd", vec![rr(rd), rr(rn), rr(rm), rr(ra)]),
+                (0, 0, true) if ra == 31 => ("mneg", vec![rr(rd), rr(rn), rr(rm)]),
+                (0, 0, true) => ("msub", vec![rr(rd), rr(rn), rr(rm), rr(ra)]),
+                (0, 1, false) if is64 && ra == 31 => ("smull", vec![x(rd), w(rn), w(rm)]),
+                (0, 1, false) if is64 => ("smaddl", vec![x(rd), w(rn), w(rm), x(ra)]),
+                (0, 1, true) if is64 && ra == 31 => ("smnegl", vec![x(rd), w(rn), w(rm)]),
+                (0, 1, true) if is64 => ("smsubl", vec![x(rd), w(rn), w(rm), x(ra)]),
+                (0, 2, false) if is64 => ("smulh", vec![x(rd), x(rn), x(rm)]),
+                (0, 5, false) if is64 && ra == 31 => ("umull", vec![x(rd), w(rn), w(rm)]),
+                (0, 5, false) if is64 => ("umaddl", vec![x(rd), w(rn), w(rm), x(ra)]),
+                (0, 5, true) if is64 && ra == 31 => ("umnegl", vec![x(rd), w(rn), w(rm)]),
+                (0, 5, true) if is64 => ("umsubl", vec![x(rd), w(rn), w(rm), x(ra)]),
+                (0, 6, false) if is64 => ("umulh", vec![x(rd), x(rn), x(rm)]),
+                _ => return None,
+            };
+            Some((res.0.to_owned(), res.1))
+        },
+        _ => None,
+    }
+}
+
+// VFPExpandImm
+fn fp_imm(imm8: u32) -> f64 {
+    let sign = (imm8 >> 7) as u64;
+    let b6 = ((imm8 >> 6) & 1) as u64;
+    let rep: u64 = if b6 != 0 { 0xff } else { 0 };
+    let exp = ((b6 ^ 1) << 10) | (rep << 2) | ((imm8 >> 4) & 3) as u64;
+    let frac = ((imm8 & 0xf) as u64) << 48;
+    unsafe { transmute::<u64, f64>((sign << 63) | (exp << 52) | frac) }
+}
+
+fn fp(insn: u32) -> Option<(String, Vec<String>)> {
+    // only scalar floating point for now
+    if bits(insn, 30, 24) != 0b0011110 || !bit(insn, 21) { return None; }
+    let is64 = bit(insn, 31);
+    let ty = bits(insn, 23, 22);
+    let rd = bits(insn, 4, 0);
+    let rn = bits(insn, 9, 5);
+    let rm = bits(insn, 20, 16);
+    if bits(insn, 15, 10) == 0 {
+        // conversion between FP and integer
+        let rmode = bits(insn, 20, 19);
+        let opcode = bits(insn, 18, 16);
+        let f = |n| fp_reg(ty, n);
+        let res = match (rmode, opcode) {
+            (0, 6) => ("fmov", vec![r(rd, is64, false), try_opt!(f(rn))]),
+            (0, 7) => ("fmov", vec![try_opt!(f(rd)), r(rn, is64, false)]),
+            (0, 2) => ("scvtf", vec![try_opt!(f(rd)), r(rn, is64, false)]),
+            (0, 3) => ("ucvtf", vec![try_opt!(f(rd)), r(rn, is64, false)]),
+            (3, 0) => ("fcvtzs", vec![r(rd, is64, false), try_opt!(f(rn))]),
+            (3, 1) => ("fcvtzu", vec![r(rd, is64, false), try_opt!(f(rn))]),
+            (0, 0) => ("fcvtns", vec![r(rd, is64, false), try_opt!(f(rn))]),
+            (0, 1) => ("fcvtnu", vec![r(rd, is64, false), try_opt!(f(rn))]),
+            (1, 0) => ("fcvtps", vec![r(rd, is64, false), try_opt!(f(rn))]),
+            (1, 1) => ("fcvtpu", vec![r(rd, is64, false), try_opt!(f(rn))]),
+            (2, 0) => ("fcvtms", vec![r(rd, is64, false), try_opt!(f(rn))]),
+            (2, 1) => ("fcvtmu", vec![r(rd, is64, false), try_opt!(f(rn))]),
+            _ => return None,
+        };
+        return Some((res.0.to_owned(), res.1));
+    }
+    if is64 { return None; }
+    if bits(insn, 14, 10) == 0b10000 {
+        let opcode = bits(insn, 20, 15);
+        let res = match opcode {
+            0 => ("fmov", try_opt!(fp_reg(ty, rd))),
+            1 => ("fabs", try_opt!(fp_reg(ty, rd))),
+            2 => ("fneg", try_opt!(fp_reg(ty, rd))),
+            3 => ("fsqrt", try_opt!(fp_reg(ty, rd))),
+            4 => ("fcvt", format!("s{}", rd)),
+            5 => ("fcvt", format!("d{}", rd)),
+            7 => ("fcvt", format!("h{}", rd)),
+            8...15 => (["frintn", "frintp", "frintm", "frintz", "frinta", "", "frintx", "frinti"][(opcode - 8) as usize], try_opt!(fp_reg(ty, rd))),
+            _ => return None,
+        };
+        if res.0 == "" { return None; }
+        return Some((res.0.to_owned(), vec![res.1, try_opt!(fp_reg(ty, rn))]));
+    }
+    if bits(insn, 13, 10) == 0b1000 && bits(insn, 15, 14) == 0 {
+        let op2 = bits(insn, 4, 0);
+        let mnem = if op2 & 0x10 != 0 { "fcmpe" } else { "fcmp" };
+        let second = if op2 & 8 != 0 { "#0.0".to_owned() } else { try_opt!(fp_reg(ty, rm)) };
+        return Some((mnem.to_owned(), vec![try_opt!(fp_reg(ty, rn)), second]));
+    }
+    if bits(insn, 12, 10) == 0b100 {
+        if bits(insn, 9, 5) != 0 { return None; }
+        let val = fp_imm(bits(insn, 20, 13));
+        return Some(("fmov".to_owned(), vec![try_opt!(fp_reg(ty, rd)), format!("#{:?}", val)]));
+    }
+    if bits(insn, 11, 10) == 0b10 {
+        let mnem = match bits(insn, 15, 12) {
+            0 => "fmul", 1 => "fdiv", 2 => "fadd", 3 => "fsub", 4 => "fmax", 5 => "fmin",
+            6 => "fmaxnm", 7 => "fminnm", 8 => "fnmul",
+            _ => return None,
+        };
+        return Some((mnem.to_owned(), vec![try_opt!(fp_reg(ty, rd)), try_opt!(fp_reg(ty, rn)), try_opt!(fp_reg(ty, rm))]));
+    }
+    if bits(insn, 11, 10) == 0b11 {
+        let cond = COND[bits(insn, 15, 12) as usize].to_owned();
+        return Some(("fcsel".to_owned(), vec![try_opt!(fp_reg(ty, rd)), try_opt!(fp_reg(ty, rn)), try_opt!(fp_reg(ty, rm)), cond]));
+    }
+    None
+}
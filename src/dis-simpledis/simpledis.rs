@@ -0,0 +1,132 @@
+// A small disassembler with no dependencies on C libraries, so there is
+// always *something* available even without LLVM.  Output is Intel syntax for
+// x86 and roughly what LLVM prints for AArch64.
+#[macro_use]
+extern crate macros;
+extern crate dis;
+extern crate exec;
+extern crate util;
+use exec::arch;
+use exec::arch::ArchAndOptions;
+
+mod x86;
+mod aarch64;
+
+pub struct SimpleDisassembler {
+    arch: arch::ArchAndOptions,
+}
+
+// The target address is always the last thing on the line, so put its symbol
+// right after it, objdump style.
+fn annotate(text: String, target: Option<u64>, resolver: Option<&dis::SymbolResolver>) -> String {
+    let resolver = some_or!(resolver, { return text; });
+    let name = some_or!(target.and_then(|addr| resolver.describe(exec::VMA(addr))), { return text; });
+    format!("{} <{}>", text, name)
+}
+
+impl dis::Disassembler for SimpleDisassembler {
+    fn arch(&self) -> &arch::ArchAndOptions { &self.arch }
+    fn can_disassemble_to_str(&self) -> bool { true }
+    fn disassemble_insn_to_str(&self, input: &dis::DisassemblerInput) -> Option<(Option<String>, u32)> {
+        match self.arch {
+            ArchAndOptions::X86(..) | ArchAndOptions::X86_64(..) => {
+                let bits = match input.mode {
+                    arch::X86Mode { bits } => bits,
+                    _ => if let ArchAndOptions::X86_64(..) = self.arch { 64 } else { 32 },
+                };
+                // no 16-bit addressing forms
+                if bits == 16 { return None; }
+                let (text, len, target) = try_opt!(x86::disassemble(input.data, input.pc.0, bits));
+                Some((Some(annotate(text, target, input.resolver)), len))
+            },
+            ArchAndOptions::AArch64(..) => {
+                if input.data.len() < 4 { return None; }
+                let insn = util::copy_from_slice::<u32>(&input.data[..4], util::LittleEndian);
+                Some((aarch64::disassemble(insn, input.pc.0).map(|(text, target)| annotate(text, target, input.resolver)), 4))
+            },
+            _ => None,
+        }
+    }
+}
+
+impl dis::DisassemblerStatics for SimpleDisassembler {
+    fn new_with_args(arch: arch::ArchAndOptions, args: &[String]) -> Result<SimpleDisassembler, dis::CreateDisError> {
+        try!(util::do_getopts_or_usage(args, "simple", 0, 0, &mut vec![]).map_err(|e| dis::CreateDisError::InvalidArgs(e)));
+        match arch {
+            ArchAndOptions::X86(..) | ArchAndOptions::X86_64(..) | ArchAndOptions::AArch64(..) => (),
+            _ => return Err(dis::CreateDisError::InvalidArgs(format!("simple disassembler doesn't support arch {}", arch.arch().name()))),
+        }
+        Ok(SimpleDisassembler { arch: arch })
+    }
+    fn name() -> &'static str { "simple" }
+}
+
+#[cfg(test)]
+mod tests {
+    use x86;
+    use aarch64;
+    use annotate;
+    use dis;
+    use exec;
+
+    struct OneSym;
+    impl dis::SymbolResolver for OneSym {
+        fn resolve(&self, addr: exec::VMA) -> Option<(&str, u64)> {
+            if addr.0 >= 0x2000 && addr.0 < 0x2100 { Some(("_foo", addr.0 - 0x2000)) } else { None }
+        }
+    }
+
+    fn x86_64(data: &[u8], pc: u64) -> Option<(String, u32)> {
+        x86::disassemble(data, pc, 64).map(|(text, len, _)| (text, len))
+    }
+
+    #[test]
+    fn test_x86_known_encodings() {
+        assert_eq!(x86_64(&[0xe8, 0xfb, 0x0f, 0x00, 0x00], 0x1000), Some(("call 0x2000".to_owned(), 5)));
+        assert_eq!(x86_64(&[0x48, 0x8d, 0x05, 0xf9, 0x0f, 0x00, 0x00], 0x1000),
+                   Some(("lea rax, [rip + 0xff9] # 0x2000".to_owned(), 7)));
+    }
+
+    #[test]
+    fn test_x86_stale_rex() {
+        // a REX prefix followed by a legacy prefix is ignored
+        assert_eq!(x86_64(&[0x48, 0x66, 0x89, 0xc8], 0), Some(("mov ax, cx".to_owned(), 4)));
+        assert_eq!(x86_64(&[0x41, 0xf3, 0x90], 0), Some(("pause".to_owned(), 3)));
+        assert_eq!(x86_64(&[0x66, 0x41, 0x89, 0xc8], 0), Some(("mov r8w, cx".to_owned(), 4)));
+    }
+
+    fn a64(insn: u32, pc: u64) -> Option<String> {
+        aarch64::disassemble(insn, pc).map(|(text, _)| text)
+    }
+
+    #[test]
+    fn test_aarch64_known_encodings() {
+        assert_eq!(a64(0xb0000000, 0x1000), Some("adrp x0, 0x2000".to_owned()));
+        assert_eq!(a64(0x91004000, 0x1004), Some("add x0, x0, #0x10".to_owned()));
+        assert_eq!(a64(0x94000040, 0x1000), Some("bl 0x1100".to_owned()));
+        assert_eq!(a64(0x97ffffff, 0x1000), Some("bl 0xffc".to_owned()));
+    }
+
+    #[test]
+    fn test_annotate_targets_only() {
+        let ann = |data: &[u8]| {
+            let (text, _, target) = x86::disassemble(data, 0x1000, 64).unwrap();
+            annotate(text, target, Some(&OneSym))
+        };
+        assert_eq!(ann(&[0xe8, 0xfb, 0x0f, 0x00, 0x00]), "call 0x2000 <_foo>");
+        assert_eq!(ann(&[0x48, 0x8d, 0x05, 0xf9, 0x0f, 0x00, 0x00]), "lea rax, [rip + 0xff9] # 0x2000 <_foo>");
+        // cmp byte ptr [rip + 0x2000], 0x20: neither the displacement nor the immediate is described
+        assert_eq!(ann(&[0x80, 0x3d, 0x00, 0x20, 0x00, 0x00, 0x20]), "cmp byte ptr [rip + 0x2000], 0x20 # 0x3007");
+        // mov eax, 0x2000 is just a number
+        assert_eq!(ann(&[0xb8, 0x00, 0x20, 0x00, 0x00]), "mov eax, 0x2000");
+
+        let ann64 = |insn: u32, pc: u64| {
+            let (text, target) = aarch64::disassemble(insn, pc).unwrap();
+            annotate(text, target, Some(&OneSym))
+        };
+        assert_eq!(ann64(0xb0000000, 0x1000), "adrp x0, 0x2000 <_foo>");
+        assert_eq!(ann64(0x91004000, 0x1004), "add x0, x0, #0x10");
+        assert_eq!(ann64(0x94000440, 0x1000), "bl 0x2100");
+        assert_eq!(ann64(0x94000401, 0x1000), "bl 0x2004 <_foo+0x4>");
+    }
+}
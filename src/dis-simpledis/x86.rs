@@ -0,0 +1,931 @@
+// A table-free-ish x86/x86_64 decoder producing Intel syntax.  It covers the
+// general purpose integer instructions, x87 memory forms and the common
+// SSE/SSE2 subset; anything else (VEX, 3-byte opcodes, ...) decodes as None.
+
+static REG64: [&'static str; 16] = ["rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15"];
+static REG32: [&'static str; 16] = ["eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "r8d", "r9d", "r10d", "r11d", "r12d", "r13d", "r14d", "r15d"];
+static REG16: [&'static str; 16] = ["ax", "cx", "dx", "bx", "sp", "bp", "si", "di", "r8w", "r9w", "r10w", "r11w", "r12w", "r13w", "r14w", "r15w"];
+static REG8_REX: [&'static str; 16] = ["al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil", "r8b", "r9b", "r10b", "r11b", "r12b", "r13b", "r14b", "r15b"];
+static REG8: [&'static str; 8] = ["al", "cl", "dl", "bl", "ah", "ch", "dh", "bh"];
+static XMM: [&'static str; 16] = ["xmm0", "xmm1", "xmm2", "xmm3", "xmm4", "xmm5", "xmm6", "xmm7", "xmm8", "xmm9", "xmm10", "xmm11", "xmm12", "xmm13", "xmm14", "xmm15"];
+static MM: [&'static str; 8] = ["mm0", "mm1", "mm2", "mm3", "mm4", "mm5", "mm6", "mm7"];
+static SEG: [&'static str; 8] = ["es", "cs", "ss", "ds", "fs", "gs", "?", "?"];
+static ST: [&'static str; 8] = ["st(0)", "st(1)", "st(2)", "st(3)", "st(4)", "st(5)", "st(6)", "st(7)"];
+static CC: [&'static str; 16] = ["o", "no", "b", "ae", "e", "ne", "be", "a", "s", "ns", "p", "np", "l", "ge", "le", "g"];
+static GRP1: [&'static str; 8] = ["add", "or", "adc", "sbb", "and", "sub", "xor", "cmp"];
+static GRP2: [&'static str; 8] = ["rol", "ror", "rcl", "rcr", "shl", "shr", "sal", "sar"];
+static GRP3: [&'static str; 8] = ["test", "test", "not", "neg", "mul", "imul", "div", "idiv"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RegKind { Gpr, Xmm, Mm }
+
+#[derive(Clone)]
+enum Operand {
+    Reg(&'static str),
+    Imm(i64),
+    Target(u64),
+    Mem {
+        size: u8, // bytes; 0 = unspecified
+        seg: Option<&'static str>,
+        base: Option<&'static str>,
+        index: Option<&'static str>,
+        scale: u8,
+        disp: i64,
+        rip: bool,
+    },
+}
+
+#[derive(Clone, Copy)]
+struct ModRM { md: u8, reg: u8, rm: u8 }
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    pc: u64,
+    bits: u8,
+    opsize: bool,
+    addrsize: bool,
+    rep: u8,
+    lock: bool,
+    seg: Option<&'static str>,
+    rex: u8,
+}
+
+fn gpr(size: u8, n: u8, rex: bool) -> &'static str {
+    match size {
+        1 => if rex { REG8_REX[n as usize] } else { REG8[n as usize & 7] },
+        2 => REG16[n as usize],
+        4 => REG32[n as usize],
+        _ => REG64[n as usize],
+    }
+}
+
+fn size_name(size: u8) -> &'static str {
+    match size {
+        1 => "byte",
+        2 => "word",
+        4 => "dword",
+        8 => "qword",
+        10 => "tbyte",
+        16 => "xmmword",
+        _ => "",
+    }
+}
+
+impl<'a> Decoder<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        // 15 bytes is the architectural limit
+        if self.pos >= 15 { return None; }
+        let b = some_or!(self.data.get(self.pos), { return None; });
+        self.pos += 1;
+        Some(*b)
+    }
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).cloned()
+    }
+    fn imm(&mut self, size: u8) -> Option<i64> {
+        let mut val: u64 = 0;
+        for i in 0..size {
+            val |= (try_opt!(self.byte()) as u64) << (8 * i);
+        }
+        let shift = 64 - 8 * (size as u32);
+        Some(((val << shift) as i64) >> shift)
+    }
+    fn rex_w(&self) -> bool { self.rex & 8 != 0 }
+    fn rex_r(&self) -> u8 { (self.rex & 4) << 1 }
+    fn rex_x(&self) -> u8 { (self.rex & 2) << 2 }
+    fn rex_b(&self) -> u8 { (self.rex & 1) << 3 }
+
+    // operand size for 'v' operands
+    fn ov(&self) -> u8 {
+        if self.rex_w() { 8 } else if self.opsize { 2 } else { 4 }
+    }
+    // same, for instructions that default to 64-bit in long mode
+    fn ov64(&self) -> u8 {
+        if self.bits == 64 { if self.opsize { 2 } else { 8 } } else { self.ov() }
+    }
+    // 'z' immediates are at most 4 bytes
+    fn oz(&self) -> u8 {
+        if self.ov() == 2 { 2 } else { 4 }
+    }
+    fn modrm(&mut self) -> Option<ModRM> {
+        let b = try_opt!(self.byte());
+        Some(ModRM { md: b >> 6, reg: (b >> 3) & 7, rm: b & 7 })
+    }
+    fn reg_of(&self, kind: RegKind, size: u8, n: u8) -> &'static str {
+        match kind {
+            RegKind::Gpr => gpr(size, n, self.rex != 0),
+            RegKind::Xmm => XMM[n as usize],
+            RegKind::Mm => MM[n as usize & 7],
+        }
+    }
+    fn g(&self, m: ModRM, size: u8) -> Operand {
+        Operand::Reg(gpr(size, m.reg | self.rex_r(), self.rex != 0))
+    }
+    fn gk(&self, m: ModRM, kind: RegKind) -> Operand {
+        Operand::Reg(self.reg_of(kind, 16, m.reg | self.rex_r()))
+    }
+    fn e(&mut self, m: ModRM, size: u8) -> Option<Operand> {
+        self.ek(m, RegKind::Gpr, size)
+    }
+    fn ek(&mut self, m: ModRM, kind: RegKind, size: u8) -> Option<Operand> {
+        if m.md == 3 {
+            let n = m.rm | self.rex_b();
+            return Some(Operand::Reg(self.reg_of(kind, size, n)));
+        }
+        self.mem(m, size)
+    }
+    fn mem(&mut self, m: ModRM, size: u8) -> Option<Operand> {
+        if m.md == 3 { return None; }
+        if self.bits != 64 && self.addrsize {
+            // 16-bit addressing
+            return None;
+        }
+        let asize = if self.bits == 64 && !self.addrsize { 8 } else { 4 };
+        let (mut base, mut index, mut scale, mut disp, mut rip) = (None, None, 1, 0, false);
+        if m.rm == 4 {
+            let sib = try_opt!(self.byte());
+            scale = 1 << (sib >> 6);
+            let idx = ((sib >> 3) & 7) | self.rex_x();
+            if idx != 4 {
+                index = Some(gpr(asize, idx, true));
+            }
+            if sib & 7 == 5 && m.md == 0 {
+                disp = try_opt!(self.imm(4));
+            } else {
+                base = Some(gpr(asize, (sib & 7) | self.rex_b(), true));
+            }
+        } else if m.rm == 5 && m.md == 0 {
+            disp = try_opt!(self.imm(4));
+            if self.bits == 64 {
+                rip = true;
+                base = Some(if asize == 8 { "rip" } else { "eip" });
+            }
+        } else {
+            base = Some(gpr(asize, m.rm | self.rex_b(), true));
+        }
+        match m.md {
+            1 => disp = try_opt!(self.imm(1)),
+            2 => disp = try_opt!(self.imm(4)),
+            _ => (),
+        }
+        Some(Operand::Mem { size: size, seg: self.seg, base: base, index: index, scale: scale, disp: disp, rip: rip })
+    }
+    fn rel(&mut self, size: u8) -> Option<Operand> {
+        let disp = try_opt!(self.imm(size));
+        let mut target = self.pc.wrapping_add(self.pos as u64).wrapping_add(disp as u64);
+        if self.bits != 64 { target &= 0xffffffff; }
+        Some(Operand::Target(target))
+    }
+    // mandatory prefix for SSE: 0 = none, 1 = 66, 2 = F2, 3 = F3
+    fn sse_prefix(&mut self) -> usize {
+        let mp = match self.rep { 0xf2 => 2, 0xf3 => 3, _ => if self.opsize { 1 } else { 0 } };
+        if mp >= 2 { self.rep = 0; } else if mp == 1 { self.opsize = false; }
+        mp
+    }
+
+    fn decode(&mut self) -> Option<(String, Vec<Operand>)> {
+        let mut op;
+        loop {
+            op = try_opt!(self.byte());
+            match op {
+                0x66 => self.opsize = true,
+                0x67 => self.addrsize = true,
+                0xf0 => self.lock = true,
+                0xf2 | 0xf3 => self.rep = op,
+                0x26 | 0x2e | 0x36 | 0x3e => self.seg = Some(SEG[((op >> 3) & 3) as usize]),
+                0x64 | 0x65 => self.seg = Some(SEG[(op & 7) as usize]),
+                0x40...0x4f if self.bits == 64 => {
+                    self.rex = op;
+                    continue;
+                },
+                _ => break,
+            }
+            // REX only counts if it comes right before the opcode
+            self.rex = 0;
+        }
+        let x64 = self.bits == 64;
+        let (mnem, ops): (&str, Vec<Operand>) = match op {
+            0x00...0x3f if op & 7 < 6 => {
+                let mnem = GRP1[(op >> 3) as usize];
+                match op & 7 {
+                    0 => { let m = try_opt!(self.modrm()); (mnem, vec![try_opt!(self.e(m, 1)), self.g(m, 1)]) },
+                    1 => { let m = try_opt!(self.modrm()); let s = self.ov(); (mnem, vec![try_opt!(self.e(m, s)), self.g(m, s)]) },
+                    2 => { let m = try_opt!(self.modrm()); (mnem, vec![self.g(m, 1), try_opt!(self.e(m, 1))]) },
+                    3 => { let m = try_opt!(self.modrm()); let s = self.ov(); (mnem, vec![self.g(m, s), try_opt!(self.e(m, s))]) },
+                    4 => (mnem, vec![Operand::Reg("al"), Operand::Imm(try_opt!(self.imm(1)))]),
+                    _ => { let s = self.ov(); let z = self.oz(); (mnem, vec![Operand::Reg(gpr(s, 0, false)), Operand::Imm(try_opt!(self.imm(z)))]) },
+                }
+            },
+            0x06 | 0x0e | 0x16 | 0x1e if !x64 => ("push", vec![Operand::Reg(SEG[(op >> 3) as usize])]),
+            0x07 | 0x17 | 0x1f if !x64 => ("pop", vec![Operand::Reg(SEG[(op >> 3) as usize])]),
+            0x27 if !x64 => ("daa", vec![]),
+            0x2f if !x64 => ("das", vec![]),
+            0x37 if !x64 => ("aaa", vec![]),
+            0x3f if !x64 => ("aas", vec![]),
+            0x40...0x47 => ("inc", vec![Operand::Reg(gpr(self.ov(), op & 7, false))]),
+            0x48...0x4f => ("dec", vec![Operand::Reg(gpr(self.ov(), op & 7, false))]),
+            0x50...0x57 => ("push", vec![Operand::Reg(gpr(self.ov64(), (op & 7) | self.rex_b(), false))]),
+            0x58...0x5f => ("pop", vec![Operand::Reg(gpr(self.ov64(), (op & 7) | self.rex_b(), false))]),
+            0x60 if !x64 => (if self.opsize { "pusha" } else { "pushad" }, vec![]),
+            0x61 if !x64 => (if self.opsize { "popa" } else { "popad" }, vec![]),
+            0x63 if x64 => {
+                let m = try_opt!(self.modrm());
+                let s = self.ov();
+                ("movsxd", vec![self.g(m, s), try_opt!(self.e(m, 4))])
+            },
+            0x68 => { let z = self.oz(); ("push", vec![Operand::Imm(try_opt!(self.imm(z)))]) },
+            0x6a => ("push", vec![Operand::Imm(try_opt!(self.imm(1)))]),
+            0x69 | 0x6b => {
+                let m = try_opt!(self.modrm());
+                let s = self.ov();
+                let g = self.g(m, s);
+                let e = try_opt!(self.e(m, s));
+                let isize = if op == 0x6b { 1 } else { self.oz() };
+                ("imul", vec![g, e, Operand::Imm(try_opt!(self.imm(isize)))])
+            },
+            0x6c => ("insb", vec![]),
+            0x6d => (if self.opsize { "insw" } else { "insd" }, vec![]),
+            0x6e => ("outsb", vec![]),
+            0x6f => (if self.opsize { "outsw" } else { "outsd" }, vec![]),
+            0x70...0x7f => {
+                let target = try_opt!(self.rel(1));
+                return Some((format!("j{}", CC[(op & 0xf) as usize]), vec![target]));
+            },
+            0x80...0x83 => {
+                if op == 0x82 && x64 { return None; }
+                let m = try_opt!(self.modrm());
+                let s = if op & 1 == 0 { 1 } else { self.ov() };
+                let e = try_opt!(self.e(m, s));
+                let isize = if op == 0x81 { self.oz() } else { 1 };
+                (GRP1[m.reg as usize], vec![e, Operand::Imm(try_opt!(self.imm(isize)))])
+            },
+            0x84...0x8b => {
+                let m = try_opt!(self.modrm());
+                let s = if op & 1 == 0 { 1 } else { self.ov() };
+                let mnem = match op { 0x84 | 0x85 => "test", 0x86 | 0x87 => "xchg", _ => "mov" };
+                let (e, g) = (try_opt!(self.e(m, s)), self.g(m, s));
+                (mnem, if op < 0x8a { vec![e, g] } else { vec![g, e] })
+            },
+            0x8c => {
+                let m = try_opt!(self.modrm());
+                let s = if m.md == 3 { self.ov() } else { 2 };
+                ("mov", vec![try_opt!(self.e(m, s)), Operand::Reg(SEG[m.reg as usize])])
+            },
+            0x8d => {
+                let m = try_opt!(self.modrm());
+                let s = self.ov();
+                ("lea", vec![self.g(m, s), try_opt!(self.mem(m, 0))])
+            },
+            0x8e => {
+                let m = try_opt!(self.modrm());
+                ("mov", vec![Operand::Reg(SEG[m.reg as usize]), try_opt!(self.e(m, 2))])
+            },
+            0x8f => {
+                let m = try_opt!(self.modrm());
+                if m.reg != 0 { return None; }
+                let s = self.ov64();
+                ("pop", vec![try_opt!(self.e(m, s))])
+            },
+            0x90 if self.rex_b() == 0 => if self.rep == 0xf3 { self.rep = 0; ("pause", vec![]) } else { ("nop", vec![]) },
+            0x90...0x97 => {
+                let s = self.ov();
+                ("xchg", vec![Operand::Reg(gpr(s, (op & 7) | self.rex_b(), false)), Operand::Reg(gpr(s, 0, false))])
+            },
+            0x98 => (match self.ov() { 2 => "cbw", 4 => "cwde", _ => "cdqe" }, vec![]),
+            0x99 => (match self.ov() { 2 => "cwd", 4 => "cdq", _ => "cqo" }, vec![]),
+            0x9b => ("wait", vec![]),
+            0x9c => ("pushf", vec![]),
+            0x9d => ("popf", vec![]),
+            0x9e => ("sahf", vec![]),
+            0x9f => ("lahf", vec![]),
+            0xa0...0xa3 => {
+                let s = if op & 1 == 0 { 1 } else { self.ov() };
+                let asize = if x64 && !self.addrsize { 8 } else { 4 };
+                let disp = try_opt!(self.imm(asize));
+                let mem = Operand::Mem { size: s, seg: self.seg, base: None, index: None, scale: 1, disp: disp, rip: false };
+                let reg = Operand::Reg(gpr(s, 0, false));
+                ("mov", if op < 0xa2 { vec![reg, mem] } else { vec![mem, reg] })
+            },
+            0xa4...0xa7 | 0xaa...0xaf => {
+                let base = match op & !1 { 0xa4 => "movs", 0xa6 => "cmps", 0xaa => "stos", 0xac => "lods", _ => "scas" };
+                let suffix = if op & 1 == 0 { "b" } else { match self.ov() { 2 => "w", 4 => "d", _ => "q" } };
+                let prefix = match (self.rep, op & !1) {
+                    (0xf3, 0xa6) | (0xf3, 0xae) => "repe ",
+                    (0xf3, _) => "rep ",
+                    (0xf2, _) => "repne ",
+                    _ => "",
+                };
+                self.rep = 0;
+                return Some((format!("{}{}{}", prefix, base, suffix), vec![]));
+            },
+            0xa8 => ("test", vec![Operand::Reg("al"), Operand::Imm(try_opt!(self.imm(1)))]),
+            0xa9 => { let s = self.ov(); let z = self.oz(); ("test", vec![Operand::Reg(gpr(s, 0, false)), Operand::Imm(try_opt!(self.imm(z)))]) },
+            0xb0...0xb7 => ("mov", vec![Operand::Reg(gpr(1, (op & 7) | self.rex_b(), self.rex != 0)), Operand::Imm(try_opt!(self.imm(1)))]),
+            0xb8...0xbf => {
+                let s = self.ov();
+                ("mov", vec![Operand::Reg(gpr(s, (op & 7) | self.rex_b(), false)), Operand::Imm(try_opt!(self.imm(s)))])
+            },
+            0xc0 | 0xc1 | 0xd0...0xd3 => {
+                let m = try_opt!(self.modrm());
+                let s = if op & 1 == 0 { 1 } else { self.ov() };
+                let e = try_opt!(self.e(m, s));
+                let count = match op {
+                    0xc0 | 0xc1 => Operand::Imm(try_opt!(self.imm(1))),
+                    0xd0 | 0xd1 => Operand::Imm(1),
+                    _ => Operand::Reg("cl"),
+                };
+                (GRP2[m.reg as usize], vec![e, count])
+            },
+            0xc2 => { self.rep = 0; ("ret", vec![Operand::Imm(try_opt!(self.imm(2)) & 0xffff)]) },
+            0xc3 => { let mnem = if self.rep == 0xf3 { "rep ret" } else { "ret" }; self.rep = 0; (mnem, vec![]) },
+            0xc6 | 0xc7 => {
+                let m = try_opt!(self.modrm());
+                if m.reg != 0 { return None; }
+                let s = if op == 0xc6 { 1 } else { self.ov() };
+                let e = try_opt!(self.e(m, s));
+                let isize = if op == 0xc6 { 1 } else { self.oz() };
+                ("mov", vec![e, Operand::Imm(try_opt!(self.imm(isize)))])
+            },
+            0xc8 => {
+                let size = try_opt!(self.imm(2)) & 0xffff;
+                ("enter", vec![Operand::Imm(size), Operand::Imm(try_opt!(self.imm(1)) & 0xff)])
+            },
+            0xc9 => ("leave", vec![]),
+            0xca => ("retf", vec![Operand::Imm(try_opt!(self.imm(2)) & 0xffff)]),
+            0xcb => ("retf", vec![]),
+            0xcc => ("int3", vec![]),
+            0xcd => ("int", vec![Operand::Imm(try_opt!(self.imm(1)) & 0xff)]),
+            0xcf => (match self.ov() { 2 => "iret", 4 => "iretd", _ => "iretq" }, vec![]),
+            0xd8...0xdf => return self.x87(op),
+            0xe0...0xe3 => {
+                let mnem = match op { 0xe0 => "loopne", 0xe1 => "loope", 0xe2 => "loop", _ => if x64 { "jrcxz" } else { "jecxz" } };
+                (mnem, vec![try_opt!(self.rel(1))])
+            },
+            0xe4 => ("in", vec![Operand::Reg("al"), Operand::Imm(try_opt!(self.imm(1)) & 0xff)]),
+            0xe5 => { let s = self.oz(); ("in", vec![Operand::Reg(gpr(s, 0, false)), Operand::Imm(try_opt!(self.imm(1)) & 0xff)]) },
+            0xe6 => ("out", vec![Operand::Imm(try_opt!(self.imm(1)) & 0xff), Operand::Reg("al")]),
+            0xe7 => { let s = self.oz(); ("out", vec![Operand::Imm(try_opt!(self.imm(1)) & 0xff), Operand::Reg(gpr(s, 0, false))]) },
+            0xe8 => { let z = self.oz(); ("call", vec![try_opt!(self.rel(z))]) },
+            0xe9 => { let z = self.oz(); ("jmp", vec![try_opt!(self.rel(z))]) },
+            0xeb => ("jmp", vec![try_opt!(self.rel(1))]),
+            0xec => ("in", vec![Operand::Reg("al"), Operand::Reg("dx")]),
+            0xed => { let s = self.oz(); ("in", vec![Operand::Reg(gpr(s, 0, false)), Operand::Reg("dx")]) },
+            0xee => ("out", vec![Operand::Reg("dx"), Operand::Reg("al")]),
+            0xef => { let s = self.oz(); ("out", vec![Operand::Reg("dx"), Operand::Reg(gpr(s, 0, false))]) },
+            0xf1 => ("int1", vec![]),
+            0xf4 => ("hlt", vec![]),
+            0xf5 => ("cmc", vec![]),
+            0xf6 | 0xf7 => {
+                let m = try_opt!(self.modrm());
+                let s = if op == 0xf6 { 1 } else { self.ov() };
+                let e = try_opt!(self.e(m, s));
+                if m.reg < 2 {
+                    let isize = if op == 0xf6 { 1 } else { self.oz() };
+                    ("test", vec![e, Operand::Imm(try_opt!(self.imm(isize)))])
+                } else {
+                    (GRP3[m.reg as usize], vec![e])
+                }
+            },
+            0xf8 => ("clc", vec![]),
+            0xf9 => ("stc", vec![]),
+            0xfa => ("cli", vec![]),
+            0xfb => ("sti", vec![]),
+            0xfc => ("cld", vec![]),
+            0xfd => ("std", vec![]),
+            0xfe => {
+                let m = try_opt!(self.modrm());
+                let mnem = match m.reg { 0 => "inc", 1 => "dec", _ => return None };
+                (mnem, vec![try_opt!(self.e(m, 1))])
+            },
+            0xff => {
+                let m = try_opt!(self.modrm());
+                match m.reg {
+                    0 | 1 => { let s = self.ov(); (if m.reg == 0 { "inc" } else { "dec" }, vec![try_opt!(self.e(m, s))]) },
+                    2 | 4 => {
+                        let s = if x64 { 8 } else { self.ov() };
+                        (if m.reg == 2 { "call" } else { "jmp" }, vec![try_opt!(self.e(m, s))])
+                    },
+                    3 | 5 => {
+                        let s = self.ov() + 2;
+                        (if m.reg == 3 { "call far" } else { "jmp far" }, vec![try_opt!(self.mem(m, s))])
+                    },
+                    6 => { let s = self.ov64(); ("push", vec![try_opt!(self.e(m, s))]) },
+                    _ => return None,
+                }
+            },
+            0x0f => return self.decode_0f(),
+            _ => return None,
+        };
+        Some((mnem.to_owned(), ops))
+    }
+
+    fn x87(&mut self, op: u8) -> Option<(String, Vec<Operand>)> {
+        let m = try_opt!(self.modrm());
+        if m.md != 3 {
+            let (mnem, size) = match (op, m.reg) {
+                (0xd8, r) => (["fadd", "fmul", "fcom", "fcomp", "fsub", "fsubr", "fdiv", "fdivr"][r as usize], 4),
+                (0xdc, r) => (["fadd", "fmul", "fcom", "fcomp", "fsub", "fsubr", "fdiv", "fdivr"][r as usize], 8),
+                (0xda, r) => (["fiadd", "fimul", "ficom", "ficomp", "fisub", "fisubr", "fidiv", "fidivr"][r as usize], 4),
+                (0xde, r) => (["fiadd", "fimul", "ficom", "ficomp", "fisub", "fisubr", "fidiv", "fidivr"][r as usize], 2),
+                (0xd9, 0) => ("fld", 4),
+                (0xd9, 2) => ("fst", 4),
+                (0xd9, 3) => ("fstp", 4),
+                (0xd9, 4) => ("fldenv", 0),
+                (0xd9, 5) => ("fldcw", 2),
+                (0xd9, 6) => ("fnstenv", 0),
+                (0xd9, 7) => ("fnstcw", 2),
+                (0xdb, 0) => ("fild", 4),
+                (0xdb, 1) => ("fisttp", 4),
+                (0xdb, 2) => ("fist", 4),
+                (0xdb, 3) => ("fistp", 4),
+                (0xdb, 5) => ("fld", 10),
+                (0xdb, 7) => ("fstp", 10),
+                (0xdd, 0) => ("fld", 8),
+                (0xdd, 1) => ("fisttp", 8),
+                (0xdd, 2) => ("fst", 8),
+                (0xdd, 3) => ("fstp", 8),
+                (0xdd, 4) => ("frstor", 0),
+                (0xdd, 6) => ("fnsave", 0),
+                (0xdd, 7) => ("fnstsw", 2),
+                (0xdf, 0) => ("fild", 2),
+                (0xdf, 1) => ("fisttp", 2),
+                (0xdf, 2) => ("fist", 2),
+                (0xdf, 3) => ("fistp", 2),
+                (0xdf, 5) => ("fild", 8),
+                (0xdf, 7) => ("fistp", 8),
+                _ => return None,
+            };
+            return Some((mnem.to_owned(), vec![try_opt!(self.mem(m, size))]));
+        }
+        let sti = Operand::Reg(ST[m.rm as usize]);
+        let st0 = Operand::Reg(ST[0]);
+        let arith = ["fadd", "fmul", "fcom", "fcomp", "fsub", "fsubr", "fdiv", "fdivr"];
+        let (mnem, ops) = match (op, m.reg) {
+            (0xd8, r) => (arith[r as usize], vec![st0, sti]),
+            (0xdc, r) if r != 2 && r != 3 => (arith[r as usize], vec![sti, st0]),
+            (0xde, 3) if m.rm == 1 => ("fcompp", vec![]),
+            (0xde, r) if r != 2 && r != 3 => (["faddp", "fmulp", "", "", "fsubrp", "fsubp", "fdivrp", "fdivp"][r as usize], vec![sti, st0]),
+            (0xd9, 0) => ("fld", vec![sti]),
+            (0xd9, 1) => ("fxch", vec![sti]),
+            (0xdd, 0) => ("ffree", vec![sti]),
+            (0xdd, 2) => ("fst", vec![sti]),
+            (0xdd, 3) => ("fstp", vec![sti]),
+            (0xdd, 4) => ("fucom", vec![sti]),
+            (0xdd, 5) => ("fucomp", vec![sti]),
+            (0xdb, 5) => ("fucomi", vec![st0, sti]),
+            (0xdb, 6) => ("fcomi", vec![st0, sti]),
+            (0xdf, 5) => ("fucomip", vec![st0, sti]),
+            (0xdf, 6) => ("fcomip", vec![st0, sti]),
+            (0xda, 5) if m.rm == 1 => ("fucompp", vec![]),
+            (0xdf, 4) if m.rm == 0 => ("fnstsw", vec![Operand::Reg("ax")]),
+            (0xdb, 4) if m.rm == 2 => ("fnclex", vec![]),
+            (0xdb, 4) if m.rm == 3 => ("fninit", vec![]),
+            (0xd9, 2) if m.rm == 0 => ("fnop", vec![]),
+            (0xd9, 4) | (0xd9, 5) | (0xd9, 6) | (0xd9, 7) => {
+                let mnem = match (m.reg << 3) | m.rm {
+                    0o40 => "fchs", 0o41 => "fabs", 0o44 => "ftst", 0o45 => "fxam",
+                    0o50 => "fld1", 0o51 => "fldl2t", 0o52 => "fldl2e", 0o53 => "fldpi",
+                    0o54 => "fldlg2", 0o55 => "fldln2", 0o56 => "fldz",
+                    0o60 => "f2xm1", 0o61 => "fyl2x", 0o62 => "fptan", 0o63 => "fpatan",
+                    0o64 => "fxtract", 0o65 => "fprem1", 0o66 => "fdecstp", 0o67 => "fincstp",
+                    0o70 => "fprem", 0o71 => "fyl2xp1", 0o72 => "fsqrt", 0o73 => "fsincos",
+                    0o74 => "frndint", 0o75 => "fscale", 0o76 => "fsin", 0o77 => "fcos",
+                    _ => return None,
+                };
+                (mnem, vec![])
+            },
+            _ => return None,
+        };
+        Some((mnem.to_owned(), ops))
+    }
+
+    fn decode_0f(&mut self) -> Option<(String, Vec<Operand>)> {
+        let op = try_opt!(self.byte());
+        let x64 = self.bits == 64;
+        let (mnem, ops): (&str, Vec<Operand>) = match op {
+            0x01 => {
+                let m = try_opt!(self.modrm());
+                if m.md == 3 {
+                    let mnem = match (m.reg << 3) | m.rm {
+                        0o20 => "xgetbv", 0o21 => "xsetbv", 0o31 => "vmmcall",
+                        0o70 => "swapgs", 0o71 => "rdtscp", 0o12 => "clac", 0o13 => "stac",
+                        _ => return None,
+                    };
+                    (mnem, vec![])
+                } else {
+                    let mnem = ["sgdt", "sidt", "lgdt", "lidt", "smsw", "", "lmsw", "invlpg"][m.reg as usize];
+                    if mnem == "" { return None; }
+                    (mnem, vec![try_opt!(self.mem(m, 0))])
+                }
+            },
+            0x05 if x64 => ("syscall", vec![]),
+            0x06 => ("clts", vec![]),
+            0x07 if x64 => ("sysret", vec![]),
+            0x0b => ("ud2", vec![]),
+            0x0d => {
+                let m = try_opt!(self.modrm());
+                (if m.reg == 1 { "prefetchw" } else { "prefetch" }, vec![try_opt!(self.mem(m, 1))])
+            },
+            0x10...0x17 | 0x28 | 0x29 | 0x2e | 0x2f | 0x50...0x5f | 0xc2 | 0xc6 => return self.sse_fp(op),
+            0x18 => {
+                let m = try_opt!(self.modrm());
+                let mnem = match m.reg { 0 => "prefetchnta", 1 => "prefetcht0", 2 => "prefetcht1", 3 => "prefetcht2", _ => "nop" };
+                (mnem, vec![try_opt!(self.mem(m, 1))])
+            },
+            0x1e if self.rep == 0xf3 && (self.peek() == Some(0xfa) || self.peek() == Some(0xfb)) => {
+                let b = try_opt!(self.byte());
+                self.rep = 0;
+                (if b == 0xfa { "endbr64" } else { "endbr32" }, vec![])
+            },
+            0x19...0x1f => {
+                let m = try_opt!(self.modrm());
+                let s = self.ov();
+                ("nop", vec![try_opt!(self.e(m, s))])
+            },
+            0x20 | 0x22 => {
+                let m = try_opt!(self.modrm());
+                let s = if x64 { 8 } else { 4 };
+                let cr = ["cr0", "cr1", "cr2", "cr3", "cr4", "cr5", "cr6", "cr7", "cr8"];
+                let cr = Operand::Reg(some_or!(cr.get((m.reg | self.rex_r()) as usize), { return None; }));
+                let g = Operand::Reg(gpr(s, m.rm | self.rex_b(), false));
+                ("mov", if op == 0x20 { vec![g, cr] } else { vec![cr, g] })
+            },
+            0x2a => {
+                let mp = self.sse_prefix();
+                let m = try_opt!(self.modrm());
+                let mnem = match mp { 2 => "cvtsi2sd", 3 => "cvtsi2ss", _ => return None };
+                let s = self.ov();
+                (mnem, vec![self.gk(m, RegKind::Xmm), try_opt!(self.e(m, s))])
+            },
+            0x2c | 0x2d => {
+                let mp = self.sse_prefix();
+                let m = try_opt!(self.modrm());
+                let (mnem, size) = match (mp, op) {
+                    (2, 0x2c) => ("cvttsd2si", 8), (3, 0x2c) => ("cvttss2si", 4),
+                    (2, _) => ("cvtsd2si", 8), (3, _) => ("cvtss2si", 4),
+                    _ => return None,
+                };
+                let s = self.ov();
+                (mnem, vec![self.g(m, s), try_opt!(self.ek(m, RegKind::Xmm, size))])
+            },
+            0x30 => ("wrmsr", vec![]),
+            0x31 => ("rdtsc", vec![]),
+            0x32 => ("rdmsr", vec![]),
+            0x33 => ("rdpmc", vec![]),
+            0x34 => ("sysenter", vec![]),
+            0x35 => ("sysexit", vec![]),
+            0x40...0x4f => {
+                let m = try_opt!(self.modrm());
+                let s = self.ov();
+                let ops = vec![self.g(m, s), try_opt!(self.e(m, s))];
+                return Some((format!("cmov{}", CC[(op & 0xf) as usize]), ops));
+            },
+            0x60...0x7f | 0xd1...0xff => return self.sse_int(op),
+            0x80...0x8f => {
+                let z = self.oz();
+                let target = try_opt!(self.rel(z));
+                return Some((format!("j{}", CC[(op & 0xf) as usize]), vec![target]));
+            },
+            0x90...0x9f => {
+                let m = try_opt!(self.modrm());
+                let ops = vec![try_opt!(self.e(m, 1))];
+                return Some((format!("set{}", CC[(op & 0xf) as usize]), ops));
+            },
+            0xa0 => ("push", vec![Operand::Reg("fs")]),
+            0xa1 => ("pop", vec![Operand::Reg("fs")]),
+            0xa2 => ("cpuid", vec![]),
+            0xa8 => ("push", vec![Operand::Reg("gs")]),
+            0xa9 => ("pop", vec![Operand::Reg("gs")]),
+            0xa3 | 0xab | 0xb3 | 0xbb => {
+                let m = try_opt!(self.modrm());
+                let s = self.ov();
+                let mnem = match op { 0xa3 => "bt", 0xab => "bts", 0xb3 => "btr", _ => "btc" };
+                (mnem, vec![try_opt!(self.e(m, s)), self.g(m, s)])
+            },
+            0xa4 | 0xa5 | 0xac | 0xad => {
+                let m = try_opt!(self.modrm());
+                let s = self.ov();
+                let e = try_opt!(self.e(m, s));
+                let count = if op & 1 == 0 { Operand::Imm(try_opt!(self.imm(1)) & 0xff) } else { Operand::Reg("cl") };
+                (if op < 0xa8 { "shld" } else { "shrd" }, vec![e, self.g(m, s), count])
+            },
+            0xae => {
+                let m = try_opt!(self.modrm());
+                if m.md == 3 {
+                    match m.reg { 5 => ("lfence", vec![]), 6 => ("mfence", vec![]), 7 => ("sfence", vec![]), _ => return None }
+                } else {
+                    let mnem = ["fxsave", "fxrstor", "ldmxcsr", "stmxcsr", "xsave", "xrstor", "xsaveopt", "clflush"][m.reg as usize];
+                    let size = if m.reg == 2 || m.reg == 3 { 4 } else { 0 };
+                    (mnem, vec![try_opt!(self.mem(m, size))])
+                }
+            },
+            0xaf => {
+                let m = try_opt!(self.modrm());
+                let s = self.ov();
+                ("imul", vec![self.g(m, s), try_opt!(self.e(m, s))])
+            },
+            0xb0 | 0xb1 | 0xc0 | 0xc1 => {
+                let m = try_opt!(self.modrm());
+                let s = if op & 1 == 0 { 1 } else { self.ov() };
+                (if op < 0xc0 { "cmpxchg" } else { "xadd" }, vec![try_opt!(self.e(m, s)), self.g(m, s)])
+            },
+            0xb6 | 0xb7 | 0xbe | 0xbf => {
+                let m = try_opt!(self.modrm());
+                let s = self.ov();
+                let from = if op & 1 == 0 { 1 } else { 2 };
+                (if op < 0xb8 { "movzx" } else { "movsx" }, vec![self.g(m, s), try_opt!(self.e(m, from))])
+            },
+            0xb8 if self.rep == 0xf3 => {
+                self.rep = 0;
+                let m = try_opt!(self.modrm());
+                let s = self.ov();
+                ("popcnt", vec![self.g(m, s), try_opt!(self.e(m, s))])
+            },
+            0xba => {
+                let m = try_opt!(self.modrm());
+                if m.reg < 4 { return None; }
+                let s = self.ov();
+                let e = try_opt!(self.e(m, s));
+                (["", "", "", "", "bt", "bts", "btr", "btc"][m.reg as usize], vec![e, Operand::Imm(try_opt!(self.imm(1)) & 0xff)])
+            },
+            0xbc | 0xbd => {
+                let mnem = match (self.rep, op) { (0xf3, 0xbc) => "tzcnt", (0xf3, _) => "lzcnt", (_, 0xbc) => "bsf", _ => "bsr" };
+                if self.rep == 0xf3 { self.rep = 0; }
+                let m = try_opt!(self.modrm());
+                let s = self.ov();
+                (mnem, vec![self.g(m, s), try_opt!(self.e(m, s))])
+            },
+            0xc7 => {
+                let m = try_opt!(self.modrm());
+                match (m.reg, m.md) {
+                    (1, md) if md != 3 => {
+                        if self.rex_w() { ("cmpxchg16b", vec![try_opt!(self.mem(m, 16))]) } else { ("cmpxchg8b", vec![try_opt!(self.mem(m, 8))]) }
+                    },
+                    (6, 3) => { let s = self.ov(); ("rdrand", vec![try_opt!(self.e(m, s))]) },
+                    (7, 3) => { let s = self.ov(); ("rdseed", vec![try_opt!(self.e(m, s))]) },
+                    _ => return None,
+                }
+            },
+            0xc8...0xcf => {
+                let s = if self.rex_w() { 8 } else { 4 };
+                ("bswap", vec![Operand::Reg(gpr(s, (op & 7) | self.rex_b(), false))])
+            },
+            _ => return None,
+        };
+        Some((mnem.to_owned(), ops))
+    }
+
+    // SSE floating point, where the mandatory prefix picks ps/pd/sd/ss
+    fn sse_fp(&mut self, op: u8) -> Option<(String, Vec<Operand>)> {
+        let mp = self.sse_prefix();
+        let m = try_opt!(self.modrm());
+        let suffix = ["ps", "pd", "sd", "ss"][mp];
+        let scalar_size = [16, 16, 8, 4][mp];
+        let x = self.gk(m, RegKind::Xmm);
+        let (mnem, ops): (String, Vec<Operand>) = match op {
+            0x10 | 0x11 => {
+                let name = ["movups", "movupd", "movsd", "movss"][mp];
+                let e = try_opt!(self.ek(m, RegKind::Xmm, scalar_size));
+                (name.to_owned(), if op == 0x10 { vec![x, e] } else { vec![e, x] })
+            },
+            0x12 | 0x13 | 0x16 | 0x17 => {
+                let hi = op >= 0x16;
+                let name = match (mp, op & 1, m.md == 3) {
+                    (0, 0, true) => if hi { "movlhps" } else { "movhlps" },
+                    (0, _, _) => if hi { "movhps" } else { "movlps" },
+                    (1, _, false) => if hi { "movhpd" } else { "movlpd" },
+                    (2, 0, _) if !hi => "movddup",
+                    (3, 0, _) => if hi { "movshdup" } else { "movsldup" },
+                    _ => return None,
+                };
+                let size = if mp == 3 { 16 } else { 8 };
+                let e = try_opt!(self.ek(m, RegKind::Xmm, size));
+                (name.to_owned(), if op & 1 == 0 { vec![x, e] } else { vec![e, x] })
+            },
+            0x14 | 0x15 if mp < 2 => {
+                let name = if op == 0x14 { "unpckl" } else { "unpckh" };
+                (format!("{}{}", name, suffix), vec![x, try_opt!(self.ek(m, RegKind::Xmm, 16))])
+            },
+            0x28 | 0x29 if mp < 2 => {
+                let e = try_opt!(self.ek(m, RegKind::Xmm, 16));
+                (format!("mova{}", suffix), if op == 0x28 { vec![x, e] } else { vec![e, x] })
+            },
+            0x2e | 0x2f if mp < 2 => {
+                let name = if op == 0x2e { "ucomis" } else { "comis" };
+                let size = if mp == 0 { 4 } else { 8 };
+                (format!("{}{}", name, if mp == 0 { "s" } else { "d" }), vec![x, try_opt!(self.ek(m, RegKind::Xmm, size))])
+            },
+            0x50 if mp < 2 && m.md == 3 => {
+                (format!("movmsk{}", suffix), vec![self.g(m, 4), try_opt!(self.ek(m, RegKind::Xmm, 16))])
+            },
+            0x51 | 0x58 | 0x59 | 0x5c...0x5f => {
+                let name = match op { 0x51 => "sqrt", 0x58 => "add", 0x59 => "mul", 0x5c => "sub", 0x5d => "min", 0x5e => "div", _ => "max" };
+                (format!("{}{}", name, suffix), vec![x, try_opt!(self.ek(m, RegKind::Xmm, scalar_size))])
+            },
+            0x52 | 0x53 if mp == 0 || mp == 3 => {
+                let name = if op == 0x52 { "rsqrt" } else { "rcp" };
+                (format!("{}{}", name, suffix), vec![x, try_opt!(self.ek(m, RegKind::Xmm, scalar_size))])
+            },
+            0x54...0x57 if mp < 2 => {
+                let name = ["and", "andn", "or", "xor"][(op - 0x54) as usize];
+                (format!("{}{}", name, suffix), vec![x, try_opt!(self.ek(m, RegKind::Xmm, 16))])
+            },
+            0x5a => {
+                let name = ["cvtps2pd", "cvtpd2ps", "cvtsd2ss", "cvtss2sd"][mp];
+                let size = [8, 16, 8, 4][mp];
+                (name.to_owned(), vec![x, try_opt!(self.ek(m, RegKind::Xmm, size))])
+            },
+            0x5b if mp != 2 => {
+                let name = ["cvtdq2ps", "cvtps2dq", "", "cvttps2dq"][mp];
+                (name.to_owned(), vec![x, try_opt!(self.ek(m, RegKind::Xmm, 16))])
+            },
+            0xc2 => {
+                let e = try_opt!(self.ek(m, RegKind::Xmm, scalar_size));
+                (format!("cmp{}", suffix), vec![x, e, Operand::Imm(try_opt!(self.imm(1)) & 0xff)])
+            },
+            0xc6 if mp < 2 => {
+                let e = try_opt!(self.ek(m, RegKind::Xmm, 16));
+                (format!("shuf{}", suffix), vec![x, e, Operand::Imm(try_opt!(self.imm(1)) & 0xff)])
+            },
+            _ => return None,
+        };
+        Some((mnem, ops))
+    }
+
+    // MMX/SSE2 integer ops: no prefix means MMX registers, 66 means XMM
+    fn sse_int(&mut self, op: u8) -> Option<(String, Vec<Operand>)> {
+        let mp = self.sse_prefix();
+        let (kind, size) = if mp == 0 { (RegKind::Mm, 8) } else { (RegKind::Xmm, 16) };
+        if op == 0x77 && mp == 0 {
+            return Some(("emms".to_owned(), vec![]));
+        }
+        let m = try_opt!(self.modrm());
+        let r = self.gk(m, kind);
+        let (mnem, ops): (&str, Vec<Operand>) = match (op, mp) {
+            (0x6e, 0) | (0x6e, 1) => {
+                let s = if self.rex_w() { 8 } else { 4 };
+                (if s == 8 { "movq" } else { "movd" }, vec![r, try_opt!(self.e(m, s))])
+            },
+            (0x7e, 0) | (0x7e, 1) => {
+                let s = if self.rex_w() { 8 } else { 4 };
+                (if s == 8 { "movq" } else { "movd" }, vec![try_opt!(self.e(m, s)), r])
+            },
+            (0x7e, 3) => ("movq", vec![self.gk(m, RegKind::Xmm), try_opt!(self.ek(m, RegKind::Xmm, 8))]),
+            (0x6f, 0) => ("movq", vec![r, try_opt!(self.ek(m, kind, 8))]),
+            (0x6f, 1) => ("movdqa", vec![r, try_opt!(self.ek(m, kind, 16))]),
+            (0x6f, 3) => ("movdqu", vec![self.gk(m, RegKind::Xmm), try_opt!(self.ek(m, RegKind::Xmm, 16))]),
+            (0x7f, 0) => ("movq", vec![try_opt!(self.ek(m, kind, 8)), r]),
+            (0x7f, 1) => ("movdqa", vec![try_opt!(self.ek(m, kind, 16)), r]),
+            (0x7f, 3) => ("movdqu", vec![try_opt!(self.ek(m, RegKind::Xmm, 16)), self.gk(m, RegKind::Xmm)]),
+            (0xd6, 1) => ("movq", vec![try_opt!(self.ek(m, RegKind::Xmm, 8)), r]),
+            (0xd7, 0) | (0xd7, 1) if m.md == 3 => ("pmovmskb", vec![self.g(m, 4), try_opt!(self.ek(m, kind, size))]),
+            (0xe7, 0) | (0xe7, 1) if m.md != 3 => (if mp == 0 { "movntq" } else { "movntdq" }, vec![try_opt!(self.mem(m, size)), r]),
+            (0xe6, _) if mp != 0 => {
+                let name = ["", "cvttpd2dq", "cvtpd2dq", "cvtdq2pd"][mp];
+                (name, vec![self.gk(m, RegKind::Xmm), try_opt!(self.ek(m, RegKind::Xmm, 16))])
+            },
+            (0x70, _) => {
+                let name = ["pshufw", "pshufd", "pshuflw", "pshufhw"][mp];
+                let (kind, size) = if mp == 0 { (RegKind::Mm, 8) } else { (RegKind::Xmm, 16) };
+                let r = self.gk(m, kind);
+                let e = try_opt!(self.ek(m, kind, size));
+                (name, vec![r, e, Operand::Imm(try_opt!(self.imm(1)) & 0xff)])
+            },
+            (0x71...0x73, 0) | (0x71...0x73, 1) if m.md == 3 => {
+                let name = match (op, m.reg) {
+                    (0x71, 2) => "psrlw", (0x71, 4) => "psraw", (0x71, 6) => "psllw",
+                    (0x72, 2) => "psrld", (0x72, 4) => "psrad", (0x72, 6) => "pslld",
+                    (0x73, 2) => "psrlq", (0x73, 6) => "psllq",
+                    (0x73, 3) if mp == 1 => "psrldq", (0x73, 7) if mp == 1 => "pslldq",
+                    _ => return None,
+                };
+                let e = try_opt!(self.ek(m, kind, size));
+                (name, vec![e, Operand::Imm(try_opt!(self.imm(1)) & 0xff)])
+            },
+            (_, 0) | (_, 1) => {
+                let name = match op {
+                    0x60 => "punpcklbw", 0x61 => "punpcklwd", 0x62 => "punpckldq", 0x63 => "packsswb",
+                    0x64 => "pcmpgtb", 0x65 => "pcmpgtw", 0x66 => "pcmpgtd", 0x67 => "packuswb",
+                    0x68 => "punpckhbw", 0x69 => "punpckhwd", 0x6a => "punpckhdq", 0x6b => "packssdw",
+                    0x6c if mp == 1 => "punpcklqdq", 0x6d if mp == 1 => "punpckhqdq",
+                    0x74 => "pcmpeqb", 0x75 => "pcmpeqw", 0x76 => "pcmpeqd",
+                    0xd1 => "psrlw", 0xd2 => "psrld", 0xd3 => "psrlq", 0xd4 => "paddq", 0xd5 => "pmullw",
+                    0xd8 => "psubusb", 0xd9 => "psubusw", 0xda => "pminub", 0xdb => "pand",
+                    0xdc => "paddusb", 0xdd => "paddusw", 0xde => "pmaxub", 0xdf => "pandn",
+                    0xe0 => "pavgb", 0xe1 => "psraw", 0xe2 => "psrad", 0xe3 => "pavgw",
+                    0xe4 => "pmulhuw", 0xe5 => "pmulhw", 0xe8 => "psubsb", 0xe9 => "psubsw",
+                    0xea => "pminsw", 0xeb => "por", 0xec => "paddsb", 0xed => "paddsw",
+                    0xee => "pmaxsw", 0xef => "pxor", 0xf1 => "psllw", 0xf2 => "pslld",
+                    0xf3 => "psllq", 0xf4 => "pmuludq", 0xf5 => "pmaddwd", 0xf6 => "psadbw",
+                    0xf8 => "psubb", 0xf9 => "psubw", 0xfa => "psubd", 0xfb => "psubq",
+                    0xfc => "paddb", 0xfd => "paddw", 0xfe => "paddd",
+                    _ => return None,
+                };
+                (name, vec![r, try_opt!(self.ek(m, kind, size))])
+            },
+            _ => return None,
+        };
+        Some((mnem.to_owned(), ops))
+    }
+
+    fn rip_target(&self, disp: i64, len: usize) -> u64 {
+        self.pc.wrapping_add(len as u64).wrapping_add(disp as u64)
+    }
+
+    // the address a branch goes to or a rip-relative operand refers to
+    fn op_target(&self, op: &Operand, len: usize) -> Option<u64> {
+        match *op {
+            Operand::Target(t) => Some(t),
+            Operand::Mem { disp, rip: true, .. } => Some(self.rip_target(disp, len)),
+            _ => None,
+        }
+    }
+
+    fn format_op(&self, op: &Operand, len: usize) -> String {
+        match *op {
+            Operand::Reg(r) => r.to_owned(),
+            Operand::Imm(i) => if i < 0 { format!("-0x{:x}", (i as u64).wrapping_neg()) } else { format!("0x{:x}", i) },
+            Operand::Target(t) => format!("0x{:x}", t),
+            Operand::Mem { size, seg, base, index, scale, disp, rip } => {
+                let mut out = String::new();
+                if size != 0 {
+                    out.push_str(size_name(size));
+                    out.push_str(" ptr ");
+                }
+                if let Some(seg) = seg {
+                    out.push_str(seg);
+                    out.push(':');
+                }
+                out.push('[');
+                let mut any = false;
+                if let Some(base) = base {
+                    out.push_str(base);
+                    any = true;
+                }
+                if let Some(index) = index {
+                    if any { out.push_str(" + "); }
+                    out.push_str(&format!("{}*{}", index, scale));
+                    any = true;
+                }
+                if disp != 0 || !any {
+                    if !any {
+                        out.push_str(&format!("0x{:x}", if self.bits == 64 { disp as u64 } else { disp as u32 as u64 }));
+                    } else if disp < 0 {
+                        out.push_str(&format!(" - 0x{:x}", (disp as u64).wrapping_neg()));
+                    } else {
+                        out.push_str(&format!(" + 0x{:x}", disp));
+                    }
+                }
+                out.push(']');
+                if rip {
+                    out.push_str(&format!(" # 0x{:x}", self.rip_target(disp, len)));
+                }
+                out
+            },
+        }
+    }
+}
+
+// (text, length, branch or rip-relative target if any)
+pub fn disassemble(data: &[u8], pc: u64, bits: u8) -> Option<(String, u32, Option<u64>)> {
+    let mut d = Decoder {
+        data: data,
+        pos: 0,
+        pc: pc,
+        bits: bits,
+        opsize: false,
+        addrsize: false,
+        rep: 0,
+        lock: false,
+        seg: None,
+        rex: 0,
+    };
+    let (mnem, ops) = some_or!(d.decode(), { return None; });
+    let len = d.pos;
+    let mut out = String::new();
+    if d.lock { out.push_str("lock "); }
+    match d.rep {
+        0xf3 => out.push_str("rep "),
+        0xf2 => out.push_str("repne "),
+        _ => (),
+    }
+    out.push_str(&mnem);
+    let mut comment = None;
+    let mut target = None;
+    for (i, op) in ops.iter().enumerate() {
+        target = target.or_else(|| d.op_target(op, len));
+        let mut s = d.format_op(op, len);
+        // keep the rip target comment at the end of the line
+        if let Some(pos) = s.find(" # ") {
+            comment = Some(s[pos..].to_owned());
+            s.truncate(pos);
+        }
+        out.push_str(if i == 0 { " " } else { ", " });
+        out.push_str(&s);
+    }
+    if let Some(comment) = comment {
+        out.push_str(&comment);
+    }
+    Some((out, len as u32, target))
+}
@@ -4,6 +4,7 @@ extern crate macros;
 use exec::arch;
 use exec::arch::CodeMode;
 use std::marker::PhantomData;
+use std::fmt;
 
 #[derive(Debug)]
 pub enum CreateDisError {
@@ -27,11 +28,80 @@ impl std::error::Error for CreateDisError {
     }
 }
 
+// Lets a backend print addresses as symbol+off.  Returns the nearest
+// symbol at or before the address and the offset from it.
+pub trait SymbolResolver {
+    fn resolve(&self, addr: exec::VMA) -> Option<(&str, u64)>;
+    fn describe(&self, addr: exec::VMA) -> Option<String> {
+        self.resolve(addr).map(|(name, off)| {
+            if off == 0 { name.to_owned() } else { format!("{}+0x{:x}", name, off) }
+        })
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct DisassemblerInput<'a> {
     pub data: &'a [u8],
     pub pc: exec::VMA,
     pub mode: CodeMode,
+    pub resolver: Option<&'a SymbolResolver>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OperandKind {
+    Reg,
+    Imm(i64),
+    Mem { base: Option<String>, index: Option<String>, scale: u8, disp: i64 },
+    PcRel(exec::VMA),
+    Other,
+}
+
+#[derive(Clone, Debug)]
+pub struct InsnOperand {
+    pub text: String,
+    pub kind: OperandKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlowKind {
+    Normal,
+    Jump { target: Option<exec::VMA>, conditional: bool },
+    Call { target: Option<exec::VMA> },
+    Return,
+    // never falls through: traps, undefined
+    Stop,
+}
+
+impl FlowKind {
+    pub fn falls_through(&self) -> bool {
+        match *self {
+            FlowKind::Normal | FlowKind::Call { .. } => true,
+            FlowKind::Jump { conditional, .. } => conditional,
+            FlowKind::Return | FlowKind::Stop => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Insn {
+    pub mnemonic: String,
+    pub operands: Vec<InsnOperand>,
+    pub flow: FlowKind,
+    pub len: u32,
+    pub comment: Option<String>,
+}
+
+impl fmt::Display for Insn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}", self.mnemonic));
+        for (i, op) in self.operands.iter().enumerate() {
+            try!(write!(f, "{}{}", if i == 0 { " " } else { ", " }, op.text));
+        }
+        if let Some(ref comment) = self.comment {
+            try!(write!(f, "    ; {}", comment));
+        }
+        Ok(())
+    }
 }
 
 pub struct TrawlLead {
@@ -48,6 +118,8 @@ pub trait Disassembler : 'static {
     fn arch(&self) -> &arch::ArchAndOptions;
     fn can_disassemble_to_str(&self) -> bool { false }
     fn disassemble_insn_to_str(&self, _input: &DisassemblerInput) -> Option<(Option<String>, u32)> { unimplemented!() }
+    fn can_disassemble_insn(&self) -> bool { false }
+    fn disassemble_insn(&self, _input: &DisassemblerInput) -> Option<Insn> { None }
     fn disassemble_multiple_to_str(&self, input: &DisassemblerInput) -> Vec<(Option<String>, exec::VMA, u32)> {
         let mut result = Vec::new();
         let mut off = 0;
@@ -57,6 +129,7 @@ pub trait Disassembler : 'static {
                 data: &input.data[off..],
                 pc: input.pc + (off as u64),
                 mode: input.mode,
+                resolver: input.resolver,
             }) {
                 result.push((dissed, input.pc + (off as u64), length));
                 off += length as usize;
@@ -15,6 +15,10 @@ pub enum Arch {
     Sparc,
     Mips,
     PowerPC,
+    RiscV,
+    S390x,
+    LoongArch,
+    M68k,
     UnknownArch,
 }
 
@@ -36,10 +40,18 @@ impl FromStr for Arch {
             Ok(AArch64)
         } else if s == "sparc" {
             Ok(Sparc)
-        } else if s == "mips" {
+        } else if s == "mips" || s == "mipsel" || s == "mips64" || s == "mips64el" {
             Ok(Mips)
-        } else if s == "ppc" || s == "powerpc" {
+        } else if s == "ppc" || s == "powerpc" || s == "ppcle" || s == "ppc64" || s == "ppc64le" || s == "powerpc64" || s == "powerpc64le" {
             Ok(PowerPC)
+        } else if s == "riscv" || s == "riscv32" || s == "riscv64" || s == "rv32" || s == "rv64" {
+            Ok(RiscV)
+        } else if s == "s390x" || s == "s390" || s == "systemz" {
+            Ok(S390x)
+        } else if s == "loongarch" || s == "loongarch64" || s == "la64" {
+            Ok(LoongArch)
+        } else if s == "m68k" || s == "68k" || s == "m68000" || s == "mc68000" || s == "mc680x0" {
+            Ok(M68k)
         } else {
             Err(())
         }
@@ -56,6 +68,10 @@ impl Arch {
             Sparc => "sparc",
             Mips => "mips",
             PowerPC => "powerpc",
+            RiscV => "riscv",
+            S390x => "s390x",
+            LoongArch => "loongarch",
+            M68k => "m68k",
             UnknownArch => "unknown",
         }
     }
@@ -80,6 +96,29 @@ pub struct _EndianOptions {
     pub _please_use_default: (),
 }
 
+pub type MipsOptions = _EndianAndWidthOptions;
+pub type PowerPCOptions = _EndianAndWidthOptions;
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub struct _EndianAndWidthOptions {
+    pub endian: Endian,
+    pub is64: bool,
+    pub _please_use_default: (),
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct RiscVOptions {
+    pub is64: bool,
+    pub compressed: bool, // the C extension, which almost everything has
+    pub _please_use_default: (),
+}
+
+impl Default for RiscVOptions {
+    fn default() -> RiscVOptions {
+        RiscVOptions { is64: true, compressed: true, _please_use_default: () }
+    }
+}
+
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum ArchAndOptions {
     X86(NoOptionsYet),
@@ -87,11 +126,24 @@ pub enum ArchAndOptions {
     ARM(ARMOptions),
     AArch64(NoOptionsYet),
     Sparc(NoOptionsYet),
-    Mips(NoOptionsYet),
-    PowerPC(NoOptionsYet),
+    Mips(MipsOptions),
+    PowerPC(PowerPCOptions),
+    RiscV(RiscVOptions),
+    S390x(NoOptionsYet),
+    LoongArch(NoOptionsYet),
+    M68k(NoOptionsYet),
     UnknownArch(NoOptionsYet),
 }
 
+fn parse_endian(s: Option<String>) -> Result<Option<Endian>, String> {
+    match s.as_ref().map(|s| &**s) {
+        Some("L") | Some("l") | Some("little") => Ok(Some(LittleEndian)),
+        Some("B") | Some("b") | Some("big") => Ok(Some(BigEndian)),
+        Some(x) => Err(format!("bad endian spec {}", x)),
+        None => Ok(None),
+    }
+}
+
 impl Default for ArchAndOptions {
     fn default() -> ArchAndOptions { ArchAndOptions::UnknownArch(Default::default()) }
 }
@@ -103,11 +155,42 @@ impl ArchAndOptions {
                 &ARMMode { thumb } => if thumb { 2 } else { 4 },
                 _ => panic!(),
             },
-            &ArchAndOptions::AArch64(..) | &ArchAndOptions::PowerPC(..) => 4, // xxx others
+            &ArchAndOptions::Mips(..) => match mode {
+                &MipsMode { isa: MipsISA::Standard } => 4,
+                &MipsMode { .. } => 2,
+                _ => 4,
+            },
+            &ArchAndOptions::PowerPC(..) => match mode {
+                &PowerPCMode { vle } => if vle { 2 } else { 4 },
+                _ => 4,
+            },
+            &ArchAndOptions::RiscV(ref opts) => if opts.compressed { 2 } else { 4 },
+            &ArchAndOptions::AArch64(..) | &ArchAndOptions::Sparc(..) | &ArchAndOptions::LoongArch(..) => 4,
+            &ArchAndOptions::S390x(..) | &ArchAndOptions::M68k(..) => 2,
             &ArchAndOptions::X86(..) | &ArchAndOptions::X86_64(..) => 1,
             _ => 1,
         }
     }
+    // used when the container doesn't say, e.g. raw binaries
+    pub fn default_endian(&self) -> Endian {
+        match self {
+            &ArchAndOptions::ARM(ref opts) => opts.endian,
+            &ArchAndOptions::Mips(ref opts) | &ArchAndOptions::PowerPC(ref opts) => opts.endian,
+            &ArchAndOptions::X86(..) | &ArchAndOptions::X86_64(..) | &ArchAndOptions::AArch64(..) |
+            &ArchAndOptions::RiscV(..) | &ArchAndOptions::LoongArch(..) => LittleEndian,
+            _ => BigEndian,
+        }
+    }
+    pub fn default_pointer_size(&self) -> usize {
+        match self {
+            &ArchAndOptions::Mips(ref opts) | &ArchAndOptions::PowerPC(ref opts) => if opts.is64 { 8 } else { 4 },
+            &ArchAndOptions::RiscV(ref opts) => if opts.is64 { 8 } else { 4 },
+            &ArchAndOptions::X86_64(..) | &ArchAndOptions::AArch64(..) |
+            &ArchAndOptions::S390x(..) | &ArchAndOptions::LoongArch(..) => 8,
+            &ArchAndOptions::UnknownArch(..) => 0,
+            _ => 4,
+        }
+    }
     pub fn new(args: &[String]) -> Result<ArchAndOptions, String> {
         if args.len() == 0 { return Err("empty args".to_owned()); }
         let arch = try!(Arch::from_str(&*args[0]).map_err(|()| "unknown arch"));
@@ -116,18 +199,40 @@ impl ArchAndOptions {
                 let m = try!(util::do_getopts_or_usage(&args[1..], &*args[0], 0,0, &mut vec![
                     getopts::optopt("E", "endian", "Endian", "little/big/L/B"),
                 ]));
-                let endian_str = m.opt_str("endian");
-                let endian_str = if let Some(ref s) = endian_str { Some(&**s) } else { None }; // XXX wtf
-                let endian = match endian_str {
-                    Some("L") | Some("l") | Some("little") => LittleEndian,
-                    Some("B") | Some("b") | Some("big") => BigEndian,
-                    Some(x) => return Err(format!("bad endian spec {}", x)),
-                    None => return Err("no endian specified".to_owned()),
-                };
+                let endian = some_or!(try!(parse_endian(m.opt_str("endian"))),
+                                      { return Err("no endian specified".to_owned()) });
                 Ok(ArchAndOptions::ARM(ARMOptions { endian: endian, ..ARMOptions::default() }))
             },
+            Mips | PowerPC => {
+                // the name can imply these, as in mips64el or ppc64le
+                let name = args[0].to_lowercase();
+                let m = try!(util::do_getopts_or_usage(&args[1..], &*args[0], 0,0, &mut vec![
+                    getopts::optopt("E", "endian", "Endian (default big)", "little/big/L/B"),
+                    getopts::optflag("", "64", "64-bit"),
+                ]));
+                let endian = try!(parse_endian(m.opt_str("endian"))).unwrap_or(
+                    if name.ends_with("el") || name.ends_with("le") { LittleEndian } else { BigEndian });
+                let opts = _EndianAndWidthOptions {
+                    endian: endian,
+                    is64: m.opt_present("64") || name.contains("64"),
+                    ..Default::default()
+                };
+                Ok(if arch == Mips { ArchAndOptions::Mips(opts) } else { ArchAndOptions::PowerPC(opts) })
+            },
+            RiscV => {
+                let name = args[0].to_lowercase();
+                let m = try!(util::do_getopts_or_usage(&args[1..], &*args[0], 0,0, &mut vec![
+                    getopts::optflag("", "32", "RV32 (default RV64)"),
+                    getopts::optflag("", "no-compressed", "no C extension"),
+                ]));
+                Ok(ArchAndOptions::RiscV(RiscVOptions {
+                    is64: !(m.opt_present("32") || name.ends_with("32")),
+                    compressed: !m.opt_present("no-compressed"),
+                    ..Default::default()
+                }))
+            },
             _ => {
-                if args.len() > 0 {
+                if args.len() > 1 {
                     return Err(format!("arch {} accepts no args", arch));
                 }
                 Ok(ArchAndOptions::new_default(arch))
@@ -156,11 +261,21 @@ macro_rules! arch_into {
     }
 }
 
-arch_into!(X86, X86_64, ARM, AArch64, Sparc, Mips, PowerPC, UnknownArch);
+arch_into!(X86, X86_64, ARM, AArch64, Sparc, Mips, PowerPC, RiscV, S390x, LoongArch, M68k, UnknownArch);
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum MipsISA {
+    Standard,
+    Mips16,
+    MicroMips,
+}
 
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum CodeMode {
     ARMMode { thumb: bool },
+    X86Mode { bits: u8 }, // 16, 32 or 64
+    MipsMode { isa: MipsISA },
+    PowerPCMode { vle: bool },
     OtherMode,
 }
 
@@ -173,6 +288,38 @@ impl CodeMode {
                 ]));
                 Ok(CodeMode::ARMMode { thumb: m.opt_present("thumb") })
             },
+            &ArchAndOptions::X86(..) | &ArchAndOptions::X86_64(..) => {
+                let m = try!(util::do_getopts_or_usage(&args, "[--bits 16/32/64]", 0, 0, &mut vec![
+                    getopts::optopt("b", "bits", "Operand/address size (real mode is 16)", "16/32/64"),
+                ]));
+                let bits = match m.opt_str("bits") {
+                    Some(s) => match &*s {
+                        "16" => 16, "32" => 32, "64" => 64,
+                        _ => return Err(format!("bad bits {}", s)),
+                    },
+                    None => if let &ArchAndOptions::X86_64(..) = arch { 64 } else { 32 },
+                };
+                Ok(CodeMode::X86Mode { bits: bits })
+            },
+            &ArchAndOptions::Mips(..) => {
+                let m = try!(util::do_getopts_or_usage(&args, "[--mips16|--micromips]", 0, 0, &mut vec![
+                    getopts::optflag("", "mips16", "MIPS16e"),
+                    getopts::optflag("", "micromips", "microMIPS"),
+                ]));
+                let isa = match (m.opt_present("mips16"), m.opt_present("micromips")) {
+                    (false, false) => MipsISA::Standard,
+                    (true, false) => MipsISA::Mips16,
+                    (false, true) => MipsISA::MicroMips,
+                    (true, true) => return Err("--mips16 and --micromips are exclusive".to_owned()),
+                };
+                Ok(CodeMode::MipsMode { isa: isa })
+            },
+            &ArchAndOptions::PowerPC(..) => {
+                let m = try!(util::do_getopts_or_usage(&args, "[--vle]", 0, 0, &mut vec![
+                    getopts::optflag("", "vle", "Variable Length Encoding"),
+                ]));
+                Ok(CodeMode::PowerPCMode { vle: m.opt_present("vle") })
+            },
             _ => {
                 let _ = try!(util::do_getopts_or_usage(&args, "[no options]", 0, 0, &mut vec![]));
                 Ok(CodeMode::OtherMode)
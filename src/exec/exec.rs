@@ -252,6 +252,16 @@ impl<'a> SymbolValue<'a> {
             _ => None
         }
     }
+    pub fn into_owned(self) -> SymbolValue<'static> {
+        match self {
+            SymbolValue::Addr(vma) => SymbolValue::Addr(vma),
+            SymbolValue::Abs(vma) => SymbolValue::Abs(vma),
+            SymbolValue::ThreadLocal(vma) => SymbolValue::ThreadLocal(vma),
+            SymbolValue::Undefined(sl) => SymbolValue::Undefined(sl),
+            SymbolValue::Resolver(vma, stub) => SymbolValue::Resolver(vma, stub),
+            SymbolValue::ReExport(name, sl) => SymbolValue::ReExport(Cow::Owned(name.into_owned()), sl),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -263,7 +273,7 @@ pub enum SourceLib {
     Flat,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Symbol<'a> {
     pub name: Cow<'a, ByteStr>,
     pub is_public: bool,
@@ -273,6 +283,19 @@ pub struct Symbol<'a> {
     pub private: usize,
 }
 
+impl<'a> Symbol<'a> {
+    pub fn into_owned(self) -> Symbol<'static> {
+        Symbol {
+            name: Cow::Owned(self.name.into_owned()),
+            is_public: self.is_public,
+            is_weak: self.is_weak,
+            val: self.val.into_owned(),
+            size: self.size,
+            private: self.private,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum SymbolSource {
     All,
@@ -1,7 +1,8 @@
 use VMA;
 use ::util;
-use ::util::{SignExtend, UnSignExtend, Endian, Narrow, Ext};
+use ::util::{SignExtend, UnSignExtend, Endian, Narrow, Ext, ReadCell};
 use std::cell::Cell;
+use std::mem::transmute;
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum RelocKind {
@@ -9,6 +10,24 @@ pub enum RelocKind {
     _64Bit,
     _32Bit,
     Arm64Br26,
+    Arm64AdrpPair, // adrp followed by add/ldr/str using the same register
+    // For the ARM and Thumb branches, the address has the low bit set iff
+    // the target is Thumb; packing switches between BL and BLX as needed.
+    ArmBr24,
+    ThumbBr22,
+    ArmMovwMovt, // adjacent pair, absolute
+    ThumbMovwMovt,
+    Rel32, // x86 call/jmp/jcc or rip-relative operand; relative to the end of the 4 bytes
+    GotPcRel, // same encoding, but the address is a pointer (GOT entry) holding the real target
+    RiscVBranch, // B-type, +/-4KB
+    RiscVJal, // J-type, +/-1MB
+    RiscVCall, // auipc + jalr pair
+    // dynamic linker stuff; these all occupy a pointer except SymCopy
+    SymCopy,
+    IRelative,
+    TlsDtpMod,
+    TlsDtpOff,
+    TlsTpOff,
 }
 use RelocKind::*;
 
@@ -29,6 +48,7 @@ pub enum RelocPackFail {
     AddrOutOfRange,
     Truncated,
     UnexpectedData,
+    NotApplicable,
 }
 
 impl RelocContext {
@@ -36,14 +56,27 @@ impl RelocContext {
         match self.kind {
             Pointer => self.pointer_size,
             _32Bit => 4, _64Bit => 8,
-            Arm64Br26 => 4,
+            Arm64Br26 | ArmBr24 | ThumbBr22 | Rel32 | GotPcRel | RiscVBranch | RiscVJal => 4,
+            Arm64AdrpPair | ArmMovwMovt | ThumbMovwMovt | RiscVCall => 8,
+            IRelative | TlsDtpMod | TlsDtpOff | TlsTpOff => self.pointer_size,
+            SymCopy => 0,
         }
     }
+    pub fn unpack_insn(&self, data: &[ReadCell<u8>]) -> Result<VMA, RelocPackFail> {
+        // fine since we don't write
+        self.pack_unpack_insn(unsafe { transmute(data) }, None)
+    }
     pub fn pack_unpack_insn(&self, data: &[Cell<u8>], new: Option<VMA>) -> Result<VMA, RelocPackFail> {
         let mut kind = self.kind;
-        if kind == Pointer { kind = if self.pointer_size == 8 { _64Bit } else { _32Bit }; }
         match kind {
-            Pointer => unreachable!(),
+            Pointer | IRelative | TlsDtpMod | TlsDtpOff | TlsTpOff => {
+                kind = if self.pointer_size == 8 { _64Bit } else { _32Bit };
+            },
+            _ => (),
+        }
+        match kind {
+            Pointer | IRelative | TlsDtpMod | TlsDtpOff | TlsTpOff => unreachable!(),
+            SymCopy => Err(RelocPackFail::NotApplicable),
             _64Bit => {
                 if data.len() < 8 { return Err(RelocPackFail::Truncated); }
                 let old: u64 = util::copy_from_slice(&data[..8], self.endian);
@@ -78,6 +111,353 @@ impl RelocContext {
                 }
                 Ok(old_addr)
             },
+            Arm64AdrpPair => {
+                if data.len() < 8 { return Err(RelocPackFail::Truncated); }
+                let adrp: u32 = util::copy_from_slice(&data[..4], self.endian);
+                let second: u32 = util::copy_from_slice(&data[4..8], self.endian);
+                if adrp & 0x9f000000 != 0x90000000 { return Err(RelocPackFail::UnexpectedData); }
+                let scale = if second & 0xffc00000 == 0x91000000 {
+                    0 // add xd, xn, #imm
+                } else if second & 0x3f000000 == 0x39000000 {
+                    second >> 30 // ldr/str (unsigned offset), scaled by size
+                } else {
+                    return Err(RelocPackFail::UnexpectedData);
+                };
+                if (second >> 5) & 0x1f != adrp & 0x1f { return Err(RelocPackFail::UnexpectedData); }
+                let pages = (adrp >> 5 & 0x7ffff) << 2 | (adrp >> 29 & 3);
+                let page_base = VMA(self.base_addr.0 & !0xfff);
+                let lo = ((second >> 10 & 0xfff) << scale) as u64;
+                let pages: u64 = pages.sign_extend(21);
+                let old_addr = page_base.wrapping_add((pages << 12).wrapping_add(lo));
+                if let Some(new) = new {
+                    let x: u32 = try!((new.0 >> 12).wrapping_sub(page_base.0 >> 12).un_sign_extend(21)
+                                      .ok_or(RelocPackFail::AddrOutOfRange));
+                    let lo = (new.0 & 0xfff) as u32;
+                    if lo & ((1 << scale) - 1) != 0 { return Err(RelocPackFail::AddrOutOfRange); }
+                    let adrp = (adrp & 0x9f00001f) | (x & 3) << 29 | (x >> 2) << 5;
+                    let second = (second & !(0xfff << 10)) | (lo >> scale) << 10;
+                    util::copy_to_slice(&data[..4], &adrp, self.endian);
+                    util::copy_to_slice(&data[4..8], &second, self.endian);
+                }
+                Ok(old_addr)
+            },
+            ArmBr24 => {
+                if data.len() < 4 { return Err(RelocPackFail::Truncated); }
+                let old_word: u32 = util::copy_from_slice(&data[..4], self.endian);
+                if old_word & 0x0e000000 != 0x0a000000 { return Err(RelocPackFail::UnexpectedData); }
+                let is_blx = old_word >> 28 == 0xf;
+                // only an unconditional BL can become a BLX
+                let is_bl = is_blx || old_word & 0xff000000 == 0xeb000000;
+                let pc = self.base_addr.wrapping_add(8);
+                let mut imm = (old_word & 0xffffff) << 2;
+                if is_blx { imm |= (old_word >> 24 & 1) << 1 | 1; }
+                let old_addr = pc.wrapping_add(imm.sign_extend(26));
+                if let Some(new) = new {
+                    let thumb = new.0 & 1 != 0;
+                    let rel = VMA(new.0 & !1).wrapping_sub(pc);
+                    if thumb && !is_bl { return Err(RelocPackFail::AddrOutOfRange); }
+                    if !thumb && rel & 3 != 0 { return Err(RelocPackFail::AddrOutOfRange); }
+                    let x: u32 = try!(rel.un_sign_extend(26).ok_or(RelocPackFail::AddrOutOfRange));
+                    let base = if thumb {
+                        0xfa000000 | (x >> 1 & 1) << 24
+                    } else if is_blx {
+                        0xeb000000
+                    } else {
+                        old_word & 0xff000000
+                    };
+                    let word = base | (x >> 2 & 0xffffff);
+                    util::copy_to_slice(&data[..4], &word, self.endian);
+                }
+                Ok(old_addr)
+            },
+            ThumbBr22 => {
+                if data.len() < 4 { return Err(RelocPackFail::Truncated); }
+                let hw1: u16 = util::copy_from_slice(&data[..2], self.endian);
+                let hw2: u16 = util::copy_from_slice(&data[2..4], self.endian);
+                let (hw1, hw2) = (hw1 as u32, hw2 as u32);
+                if hw1 & 0xf800 != 0xf000 { return Err(RelocPackFail::UnexpectedData); }
+                // bl, blx, b.w
+                let (is_bl, is_blx) = match hw2 & 0xd000 {
+                    0xd000 => (true, false),
+                    0xc000 => (false, true),
+                    0x9000 => (false, false),
+                    _ => return Err(RelocPackFail::UnexpectedData),
+                };
+                let pc = self.base_addr.wrapping_add(4);
+                let s = hw1 >> 10 & 1;
+                let i1 = !(hw2 >> 13 ^ s) & 1;
+                let i2 = !(hw2 >> 11 ^ s) & 1;
+                let imm = s << 24 | i1 << 23 | i2 << 22 | (hw1 & 0x3ff) << 12 | (hw2 & 0x7ff) << 1;
+                let old_addr = if is_blx {
+                    VMA(pc.0 & !3).wrapping_add(imm.sign_extend(25))
+                } else {
+                    VMA(pc.wrapping_add(imm.sign_extend(25)).0 | 1)
+                };
+                if let Some(new) = new {
+                    let thumb = new.0 & 1 != 0;
+                    if !thumb && !(is_bl || is_blx) { return Err(RelocPackFail::AddrOutOfRange); }
+                    let rel = if thumb {
+                        VMA(new.0 & !1).wrapping_sub(pc)
+                    } else {
+                        new.wrapping_sub(VMA(pc.0 & !3))
+                    };
+                    if rel & (if thumb { 1 } else { 3 }) != 0 { return Err(RelocPackFail::AddrOutOfRange); }
+                    let x: u32 = try!(rel.un_sign_extend(25).ok_or(RelocPackFail::AddrOutOfRange));
+                    let s = x >> 24 & 1;
+                    let j1 = !(x >> 23 ^ s) & 1;
+                    let j2 = !(x >> 22 ^ s) & 1;
+                    let op = if !thumb { 0xc000 } else if is_bl || is_blx { 0xd000 } else { 0x9000 };
+                    let hw1 = (0xf000 | s << 10 | (x >> 12 & 0x3ff)) as u16;
+                    let hw2 = (op | j1 << 13 | j2 << 11 | (x >> 1 & 0x7ff)) as u16;
+                    util::copy_to_slice(&data[..2], &hw1, self.endian);
+                    util::copy_to_slice(&data[2..4], &hw2, self.endian);
+                }
+                Ok(old_addr)
+            },
+            ArmMovwMovt => {
+                if data.len() < 8 { return Err(RelocPackFail::Truncated); }
+                let movw: u32 = util::copy_from_slice(&data[..4], self.endian);
+                let movt: u32 = util::copy_from_slice(&data[4..8], self.endian);
+                if movw & 0x0ff00000 != 0x03000000 || movt & 0x0ff00000 != 0x03400000 ||
+                   movw >> 12 & 0xf != movt >> 12 & 0xf {
+                    return Err(RelocPackFail::UnexpectedData);
+                }
+                let get = |w: u32| (w >> 4 & 0xf000) | (w & 0xfff);
+                let set = |w: u32, imm: u32| (w & 0xfff0f000) | (imm & 0xf000) << 4 | (imm & 0xfff);
+                let old = get(movt) << 16 | get(movw);
+                if let Some(VMA(new)) = new {
+                    let new: u32 = try!(new.narrow().ok_or(RelocPackFail::AddrOutOfRange));
+                    util::copy_to_slice(&data[..4], &set(movw, new & 0xffff), self.endian);
+                    util::copy_to_slice(&data[4..8], &set(movt, new >> 16), self.endian);
+                }
+                Ok(VMA(old.ext()))
+            },
+            ThumbMovwMovt => {
+                if data.len() < 8 { return Err(RelocPackFail::Truncated); }
+                let mut hws = [0u32; 4];
+                for (i, hw) in hws.iter_mut().enumerate() {
+                    let x: u16 = util::copy_from_slice(&data[i*2..i*2+2], self.endian);
+                    *hw = x as u32;
+                }
+                if hws[0] & 0xfbf0 != 0xf240 || hws[2] & 0xfbf0 != 0xf2c0 ||
+                   hws[1] & 0x8000 != 0 || hws[3] & 0x8000 != 0 ||
+                   hws[1] >> 8 & 0xf != hws[3] >> 8 & 0xf {
+                    return Err(RelocPackFail::UnexpectedData);
+                }
+                let get = |hw1: u32, hw2: u32| (hw1 & 0xf) << 12 | (hw1 >> 10 & 1) << 11 | (hw2 >> 12 & 7) << 8 | (hw2 & 0xff);
+                let old = get(hws[2], hws[3]) << 16 | get(hws[0], hws[1]);
+                if let Some(VMA(new)) = new {
+                    let new: u32 = try!(new.narrow().ok_or(RelocPackFail::AddrOutOfRange));
+                    for &(i, imm) in &[(0, new & 0xffff), (2, new >> 16)] {
+                        let hw1 = ((hws[i] & 0xfbf0) | (imm >> 11 & 1) << 10 | imm >> 12) as u16;
+                        let hw2 = ((hws[i+1] & 0x8f00) | (imm >> 8 & 7) << 12 | (imm & 0xff)) as u16;
+                        util::copy_to_slice(&data[i*2..i*2+2], &hw1, self.endian);
+                        util::copy_to_slice(&data[i*2+2..i*2+4], &hw2, self.endian);
+                    }
+                }
+                Ok(VMA(old.ext()))
+            },
+            Rel32 | GotPcRel => {
+                if data.len() < 4 { return Err(RelocPackFail::Truncated); }
+                let old: u32 = util::copy_from_slice(&data[..4], self.endian);
+                let end = self.base_addr.wrapping_add(4);
+                let old_addr = end.wrapping_add(old.sign_extend(32));
+                if let Some(new) = new {
+                    let rel = new.wrapping_sub(end);
+                    if rel.wrapping_add(0x80000000) >> 32 != 0 { return Err(RelocPackFail::AddrOutOfRange); }
+                    util::copy_to_slice(&data[..4], &(rel as u32), self.endian);
+                }
+                Ok(old_addr)
+            },
+            RiscVBranch => {
+                if data.len() < 4 { return Err(RelocPackFail::Truncated); }
+                let old_word: u32 = util::copy_from_slice(&data[..4], self.endian);
+                if old_word & 0x7f != 0x63 { return Err(RelocPackFail::UnexpectedData); }
+                let imm = (old_word >> 31 & 1) << 12 | (old_word >> 7 & 1) << 11 |
+                          (old_word >> 25 & 0x3f) << 5 | (old_word >> 8 & 0xf) << 1;
+                let old_addr = self.base_addr.wrapping_add(imm.sign_extend(13));
+                if let Some(new) = new {
+                    let rel = new.wrapping_sub(self.base_addr);
+                    if rel & 1 != 0 { return Err(RelocPackFail::AddrOutOfRange); }
+                    let x: u32 = try!(rel.un_sign_extend(13).ok_or(RelocPackFail::AddrOutOfRange));
+                    let word = (old_word & 0x01fff07f) |
+                               (x >> 12 & 1) << 31 | (x >> 11 & 1) << 7 |
+                               (x >> 5 & 0x3f) << 25 | (x >> 1 & 0xf) << 8;
+                    util::copy_to_slice(&data[..4], &word, self.endian);
+                }
+                Ok(old_addr)
+            },
+            RiscVJal => {
+                if data.len() < 4 { return Err(RelocPackFail::Truncated); }
+                let old_word: u32 = util::copy_from_slice(&data[..4], self.endian);
+                if old_word & 0x7f != 0x6f { return Err(RelocPackFail::UnexpectedData); }
+                let imm = (old_word >> 31 & 1) << 20 | (old_word >> 12 & 0xff) << 12 |
+                          (old_word >> 20 & 1) << 11 | (old_word >> 21 & 0x3ff) << 1;
+                let old_addr = self.base_addr.wrapping_add(imm.sign_extend(21));
+                if let Some(new) = new {
+                    let rel = new.wrapping_sub(self.base_addr);
+                    if rel & 1 != 0 { return Err(RelocPackFail::AddrOutOfRange); }
+                    let x: u32 = try!(rel.un_sign_extend(21).ok_or(RelocPackFail::AddrOutOfRange));
+                    let word = (old_word & 0xfff) |
+                               (x >> 20 & 1) << 31 | (x >> 12 & 0xff) << 12 |
+                               (x >> 11 & 1) << 20 | (x >> 1 & 0x3ff) << 21;
+                    util::copy_to_slice(&data[..4], &word, self.endian);
+                }
+                Ok(old_addr)
+            },
+            RiscVCall => {
+                if data.len() < 8 { return Err(RelocPackFail::Truncated); }
+                let auipc: u32 = util::copy_from_slice(&data[..4], self.endian);
+                let jalr: u32 = util::copy_from_slice(&data[4..8], self.endian);
+                if auipc & 0x7f != 0x17 || jalr & 0x707f != 0x67 {
+                    return Err(RelocPackFail::UnexpectedData);
+                }
+                let hi: u64 = (auipc & !0xfff).sign_extend(32);
+                let lo: u64 = (jalr >> 20).sign_extend(12);
+                let old_addr = self.base_addr.wrapping_add(hi.wrapping_add(lo));
+                if let Some(new) = new {
+                    let rel = new.wrapping_sub(self.base_addr);
+                    // jalr's immediate is signed, so round the auipc part
+                    let hi = rel.wrapping_add(0x800) & !0xfff;
+                    let lo = rel.wrapping_sub(hi) & 0xfff;
+                    if hi.wrapping_add(0x80000000) >> 32 != 0 { return Err(RelocPackFail::AddrOutOfRange); }
+                    let auipc = (auipc & 0xfff) | hi as u32;
+                    let jalr = (jalr & 0xfffff) | (lo as u32) << 20;
+                    util::copy_to_slice(&data[..4], &auipc, self.endian);
+                    util::copy_to_slice(&data[4..8], &jalr, self.endian);
+                }
+                Ok(old_addr)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::LittleEndian;
+    use std::cell::Cell;
+
+    fn le32(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|&w| (0..4).map(move |i| (w >> (i * 8)) as u8)).collect()
+    }
+    fn le16(hws: &[u16]) -> Vec<u8> {
+        hws.iter().flat_map(|&hw| vec![hw as u8, (hw >> 8) as u8]).collect()
+    }
+    fn ctx(kind: RelocKind, base: u64) -> RelocContext {
+        RelocContext { kind: kind, base_addr: VMA(base), pointer_size: 4, endian: LittleEndian }
+    }
+    fn unpack(kind: RelocKind, base: u64, data: &[u8]) -> Result<VMA, RelocPackFail> {
+        let cells: Vec<Cell<u8>> = data.iter().map(|&b| Cell::new(b)).collect();
+        ctx(kind, base).pack_unpack_insn(&cells, None)
+    }
+    // packs new, checks that it unpacks again, and returns the new bytes
+    fn pack(kind: RelocKind, base: u64, data: &[u8], new: u64) -> Result<Vec<u8>, RelocPackFail> {
+        let cells: Vec<Cell<u8>> = data.iter().map(|&b| Cell::new(b)).collect();
+        let res = ctx(kind, base).pack_unpack_insn(&cells, Some(VMA(new)));
+        let out: Vec<u8> = cells.iter().map(|c| c.get()).collect();
+        match res {
+            Ok(_) => {
+                assert_eq!(unpack(kind, base, &out), Ok(VMA(new)));
+                Ok(out)
+            },
+            Err(e) => {
+                assert_eq!(&out[..], data);
+                Err(e)
+            },
+        }
+    }
+
+    #[test]
+    fn test_arm_br24() {
+        // bl 0x2000 at 0x1000
+        let bl = le32(&[0xeb0003fe]);
+        assert_eq!(unpack(ArmBr24, 0x1000, &bl), Ok(VMA(0x2000)));
+        // b 0x800
+        assert_eq!(unpack(ArmBr24, 0x1000, &le32(&[0xeafffdfe])), Ok(VMA(0x800)));
+        assert_eq!(pack(ArmBr24, 0x1000, &le32(&[0xea000000]), 0x800), Ok(le32(&[0xeafffdfe])));
+        // a Thumb target turns bl into blx, with the halfword bit in H, and back
+        let blx = le32(&[0xfb0003fe]);
+        assert_eq!(pack(ArmBr24, 0x1000, &bl, 0x2003), Ok(blx.clone()));
+        assert_eq!(pack(ArmBr24, 0x1000, &blx, 0x2000), Ok(bl.clone()));
+        // limits: +/-32MB from pc
+        assert_eq!(pack(ArmBr24, 0x1000, &bl, 0x1008 + 0x1fffffc), Ok(le32(&[0xeb7fffff])));
+        assert_eq!(pack(ArmBr24, 0x10000000, &bl, 0x10000008 - 0x2000000), Ok(le32(&[0xeb800000])));
+        assert_eq!(pack(ArmBr24, 0x1000, &bl, 0x1008 + 0x2000000), Err(RelocPackFail::AddrOutOfRange));
+        assert_eq!(pack(ArmBr24, 0x10000000, &bl, 0x10000004 - 0x2000000), Err(RelocPackFail::AddrOutOfRange));
+        // misaligned ARM target; Thumb target for a conditional branch
+        assert_eq!(pack(ArmBr24, 0x1000, &bl, 0x2002), Err(RelocPackFail::AddrOutOfRange));
+        assert_eq!(pack(ArmBr24, 0x1000, &le32(&[0x0a000000]), 0x2001), Err(RelocPackFail::AddrOutOfRange));
+        assert_eq!(unpack(ArmBr24, 0x1000, &le32(&[0xe3a00000])), Err(RelocPackFail::UnexpectedData));
+        assert_eq!(unpack(ArmBr24, 0x1000, &bl[..3]), Err(RelocPackFail::Truncated));
+    }
+
+    #[test]
+    fn test_thumb_br22() {
+        // bl 0x2000 (Thumb) at 0x1000
+        let bl = le16(&[0xf000, 0xfffe]);
+        assert_eq!(unpack(ThumbBr22, 0x1000, &bl), Ok(VMA(0x2001)));
+        // bl to itself
+        assert_eq!(pack(ThumbBr22, 0x1000, &bl, 0x1001), Ok(le16(&[0xf7ff, 0xfffe])));
+        // J1/J2 are I1/I2 xor'd with the sign: positive with I2 set, negative with I1 set
+        assert_eq!(pack(ThumbBr22, 0x1000, &bl, 0x1004 + 0x400000 + 1), Ok(le16(&[0xf000, 0xf000])));
+        assert_eq!(pack(ThumbBr22, 0x1000000, &bl, 0x1000004 - 0x800000 + 1), Ok(le16(&[0xf400, 0xf000])));
+        assert_eq!(unpack(ThumbBr22, 0x1000000, &le16(&[0xf400, 0xf000])), Ok(VMA(0x800005)));
+        // limits: +/-16MB from pc
+        assert_eq!(pack(ThumbBr22, 0x1000, &bl, 0x1004 + 0xfffffe + 1), Ok(le16(&[0xf3ff, 0xd7ff])));
+        assert_eq!(pack(ThumbBr22, 0x2000000, &bl, 0x2000004 - 0x1000000 + 1), Ok(le16(&[0xf400, 0xd000])));
+        assert_eq!(pack(ThumbBr22, 0x1000, &bl, 0x1004 + 0x1000000 + 1), Err(RelocPackFail::AddrOutOfRange));
+        assert_eq!(pack(ThumbBr22, 0x2000000, &bl, 0x2000004 - 0x1000002 + 1), Err(RelocPackFail::AddrOutOfRange));
+        // an ARM target makes it blx, relative to the word-aligned pc
+        let blx = le16(&[0xf000, 0xeffe]);
+        assert_eq!(pack(ThumbBr22, 0x1002, &bl, 0x2000), Ok(blx.clone()));
+        assert_eq!(pack(ThumbBr22, 0x1002, &blx, 0x2001), Ok(le16(&[0xf000, 0xfffd])));
+        assert_eq!(pack(ThumbBr22, 0x1002, &bl, 0x2002), Err(RelocPackFail::AddrOutOfRange));
+        // b.w can't switch modes
+        let bw = le16(&[0xf000, 0xbffe]);
+        assert_eq!(unpack(ThumbBr22, 0x1000, &bw), Ok(VMA(0x2001)));
+        assert_eq!(pack(ThumbBr22, 0x1000, &bw, 0x2000), Err(RelocPackFail::AddrOutOfRange));
+        assert_eq!(unpack(ThumbBr22, 0x1000, &le16(&[0xf000, 0x8000])), Err(RelocPackFail::UnexpectedData));
+        assert_eq!(unpack(ThumbBr22, 0x1000, &le16(&[0xe000, 0xfffe])), Err(RelocPackFail::UnexpectedData));
+    }
+
+    #[test]
+    fn test_arm_movw_movt() {
+        // movw r0, #0x5678; movt r0, #0x1234
+        let pair = le32(&[0xe3050678, 0xe3410234]);
+        assert_eq!(unpack(ArmMovwMovt, 0, &pair), Ok(VMA(0x12345678)));
+        assert_eq!(pack(ArmMovwMovt, 0, &pair, 0xdeadbeef), Ok(le32(&[0xe30b0eef, 0xe34d0ead])));
+        assert_eq!(pack(ArmMovwMovt, 0, &pair, 0x100000000), Err(RelocPackFail::AddrOutOfRange));
+        // different registers
+        assert_eq!(unpack(ArmMovwMovt, 0, &le32(&[0xe3050678, 0xe3411234])), Err(RelocPackFail::UnexpectedData));
+        assert_eq!(unpack(ArmMovwMovt, 0, &pair[..4]), Err(RelocPackFail::Truncated));
+    }
+
+    #[test]
+    fn test_thumb_movw_movt() {
+        // movw r0, #0x5678; movt r0, #0x1234
+        let pair = le16(&[0xf245, 0x6078, 0xf2c1, 0x2034]);
+        assert_eq!(unpack(ThumbMovwMovt, 0, &pair), Ok(VMA(0x12345678)));
+        // both halves need the i bit
+        assert_eq!(pack(ThumbMovwMovt, 0, &pair, 0xdeadbeef), Ok(le16(&[0xf64b, 0x60ef, 0xf6cd, 0x60ad])));
+        assert_eq!(pack(ThumbMovwMovt, 0, &pair, 0x100000000), Err(RelocPackFail::AddrOutOfRange));
+        // different registers
+        assert_eq!(unpack(ThumbMovwMovt, 0, &le16(&[0xf245, 0x6078, 0xf2c1, 0x2134])), Err(RelocPackFail::UnexpectedData));
+    }
+
+    #[test]
+    fn test_rel32() {
+        // the field of call 0x2000 at 0x1000
+        let call = le32(&[0xffb]);
+        for &kind in &[Rel32, GotPcRel] {
+            assert_eq!(unpack(kind, 0x1001, &call), Ok(VMA(0x2000)));
+            assert_eq!(pack(kind, 0x1001, &call, 0x800), Ok(le32(&[0xfffff7fb])));
+            // limits: +/-2GB from the end of the field
+            assert_eq!(pack(kind, 0x1001, &call, 0x1005 + 0x7fffffff), Ok(le32(&[0x7fffffff])));
+            assert_eq!(pack(kind, 0x80001001, &call, 0x80001005 - 0x80000000), Ok(le32(&[0x80000000])));
+            assert_eq!(pack(kind, 0x1001, &call, 0x1005 + 0x80000000), Err(RelocPackFail::AddrOutOfRange));
+            assert_eq!(pack(kind, 0x80001001, &call, 0x80001004 - 0x80000000), Err(RelocPackFail::AddrOutOfRange));
+            assert_eq!(unpack(kind, 0x1001, &call[..3]), Err(RelocPackFail::Truncated));
         }
     }
 }
@@ -18,13 +18,15 @@ extern crate fmt_all;
 
 use std::fs;
 use std::path::Path;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::cmp::min;
 use std::str::FromStr;
 use std::any::Any;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 
 use util::{into_cow, Ext, vec_extend_from_slice};
-use exec::{arch, SymbolValue, VMA};
+use exec::{arch, SymbolValue, RelocTarget, VMA};
 use exec::arch::{ArchAndOptions, CodeMode};
 
 fn macho_filedata_info(mo: &macho::MachO) {
@@ -46,6 +48,293 @@ fn macho_filedata_info(mo: &macho::MachO) {
     entry(&mo.dyld_weak_bind, "dyld weak_bind");
     entry(&mo.dyld_lazy_bind, "dyld lazy_bind");
     entry(&mo.dyld_export,    "dyld export");
+    entry(&mo.dyld_chained_fixups, "chained fixups");
+    entry(&mo.dyld_exports_trie, "exports trie");
+}
+
+fn macho_codesign(mo: &macho::MachO) {
+    use macho::codesign::*;
+    let hex = |data: &[u8]| -> String { data.iter().map(|b| format!("{:02x}", b)).collect() };
+    let cs = match mo.parse_code_signature() {
+        Ok(Some(cs)) => cs,
+        Ok(None) => { println!("No code signature."); return; },
+        Err(e) => { errln!("code signature: {}", e.message); return; },
+    };
+    let file = mo.code_signed_file();
+    let sig_off = cs.blob.byte_offset_in(&mo.eb.whole_buf.as_ref().unwrap()).unwrap_or(0);
+    println!("Code signature: offset {:#x}, length {:#x}", sig_off, cs.blob.len());
+    for b in &cs.blobs {
+        println!("  {:<24} offset {:<#8x} length {:<#8x} magic {:#x}",
+                 slot_name(b.slot), b.offset, b.blob.len(), b.magic);
+    }
+    for cd in &cs.code_directories {
+        println!("{}:", slot_name(cd.slot));
+        println!("  version:    {:#x}", cd.version);
+        println!("  flags:      {:#x} ({})", cd.flags, cs_flags_desc(cd.flags));
+        println!("  hash type:  {} ({}), size {}", cd.hash_type, hash_type_name(cd.hash_type), cd.hash_size);
+        println!("  page size:  {}", cd.page_size().map_or("none".to_owned(), |ps| format!("{:#x}", ps)));
+        println!("  platform:   {}", cd.platform);
+        println!("  ident:      {}", cd.ident);
+        if let Some(ref team_id) = cd.team_id {
+            println!("  team id:    {}", team_id);
+        }
+        println!("  code limit: {:#x}", cd.code_limit);
+        if let Some(ref es) = cd.exec_seg {
+            println!("  exec seg:   base {:#x} limit {:#x} flags {:#x} ({})",
+                     es.base, es.limit, es.flags, exec_seg_flags_desc(es.flags));
+        }
+        if let Some(runtime) = cd.runtime {
+            println!("  runtime:    {}", macho::PackedVersion(runtime));
+        }
+        if let Some(cdhash) = cd.cdhash() {
+            println!("  cdhash:     {}", hex(&cdhash));
+        }
+        for (slot, status) in cs.check_special_slots(cd) {
+            println!("  special slot {} ({}): {:?}", slot, slot_name(slot), status);
+        }
+        match cd.bad_pages(file) {
+            Ok(ref bad) if bad.is_empty() => println!("  pages:      all {} verify", cd.code_hashes.len()),
+            Ok(bad) => {
+                println!("  pages:      {} of {} fail to verify:", bad.len(), cd.code_hashes.len());
+                for i in bad {
+                    let range = cd.page_range(i);
+                    println!("    page {} ({:#x}-{:#x})", i, range.start, range.end);
+                }
+            },
+            Err(e) => println!("  pages:      can't verify: {}", e.message),
+        }
+    }
+    for &(ty, ref req) in &cs.requirements {
+        println!("Requirement: {} ({}), length {:#x}", ty, requirement_type_name(ty), req.len());
+    }
+    if let Some(ref ents) = cs.entitlements {
+        println!("Entitlements:\n{}", ents);
+    }
+    if let Some(ref der) = cs.der_entitlements {
+        println!("DER entitlements: length {:#x}", der.len());
+    }
+    match cs.cms {
+        Some((off, len)) => println!("CMS signature: offset {:#x}, length {:#x}", sig_off + off, len),
+        None => println!("CMS signature: none"),
+    }
+}
+
+// Roughly otool -l.
+fn macho_lcs(mo: &macho::MachO) {
+    use macho::load_commands::*;
+    for (i, lc) in mo.load_commands.iter().enumerate() {
+        let parsed = parse_load_command(lc.get(), mo.eb.endian);
+        let cmd = parsed.cmd();
+        println!("Load command {}", i);
+        match lc_name(cmd) {
+            Some(name) => println!("      cmd {}", name),
+            None => println!("      cmd {:#x}", cmd),
+        }
+        println!("  cmdsize {}", lc.len());
+        match parsed {
+            LoadCommand::Segment(seg) => {
+                println!("  segname {}", seg.segname);
+                println!("   vmaddr {:#x}", seg.vmaddr);
+                println!("   vmsize {:#x}", seg.vmsize);
+                println!("  fileoff {}", seg.fileoff);
+                println!(" filesize {}", seg.filesize);
+                println!("  maxprot {:#x}", seg.maxprot);
+                println!(" initprot {:#x}", seg.initprot);
+                println!("   nsects {}", seg.sections.len());
+                println!("    flags {:#x}", seg.flags);
+                for sect in &seg.sections {
+                    println!("Section");
+                    println!("  sectname {}", sect.sectname);
+                    println!("   segname {}", sect.segname);
+                    println!("      addr {:#x}", sect.addr);
+                    println!("      size {:#x}", sect.size);
+                    println!("    offset {}", sect.offset);
+                    println!("     align 2^{} ({})", sect.align, 1u64.checked_shl(sect.align).unwrap_or(0));
+                    println!("    reloff {}", sect.reloff);
+                    println!("    nreloc {}", sect.nreloc);
+                    println!("     flags {:#x}", sect.flags);
+                    println!(" reserved1 {}", sect.reserved1);
+                    println!(" reserved2 {}", sect.reserved2);
+                }
+            },
+            LoadCommand::Symtab(st) => {
+                println!("   symoff {}", st.symoff);
+                println!("    nsyms {}", st.nsyms);
+                println!("   stroff {}", st.stroff);
+                println!("  strsize {}", st.strsize);
+            },
+            LoadCommand::Dysymtab(ds) => {
+                println!("      ilocalsym {}", ds.ilocalsym);
+                println!("      nlocalsym {}", ds.nlocalsym);
+                println!("     iextdefsym {}", ds.iextdefsym);
+                println!("     nextdefsym {}", ds.nextdefsym);
+                println!("      iundefsym {}", ds.iundefsym);
+                println!("      nundefsym {}", ds.nundefsym);
+                println!("         tocoff {}", ds.tocoff);
+                println!("           ntoc {}", ds.ntoc);
+                println!("      modtaboff {}", ds.modtaboff);
+                println!("        nmodtab {}", ds.nmodtab);
+                println!("   extrefsymoff {}", ds.extrefsymoff);
+                println!("    nextrefsyms {}", ds.nextrefsyms);
+                println!(" indirectsymoff {}", ds.indirectsymoff);
+                println!("  nindirectsyms {}", ds.nindirectsyms);
+                println!("      extreloff {}", ds.extreloff);
+                println!("        nextrel {}", ds.nextrel);
+                println!("      locreloff {}", ds.locreloff);
+                println!("        nlocrel {}", ds.nlocrel);
+            },
+            LoadCommand::DyldInfo(di) => {
+                println!("     rebase_off {}", di.rebase_off);
+                println!("    rebase_size {}", di.rebase_size);
+                println!("       bind_off {}", di.bind_off);
+                println!("      bind_size {}", di.bind_size);
+                println!("  weak_bind_off {}", di.weak_bind_off);
+                println!(" weak_bind_size {}", di.weak_bind_size);
+                println!("  lazy_bind_off {}", di.lazy_bind_off);
+                println!(" lazy_bind_size {}", di.lazy_bind_size);
+                println!("     export_off {}", di.export_off);
+                println!("    export_size {}", di.export_size);
+            },
+            LoadCommand::LinkeditData(ld) => {
+                println!("  dataoff {}", ld.dataoff);
+                println!(" datasize {}", ld.datasize);
+            },
+            LoadCommand::Dylib { name, timestamp, current_version, compatibility_version, .. } => {
+                println!("         name {}", name);
+                println!("   time stamp {}", timestamp);
+                println!("      current version {}", current_version);
+                println!("compatibility version {}", compatibility_version);
+            },
+            LoadCommand::Dylinker { name, .. } | LoadCommand::DyldEnvironment(name) => {
+                println!("         name {}", name);
+            },
+            LoadCommand::Rpath(path) => println!("         path {}", path),
+            LoadCommand::Uuid(uuid) => {
+                let hex: Vec<String> = uuid.iter().map(|b| format!("{:02X}", b)).collect();
+                println!("    uuid {}-{}-{}-{}-{}", hex[0..4].concat(), hex[4..6].concat(), hex[6..8].concat(),
+                         hex[8..10].concat(), hex[10..16].concat());
+            },
+            LoadCommand::BuildVersion(bv) => {
+                match platform_name(bv.platform) {
+                    Some(name) => println!(" platform {}", name),
+                    None => println!(" platform {}", bv.platform),
+                }
+                println!("    minos {}", bv.minos);
+                println!("      sdk {}", bv.sdk);
+                println!("   ntools {}", bv.tools.len());
+                for &(tool, version) in &bv.tools {
+                    match tool_name(tool) {
+                        Some(name) => println!("     tool {}", name),
+                        None => println!("     tool {}", tool),
+                    }
+                    println!("  version {}", version);
+                }
+            },
+            LoadCommand::VersionMin(vm) => {
+                println!("  version {}", vm.version);
+                println!("      sdk {}", vm.sdk);
+            },
+            LoadCommand::Main(ep) => {
+                println!("  entryoff {}", ep.entryoff);
+                println!(" stacksize {}", ep.stacksize);
+            },
+            LoadCommand::SourceVersion(sv) => println!("  version {}", sv),
+            LoadCommand::EncryptionInfo(ei) => {
+                println!(" cryptoff {}", ei.cryptoff);
+                println!("cryptsize {}", ei.cryptsize);
+                println!("  cryptid {}", ei.cryptid);
+            },
+            LoadCommand::Other { .. } => (),
+        }
+    }
+}
+
+fn macho_objc(mo: &macho::MachO) {
+    use macho::objc::{ObjCMethod, ObjCProperty};
+    fn protocols(protos: &[util::ByteString]) -> String {
+        if protos.is_empty() { return String::new(); }
+        format!(" <{}>", protos.iter().map(|p| p.lossy().into_owned()).collect::<Vec<_>>().join(", "))
+    }
+    fn methods(prefix: &str, methods: &[ObjCMethod]) {
+        for m in methods {
+            print!("{} {}; // {}", prefix, m.name, m.types);
+            if let Some(imp) = m.imp { print!(" {}", imp); }
+            println!("");
+        }
+    }
+    fn properties(props: &[ObjCProperty]) {
+        for p in props {
+            println!("@property {}; // {}", p.name, p.attributes);
+        }
+    }
+    let info = mo.parse_objc();
+    for p in &info.protocols {
+        println!("@protocol {}{} // {}", p.name, protocols(&p.protocols), p.addr);
+        properties(&p.properties);
+        methods("+", &p.class_methods);
+        methods("-", &p.instance_methods);
+        if !p.optional_class_methods.is_empty() || !p.optional_instance_methods.is_empty() {
+            println!("@optional");
+            methods("+", &p.optional_class_methods);
+            methods("-", &p.optional_instance_methods);
+        }
+        println!("@end\n");
+    }
+    for c in &info.classes {
+        print!("@interface {}", c.name);
+        if let Some(ref sup) = c.superclass { print!(" : {}", sup); }
+        print!("{} // {}", protocols(&c.protocols), c.addr);
+        if c.is_swift { print!(" (swift)"); }
+        println!("");
+        if !c.ivars.is_empty() {
+            println!("{{");
+            for ivar in &c.ivars {
+                print!("    {} {}; //", ivar.type_, ivar.name);
+                if let Some(offset) = ivar.offset { print!(" offset {:#x},", offset); }
+                println!(" size {:#x}", ivar.size);
+            }
+            println!("}}");
+        }
+        properties(&c.properties);
+        methods("+", &c.class_methods);
+        methods("-", &c.instance_methods);
+        println!("@end\n");
+    }
+    for c in &info.categories {
+        let class_name = c.class_name.as_ref().map_or("?".into(), |n| n.lossy());
+        println!("@interface {} ({}){} // {}", class_name, c.name, protocols(&c.protocols), c.addr);
+        properties(&c.properties);
+        methods("+", &c.class_methods);
+        methods("-", &c.instance_methods);
+        println!("@end\n");
+    }
+}
+
+fn macho_swift(mo: &macho::MachO) {
+    use macho::swift::{SwiftTypeKind, FIELD_IS_VAR, FIELD_IS_INDIRECT_CASE};
+    let info = mo.parse_swift();
+    for ty in &info.types {
+        print!("{} {}", ty.kind.keyword(), ty.name);
+        if let Some(ref sup) = ty.superclass { print!(" : {}", sup); }
+        println!(" // {}", ty.addr);
+        for field in &ty.fields {
+            if ty.kind == SwiftTypeKind::Enum {
+                print!("    {}case {}", if field.flags & FIELD_IS_INDIRECT_CASE != 0 { "indirect " } else { "" }, field.name);
+                if let Some(ref type_name) = field.type_name { print!("({})", type_name); }
+                println!("");
+            } else {
+                println!("    {} {}: {}", if field.flags & FIELD_IS_VAR != 0 { "var" } else { "let" },
+                         field.name, field.type_name.as_ref().map_or("?", |t| &t[..]));
+            }
+        }
+        println!("");
+    }
+    if !info.conformances.is_empty() {
+        println!("Conformances:");
+        for conf in &info.conformances {
+            println!("{} : {} // {}", conf.type_name, conf.protocol, conf.addr);
+        }
+    }
 }
 
 fn elf_dynamic_raw(elf: &elf::Elf) {
@@ -63,8 +352,7 @@ fn elf_dynamic(elf: &mut elf::Elf) {
     elf.dynamic_info.dump(Some(elf));
 }
 
-fn get_dump_from_spec(ex: &Box<exec::Exec>, dump_spec: String) -> Result<Vec<u8>, String> {
-    let eb = ex.get_exec_base();
+fn parse_dump_spec(dump_spec: &str) -> Result<(VMA, u64), String> {
     let z;
     let is_addr_end: bool;
     if let Some(z_) = dump_spec.find('+') {
@@ -74,10 +362,8 @@ fn get_dump_from_spec(ex: &Box<exec::Exec>, dump_spec: String) -> Result<Vec<u8>
     } else {
         return Err(format!("invalid dump spec '{}' - should be addr+len or addr1-addr2", dump_spec));
     }
-    let addr: u64 = util::stoi(&dump_spec[..z]).unwrap();
-    let mut size: u64 = util::stoi(&dump_spec[z+1..]).unwrap();
-    assert!(size <= (std::usize::MAX as u64));
-    let mut ret = Vec::with_capacity(size as usize);
+    let addr: u64 = try!(util::stoi(&dump_spec[..z]).ok_or_else(|| format!("bad address in dump spec '{}'", dump_spec)));
+    let mut size: u64 = try!(util::stoi(&dump_spec[z+1..]).ok_or_else(|| format!("bad length in dump spec '{}'", dump_spec)));
     if is_addr_end {
         // 'size' is actually end
         if size < addr {
@@ -85,13 +371,17 @@ fn get_dump_from_spec(ex: &Box<exec::Exec>, dump_spec: String) -> Result<Vec<u8>
         }
         size -= addr;
     }
+    Ok((VMA(addr), size))
+}
 
-    let (mut addr, mut size) = (VMA(addr), size);
+fn read_vm(eb: &exec::ExecBase, mut addr: VMA, mut size: u64) -> Result<Vec<u8>, String> {
+    assert!(size <= (std::usize::MAX as u64));
+    let mut ret = Vec::with_capacity(size as usize);
     while size != 0 {
         if let Some((seg, off, osize)) = exec::addr_to_seg_off_range(&eb.segments, addr) {
             let osize = min(osize, size);
-            if osize > seg.filesize {
-                return Err(format!("zerofill at: {} (in segment '{}')", addr + seg.filesize, seg.pretty_name()));
+            if off + osize > seg.filesize {
+                return Err(format!("zerofill at: {} (in segment '{}')", seg.vmaddr + seg.filesize, seg.pretty_name()));
             }
             let buf = seg.data.as_ref().unwrap().get();
             vec_extend_from_slice(&mut ret, &buf[off as usize..(off+osize) as usize]);
@@ -101,10 +391,255 @@ fn get_dump_from_spec(ex: &Box<exec::Exec>, dump_spec: String) -> Result<Vec<u8>
             return Err(format!("unmapped at: {}", addr));
         }
     }
-
     Ok(ret)
 }
 
+fn get_dump_from_spec(ex: &Box<exec::Exec>, dump_spec: String) -> Result<Vec<u8>, String> {
+    let (addr, size) = try!(parse_dump_spec(&dump_spec));
+    read_vm(ex.get_exec_base(), addr, size)
+}
+
+struct DisSym {
+    addr: VMA,
+    name: String,
+    size: Option<u64>,
+    thumb: bool,
+}
+
+// Sorted by address; only symbols that point at something we could
+// disassemble, plus names for import pointers and the stubs using them.
+fn collect_dis_syms(ex: &Box<exec::Exec>, macho: Option<&macho::MachO>, opts: Option<&Any>) -> Vec<DisSym> {
+    let eb = ex.get_exec_base();
+    let is_arm = eb.arch == arch::ARM;
+    let mut syms: Vec<DisSym> = ex.get_symbol_list(exec::SymbolSource::All, opts).into_iter().filter_map(|sym| {
+        let addr = match sym.val {
+            SymbolValue::Addr(vma) | SymbolValue::Resolver(vma, _) => vma,
+            _ => return None,
+        };
+        let thumb = is_arm && addr.0 & 1 != 0;
+        Some(DisSym {
+            addr: if thumb { VMA(addr.0 & !1) } else { addr },
+            name: sym.name.lossy().into_owned(),
+            size: sym.size,
+            thumb: thumb,
+        })
+    }).collect();
+    let mut ptr_names: HashMap<VMA, String> = HashMap::new();
+    for rel in ex.get_reloc_list(None) {
+        if let RelocTarget::Import(sym) = rel.target {
+            ptr_names.insert(rel.address, sym.name.lossy().into_owned());
+        }
+    }
+    if let Some(mo) = macho {
+        for (stub, ptr) in macho_dsc_extraction::stub_targets(mo) {
+            if let Some(name) = ptr_names.get(&ptr) {
+                syms.push(DisSym { addr: stub, name: name.clone(), size: None, thumb: false });
+            }
+        }
+    }
+    for (addr, name) in ptr_names {
+        syms.push(DisSym { addr: addr, name: format!("{}@GOT", name), size: Some(eb.pointer_size as u64), thumb: false });
+    }
+    syms.sort_by_key(|sym| sym.addr);
+    // a symbol without a size runs until the next one or the end of its section
+    for i in 0..syms.len() {
+        if syms[i].size.is_some() { continue; }
+        let addr = syms[i].addr;
+        let next = syms[i+1..].iter().map(|sym| sym.addr).find(|&next| next > addr);
+        let sect_end = eb.sections.iter().find(|sect| addr >= sect.vmaddr && addr - sect.vmaddr < sect.vmsize)
+                                         .map(|sect| sect.vmaddr + sect.vmsize);
+        let end = match (next, sect_end) {
+            (Some(a), Some(b)) => Some(min(a, b)),
+            (a, b) => a.or(b),
+        };
+        syms[i].size = end.map(|end| end - addr);
+    }
+    syms
+}
+
+fn syms_at<'a>(syms: &'a [DisSym], addr: VMA) -> &'a [DisSym] {
+    let start = match syms.binary_search_by(|sym| if sym.addr < addr { Ordering::Less } else { Ordering::Greater }) {
+        Ok(i) | Err(i) => i,
+    };
+    let len = syms[start..].iter().take_while(|sym| sym.addr == addr).count();
+    &syms[start..start+len]
+}
+
+struct DisSymResolver<'a>(&'a [DisSym]);
+
+impl<'a> dis::SymbolResolver for DisSymResolver<'a> {
+    fn resolve(&self, addr: VMA) -> Option<(&str, u64)> {
+        let syms = self.0;
+        let idx = match syms.binary_search_by(|sym| if sym.addr <= addr { Ordering::Less } else { Ordering::Greater }) {
+            Ok(_) => unreachable!(),
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+        let sym = &syms[idx];
+        let off = addr - sym.addr;
+        if let Some(size) = sym.size {
+            if off != 0 && off >= size { return None; }
+        }
+        Some((&sym.name[..], off))
+    }
+}
+
+// Take endianness and width from the binary, since --arch only names the arch.
+fn arch_opts_for(eb: &exec::ExecBase, arch: arch::Arch) -> ArchAndOptions {
+    let mut arch_opts = ArchAndOptions::new_default(arch);
+    match arch_opts {
+        ArchAndOptions::ARM(ref mut opts) => opts.endian = eb.endian,
+        ArchAndOptions::Mips(ref mut opts) | ArchAndOptions::PowerPC(ref mut opts) => {
+            opts.endian = eb.endian;
+            opts.is64 = eb.pointer_size == 8;
+        },
+        ArchAndOptions::RiscV(ref mut opts) => opts.is64 = eb.pointer_size == 8,
+        _ => (),
+    }
+    arch_opts
+}
+
+fn code_mode_for(arch_opts: &ArchAndOptions, thumb: bool) -> CodeMode {
+    match arch_opts {
+        &ArchAndOptions::ARM(..) => CodeMode::ARMMode { thumb: thumb },
+        _ => CodeMode::new(arch_opts, &[]).unwrap(),
+    }
+}
+
+fn print_dis(dis: &dis::Disassembler, syms: &[DisSym], data: &[u8], base_pc: VMA, mode: CodeMode) {
+    let resolver = DisSymResolver(syms);
+    let results = dis.disassemble_multiple_to_str(&dis::DisassemblerInput {
+        data: data,
+        pc: base_pc,
+        mode: mode,
+        resolver: Some(&resolver),
+    });
+    let mut last_end: VMA = base_pc;
+    let mut leads = Vec::new();
+    for (dissed, pc, length) in results {
+        let diff = pc - last_end;
+        if diff != 0 {
+            println!("...skip {}", diff);
+        }
+        for sym in syms_at(syms, pc) {
+            println!("{}:", sym.name);
+        }
+        let text = if let Some(ref s) = dissed { &s[..] } else { "<?>" };
+        // backends that take the resolver name things themselves; trawl
+        // finds the targets the others don't print
+        let mut annots: Vec<String> = Vec::new();
+        if dis.can_trawl() {
+            leads.clear();
+            let off = (pc - base_pc) as usize;
+            dis.trawl(&dis::DisassemblerInput { data: &data[off..], pc: pc, mode: mode, resolver: None }, &mut leads);
+            for lead in &leads {
+                if let dis::TrawlLeadKind::NextInsn = lead.kind { continue; }
+                if let Some(desc) = dis::SymbolResolver::describe(&resolver, lead.addr) {
+                    annots.push(format!("<{}>", desc));
+                }
+            }
+        }
+        if annots.is_empty() {
+            println!("-> {}: {}", pc, text);
+        } else {
+            println!("-> {}: {}    ; {}", pc, text, annots.join(", "));
+        }
+        last_end = pc + length.ext();
+    }
+    let expected_end = base_pc + (data.len() as u64);
+    if last_end < expected_end {
+        println!("...skip {}", expected_end - last_end);
+    } else if last_end > expected_end {
+        println!("...over-read by {}", last_end - expected_end);
+    }
+}
+
+struct FuncRange {
+    start: VMA,
+    end: VMA,
+    thumb: bool,
+}
+
+// Split the executable sections into functions, using symbols plus
+// LC_FUNCTION_STARTS on Mach-O.  ELF symbol sizes bound a function when
+// known; otherwise it runs to the next start.
+fn find_func_ranges(eb: &exec::ExecBase, macho: Option<&macho::MachO>, syms: &[DisSym]) -> Vec<FuncRange> {
+    let is_arm = eb.arch == arch::ARM;
+    let mut starts: Vec<(VMA, bool)> = syms.iter().map(|sym| (sym.addr, sym.thumb)).collect();
+    if let Some(mo) = macho {
+        for addr in mo.parse_function_starts() {
+            // ld64 sets the low bit for thumb functions
+            if is_arm && addr.0 & 1 != 0 {
+                starts.push((VMA(addr.0 & !1), true));
+            } else {
+                starts.push((addr, false));
+            }
+        }
+    }
+    // if any source says thumb, believe it
+    starts.sort_by_key(|&(addr, thumb)| (addr, !thumb));
+    let mut sizes: HashMap<VMA, u64> = HashMap::new();
+    for sym in syms {
+        if let Some(size) = sym.size {
+            if size == 0 { continue; }
+            let e = sizes.entry(sym.addr).or_insert(0);
+            *e = std::cmp::max(*e, size);
+        }
+    }
+    let sects = if eb.sections.is_empty() { &eb.segments } else { &eb.sections };
+    let mut res = Vec::new();
+    for sect in sects {
+        let is_code = match macho {
+            Some(mo) => mo.sect_has_code(sect),
+            None => sect.prot.x,
+        };
+        if !is_code || sect.vmsize == 0 { continue; }
+        let (sect_start, sect_end) = (sect.vmaddr, sect.vmaddr + sect.vmsize);
+        let mut here: Vec<(VMA, bool)> = Vec::new();
+        for &(addr, thumb) in &starts {
+            if addr < sect_start || addr >= sect_end { continue; }
+            if here.last().map_or(false, |&(last, _)| last == addr) { continue; }
+            here.push((addr, thumb));
+        }
+        if here.first().map_or(true, |&(addr, _)| addr != sect_start) {
+            here.insert(0, (sect_start, false));
+        }
+        for (i, &(start, thumb)) in here.iter().enumerate() {
+            let mut end = here.get(i + 1).map_or(sect_end, |&(addr, _)| addr);
+            if let Some(&size) = sizes.get(&start) {
+                end = min(end, start + size);
+            }
+            res.push(FuncRange { start: start, end: end, thumb: thumb });
+        }
+    }
+    res
+}
+
+fn dis_func_range(dis: &dis::Disassembler, eb: &exec::ExecBase, syms: &[DisSym], dice: &[macho::DataInCodeEntry], arch_opts: &ArchAndOptions, func: &FuncRange) {
+    if syms_at(syms, func.start).is_empty() {
+        println!("sub_{:x}:", func.start.0);
+    }
+    let mode = code_mode_for(arch_opts, func.thumb);
+    let dis_piece = |start: VMA, end: VMA| {
+        match read_vm(eb, start, end - start) {
+            Ok(data) => print_dis(dis, syms, &data[..], start, mode),
+            Err(msg) => errln!("warning: can't disassemble {}-{}: {}", start, end, msg),
+        }
+    };
+    let mut addr = func.start;
+    for d in dice {
+        if d.addr >= func.end || d.addr + d.length <= addr { continue; }
+        if d.addr > addr {
+            dis_piece(addr, d.addr);
+        }
+        println!("-> {}: .data {} bytes (kind {})", d.addr, d.length, d.kind);
+        addr = std::cmp::max(addr, d.addr + d.length);
+    }
+    if addr < func.end {
+        dis_piece(addr, func.end);
+    }
+}
+
 fn print_segs(segs: &[exec::Segment]) {
     let pretty_names: Vec<_> = segs.iter().map(|s| s.pretty_name()).collect();
     let maxlen = pretty_names.iter().map(|pn| pn.len()).max().unwrap_or(0);
@@ -137,11 +672,21 @@ fn do_stuff(ex: &Box<exec::Exec>, m: &getopts::Matches) {
         assert!(elf.is_some());
         elf_specific.append_version = true;
     }
+    if let Some(which) = m.opt_str("elf-symtab") {
+        assert!(elf.is_some());
+        elf_specific.which = match &*which {
+            "dynamic" => elf::ElfWhichSymtab::Dynamic,
+            "static" => elf::ElfWhichSymtab::Static,
+            "both" => elf::ElfWhichSymtab::Both,
+            _ => { errln!("--elf-symtab: expected dynamic, static or both"); util::exit() },
+        };
+    }
     const KINDS: &'static [(&'static str, &'static str, exec::SymbolSource)] = &[
         ("syms", "All symbols", exec::SymbolSource::All),
         ("imports", "Imported symbols", exec::SymbolSource::Imported),
         ("exports", "Exported symbols", exec::SymbolSource::Exported),
     ];
+    let demangle = m.opt_present("demangle");
     for &(name, desc, kind) in KINDS {
         if !m.opt_present(name) { continue; }
         println!("{}:", desc);
@@ -149,7 +694,12 @@ fn do_stuff(ex: &Box<exec::Exec>, m: &getopts::Matches) {
             Some(&elf_specific as &Any)
         } else { None };
         for sym in ex.get_symbol_list(kind, opts) {
-            let name = sym.name.lossy();
+            let mut name = sym.name.lossy();
+            if demangle {
+                if let Some(demangled) = macho::swift_demangle::demangle(&name) {
+                    name = demangled.into();
+                }
+            }
             match sym.val {
                 SymbolValue::Addr(vma) =>        print!("{:<16}", vma),
                 SymbolValue::Abs(vma) =>         print!("{:<16}", vma),
@@ -182,19 +732,32 @@ fn do_stuff(ex: &Box<exec::Exec>, m: &getopts::Matches) {
     if m.opt_present("relocs") {
         println!("Relocations:");
         for rel in ex.get_reloc_list(None) {
-            println!("addr={} kind={:?}", rel.address, rel.kind);
-            /*
-            if let Some(add) = rel.addend {
-                println!(" addend=0x{:x}", add);
-            } else {
-                println!("");
+            print!("addr={} kind={:?}", rel.address, rel.kind);
+            if let Some(base) = rel.base {
+                print!(" base={}", base);
+            }
+            match rel.target {
+                RelocTarget::ThisImageSlide => println!(" target=<slide>"),
+                RelocTarget::ThisSegmentSlide => println!(" target=<segment slide>"),
+                RelocTarget::Import(sym) => println!(" target={}", sym.name.lossy()),
             }
-            */
         }
     }
     if m.opt_present("macho-filedata-info") {
         macho_filedata_info(macho.expect("macho-filedata-info: not mach-o"));
     }
+    if m.opt_present("macho-lcs") {
+        macho_lcs(macho.expect("macho-lcs: not mach-o"));
+    }
+    if m.opt_present("macho-codesign") {
+        macho_codesign(macho.expect("macho-codesign: not mach-o"));
+    }
+    if m.opt_present("objc") {
+        macho_objc(macho.expect("objc: not mach-o"));
+    }
+    if m.opt_present("swift") {
+        macho_swift(macho.expect("swift: not mach-o"));
+    }
     if let Some(off_str) = m.opt_str("o2a") {
         let off: u64 = util::stoi(&off_str).unwrap();
         if let Some(VMA(vma)) = exec::off_to_addr(&eb.segments, off, 0) {
@@ -219,40 +782,48 @@ fn do_stuff(ex: &Box<exec::Exec>, m: &getopts::Matches) {
     }
     let arch = match m.opt_str("arch") {
         Some(arch_s) => arch::Arch::from_str(&*arch_s).unwrap(),
-        None => arch::Arch::UnknownArch,
+        None => eb.arch,
     };
     // XXXXX
-    let arch_opts: ArchAndOptions = ArchAndOptions::new_default(arch);
+    let arch_opts: ArchAndOptions = arch_opts_for(eb, arch);
     // XXX should accept multiple copies of these
-    let mut dis_opts = vec!["llvmdis".to_owned()];
+    // prefer llvm if we were built with it
+    let default_dis = if dis_all::ALL_FAMILIES.iter().any(|df| df.name() == "llvm") { "llvm" } else { "simple" };
+    let mut dis_opts = vec![default_dis.to_owned()];
     if let Some(name) = m.opt_str("dis") {
         // XXX I should probably support [ and ] - also depends on customizing getopts
         dis_opts = vec![name.to_owned()];
     }
-    if let Some(dump_spec) = m.opt_str("dis-range") {
-        let dis = dis::create(dis_all::ALL_FAMILIES, arch_opts, &dis_opts).unwrap();
-        let dump_data = get_dump_from_spec(ex, dump_spec).unwrap();
-        let base_pc = VMA(0);
-        let results = dis.disassemble_multiple_to_str(&dis::DisassemblerInput {
-            data: &dump_data[..],
-            pc: base_pc,
-            mode: CodeMode::new(&arch_opts, &[]).unwrap(), // XXX
+    let dis_func = m.opt_str("dis-func");
+    let dis_all_text = m.opt_present("dis-all-text");
+    let dis_range = m.opt_str("dis-range");
+    if dis_range.is_none() && dis_func.is_none() && !dis_all_text { return; }
+    let dis = dis::create(dis_all::ALL_FAMILIES, arch_opts, &dis_opts).unwrap();
+    let syms = collect_dis_syms(ex, macho, if elf.is_some() { Some(&elf_specific as &Any) } else { None });
+    if let Some(dump_spec) = dis_range {
+        let (addr, size) = parse_dump_spec(&dump_spec).unwrap_or_else(usage_panic);
+        let dump_data = read_vm(eb, addr, size).unwrap();
+        print_dis(&*dis, &syms, &dump_data[..], addr, code_mode_for(&arch_opts, false));
+    }
+    if dis_func.is_none() && !dis_all_text { return; }
+    let funcs = find_func_ranges(eb, macho, &syms);
+    let mut dice = macho.map_or(Vec::new(), |mo| mo.parse_data_in_code());
+    dice.sort_by_key(|d| d.addr);
+    if let Some(name) = dis_func {
+        let sym = some_or!(syms.iter().find(|sym| sym.name == name), {
+            errln!("--dis-func: no symbol named {}", name);
+            util::exit()
         });
-        let mut last_end: VMA = base_pc;
-        for (dissed, pc, length) in results {
-            let diff = last_end - pc;
-            if diff != 0 {
-                println!("...skip {}", diff);
-            }
-            println!("-> {}: {}", pc,
-                if let Some(ref s) = dissed { &s[..] } else { "<?>" });
-            last_end = pc + length.ext();
-        }
-        let expected_end = base_pc + (dump_data.len() as u64);
-        if last_end < expected_end {
-            println!("...skip {}", expected_end - last_end);
-        } else if last_end > expected_end {
-            println!("...over-read by {}", last_end - expected_end);
+        let func = some_or!(funcs.iter().find(|func| func.start == sym.addr), {
+            errln!("--dis-func: {} ({}) is not in an executable section", name, sym.addr);
+            util::exit()
+        });
+        dis_func_range(&*dis, eb, &syms, &dice, &arch_opts, func);
+    }
+    if dis_all_text {
+        for func in &funcs {
+            println!("");
+            dis_func_range(&*dis, eb, &syms, &dice, &arch_opts, func);
         }
     }
 }
@@ -264,6 +835,21 @@ fn do_mut_stuff(ex: &mut exec::Exec, m: &getopts::Matches) {
         // TODO generic
         let macho = get_macho(ex);
         macho_dsc_extraction::extract_as_necessary(macho, None, None, /*minimal_processing*/ false).unwrap();
+        if m.opt_present("macho-adhoc-sign") {
+            let entitlements = m.opt_str("macho-entitlements").map(|path| {
+                let mut buf = Vec::new();
+                fs::File::open(&Path::new(&path)).and_then(|mut fp| fp.read_to_end(&mut buf)).unwrap_or_else(|e| {
+                    errln!("read {} failed: {}", path, e);
+                    util::exit();
+                });
+                buf
+            });
+            let fallback_ident = Path::new(&out_file).file_name().unwrap().to_string_lossy().into_owned();
+            macho.adhoc_sign(util::ByteStr::from_str(&fallback_ident), entitlements.as_ref().map(|e| &e[..])).unwrap_or_else(|e| {
+                errln!("adhoc sign failed: {}", e.message);
+                util::exit();
+            });
+        }
         let mut fp = fs::File::create(&Path::new(&out_file)).unwrap();
         let buf = unsafe { macho.eb.whole_buf.as_ref().unwrap().get_plain_slice() };
         fp.write_all(buf).unwrap();
@@ -293,19 +879,29 @@ fn main() {
         getopts::optflag("",  "syms",  "List symbols"),
         getopts::optflag("",  "exports","List exported symbols"),
         getopts::optflag("",  "imports","List imported symbols"),
+        getopts::optflag("",  "demangle", "When listing symbols, demangle Swift names"),
         getopts::optflag("",  "dep-libs", "List library dependencies"),
         getopts::optflag("",  "relocs", "List relocs"),
         getopts::optopt( "",  "o2a",   "Offset to address", "off"),
         getopts::optopt( "",  "a2o",   "Address to offset", "addr"),
         getopts::optopt( "",  "dump",  "Dump address range", "addr+len"),
         getopts::optopt( "",  "dis-range",   "Disassemble address range", "addr+len"),
+        getopts::optopt( "",  "dis-func",    "Disassemble the function with the given symbol name", "name"),
+        getopts::optflag("",  "dis-all-text", "Disassemble all executable sections, split by function"),
         getopts::optopt( "",  "dis",   "Disassembler name and options", "llvm/..."),
         getopts::optopt( "",  "extract", "Rewrite whole file", "outfile"),
         // todo: option groups
         getopts::optflag("",  "macho-filedata-info", "List data areas within the file"),
+        getopts::optflag("",  "macho-lcs", "Dump load commands (like otool -l)"),
+        getopts::optflag("",  "macho-codesign", "Dump the code signature and check page hashes"),
+        getopts::optflag("",  "objc", "Dump ObjC classes, categories and protocols"),
+        getopts::optflag("",  "swift", "Dump Swift types, fields and protocol conformances"),
+        getopts::optflag("",  "macho-adhoc-sign", "With --extract, replace the code signature with an ad-hoc one"),
+        getopts::optopt( "",  "macho-entitlements", "Entitlements plist for --macho-adhoc-sign", "file"),
         getopts::optflag("",  "elf-dynamic", "List ELF .dynamic contents"),
         getopts::optflag("",  "elf-dynamic-raw", "List ELF .dynamic contents (raw)"),
         getopts::optflag("",  "elf-append-version", "When listing symbols, include @VERSION"),
+        getopts::optopt( "",  "elf-symtab", "Which symbol tables to list (default both)", "dynamic|static|both"),
     );
     let mut args: Vec<String> = std::env::args().collect();
     if args.len() < 2 || args[1].starts_with("-") {
@@ -337,6 +933,14 @@ fn main() {
             }
         });
         let m = util::do_getopts_or_usage(&*real_args, top, 0, 0, &mut optgrps).unwrap_or_else(usage_panic);
+        if !m.opt_present("extract") && (m.opt_present("macho-adhoc-sign") || m.opt_present("macho-entitlements")) {
+            errln!("--macho-adhoc-sign and --macho-entitlements only make sense with --extract");
+            usage_panic::<()>(util::usage(top, &mut optgrps));
+        }
+        if m.opt_present("macho-entitlements") && !m.opt_present("macho-adhoc-sign") {
+            errln!("--macho-entitlements only makes sense with --macho-adhoc-sign");
+            usage_panic::<()>(util::usage(top, &mut optgrps));
+        }
         do_stuff(&ex, &m);
         do_mut_stuff(&mut *ex, &m);
     } else {
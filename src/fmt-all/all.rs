@@ -1,5 +1,7 @@
 extern crate fmt_macho as macho;
 extern crate fmt_elf as elf;
+extern crate fmt_pe as pe;
+extern crate fmt_ar as ar;
 extern crate fmt_raw_binary as raw_binary;
 extern crate exec;
 use self::macho::dyldcache;
@@ -15,6 +17,8 @@ pub fn all_probers() -> Vec<exec::ExecProberRef> {
             mem::transmute(&dyldcache::DyldSingleProber as &ExecProber),
             mem::transmute(&self::macho::FatMachOProber as &ExecProber),
             mem::transmute(&self::elf::ElfProber        as &ExecProber),
+            mem::transmute(&self::pe::PeProber          as &ExecProber),
+            mem::transmute(&self::ar::ArProber          as &ExecProber),
             mem::transmute(&self::raw_binary::RawProber as &ExecProber),
         );
     }
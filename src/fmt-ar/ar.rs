@@ -0,0 +1,207 @@
+#[macro_use]
+extern crate macros;
+extern crate util;
+extern crate exec;
+extern crate bsdlike_getopts as getopts;
+
+use std::mem::replace;
+use std::str::FromStr;
+use std::collections::HashSet;
+use util::{Mem, ByteString, ByteStr, VecStrExt, SliceExt, ReadCell, CheckAdd, fast_slice_to_owned};
+use exec::{ErrorKind, err};
+
+const AR_MAGIC: &'static [u8] = b"!<arch>\n";
+const AR_THIN_MAGIC: &'static [u8] = b"!<thin>\n";
+const AR_HDR_SIZE: usize = 60;
+
+pub struct ArMember {
+    pub name: ByteString,
+    pub off: usize, // of the data, not the header
+    pub size: usize,
+}
+
+fn is_symtab_name(name: &[u8]) -> bool {
+    let names: [&'static [u8]; 6] = [b"/", b"/SYM64/", b"__.SYMDEF", b"__.SYMDEF SORTED", b"__.SYMDEF_64", b"__.SYMDEF_64 SORTED"];
+    names.iter().any(|&n| n == name)
+}
+
+fn trim_spaces(field: &[u8]) -> &[u8] {
+    let len = field.iter().rposition(|&c| c != b' ').map_or(0, |i| i + 1);
+    &field[..len]
+}
+
+fn parse_dec(field: &[u8]) -> Option<usize> {
+    let s = some_or!(std::str::from_utf8(field).ok(), { return None; });
+    FromStr::from_str(s.trim_right_matches(' ')).ok()
+}
+
+fn is_thin(buf: &[ReadCell<u8>]) -> bool {
+    buf.slice_opt(0, AR_THIN_MAGIC.len()).map_or(false, |magic| &fast_slice_to_owned(magic)[..] == AR_THIN_MAGIC)
+}
+
+// Handles both the BSD (#1/len) and GNU/SysV (/offset into '//') long name
+// schemes.  Symbol tables and the long name table itself are not returned.
+pub fn parse_members(buf: &[ReadCell<u8>]) -> Result<Vec<ArMember>, &'static str> {
+    let magic = some_or!(buf.slice_opt(0, AR_MAGIC.len()), { return Err("too short"); });
+    let magic = fast_slice_to_owned(magic);
+    if &magic[..] == AR_THIN_MAGIC {
+        return Err("thin archives are not supported");
+    }
+    if &magic[..] != AR_MAGIC {
+        return Err("bad magic");
+    }
+    let mut result = Vec::new();
+    let mut long_names: Option<Vec<u8>> = None;
+    let mut off = AR_MAGIC.len();
+    while off < buf.len() {
+        let hdr = some_or!(buf.slice_opt(off, off + AR_HDR_SIZE), {
+            errln!("ar: truncated member header at offset {}", off);
+            break;
+        });
+        let hdr = fast_slice_to_owned(hdr);
+        if &hdr[58..60] != b"`\n" {
+            errln!("ar: bad member header magic at offset {}", off);
+            break;
+        }
+        let size = some_or!(parse_dec(&hdr[48..58]), {
+            errln!("ar: bad member size at offset {}", off);
+            break;
+        });
+        let mut data_off = off + AR_HDR_SIZE;
+        let end = some_or!(data_off.check_add(size), { break; });
+        if end > buf.len() {
+            errln!("ar: member at offset {} runs past end of file (truncated?)", off);
+            break;
+        }
+        let raw_name = trim_spaces(&hdr[0..16]);
+        let name: Vec<u8> = if raw_name.starts_with(b"#1/") {
+            // BSD: the name immediately follows the header and counts toward the size
+            let name_len = some_or!(parse_dec(&raw_name[3..]), {
+                errln!("ar: bad BSD long name length at offset {}", off);
+                break;
+            });
+            if name_len > size {
+                errln!("ar: BSD long name longer than member at offset {}", off);
+                break;
+            }
+            let name = fast_slice_to_owned(&buf[data_off..data_off + name_len]);
+            data_off += name_len;
+            util::from_cstr(&name[..]).to_vec()
+        } else if raw_name == b"//" {
+            // GNU long name table
+            long_names = Some(fast_slice_to_owned(&buf[data_off..end]));
+            off = end + (end & 1);
+            continue;
+        } else if raw_name.len() > 1 && raw_name[0] == b'/' && raw_name[1] != b'/' && !is_symtab_name(raw_name) {
+            let name_off = some_or!(parse_dec(&raw_name[1..]), {
+                errln!("ar: bad long name reference '{}' at offset {}", ByteStr::from_bytes(raw_name), off);
+                break;
+            });
+            let table = some_or!(long_names.as_ref(), {
+                errln!("ar: long name reference without a long name table at offset {}", off);
+                break;
+            });
+            let rest = some_or!(table.slice_opt(name_off, table.len()), {
+                errln!("ar: long name reference out of range at offset {}", off);
+                break;
+            });
+            let name_end = rest.iter().position(|&c| c == b'\n').unwrap_or(rest.len());
+            let name = &rest[..name_end];
+            (if name.last() == Some(&b'/') { &name[..name.len() - 1] } else { name }).to_vec()
+        } else if raw_name.len() > 1 && raw_name.last() == Some(&b'/') && !is_symtab_name(raw_name) {
+            // GNU short name, terminated by '/'
+            raw_name[..raw_name.len() - 1].to_vec()
+        } else {
+            raw_name.to_vec()
+        };
+        if !is_symtab_name(&name[..]) {
+            result.push(ArMember {
+                name: ByteString::from_vec(name),
+                off: data_off,
+                size: end - data_off,
+            });
+        }
+        // members are 2-byte aligned
+        off = end + (end & 1);
+    }
+    Ok(result)
+}
+
+pub struct ArProber;
+
+impl exec::ExecProber for ArProber {
+    fn name(&self) -> &str {
+        "ar"
+    }
+    fn probe(&self, eps: &Vec<exec::ExecProberRef>, mc: Mem<u8>) -> Vec<exec::ProbeResult> {
+        if is_thin(mc.get()) {
+            // claim it anyway, so create can say why it won't work
+            return vec!(exec::ProbeResult {
+                desc: "thin ar archive (unsupported)".to_string(),
+                arch: exec::arch::UnknownArch,
+                likely: true,
+                cmd: vec!("ar".to_string()),
+            });
+        }
+        let members = some_or!(parse_members(mc.get()).ok(), { return vec!(); });
+        // duplicate names are perfectly legal, so those get selected by index
+        let mut seen = HashSet::new();
+        let mut dups = HashSet::new();
+        for member in &members {
+            if !seen.insert(&member.name) { dups.insert(&member.name); }
+        }
+        let mut result = Vec::new();
+        for (i, member) in members.iter().enumerate() {
+            let name = member.name.lossy();
+            let unique = !dups.contains(&member.name);
+            let sel: Vec<String> = if unique && std::str::from_utf8(&member.name).is_ok() {
+                vec!("ar", "--member", &*name).strings()
+            } else {
+                vec!("ar".to_string(), "--index".to_string(), i.to_string())
+            };
+            for pr in exec::probe_all(eps, mc.slice(member.off, member.off + member.size).unwrap()).into_iter() {
+                result.push(exec::ProbeResult {
+                    desc: format!("(member {}) {}", name, pr.desc),
+                    arch: pr.arch,
+                    likely: pr.likely,
+                    cmd: { let mut s = sel.clone(); s.extend_from_slice(&*pr.cmd); s },
+                });
+            }
+        }
+        result
+    }
+
+    fn create(&self, eps: &Vec<exec::ExecProberRef>, mc: Mem<u8>, args: Vec<String>) -> exec::ExecResult<(Box<exec::Exec>, Vec<String>)> {
+        let top = "ar (--member NAME | -i INDEX)";
+        let mut optgrps = vec!(
+            getopts::optopt("m", "member", "choose by member name", "name"),
+            getopts::optopt("i", "index", "choose by member index", "index"),
+        );
+        if is_thin(mc.get()) {
+            return err(ErrorKind::Other, "thin archives aren't supported: the members are separate files, so open those directly");
+        }
+        let mut m = try!(exec::usage_to_invalid_args(util::do_getopts_or_usage(&*args, top, 0, std::usize::MAX, &mut optgrps)));
+        let name = m.opt_str("member");
+        let index = m.opt_str("index");
+        if name.is_some() == index.is_some() {
+            return exec::usage_to_invalid_args(Err(util::usage(top, &mut optgrps)));
+        }
+        let members = try!(parse_members(mc.get()).map_err(|e| exec::err_only(ErrorKind::BadData, format!("invalid ar archive: {}", e))));
+        let member = if let Some(name) = name {
+            let mut matches = members.iter().filter(|mem| &**mem.name == ByteStr::from_str(&*name));
+            let member = some_or!(matches.next(), {
+                return err(ErrorKind::Other, format!("no member named '{}'", name));
+            });
+            if matches.next().is_some() {
+                errln!("warning: multiple members named '{}'; using the first", name);
+            }
+            member
+        } else {
+            let index: usize = try!(FromStr::from_str(&*index.unwrap()).map_err(|_| exec::err_only(ErrorKind::InvalidArgs, "bad --index")));
+            some_or!(members.get(index), {
+                return err(ErrorKind::Other, format!("member index {} out of range ({} members)", index, members.len()));
+            })
+        };
+        exec::create(eps, mc.slice(member.off, member.off + member.size).unwrap(), replace(&mut m.free, vec!()))
+    }
+}
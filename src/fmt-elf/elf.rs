@@ -18,7 +18,7 @@ use util::SmallVector;
 
 use exec::arch::Arch;
 use util::{Mem, SliceExt, ByteStr, ByteString, Swap, CheckMul, CheckAdd, CheckSub, Ext, Lazy, Narrow, ReadCell};
-use exec::{ExecResult, ErrorKind, Segment, VMA, Prot, Symbol, SymbolValue, SymbolSource, SourceLib, DepLib, read_cstr, ReadVMA};
+use exec::{ExecResult, ErrorKind, Segment, VMA, Prot, Symbol, SymbolValue, SymbolSource, SourceLib, DepLib, Reloc, RelocKind, RelocTarget, read_cstr, ReadVMA};
 use elf_bind::*;
 
 macro_rules! convert_each {
@@ -444,6 +444,7 @@ pub struct Elf {
     pub dynamic_info: DynamicInfo,
     pub dynstr: Mem<u8>,
     verneed_info_cache: Lazy<VerneedInfo>,
+    reloc_syms_cache: Lazy<RelocSyms>,
 }
 
 fn fix_ocs(cs: &mut OffCountSize, len: usize, what: &str) {
@@ -731,9 +732,193 @@ impl Swap for GNUHashHeader {
     }
 }
 
+fn gnu_hash(name: &[u8]) -> u32 {
+    let mut h: u32 = 5381;
+    for &c in name {
+        h = h.wrapping_mul(33).wrapping_add(c as u32);
+    }
+    h
+}
+
+fn sysv_hash(name: &[u8]) -> u32 {
+    let mut h: u32 = 0;
+    for &c in name {
+        h = (h << 4).wrapping_add(c as u32);
+        let g = h & 0xf0000000;
+        h ^= g >> 24;
+        h &= !g;
+    }
+    h
+}
+
+struct RelocTable {
+    data: Mem<u8>,
+    is_rela: bool,
+    entsize: usize,
+    symtab_sect: Option<usize>, // None = dynamic symbol table
+    target_sect: Option<usize>, // for ET_REL, r_offset is relative to this
+}
+
+// symbol lists indexed by symbol number, for Import targets
+struct RelocSyms {
+    dynamic: Vec<Symbol<'static>>,
+    by_sect: VecMap<Vec<Symbol<'static>>>,
+}
+
+#[derive(Copy, Clone)]
+enum RelocClass {
+    Ignore,
+    Relative(RelocKind),
+    Symbolic(RelocKind),
+}
+
+fn classify_reloc(arch: Arch, rtype: u32) -> Option<RelocClass> {
+    use RelocClass::*;
+    use exec::RelocKind::*;
+    Some(match arch {
+        Arch::X86 => match rtype {
+            R_386_NONE => Ignore,
+            R_386_32 => Symbolic(_32Bit),
+            R_386_PC32 | R_386_PLT32 => Symbolic(Rel32),
+            R_386_GLOB_DAT | R_386_JMP_SLOT => Symbolic(Pointer),
+            R_386_RELATIVE => Relative(Pointer),
+            R_386_COPY => Symbolic(SymCopy),
+            R_386_TLS_DTPMOD32 => Symbolic(TlsDtpMod),
+            R_386_TLS_DTPOFF32 => Symbolic(TlsDtpOff),
+            R_386_TLS_TPOFF => Symbolic(TlsTpOff),
+            R_386_IRELATIVE => Relative(IRelative),
+            _ => return None,
+        },
+        Arch::X86_64 => match rtype {
+            R_X86_64_NONE => Ignore,
+            R_X86_64_64 => Symbolic(_64Bit),
+            R_X86_64_32 => Symbolic(_32Bit),
+            R_X86_64_PC32 | R_X86_64_PLT32 => Symbolic(Rel32),
+            R_X86_64_GOTPCREL | R_X86_64_GOTPCRELX | R_X86_64_REX_GOTPCRELX => Symbolic(GotPcRel),
+            R_X86_64_GLOB_DAT | R_X86_64_JUMP_SLOT => Symbolic(Pointer),
+            R_X86_64_RELATIVE => Relative(Pointer),
+            R_X86_64_RELATIVE64 => Relative(_64Bit),
+            R_X86_64_COPY => Symbolic(SymCopy),
+            R_X86_64_DTPMOD64 => Symbolic(TlsDtpMod),
+            R_X86_64_DTPOFF64 => Symbolic(TlsDtpOff),
+            R_X86_64_TPOFF64 => Symbolic(TlsTpOff),
+            R_X86_64_IRELATIVE => Relative(IRelative),
+            _ => return None,
+        },
+        Arch::ARM => match rtype {
+            R_ARM_NONE => Ignore,
+            R_ARM_ABS32 => Symbolic(_32Bit),
+            R_ARM_PC24 | R_ARM_CALL | R_ARM_JUMP24 => Symbolic(ArmBr24),
+            R_ARM_THM_PC22 | R_ARM_THM_JUMP24 => Symbolic(ThumbBr22),
+            R_ARM_GLOB_DAT | R_ARM_JUMP_SLOT => Symbolic(Pointer),
+            R_ARM_RELATIVE => Relative(Pointer),
+            R_ARM_COPY => Symbolic(SymCopy),
+            R_ARM_TLS_DTPMOD32 => Symbolic(TlsDtpMod),
+            R_ARM_TLS_DTPOFF32 => Symbolic(TlsDtpOff),
+            R_ARM_TLS_TPOFF32 => Symbolic(TlsTpOff),
+            R_ARM_IRELATIVE => Relative(IRelative),
+            _ => return None,
+        },
+        Arch::AArch64 => match rtype {
+            R_AARCH64_NONE => Ignore,
+            R_AARCH64_ABS64 => Symbolic(_64Bit),
+            R_AARCH64_ABS32 => Symbolic(_32Bit),
+            R_AARCH64_JUMP26 | R_AARCH64_CALL26 => Symbolic(Arm64Br26),
+            R_AARCH64_GLOB_DAT | R_AARCH64_JUMP_SLOT => Symbolic(Pointer),
+            R_AARCH64_RELATIVE => Relative(Pointer),
+            R_AARCH64_COPY => Symbolic(SymCopy),
+            R_AARCH64_TLS_DTPMOD64 => Symbolic(TlsDtpMod),
+            R_AARCH64_TLS_DTPREL64 => Symbolic(TlsDtpOff),
+            R_AARCH64_TLS_TPREL64 => Symbolic(TlsTpOff),
+            R_AARCH64_IRELATIVE => Relative(IRelative),
+            _ => return None,
+        },
+        Arch::Mips => match rtype {
+            R_MIPS_NONE => Ignore,
+            R_MIPS_32 => Symbolic(_32Bit),
+            R_MIPS_64 => Symbolic(_64Bit),
+            // with a symbol, this is S + A
+            R_MIPS_REL32 => Relative(Pointer),
+            R_MIPS_GLOB_DAT | R_MIPS_JUMP_SLOT => Symbolic(Pointer),
+            R_MIPS_COPY => Symbolic(SymCopy),
+            R_MIPS_TLS_DTPMOD32 | R_MIPS_TLS_DTPMOD64 => Symbolic(TlsDtpMod),
+            R_MIPS_TLS_DTPREL32 | R_MIPS_TLS_DTPREL64 => Symbolic(TlsDtpOff),
+            R_MIPS_TLS_TPREL32 | R_MIPS_TLS_TPREL64 => Symbolic(TlsTpOff),
+            _ => return None,
+        },
+        // PPC64 uses the same numbers where they overlap
+        Arch::PowerPC => match rtype {
+            R_PPC_NONE => Ignore,
+            R_PPC_ADDR32 | R_PPC_UADDR32 => Symbolic(_32Bit),
+            R_PPC64_ADDR64 | R_PPC64_UADDR64 => Symbolic(_64Bit),
+            R_PPC_GLOB_DAT | R_PPC_JMP_SLOT => Symbolic(Pointer),
+            R_PPC_RELATIVE => Relative(Pointer),
+            R_PPC_COPY => Symbolic(SymCopy),
+            R_PPC_DTPMOD32 => Symbolic(TlsDtpMod),
+            R_PPC_DTPREL32 => Symbolic(TlsDtpOff),
+            R_PPC_TPREL32 => Symbolic(TlsTpOff),
+            R_PPC_IRELATIVE => Relative(IRelative),
+            _ => return None,
+        },
+        Arch::RiscV => match rtype {
+            R_RISCV_NONE => Ignore,
+            R_RISCV_32 => Symbolic(_32Bit),
+            R_RISCV_64 => Symbolic(_64Bit),
+            R_RISCV_BRANCH => Symbolic(RiscVBranch),
+            R_RISCV_JAL => Symbolic(RiscVJal),
+            R_RISCV_CALL | R_RISCV_CALL_PLT => Symbolic(RiscVCall),
+            R_RISCV_JUMP_SLOT => Symbolic(Pointer),
+            R_RISCV_RELATIVE => Relative(Pointer),
+            R_RISCV_COPY => Symbolic(SymCopy),
+            R_RISCV_TLS_DTPMOD32 | R_RISCV_TLS_DTPMOD64 => Symbolic(TlsDtpMod),
+            R_RISCV_TLS_DTPREL32 | R_RISCV_TLS_DTPREL64 => Symbolic(TlsDtpOff),
+            R_RISCV_TLS_TPREL32 | R_RISCV_TLS_TPREL64 => Symbolic(TlsTpOff),
+            R_RISCV_IRELATIVE => Relative(IRelative),
+            _ => return None,
+        },
+        Arch::S390x => match rtype {
+            R_390_NONE => Ignore,
+            R_390_32 => Symbolic(_32Bit),
+            R_390_64 => Symbolic(_64Bit),
+            R_390_GLOB_DAT | R_390_JMP_SLOT => Symbolic(Pointer),
+            R_390_RELATIVE => Relative(Pointer),
+            R_390_COPY => Symbolic(SymCopy),
+            R_390_TLS_DTPMOD => Symbolic(TlsDtpMod),
+            R_390_TLS_DTPOFF => Symbolic(TlsDtpOff),
+            R_390_TLS_TPOFF => Symbolic(TlsTpOff),
+            R_390_IRELATIVE => Relative(IRelative),
+            _ => return None,
+        },
+        Arch::LoongArch => match rtype {
+            R_LARCH_NONE => Ignore,
+            R_LARCH_32 => Symbolic(_32Bit),
+            R_LARCH_64 => Symbolic(_64Bit),
+            R_LARCH_JUMP_SLOT => Symbolic(Pointer),
+            R_LARCH_RELATIVE => Relative(Pointer),
+            R_LARCH_COPY => Symbolic(SymCopy),
+            R_LARCH_TLS_DTPMOD32 | R_LARCH_TLS_DTPMOD64 => Symbolic(TlsDtpMod),
+            R_LARCH_TLS_DTPREL32 | R_LARCH_TLS_DTPREL64 => Symbolic(TlsDtpOff),
+            R_LARCH_TLS_TPREL32 | R_LARCH_TLS_TPREL64 => Symbolic(TlsTpOff),
+            R_LARCH_IRELATIVE => Relative(IRelative),
+            _ => return None,
+        },
+        Arch::M68k => match rtype {
+            R_68K_NONE => Ignore,
+            R_68K_32 => Symbolic(_32Bit),
+            R_68K_GLOB_DAT | R_68K_JMP_SLOT => Symbolic(Pointer),
+            R_68K_RELATIVE => Relative(Pointer),
+            R_68K_COPY => Symbolic(SymCopy),
+            R_68K_TLS_DTPMOD32 => Symbolic(TlsDtpMod),
+            R_68K_TLS_DTPREL32 => Symbolic(TlsDtpOff),
+            R_68K_TLS_TPREL32 => Symbolic(TlsTpOff),
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
 struct GNUHash {
     header: GNUHashHeader,
-    #[allow(dead_code)]
     bitmask: Mem<u8>,
     buckets: Mem<u8>,
     chain_addr: VMA,
@@ -768,6 +953,7 @@ impl Elf {
                 dynamic_info: Default::default(),
                 dynstr: Default::default(),
                 verneed_info_cache: Lazy::new(),
+                reloc_syms_cache: Lazy::new(),
             }
         };
         res.eb.whole_buf = Some(buf);
@@ -841,7 +1027,6 @@ impl Elf {
         let symcount: u64 = match mode {
             SymtabTraverseMode::Hash => {
                 let dt_hash = some_or!(self.dynamic_info.hash, { return None });
-                some_or!(None, return None);
                 let nchain_buf = self.eb.read(dt_hash + 4, 4);
                 if nchain_buf.len() != 4 {
                     errln!("warning: invalid DT_HASH address (get_full_symtab)");
@@ -916,50 +1101,60 @@ impl Elf {
 
         Some((buf.slice(0, read_symcount * syment).unwrap(), versym_buf, syment))
     }
-    fn get_verneed_info(&self) -> &VerneedInfo {
-        self.verneed_info_cache.get(|| self.dynamic_info.fetch_verneed_info(self))
-    }
-    fn dump_verneed(&self) {
-        let vns = self.get_verneed_info();
-        for vn in &vns.verneed {
-            print!(" -> dep '{}' versions:", vn.filename);
-            for vna in &vn.aux {
-                print!(" '{}'(id={}, flags={})", vna.name, vna.other, vna.flags);
-            }
-            print!("\n");
-        }
-
-    }
-
-}
-
-#[derive(Default, Clone)]
-pub struct ElfGetSymbolListSpecific {
-    pub append_version: bool,
-}
-
-impl exec::Exec for Elf {
-    fn get_exec_base<'a>(&'a self) -> &'a exec::ExecBase {
-        &self.eb
-    }
-    fn as_any(&self) -> &Any { self as &Any }
-
-    fn get_symbol_list(&self, source: SymbolSource, specific: Option<&Any>) -> Vec<Symbol> {
-        let esp = if let Some(sp) = specific {
-            sp.downcast_ref::<ElfGetSymbolListSpecific>().unwrap()
-        } else {
-            static DEFAULT: ElfGetSymbolListSpecific = ElfGetSymbolListSpecific { append_version: false };
-            &DEFAULT
-        };
-
+    fn get_dynsym_list(&self, source: SymbolSource, esp: &ElfGetSymbolListSpecific) -> Vec<Symbol> {
         // try to get the count somehow
         let (symtab, versym, syment) = some_or!(
             self.get_full_symtab(SymtabTraverseMode::BruteForce).or_else(||
             self.get_full_symtab(SymtabTraverseMode::GNUHash)).or_else(||
             self.get_full_symtab(SymtabTraverseMode::Hash)),
             { return Vec::new(); });
+        self.decode_symtab(symtab.get(), versym.get(), &[], syment, None, source, esp)
+    }
+    fn get_sect_sym_list(&self, sect_idx: usize, source: SymbolSource, esp: &ElfGetSymbolListSpecific) -> Vec<Symbol> {
+        let (sect, shdr) = some_or!(self.eb.sections.get(sect_idx).and_then(|sect| self.shdrs.get(sect_idx).map(|shdr| (sect, shdr))), {
+            errln!("warning: symbol table section index {} out of range", sect_idx);
+            return Vec::new();
+        });
+        let symtab = some_or!(sect.data.as_ref(), {
+            errln!("warning: symbol table section {} out of file range", sect.pretty_name());
+            return Vec::new();
+        });
+        let strtab = some_or!(self.eb.sections.get(shdr.sh_link as usize).and_then(|s| s.data.as_ref()), {
+            errln!("warning: symbol table section {} has bad sh_link {}", sect.pretty_name(), shdr.sh_link);
+            return Vec::new();
+        });
+        let min_syment = if self.basics.is64 { size_of::<Elf64_Sym>() } else { size_of::<Elf32_Sym>() };
+        let syment = if shdr.sh_entsize == 0 { min_syment } else { shdr.sh_entsize as usize };
+        if syment < min_syment {
+            errln!("warning: symbol table section {} has entsize {}, too small", sect.pretty_name(), syment);
+            return Vec::new();
+        }
         let symtab = symtab.get();
-        let versym = versym.get();
+        let symtab = &symtab[..symtab.len() - symtab.len() % syment];
+        let mut shndx_tab: &[ReadCell<u8>] = &[];
+        for (xsect, xshdr) in self.eb.sections.iter().zip(&self.shdrs) {
+            if xshdr.sh_type == SHT_SYMTAB_SHNDX && xshdr.sh_link as usize == sect_idx {
+                if let Some(ref data) = xsect.data {
+                    shndx_tab = data.get();
+                } else {
+                    errln!("warning: SHT_SYMTAB_SHNDX section {} out of file range", xsect.pretty_name());
+                }
+            }
+        }
+        self.decode_symtab(symtab, &[], shndx_tab, syment, Some(strtab.get()), source, esp)
+    }
+    fn read_symstr<'a>(&'a self, strtab: Option<&'a [ReadCell<u8>]>, off: u64) -> Option<Cow<'a, ByteStr>> {
+        if let Some(st) = strtab {
+            if off >= st.len() as u64 { return None; }
+            util::from_cstr_strict(&st[off as usize..]).map(Cow::Borrowed)
+        } else {
+            self.read_dynstr(off)
+        }
+    }
+    // strtab = None means dynstr
+    fn decode_symtab<'a>(&'a self, symtab: &[ReadCell<u8>], versym: &[ReadCell<u8>], shndx_tab: &[ReadCell<u8>],
+                         syment: usize, strtab: Option<&'a [ReadCell<u8>]>, source: SymbolSource,
+                         esp: &ElfGetSymbolListSpecific) -> Vec<Symbol<'a>> {
         let verneed_info = self.get_verneed_info();
         let end = self.eb.endian;
         branch!(if (self.basics.is64) {
@@ -972,7 +1167,7 @@ impl exec::Exec for Elf {
                 let vs: u16 = if versym.len() >= (2 * i + 2) {
                     util::copy_from_slice(&versym[2 * i..2 * i + 2], end)
                 } else { 0 };
-                let mut name = self.read_dynstr(sym.st_name as u64)
+                let mut name = self.read_symstr(strtab, sym.st_name as u64)
                                .unwrap_or_else(|| {
                                 errln!("warning: symbol has invalid st_name {}", sym.st_name);
                                 ByteString::from_string(format!("<<{}>>", sym.st_name)).into()
@@ -1007,35 +1202,32 @@ impl exec::Exec for Elf {
                 let stval = sym.st_value as u64;
                 if source == SymbolSource::Exported &&
                    (sym.st_shndx as u32 == SHN_UNDEF ||
+                    st_bind as u32 == STB_LOCAL ||
                     vs != 0) {
                     return None;
                 }
                 if source == SymbolSource::Imported && sym.st_shndx as u32 != SHN_UNDEF {
                     return None;
                 }
-                // TODO SHN_XINDEX
-                let val = match sym.st_shndx as u32 {
-                    SHN_ABS => SymbolValue::Abs(VMA(stval)),
-                    SHN_COMMON | SHN_UNDEF => SymbolValue::Undefined(source_lib),
-                    SHN_LORESERVE ... SHN_HIRESERVE => {
+                let shndx: Option<u32> = if sym.st_shndx as u32 == SHN_XINDEX {
+                    if shndx_tab.len() >= (4 * i + 4) {
+                        Some(util::copy_from_slice(&shndx_tab[4 * i..4 * i + 4], end))
+                    } else {
+                        errln!("warning: SHN_XINDEX for symbol {} but no SHT_SYMTAB_SHNDX entry", i);
+                        None
+                    }
+                } else { Some(sym.st_shndx as u32) };
+                let val = match shndx {
+                    None => SymbolValue::Undefined(source_lib),
+                    // extended indices are always real sections
+                    Some(idx) if sym.st_shndx as u32 == SHN_XINDEX => self.sect_sym_value(idx, stval, sym.st_size as u64, i),
+                    Some(SHN_ABS) => SymbolValue::Abs(VMA(stval)),
+                    Some(SHN_COMMON) | Some(SHN_UNDEF) => SymbolValue::Undefined(source_lib),
+                    Some(SHN_LORESERVE ... SHN_HIRESERVE) => {
                         errln!("warning: unknown special st_shndx 0x{:x} for symbol {}", sym.st_shndx, i);
                         SymbolValue::Undefined(source_lib)
                     },
-                    // ugh ugh ugh
-                    _ => if self.ehdr.type_ as u32 != ET_REL {
-                        SymbolValue::Addr(VMA(stval))
-                    } else {
-                        if let Some(sect) = self.eb.sections.get(sym.st_shndx as usize) {
-                            if stval > sect.vmsize || sym.st_size as u64 > sect.vmsize - stval {
-                                errln!("warning: section offset out of range for symbol {}", i);
-                            }
-                            SymbolValue::Addr(sect.vmaddr + stval)
-                        } else {
-                            errln!("warning: invalid non-special st_shndx {} for symbol {}", sym.st_shndx, i);
-                            SymbolValue::Undefined(source_lib)
-                        }
-
-                    }
+                    Some(idx) => self.sect_sym_value(idx, stval, sym.st_size as u64, i),
                 };
                 Some(Symbol {
                     name: name,
@@ -1048,6 +1240,408 @@ impl exec::Exec for Elf {
             }).filter_map(|x| x).collect::<Vec<Symbol>>()
         })
     }
+    fn sect_sym_value(&self, shndx: u32, stval: u64, size: u64, i: usize) -> SymbolValue<'static> {
+        // ugh ugh ugh
+        if self.ehdr.type_ as u32 != ET_REL {
+            return SymbolValue::Addr(VMA(stval));
+        }
+        if let Some(sect) = self.eb.sections.get(shndx as usize) {
+            if stval > sect.vmsize || size > sect.vmsize - stval {
+                errln!("warning: section offset out of range for symbol {}", i);
+            }
+            SymbolValue::Addr(sect.vmaddr + stval)
+        } else {
+            errln!("warning: invalid non-special st_shndx {} for symbol {}", shndx, i);
+            SymbolValue::Undefined(SourceLib::None)
+        }
+    }
+    fn split_r_info(&self, info: u64) -> (usize, u32) {
+        if !self.basics.is64 {
+            ((info >> 8) as usize, (info & 0xff) as u32)
+        } else if self.basics.arch == Arch::Mips {
+            // MIPS64 has its own layout with up to three types; just take the first
+            match self.eb.endian {
+                util::LittleEndian => ((info & 0xffffffff) as usize, (info >> 56) as u32),
+                util::BigEndian => ((info >> 32) as usize, (info & 0xff) as u32),
+            }
+        } else {
+            ((info >> 32) as usize, (info & 0xffffffff) as u32)
+        }
+    }
+    fn get_reloc_tables(&self) -> Vec<RelocTable> {
+        let mut tables = Vec::new();
+        let (rel_size, rela_size) = if self.basics.is64 {
+            (size_of::<Elf64_Rel>(), size_of::<Elf64_Rela>())
+        } else {
+            (size_of::<Elf32_Rel>(), size_of::<Elf32_Rela>())
+        };
+        {
+            let di = &self.dynamic_info;
+            let mut add_dynamic = |addr: VMA, size: u64, entsize: u64, is_rela: bool, what: &str| {
+                if entsize < (if is_rela { rela_size } else { rel_size }) as u64 {
+                    errln!("warning: {} entry size {} too small", what, entsize);
+                    return;
+                }
+                let data = self.eb.read(addr, size);
+                if (data.len() as u64) < size {
+                    errln!("warning: couldn't read {} (got {}/{} bytes)", what, data.len(), size);
+                }
+                tables.push(RelocTable {
+                    data: data,
+                    is_rela: is_rela,
+                    entsize: entsize as usize,
+                    symtab_sect: None,
+                    target_sect: None,
+                });
+            };
+            if let Some(ocs) = di.rela {
+                add_dynamic(VMA(ocs.off), ocs.count.saturating_mul(ocs.size), ocs.size, true, "DT_RELA");
+            }
+            if let Some(ocs) = di.rel {
+                add_dynamic(VMA(ocs.off), ocs.count.saturating_mul(ocs.size), ocs.size, false, "DT_REL");
+            }
+            if let Some(jmprel) = di.jmprel {
+                let size = di.pltrelsz.unwrap_or_else(|| {
+                    errln!("warning: DT_JMPREL but no DT_PLTRELSZ");
+                    0
+                });
+                let is_rela = match di.pltrel {
+                    Some(DT_RELA) => true,
+                    Some(DT_REL) => false,
+                    Some(x) => {
+                        errln!("warning: invalid DT_PLTREL {}", x);
+                        di.rela.is_some()
+                    },
+                    None => {
+                        errln!("warning: DT_JMPREL but no DT_PLTREL");
+                        di.rela.is_some()
+                    },
+                };
+                add_dynamic(jmprel, size, (if is_rela { rela_size } else { rel_size }) as u64, is_rela, "DT_JMPREL");
+            }
+        }
+        for (sect, shdr) in self.eb.sections.iter().zip(&self.shdrs) {
+            let is_rela = match shdr.sh_type {
+                SHT_RELA => true,
+                SHT_REL => false,
+                _ => continue,
+            };
+            // loaded reloc sections should already be covered by .dynamic
+            if !self.dyns.is_empty() && shdr.sh_flags & (SHF_ALLOC as u64) != 0 { continue; }
+            let data = some_or!(sect.data.clone(), {
+                errln!("warning: reloc section {} out of file range", sect.pretty_name());
+                continue;
+            });
+            let min_size = if is_rela { rela_size } else { rel_size } as u64;
+            let entsize = if shdr.sh_entsize == 0 { min_size } else { shdr.sh_entsize };
+            if entsize < min_size {
+                errln!("warning: reloc section {} entry size {} too small", sect.pretty_name(), entsize);
+                continue;
+            }
+            tables.push(RelocTable {
+                data: data,
+                is_rela: is_rela,
+                entsize: entsize as usize,
+                symtab_sect: if shdr.sh_link == SHN_UNDEF { None } else { Some(shdr.sh_link as usize) },
+                target_sect: if self.ehdr.type_ as u32 == ET_REL { Some(shdr.sh_info as usize) } else { None },
+            });
+        }
+        tables
+    }
+    fn get_reloc_syms(&self, tables: &[RelocTable]) -> &RelocSyms {
+        self.reloc_syms_cache.get(|| {
+            let esp = ElfGetSymbolListSpecific::default();
+            let mut by_sect = VecMap::new();
+            for table in tables {
+                if let Some(idx) = table.symtab_sect {
+                    if !by_sect.contains_key(idx) {
+                        let syms = self.get_sect_sym_list(idx, SymbolSource::All, &esp);
+                        by_sect.insert(idx, syms.into_iter().map(|s| s.into_owned()).collect());
+                    }
+                }
+            }
+            RelocSyms {
+                dynamic: self.get_dynsym_list(SymbolSource::All, &esp).into_iter().map(|s| s.into_owned()).collect(),
+                by_sect: by_sect,
+            }
+        })
+    }
+    fn read_u32(&self, addr: VMA) -> Option<u32> {
+        let buf = self.eb.read(addr, 4);
+        if buf.len() != 4 { return None; }
+        Some(util::copy_from_slice(buf.get(), self.eb.endian))
+    }
+    // returns the symbol if it's a defined export named 'name'
+    fn check_dynsym<'a>(&'a self, idx: u64, name: &ByteStr, esp: &ElfGetSymbolListSpecific) -> Option<Symbol<'a>> {
+        let symtab = some_or!(self.dynamic_info.symtab, { return None });
+        let syment = self.dynamic_info.syment.unwrap_or(
+            (if self.basics.is64 { size_of::<Elf64_Sym>() } else { size_of::<Elf32_Sym>() }) as u32) as u64;
+        let symdat = self.eb.read(symtab + idx.saturating_mul(syment), syment);
+        if (symdat.len() as u64) < syment {
+            errln!("warning: lookup_export: couldn't read symbol {}", idx);
+            return None;
+        }
+        let versym = if let Some(versym) = self.dynamic_info.versym {
+            self.eb.read(versym + idx.saturating_mul(2), 2)
+        } else { Mem::<u8>::default() };
+        let st_name: u32 = branch!(if (self.basics.is64) {
+            type ElfX_Sym = Elf64_Sym;
+        } else {
+            type ElfX_Sym = Elf32_Sym;
+        } then {
+            let sym: ElfX_Sym = util::copy_from_slice(symdat.get(), self.eb.endian);
+            sym.st_name
+        });
+        if &*some_or!(self.read_dynstr(st_name as u64), { return None }) != name { return None; }
+        if versym.len() == 2 {
+            let vs: u16 = util::copy_from_slice(versym.get(), self.eb.endian);
+            // hidden versions can't be linked against by name alone
+            if vs & 0x8000 != 0 { return None; }
+        }
+        let mut syms = self.decode_symtab(symdat.get(), versym.get(), &[], syment as usize, None, SymbolSource::All, esp);
+        let mut sym = some_or!(syms.pop(), { return None });
+        if !sym.is_public { return None; }
+        if let SymbolValue::Undefined(_) = sym.val { return None; }
+        sym.private = idx as usize;
+        Some(sym)
+    }
+    fn lookup_export_gnu_hash<'a>(&'a self, name: &ByteStr, esp: &ElfGetSymbolListSpecific, out: &mut Vec<Symbol<'a>>) -> Option<()> {
+        let gh = some_or!(self.get_gnu_hash(), { return None });
+        let end = self.eb.endian;
+        let h = gnu_hash(name);
+        // bloom filter first
+        let nwords = gh.header.bitmask_nwords;
+        if nwords == 0 || gh.header.nbuckets == 0 { return Some(()); }
+        let word_bits = (self.eb.pointer_size * 8) as u32;
+        let word_idx = ((h / word_bits) % nwords) as usize;
+        let bitmask = gh.bitmask.get();
+        let word: u64 = if self.basics.is64 {
+            util::copy_from_slice(&bitmask[word_idx * 8..word_idx * 8 + 8], end)
+        } else {
+            let w: u32 = util::copy_from_slice(&bitmask[word_idx * 4..word_idx * 4 + 4], end);
+            w as u64
+        };
+        let mask = (1u64 << (h % word_bits)) | (1u64 << (h.checked_shr(gh.header.shift).unwrap_or(0) % word_bits));
+        if word & mask != mask { return Some(()); }
+
+        let bucket = (h % gh.header.nbuckets) as usize;
+        let mut idx: u32 = util::copy_from_slice(&gh.buckets.get()[bucket * 4..bucket * 4 + 4], end);
+        if idx == 0 { return Some(()); }
+        if idx < gh.header.symbias {
+            errln!("warning: DT_GNU_HASH bucket entry {} < symbias {}", idx, gh.header.symbias);
+            return Some(());
+        }
+        loop {
+            let chain_addr = gh.chain_addr + ((idx - gh.header.symbias) as u64) * 4;
+            let h2 = some_or!(self.read_u32(chain_addr), {
+                errln!("warning: DT_GNU_HASH chain out of range");
+                break;
+            });
+            if h | 1 == h2 | 1 {
+                if let Some(sym) = self.check_dynsym(idx as u64, name, esp) {
+                    out.push(sym);
+                }
+            }
+            if h2 & 1 != 0 { break; }
+            idx = some_or!(idx.checked_add(1), { break });
+        }
+        Some(())
+    }
+    fn lookup_export_sysv_hash<'a>(&'a self, name: &ByteStr, esp: &ElfGetSymbolListSpecific, out: &mut Vec<Symbol<'a>>) -> Option<()> {
+        let dt_hash = some_or!(self.dynamic_info.hash, { return None });
+        let (nbucket, nchain) = match (self.read_u32(dt_hash), self.read_u32(dt_hash + 4)) {
+            (Some(nb), Some(nc)) => (nb, nc),
+            _ => {
+                errln!("warning: invalid DT_HASH address");
+                return None;
+            },
+        };
+        if nbucket == 0 { return Some(()); }
+        let buckets = dt_hash + 8;
+        let chains = buckets + (nbucket as u64) * 4;
+        let h = sysv_hash(name);
+        let mut idx = some_or!(self.read_u32(buckets + ((h % nbucket) as u64) * 4), {
+            errln!("warning: DT_HASH bucket out of range");
+            return None;
+        });
+        // don't loop forever on bad data
+        let mut steps = 0;
+        while idx != 0 && steps <= nchain {
+            if idx >= nchain {
+                errln!("warning: DT_HASH chain index {} >= nchain {}", idx, nchain);
+                break;
+            }
+            if let Some(sym) = self.check_dynsym(idx as u64, name, esp) {
+                out.push(sym);
+            }
+            idx = some_or!(self.read_u32(chains + (idx as u64) * 4), {
+                errln!("warning: DT_HASH chain out of range");
+                break;
+            });
+            steps += 1;
+        }
+        Some(())
+    }
+    fn get_verneed_info(&self) -> &VerneedInfo {
+        self.verneed_info_cache.get(|| self.dynamic_info.fetch_verneed_info(self))
+    }
+    fn dump_verneed(&self) {
+        let vns = self.get_verneed_info();
+        for vn in &vns.verneed {
+            print!(" -> dep '{}' versions:", vn.filename);
+            for vna in &vn.aux {
+                print!(" '{}'(id={}, flags={})", vna.name, vna.other, vna.flags);
+            }
+            print!("\n");
+        }
+
+    }
+
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ElfWhichSymtab {
+    Dynamic,
+    Static,
+    Both,
+}
+impl Default for ElfWhichSymtab { fn default() -> Self { ElfWhichSymtab::Both } }
+
+#[derive(Default, Clone)]
+pub struct ElfGetSymbolListSpecific {
+    pub append_version: bool,
+    pub which: ElfWhichSymtab,
+}
+
+impl exec::Exec for Elf {
+    fn get_exec_base<'a>(&'a self) -> &'a exec::ExecBase {
+        &self.eb
+    }
+    fn as_any(&self) -> &Any { self as &Any }
+
+    fn get_symbol_list(&self, source: SymbolSource, specific: Option<&Any>) -> Vec<Symbol> {
+        let esp = if let Some(sp) = specific {
+            sp.downcast_ref::<ElfGetSymbolListSpecific>().unwrap()
+        } else {
+            static DEFAULT: ElfGetSymbolListSpecific = ElfGetSymbolListSpecific { append_version: false, which: ElfWhichSymtab::Both };
+            &DEFAULT
+        };
+        let mut out = Vec::new();
+        if esp.which != ElfWhichSymtab::Dynamic {
+            for (i, shdr) in self.shdrs.iter().enumerate() {
+                if shdr.sh_type == SHT_SYMTAB {
+                    out.extend(self.get_sect_sym_list(i, source, esp));
+                }
+            }
+        }
+        if esp.which != ElfWhichSymtab::Static {
+            let dynsyms = self.get_dynsym_list(source, esp);
+            if out.is_empty() {
+                out = dynsyms;
+            } else {
+                // .symtab usually duplicates everything in .dynsym, so skip those; only one side
+                // may have a version suffix, so compare without it
+                fn unversioned(name: &ByteStr) -> &ByteStr {
+                    ByteStr::from_bytes(&name[..name.find(b'@').unwrap_or(name.len())])
+                }
+                let dynsyms: Vec<Symbol> = {
+                    let mut seen = util::new_fnv_hashset();
+                    for sym in &out {
+                        seen.insert((unversioned(&sym.name), sym.val.some_vma()));
+                    }
+                    dynsyms.into_iter().filter(|sym| {
+                        !seen.contains(&(unversioned(&sym.name), sym.val.some_vma()))
+                    }).collect()
+                };
+                out.extend(dynsyms);
+            }
+        }
+        out
+    }
+
+    fn lookup_export(&self, name: &ByteStr, specific: Option<&Any>) -> Vec<Symbol> {
+        let esp = if let Some(sp) = specific {
+            sp.downcast_ref::<ElfGetSymbolListSpecific>().unwrap().clone()
+        } else { ElfGetSymbolListSpecific::default() };
+        let mut out = Vec::new();
+        if self.lookup_export_gnu_hash(name, &esp, &mut out).is_some() ||
+           self.lookup_export_sysv_hash(name, &esp, &mut out).is_some() {
+            return out;
+        }
+        // no hash table at all
+        self.get_dynsym_list(SymbolSource::Exported, &esp).into_iter().filter(|sym| &*sym.name == name).collect()
+    }
+
+    fn get_reloc_list<'a>(&'a self, specific: Option<&'a Any>) -> Vec<Reloc<'a>> {
+        assert!(specific.is_none());
+        let tables = self.get_reloc_tables();
+        let syms = self.get_reloc_syms(&tables);
+        let end = self.eb.endian;
+        let mut out = Vec::new();
+        let mut unknown = 0;
+        for table in &tables {
+            let symlist: &'a [Symbol<'static>] = match table.symtab_sect {
+                None => &syms.dynamic,
+                Some(idx) => &syms.by_sect[idx],
+            };
+            let base_addr = if let Some(idx) = table.target_sect {
+                some_or!(self.eb.sections.get(idx), {
+                    errln!("warning: reloc section has invalid sh_info {}", idx);
+                    continue;
+                }).vmaddr
+            } else { VMA(0) };
+            for ent in table.data.get().chunks(table.entsize) {
+                if ent.len() < table.entsize { break; }
+                let (offset, info, addend): (u64, u64, Option<i64>) = branch!(if (self.basics.is64) {
+                    type ElfX_Rel = Elf64_Rel;
+                    type ElfX_Rela = Elf64_Rela;
+                } else {
+                    type ElfX_Rel = Elf32_Rel;
+                    type ElfX_Rela = Elf32_Rela;
+                } then {
+                    if table.is_rela {
+                        let r: ElfX_Rela = util::copy_from_slice(&ent[..size_of::<ElfX_Rela>()], end);
+                        (r.r_offset as u64, r.r_info as u64, Some(r.r_addend as i64))
+                    } else {
+                        let r: ElfX_Rel = util::copy_from_slice(&ent[..size_of::<ElfX_Rel>()], end);
+                        (r.r_offset as u64, r.r_info as u64, None)
+                    }
+                });
+                let (sym_idx, rtype) = self.split_r_info(info);
+                let address = base_addr + offset;
+                let (kind, target) = match classify_reloc(self.eb.arch, rtype) {
+                    None => { unknown += 1; continue; },
+                    Some(RelocClass::Ignore) => continue,
+                    Some(RelocClass::Relative(kind)) if sym_idx == 0 => (kind, RelocTarget::ThisImageSlide),
+                    Some(RelocClass::Relative(kind)) | Some(RelocClass::Symbolic(kind)) => {
+                        if sym_idx == 0 {
+                            match kind {
+                                // local TLS still depends on which module we are
+                                RelocKind::TlsDtpMod | RelocKind::TlsTpOff => (kind, RelocTarget::ThisImageSlide),
+                                // absolute, nothing to do
+                                _ => continue,
+                            }
+                        } else if let Some(sym) = symlist.get(sym_idx) {
+                            (kind, RelocTarget::Import(sym))
+                        } else {
+                            errln!("warning: reloc at {} has out-of-range symbol index {}", address, sym_idx);
+                            continue;
+                        }
+                    },
+                };
+                out.push(Reloc {
+                    address: address,
+                    kind: kind,
+                    base: addend.map(|a| VMA(a as u64)),
+                    target: target,
+                });
+            }
+        }
+        if unknown > 0 {
+            errln!("warning: skipped {} relocs of unknown type (machine {})", unknown, self.basics.machine);
+        }
+        out
+    }
 
     fn get_dep_libs(&self) -> Cow<[DepLib]> {
         (&*self.get_verneed_info().dep_libs).into()
@@ -1142,6 +1736,10 @@ fn check_elf_basics(buf: &[ReadCell<u8>], warn: bool) -> Result<ElfBasics, &'sta
             EM_SPARC | EM_SPARC32PLUS | EM_SPARCV9 => Arch::Sparc,
             EM_MIPS | EM_MIPS_RS3_LE | EM_MIPS_X => Arch::Mips,
             EM_PPC | EM_PPC64 => Arch::PowerPC,
+            EM_RISCV => Arch::RiscV,
+            EM_S390 => Arch::S390x,
+            EM_LOONGARCH => Arch::LoongArch,
+            EM_68K => Arch::M68k,
             _ => Arch::UnknownArch,
         }
     })
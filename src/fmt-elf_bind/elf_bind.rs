@@ -153,6 +153,8 @@ pub fn e_machine_to_str(tag: u32) -> Option<&'static str> {
         EM_AARCH64 => "EM_AARCH64",
         EM_TILEPRO => "EM_TILEPRO",
         EM_TILEGX => "EM_TILEGX",
+        EM_RISCV => "EM_RISCV",
+        EM_LOONGARCH => "EM_LOONGARCH",
         EM_ALPHA => "EM_ALPHA",
 
         _ => return None,
@@ -0,0 +1,701 @@
+// LC_CODE_SIGNATURE: a big-endian SuperBlob containing CodeDirectories (one per hash type),
+// requirements, entitlements and the CMS signature.  See cs_blobs.h in xnu.
+use util;
+use util::{Mem, ByteString, ByteStr, ReadCell, ShaKind, IntStuff, sha_digest, copy_memory};
+use exec::{ExecResult, err};
+use exec::ErrorKind::{BadData, Other};
+use std::cmp::min;
+use std::ops::Range;
+use macho_bind::MH_EXECUTE;
+use ::MachO;
+
+pub const CSMAGIC_REQUIREMENT: u32 = 0xfade0c00;
+pub const CSMAGIC_REQUIREMENTS: u32 = 0xfade0c01;
+pub const CSMAGIC_CODEDIRECTORY: u32 = 0xfade0c02;
+pub const CSMAGIC_EMBEDDED_SIGNATURE: u32 = 0xfade0cc0;
+pub const CSMAGIC_EMBEDDED_ENTITLEMENTS: u32 = 0xfade7171;
+pub const CSMAGIC_EMBEDDED_DER_ENTITLEMENTS: u32 = 0xfade7172;
+pub const CSMAGIC_BLOBWRAPPER: u32 = 0xfade0b01;
+
+pub const CSSLOT_CODEDIRECTORY: u32 = 0;
+pub const CSSLOT_INFOSLOT: u32 = 1;
+pub const CSSLOT_REQUIREMENTS: u32 = 2;
+pub const CSSLOT_RESOURCEDIR: u32 = 3;
+pub const CSSLOT_APPLICATION: u32 = 4;
+pub const CSSLOT_ENTITLEMENTS: u32 = 5;
+pub const CSSLOT_DER_ENTITLEMENTS: u32 = 7;
+pub const CSSLOT_ALTERNATE_CODEDIRECTORIES: u32 = 0x1000;
+pub const CSSLOT_ALTERNATE_CODEDIRECTORY_MAX: u32 = 5;
+pub const CSSLOT_SIGNATURESLOT: u32 = 0x10000;
+
+pub const CS_HASHTYPE_SHA1: u8 = 1;
+pub const CS_HASHTYPE_SHA256: u8 = 2;
+pub const CS_HASHTYPE_SHA256_TRUNCATED: u8 = 3;
+pub const CS_HASHTYPE_SHA384: u8 = 4;
+
+pub const CS_SUPPORTSSCATTER: u32 = 0x20100;
+pub const CS_SUPPORTSTEAMID: u32 = 0x20200;
+pub const CS_SUPPORTSCODELIMIT64: u32 = 0x20300;
+pub const CS_SUPPORTSEXECSEG: u32 = 0x20400;
+pub const CS_SUPPORTSRUNTIME: u32 = 0x20500;
+
+pub const CS_ADHOC: u32 = 0x2;
+pub const CS_LINKER_SIGNED: u32 = 0x20000;
+
+pub const CS_EXECSEG_MAIN_BINARY: u64 = 0x1;
+
+const CS_FLAG_NAMES: &'static [(u64, &'static str)] = &[
+    (0x1, "valid"),
+    (0x2, "adhoc"),
+    (0x4, "get-task-allow"),
+    (0x8, "installer"),
+    (0x10, "forced-lv"),
+    (0x20, "invalid-allowed"),
+    (0x100, "hard"),
+    (0x200, "kill"),
+    (0x400, "check-expiration"),
+    (0x800, "restrict"),
+    (0x1000, "enforcement"),
+    (0x2000, "require-lv"),
+    (0x4000, "entitlements-validated"),
+    (0x8000, "nvram-unrestricted"),
+    (0x10000, "runtime"),
+    (0x20000, "linker-signed"),
+];
+
+const EXECSEG_FLAG_NAMES: &'static [(u64, &'static str)] = &[
+    (0x1, "main-binary"),
+    (0x10, "allow-unsigned"),
+    (0x20, "debugger"),
+    (0x40, "jit"),
+    (0x80, "skip-lv"),
+    (0x100, "can-load-cdhash"),
+    (0x200, "can-exec-cdhash"),
+];
+
+fn flag_names(flags: u64, names: &[(u64, &str)]) -> String {
+    let mut out = Vec::new();
+    let mut rest = flags;
+    for &(bit, name) in names {
+        if flags & bit != 0 {
+            out.push(name.to_owned());
+            rest &= !bit;
+        }
+    }
+    if rest != 0 { out.push(format!("0x{:x}", rest)); }
+    out.join(",")
+}
+
+pub fn cs_flags_desc(flags: u32) -> String { flag_names(flags as u64, CS_FLAG_NAMES) }
+pub fn exec_seg_flags_desc(flags: u64) -> String { flag_names(flags, EXECSEG_FLAG_NAMES) }
+
+pub fn hash_type_name(hash_type: u8) -> &'static str {
+    match hash_type {
+        CS_HASHTYPE_SHA1 => "sha1",
+        CS_HASHTYPE_SHA256 => "sha256",
+        CS_HASHTYPE_SHA256_TRUNCATED => "sha256-truncated",
+        CS_HASHTYPE_SHA384 => "sha384",
+        _ => "unknown",
+    }
+}
+
+fn hash_type_sha(hash_type: u8) -> Option<ShaKind> {
+    match hash_type {
+        CS_HASHTYPE_SHA1 => Some(ShaKind::Sha1),
+        CS_HASHTYPE_SHA256 | CS_HASHTYPE_SHA256_TRUNCATED => Some(ShaKind::Sha256),
+        CS_HASHTYPE_SHA384 => Some(ShaKind::Sha384),
+        _ => None,
+    }
+}
+
+// higher is stronger; None for hash types we can't check
+fn hash_type_rank(hash_type: u8) -> Option<u32> {
+    match hash_type {
+        CS_HASHTYPE_SHA1 => Some(1),
+        CS_HASHTYPE_SHA256_TRUNCATED => Some(2),
+        CS_HASHTYPE_SHA256 => Some(3),
+        CS_HASHTYPE_SHA384 => Some(4),
+        _ => None,
+    }
+}
+
+pub fn slot_name(slot: u32) -> String {
+    match slot {
+        CSSLOT_CODEDIRECTORY => "code directory".to_owned(),
+        CSSLOT_INFOSLOT => "info".to_owned(),
+        CSSLOT_REQUIREMENTS => "requirements".to_owned(),
+        CSSLOT_RESOURCEDIR => "resource dir".to_owned(),
+        CSSLOT_APPLICATION => "application".to_owned(),
+        CSSLOT_ENTITLEMENTS => "entitlements".to_owned(),
+        CSSLOT_DER_ENTITLEMENTS => "DER entitlements".to_owned(),
+        CSSLOT_SIGNATURESLOT => "CMS signature".to_owned(),
+        _ if slot >= CSSLOT_ALTERNATE_CODEDIRECTORIES &&
+             slot < CSSLOT_ALTERNATE_CODEDIRECTORIES + CSSLOT_ALTERNATE_CODEDIRECTORY_MAX =>
+            format!("alternate code directory {}", slot - CSSLOT_ALTERNATE_CODEDIRECTORIES),
+        _ => format!("slot 0x{:x}", slot),
+    }
+}
+
+pub fn requirement_type_name(ty: u32) -> &'static str {
+    match ty {
+        1 => "host",
+        2 => "guest",
+        3 => "designated",
+        4 => "library",
+        5 => "plugin",
+        _ => "unknown",
+    }
+}
+
+fn be32(buf: &[ReadCell<u8>], off: usize) -> u32 {
+    util::copy_from_slice(&buf[off..off+4], util::BigEndian)
+}
+fn be64(buf: &[ReadCell<u8>], off: usize) -> u64 {
+    util::copy_from_slice(&buf[off..off+8], util::BigEndian)
+}
+
+fn c_string_at(buf: &[ReadCell<u8>], off: usize) -> ByteString {
+    let rest = &buf[min(off, buf.len())..];
+    let len = rest.iter().position(|c| c.get() == 0).unwrap_or(rest.len());
+    ByteString::from_bytes(&rest[..len])
+}
+
+// Returns the whole blob (including the magic/length header), checking the magic.
+fn sub_blob(blob: &Mem<u8>, off: usize, magic: Option<u32>) -> ExecResult<Mem<u8>> {
+    let buf = blob.get();
+    if off.checked_add(8).map_or(true, |end| end > buf.len()) {
+        return err(BadData, format!("blob at 0x{:x} out of range", off));
+    }
+    let actual_magic = be32(buf, off);
+    if let Some(magic) = magic {
+        if actual_magic != magic {
+            return err(BadData, format!("blob at 0x{:x} has magic 0x{:x}, expected 0x{:x}", off, actual_magic, magic));
+        }
+    }
+    let len = be32(buf, off + 4) as usize;
+    if len < 8 || len > buf.len() - off {
+        return err(BadData, format!("blob at 0x{:x} has bad length 0x{:x}", off, len));
+    }
+    Ok(blob.slice(off, off + len).unwrap())
+}
+
+#[derive(Clone)]
+pub struct BlobIndexEntry {
+    pub slot: u32,
+    pub offset: u32,
+    pub magic: u32,
+    pub blob: Mem<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ExecSeg {
+    pub base: u64,
+    pub limit: u64,
+    pub flags: u64,
+}
+
+#[derive(Clone)]
+pub struct CodeDirectory {
+    pub slot: u32,
+    pub blob: Mem<u8>,
+    pub version: u32,
+    pub flags: u32,
+    pub hash_type: u8,
+    pub hash_size: u8,
+    pub platform: u8,
+    pub page_size_log2: u8, // 0 means one page covering everything
+    pub ident: ByteString,
+    pub team_id: Option<ByteString>,
+    pub code_limit: u64,
+    pub exec_seg: Option<ExecSeg>,
+    pub runtime: Option<u32>,
+    // special_hashes[0] is slot 1 (info), [1] is slot 2, etc.
+    pub special_hashes: Vec<Mem<u8>>,
+    pub code_hashes: Vec<Mem<u8>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SpecialSlotStatus {
+    Ok,
+    Mismatch,
+    Missing, // hash present but no blob
+    Unexpected, // blob present but no hash
+    Unchecked, // refers to something outside the binary (Info.plist, resources)
+}
+
+impl CodeDirectory {
+    pub fn parse(blob: Mem<u8>, slot: u32) -> ExecResult<CodeDirectory> {
+        let buf = blob.get();
+        if buf.len() < 44 {
+            return err(BadData, "code directory too short");
+        }
+        let version = be32(buf, 8);
+        let flags = be32(buf, 12);
+        let hash_offset = be32(buf, 16) as usize;
+        let ident_offset = be32(buf, 20) as usize;
+        let n_special_slots = be32(buf, 24) as usize;
+        let n_code_slots = be32(buf, 28) as usize;
+        let mut code_limit = be32(buf, 32) as u64;
+        let hash_size = buf[36].get();
+        let hash_type = buf[37].get();
+        let platform = buf[38].get();
+        let page_size_log2 = buf[39].get();
+        // each field is only present if the version is new enough (and the blob long enough)
+        let has = |min_version: u32, end: usize| version >= min_version && buf.len() >= end;
+        let team_id = if has(CS_SUPPORTSTEAMID, 52) {
+            let off = be32(buf, 48) as usize;
+            if off != 0 { Some(c_string_at(buf, off)) } else { None }
+        } else { None };
+        if has(CS_SUPPORTSCODELIMIT64, 64) {
+            let limit64 = be64(buf, 56);
+            if limit64 != 0 { code_limit = limit64; }
+        }
+        let exec_seg = if has(CS_SUPPORTSEXECSEG, 88) {
+            Some(ExecSeg { base: be64(buf, 64), limit: be64(buf, 72), flags: be64(buf, 80) })
+        } else { None };
+        let runtime = if has(CS_SUPPORTSRUNTIME, 92) { Some(be32(buf, 88)) } else { None };
+
+        let hash_size_ = hash_size as usize;
+        if hash_size_ == 0 {
+            return err(BadData, "code directory has zero hash size");
+        }
+        if page_size_log2 >= 32 {
+            return err(BadData, format!("code directory page size 2^{} too large", page_size_log2));
+        }
+        if n_special_slots.checked_mul(hash_size_).map_or(true, |size| size > hash_offset) {
+            return err(BadData, "code directory special slots start before blob");
+        }
+        let code_end = hash_offset.checked_add(n_code_slots.saturating_mul(hash_size_));
+        if code_end.map_or(true, |end| end > buf.len()) {
+            return err(BadData, format!("code directory hashes out of range ({} code slots of size {})", n_code_slots, hash_size));
+        }
+        let hash_at = |off: usize| blob.slice(off, off + hash_size_).unwrap();
+        // slot -n is stored n hashes before hash_offset
+        let special_hashes = (1..n_special_slots+1).map(|n| hash_at(hash_offset - n * hash_size_)).collect();
+        let code_hashes = (0..n_code_slots).map(|i| hash_at(hash_offset + i * hash_size_)).collect();
+        Ok(CodeDirectory {
+            slot: slot,
+            version: version,
+            flags: flags,
+            hash_type: hash_type,
+            hash_size: hash_size,
+            platform: platform,
+            page_size_log2: page_size_log2,
+            ident: c_string_at(buf, ident_offset),
+            team_id: team_id,
+            code_limit: code_limit,
+            exec_seg: exec_seg,
+            runtime: runtime,
+            special_hashes: special_hashes,
+            code_hashes: code_hashes,
+            blob: blob.clone(),
+        })
+    }
+
+    pub fn page_size(&self) -> Option<u64> {
+        if self.page_size_log2 == 0 { None } else { Some(1u64 << self.page_size_log2) }
+    }
+
+    pub fn hash(&self, data: &[ReadCell<u8>]) -> Option<Vec<u8>> {
+        let kind = some_or!(hash_type_sha(self.hash_type), { return None; });
+        let mut digest = sha_digest(kind, data);
+        digest.truncate(self.hash_size as usize);
+        Some(digest)
+    }
+
+    // The hash of the code directory itself, as used to identify the binary.
+    pub fn cdhash(&self) -> Option<Vec<u8>> {
+        self.hash(self.blob.get()).map(|mut h| { h.truncate(20); h })
+    }
+
+    // Byte range of the file (relative to the Mach-O header) covered by code slot i.
+    pub fn page_range(&self, i: usize) -> Range<u64> {
+        match self.page_size() {
+            Some(ps) => {
+                let start = (i as u64).saturating_mul(ps);
+                min(start, self.code_limit)..min(start.saturating_add(ps), self.code_limit)
+            },
+            None => 0..self.code_limit,
+        }
+    }
+
+    // Check each code slot against the file contents; returns the indices of pages that don't
+    // match (including pages that run past the end of the file).
+    pub fn bad_pages(&self, file: &[ReadCell<u8>]) -> ExecResult<Vec<usize>> {
+        if hash_type_sha(self.hash_type).is_none() {
+            return err(BadData, format!("unknown hash type {}", self.hash_type));
+        }
+        let mut bad = Vec::new();
+        for (i, expected) in self.code_hashes.iter().enumerate() {
+            let range = self.page_range(i);
+            let ok = if range.end > file.len() as u64 {
+                false
+            } else {
+                let actual = self.hash(&file[range.start as usize..range.end as usize]).unwrap();
+                &actual[..] == unsafe { expected.get_plain_slice() }
+            };
+            if !ok { bad.push(i); }
+        }
+        Ok(bad)
+    }
+}
+
+#[derive(Clone)]
+pub struct CodeSignature {
+    pub blob: Mem<u8>,
+    pub blobs: Vec<BlobIndexEntry>,
+    pub code_directories: Vec<CodeDirectory>,
+    // (requirement type, CSMAGIC_REQUIREMENT blob)
+    pub requirements: Vec<(u32, Mem<u8>)>,
+    pub entitlements: Option<ByteString>,
+    pub der_entitlements: Option<Mem<u8>>,
+    // offset and length of the CMS data (without the blob wrapper header), relative to the
+    // start of the signature; empty for ad-hoc signatures
+    pub cms: Option<(usize, usize)>,
+}
+
+impl CodeSignature {
+    pub fn parse(blob: &Mem<u8>) -> ExecResult<CodeSignature> {
+        let superblob = try!(sub_blob(blob, 0, Some(CSMAGIC_EMBEDDED_SIGNATURE)));
+        let buf = superblob.get();
+        if buf.len() < 12 {
+            return err(BadData, "superblob too short");
+        }
+        let count = be32(buf, 8) as usize;
+        if count > (buf.len() - 12) / 8 {
+            return err(BadData, format!("superblob index count {} too large", count));
+        }
+        let mut cs = CodeSignature {
+            blob: superblob.clone(),
+            blobs: Vec::new(),
+            code_directories: Vec::new(),
+            requirements: Vec::new(),
+            entitlements: None,
+            der_entitlements: None,
+            cms: None,
+        };
+        for i in 0..count {
+            let slot = be32(buf, 12 + i * 8);
+            let offset = be32(buf, 12 + i * 8 + 4);
+            let sub = try!(sub_blob(&superblob, offset as usize, None));
+            let magic = be32(sub.get(), 0);
+            let payload = sub.slice(8, sub.len()).unwrap();
+            match magic {
+                CSMAGIC_CODEDIRECTORY => {
+                    cs.code_directories.push(try!(CodeDirectory::parse(sub.clone(), slot)));
+                },
+                CSMAGIC_REQUIREMENTS => {
+                    let rbuf = sub.get();
+                    let rcount = if rbuf.len() >= 12 { be32(rbuf, 8) as usize } else { 0 };
+                    if rcount > (rbuf.len() - min(rbuf.len(), 12)) / 8 {
+                        return err(BadData, format!("requirements count {} too large", rcount));
+                    }
+                    for j in 0..rcount {
+                        let ty = be32(rbuf, 12 + j * 8);
+                        let roff = be32(rbuf, 12 + j * 8 + 4) as usize;
+                        cs.requirements.push((ty, try!(sub_blob(&sub, roff, Some(CSMAGIC_REQUIREMENT)))));
+                    }
+                },
+                CSMAGIC_EMBEDDED_ENTITLEMENTS => {
+                    cs.entitlements = Some(ByteString::from_bytes(payload.get()));
+                },
+                CSMAGIC_EMBEDDED_DER_ENTITLEMENTS => {
+                    cs.der_entitlements = Some(payload);
+                },
+                CSMAGIC_BLOBWRAPPER if slot == CSSLOT_SIGNATURESLOT => {
+                    cs.cms = Some((offset as usize + 8, payload.len()));
+                },
+                _ => errln!("warning: code signature: unknown blob magic 0x{:x} in {}", magic, slot_name(slot)),
+            }
+            cs.blobs.push(BlobIndexEntry { slot: slot, offset: offset, magic: magic, blob: sub });
+        }
+        Ok(cs)
+    }
+
+    pub fn blob_for_slot(&self, slot: u32) -> Option<&Mem<u8>> {
+        self.blobs.iter().find(|b| b.slot == slot).map(|b| &b.blob)
+    }
+
+    // Check cd's special slot hashes against the other blobs in this signature.
+    pub fn check_special_slots(&self, cd: &CodeDirectory) -> Vec<(u32, SpecialSlotStatus)> {
+        let mut res = Vec::new();
+        for (i, expected) in cd.special_hashes.iter().enumerate() {
+            let slot = (i + 1) as u32;
+            let zero = expected.get().iter().all(|c| c.get() == 0);
+            let status = match (slot, self.blob_for_slot(slot)) {
+                (CSSLOT_INFOSLOT, _) | (CSSLOT_RESOURCEDIR, _) => {
+                    if zero { continue; }
+                    SpecialSlotStatus::Unchecked
+                },
+                (_, None) => {
+                    if zero { continue; }
+                    SpecialSlotStatus::Missing
+                },
+                (_, Some(blob)) => {
+                    if zero {
+                        SpecialSlotStatus::Unexpected
+                    } else {
+                        match cd.hash(blob.get()) {
+                            Some(ref actual) if &actual[..] == unsafe { expected.get_plain_slice() } => SpecialSlotStatus::Ok,
+                            _ => SpecialSlotStatus::Mismatch,
+                        }
+                    }
+                },
+            };
+            res.push((slot, status));
+        }
+        res
+    }
+
+    // The code directory the kernel would prefer: the strongest hash type.
+    pub fn best_code_directory(&self) -> Option<&CodeDirectory> {
+        self.code_directories.iter()
+            .filter_map(|cd| hash_type_rank(cd.hash_type).map(|rank| (rank, cd)))
+            .max_by_key(|&(rank, _)| rank)
+            .map(|(_, cd)| cd)
+    }
+}
+
+impl MachO {
+    pub fn parse_code_signature(&self) -> ExecResult<Option<CodeSignature>> {
+        if self.code_signature.len() == 0 {
+            return Ok(None);
+        }
+        CodeSignature::parse(&self.code_signature).map(Some)
+    }
+
+    // The file as covered by code directory page hashes, i.e. starting at the Mach-O header.
+    pub fn code_signed_file(&self) -> &[ReadCell<u8>] {
+        let buf = self.eb.whole_buf.as_ref().unwrap().get();
+        &buf[min(self.dc_info.hdr_offset, buf.len())..]
+    }
+}
+
+const ADHOC_PAGE_SIZE_LOG2: u8 = 12;
+const ADHOC_HASH_SIZE: usize = 32;
+const ADHOC_CD_HEADER_SIZE: usize = 88; // through execSegFlags (version 0x20400)
+
+fn push_be32(buf: &mut Vec<u8>, val: u32) {
+    util::copy_to_vec(buf, &val, util::BigEndian);
+}
+fn push_be64(buf: &mut Vec<u8>, val: u64) {
+    util::copy_to_vec(buf, &val, util::BigEndian);
+}
+
+fn adhoc_special_slots(entitlements: Option<&[u8]>) -> usize {
+    if entitlements.is_some() { CSSLOT_ENTITLEMENTS as usize } else { CSSLOT_REQUIREMENTS as usize }
+}
+
+fn adhoc_signature_size(code_limit: u64, ident: &ByteStr, entitlements: Option<&[u8]>) -> usize {
+    let n_code_slots = (code_limit.align_up_to(1 << ADHOC_PAGE_SIZE_LOG2) >> ADHOC_PAGE_SIZE_LOG2) as usize;
+    let n_blobs = if entitlements.is_some() { 4 } else { 3 };
+    let cd_len = ADHOC_CD_HEADER_SIZE + ident.len() + 1 +
+                 (adhoc_special_slots(entitlements) + n_code_slots) * ADHOC_HASH_SIZE;
+    12 + n_blobs * 8 + cd_len + 12 + entitlements.map_or(0, |e| 8 + e.len()) + 8
+}
+
+// file is everything that gets hashed, i.e. up to where the signature goes.
+fn build_adhoc_signature(file: &[ReadCell<u8>], ident: &ByteStr, entitlements: Option<&[u8]>,
+                         exec_seg: &ExecSeg) -> Vec<u8> {
+    let hash = |data: &[u8]| sha_digest(ShaKind::Sha256, data);
+    // an empty requirement set, like codesign -s - makes
+    let mut requirements = Vec::new();
+    push_be32(&mut requirements, CSMAGIC_REQUIREMENTS);
+    push_be32(&mut requirements, 12);
+    push_be32(&mut requirements, 0);
+    let ents_blob = entitlements.map(|ents| {
+        let mut blob = Vec::new();
+        push_be32(&mut blob, CSMAGIC_EMBEDDED_ENTITLEMENTS);
+        push_be32(&mut blob, (8 + ents.len()) as u32);
+        blob.extend_from_slice(ents);
+        blob
+    });
+    let mut cms = Vec::new();
+    push_be32(&mut cms, CSMAGIC_BLOBWRAPPER);
+    push_be32(&mut cms, 8);
+
+    let page_size = 1usize << ADHOC_PAGE_SIZE_LOG2;
+    let n_special_slots = adhoc_special_slots(entitlements);
+    let n_code_slots = (file.len() + page_size - 1) / page_size;
+    let ident_offset = ADHOC_CD_HEADER_SIZE;
+    let hash_offset = ident_offset + ident.len() + 1 + n_special_slots * ADHOC_HASH_SIZE;
+    let cd_len = hash_offset + n_code_slots * ADHOC_HASH_SIZE;
+    let mut cd = Vec::with_capacity(cd_len);
+    push_be32(&mut cd, CSMAGIC_CODEDIRECTORY);
+    push_be32(&mut cd, cd_len as u32);
+    push_be32(&mut cd, CS_SUPPORTSEXECSEG);
+    push_be32(&mut cd, CS_ADHOC);
+    push_be32(&mut cd, hash_offset as u32);
+    push_be32(&mut cd, ident_offset as u32);
+    push_be32(&mut cd, n_special_slots as u32);
+    push_be32(&mut cd, n_code_slots as u32);
+    let code_limit = file.len() as u64;
+    push_be32(&mut cd, if code_limit > (!0u32 as u64) { 0 } else { code_limit as u32 });
+    cd.extend_from_slice(&[ADHOC_HASH_SIZE as u8, CS_HASHTYPE_SHA256, 0, ADHOC_PAGE_SIZE_LOG2]);
+    push_be32(&mut cd, 0); // spare2
+    push_be32(&mut cd, 0); // scatterOffset
+    push_be32(&mut cd, 0); // teamOffset
+    push_be32(&mut cd, 0); // spare3
+    push_be64(&mut cd, if code_limit > (!0u32 as u64) { code_limit } else { 0 });
+    push_be64(&mut cd, exec_seg.base);
+    push_be64(&mut cd, exec_seg.limit);
+    push_be64(&mut cd, exec_seg.flags);
+    assert_eq!(cd.len(), ADHOC_CD_HEADER_SIZE);
+    cd.extend_from_slice(ident);
+    cd.push(0);
+    // special slots are stored in reverse, ending just before hash_offset
+    for slot in (1..n_special_slots as u32 + 1).rev() {
+        match slot {
+            CSSLOT_REQUIREMENTS => cd.extend_from_slice(&hash(&requirements)),
+            CSSLOT_ENTITLEMENTS => cd.extend_from_slice(&hash(ents_blob.as_ref().unwrap())),
+            _ => cd.extend_from_slice(&[0; ADHOC_HASH_SIZE]),
+        }
+    }
+    assert_eq!(cd.len(), hash_offset);
+    for page in file.chunks(page_size) {
+        cd.extend_from_slice(&sha_digest(ShaKind::Sha256, page));
+    }
+    assert_eq!(cd.len(), cd_len);
+
+    let mut blobs: Vec<(u32, &[u8])> = vec![(CSSLOT_CODEDIRECTORY, &cd[..]), (CSSLOT_REQUIREMENTS, &requirements[..])];
+    if let Some(ref ents_blob) = ents_blob {
+        blobs.push((CSSLOT_ENTITLEMENTS, &ents_blob[..]));
+    }
+    blobs.push((CSSLOT_SIGNATURESLOT, &cms[..]));
+    let mut out = Vec::new();
+    let total_len = 12 + blobs.len() * 8 + blobs.iter().map(|&(_, b)| b.len()).sum::<usize>();
+    push_be32(&mut out, CSMAGIC_EMBEDDED_SIGNATURE);
+    push_be32(&mut out, total_len as u32);
+    push_be32(&mut out, blobs.len() as u32);
+    let mut off = 12 + blobs.len() * 8;
+    for &(slot, blob) in &blobs {
+        push_be32(&mut out, slot);
+        push_be32(&mut out, off as u32);
+        off += blob.len();
+    }
+    for &(_, blob) in &blobs {
+        out.extend_from_slice(blob);
+    }
+    assert_eq!(out.len(), total_len);
+    out
+}
+
+impl MachO {
+    // Replace any existing signature with a fresh ad-hoc one (SHA-256 only, no CMS), laying out
+    // the file again in the process.  The identifier is the install name's basename, or
+    // fallback_ident if there isn't one.  entitlements is an XML plist.
+    pub fn adhoc_sign(&mut self, fallback_ident: &ByteStr, entitlements: Option<&[u8]>) -> ExecResult<()> {
+        let ident = ByteString::new(self.install_name().map_or(fallback_ident, |name| name.unix_basename()));
+        // first lay out without a signature to find out where it'll go
+        try!(self.reallocate());
+        let (text_fileoff, text_filesize, sig_off) = {
+            let segs = &self.eb.segments;
+            let text = some_or!(segs.iter().find(|seg| seg.fileoff == 0 && seg.filesize != 0), {
+                return err(BadData, "adhoc_sign: no text segment?");
+            });
+            let linkedit = some_or!(segs.iter().find(|seg| seg.name.as_ref().map(|s| &s[..]) == Some(ByteStr::from_str("__LINKEDIT"))), {
+                return err(Other, "adhoc_sign: no __LINKEDIT segment");
+            });
+            (text.fileoff, text.filesize, (linkedit.fileoff + linkedit.filesize).align_up_to(16))
+        };
+        let size = adhoc_signature_size(sig_off, &ident, entitlements);
+        self.code_signature = Mem::with_vec(vec![0; size]);
+        try!(self.reallocate_keeping_signature());
+        self.rewhole();
+
+        let whole = self.eb.whole_buf.as_ref().unwrap().clone();
+        let exec_seg = ExecSeg {
+            base: text_fileoff,
+            limit: text_filesize,
+            flags: if self.mh.filetype == MH_EXECUTE { CS_EXECSEG_MAIN_BINARY } else { 0 },
+        };
+        let sig = build_adhoc_signature(&whole.get()[..sig_off as usize], &ident, entitlements, &exec_seg);
+        if sig.len() != size {
+            return err(BadData, format!("adhoc_sign: signature came out {} bytes, expected {}", sig.len(), size));
+        }
+        let sig_end = sig_off as usize + size;
+        if sig_end != whole.len() {
+            return err(BadData, format!("adhoc_sign: signature ends at 0x{:x}, but the file is 0x{:x} bytes", sig_end, whole.len()));
+        }
+        copy_memory(&sig[..], &whole.get_mut()[sig_off as usize..sig_end]);
+        self.code_signature = whole.slice(sig_off as usize, sig_end).unwrap();
+        // keep __LINKEDIT's data in sync with whole_buf
+        for seg in &mut self.eb.segments {
+            if seg.fileoff + seg.filesize == sig_end as u64 && seg.filesize >= size as u64 {
+                seg.data = Some(whole.slice(seg.fileoff as usize, sig_end).unwrap());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::{Mem, ByteStr, ByteString};
+
+    #[test]
+    fn test_adhoc_round_trip() {
+        let data: Vec<u8> = (0..10000).map(|i| (i * 7) as u8).collect();
+        let file = Mem::with_vec(data);
+        let ents = b"<plist><dict/></plist>";
+        let exec_seg = ExecSeg { base: 0, limit: 0x1000, flags: CS_EXECSEG_MAIN_BINARY };
+        let sig = build_adhoc_signature(file.get(), ByteStr::from_str("test"), Some(&ents[..]), &exec_seg);
+        assert_eq!(sig.len(), adhoc_signature_size(file.len() as u64, ByteStr::from_str("test"), Some(&ents[..])));
+
+        let cs = CodeSignature::parse(&Mem::with_vec(sig)).unwrap();
+        assert_eq!(cs.entitlements.as_ref().map(|e| &e[..]), Some(ByteStr::from_bytes(&ents[..])));
+        let cd = cs.best_code_directory().unwrap();
+        assert_eq!(cd.code_hashes.len(), 3);
+        assert_eq!(cd.code_limit, 10000);
+        assert_eq!(cd.bad_pages(file.get()).unwrap(), Vec::<usize>::new());
+        assert_eq!(cs.check_special_slots(cd),
+                   vec![(CSSLOT_REQUIREMENTS, SpecialSlotStatus::Ok), (CSSLOT_ENTITLEMENTS, SpecialSlotStatus::Ok)]);
+
+        // and a changed page is caught
+        file.get_mut()[5000].set(0xff);
+        assert_eq!(cd.bad_pages(file.get()).unwrap(), vec![1]);
+    }
+
+    fn dummy_cd(slot: u32, hash_type: u8) -> CodeDirectory {
+        CodeDirectory {
+            slot: slot,
+            blob: Mem::with_vec(Vec::new()),
+            version: 0x20400,
+            flags: 0,
+            hash_type: hash_type,
+            hash_size: 0,
+            platform: 0,
+            page_size_log2: 12,
+            ident: ByteString::from_str("test"),
+            team_id: None,
+            code_limit: 0,
+            exec_seg: None,
+            runtime: None,
+            special_hashes: Vec::new(),
+            code_hashes: Vec::new(),
+        }
+    }
+
+    fn best_of(hash_types: &[u8]) -> u8 {
+        let cs = CodeSignature {
+            blob: Mem::with_vec(Vec::new()),
+            blobs: Vec::new(),
+            code_directories: hash_types.iter().enumerate().map(|(i, &ht)| dummy_cd(i as u32, ht)).collect(),
+            requirements: Vec::new(),
+            entitlements: None,
+            der_entitlements: None,
+            cms: None,
+        };
+        cs.best_code_directory().unwrap().hash_type
+    }
+
+    #[test]
+    fn test_best_code_directory() {
+        assert_eq!(best_of(&[CS_HASHTYPE_SHA1, CS_HASHTYPE_SHA256_TRUNCATED]), CS_HASHTYPE_SHA256_TRUNCATED);
+        assert_eq!(best_of(&[CS_HASHTYPE_SHA256_TRUNCATED, CS_HASHTYPE_SHA1]), CS_HASHTYPE_SHA256_TRUNCATED);
+        assert_eq!(best_of(&[CS_HASHTYPE_SHA256_TRUNCATED, CS_HASHTYPE_SHA256]), CS_HASHTYPE_SHA256);
+        assert_eq!(best_of(&[CS_HASHTYPE_SHA1, CS_HASHTYPE_SHA384, CS_HASHTYPE_SHA256]), CS_HASHTYPE_SHA384);
+        assert_eq!(best_of(&[CS_HASHTYPE_SHA1, 99]), CS_HASHTYPE_SHA1);
+    }
+}
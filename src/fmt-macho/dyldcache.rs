@@ -4,7 +4,7 @@ use macho_bind;
 use util::{Mem, ByteString, Ext, SliceExt, ByteStr, Narrow, Lazy, Fnv, CheckMul, Cast, Unswapped, CheckSub, ReadCell, LazyBox, LittleEndian};
 use exec::ErrorKind::BadData;
 use exec::arch;
-use exec::{Reloc, RelocKind, RelocTarget, ExecResult, err, ExecBase, VMA, Exec, ExecProber, ProbeResult, Segment, ErrorKind, intersect_start_size};
+use exec::{Reloc, RelocKind, RelocTarget, ExecResult, err, err_only, ExecBase, VMA, Exec, ExecProber, ProbeResult, Segment, ErrorKind, intersect_start_size};
 use std::mem::size_of;
 use std::cmp::{min, max, Ordering};
 use std::ops::Range;
@@ -12,10 +12,17 @@ use std::collections::{HashSet, HashMap};
 use std::collections::hash_map::Entry;
 use std::any::Any;
 use std::cell::Cell;
+use std::sync::Arc;
+use std::fs::File;
+use std::path::{Path, PathBuf};
 use std;
-pub use macho_bind::{dyld_cache_header, dyld_cache_mapping_info, dyld_cache_image_info, dyld_cache_local_symbols_info, dyld_cache_local_symbols_entry, dyld_cache_slide_info, dyld_cache_slide_info2, DYLD_CACHE_SLIDE_PAGE_ATTR_NO_REBASE, DYLD_CACHE_SLIDE_PAGE_ATTR_EXTRA, DYLD_CACHE_SLIDE_PAGE_ATTR_END};
+pub use macho_bind::{dyld_cache_header, dyld_cache_mapping_info, dyld_cache_image_info, dyld_cache_local_symbols_info, dyld_cache_local_symbols_entry, dyld_cache_local_symbols_entry_64, dyld_cache_mapping_and_slide_info, dyld_subcache_entry, dyld_subcache_entry_v1, dyld_cache_slide_info, dyld_cache_slide_info2, dyld_cache_slide_info3, dyld_cache_slide_info4, DYLD_CACHE_SLIDE_PAGE_ATTR_NO_REBASE, DYLD_CACHE_SLIDE_PAGE_ATTR_EXTRA, DYLD_CACHE_SLIDE_PAGE_ATTR_END, DYLD_CACHE_SLIDE_V3_PAGE_ATTR_NO_REBASE, DYLD_CACHE_SLIDE4_PAGE_NO_REBASE, DYLD_CACHE_SLIDE4_PAGE_INDEX, DYLD_CACHE_SLIDE4_PAGE_USE_EXTRA, DYLD_CACHE_SLIDE4_PAGE_EXTRA_END};
 use ::{MachO, GuessBrokenCacheSlideResult, MachODCInfo, file_array};
 
+macro_rules! has_field {($ch:expr, $field:ident) => (
+    $ch.has(offset_of!(dyld_cache_header, $field) + std::mem::size_of_val(&$ch.hdr.$field))
+)}
+
 pub struct ImageInfo {
     pub address: u64,
     pub mod_time: u64,
@@ -25,6 +32,7 @@ pub struct ImageInfo {
 
 pub struct LocalSymbols {
     entries: Mem<u8>,
+    entries_64: bool, // dyld_cache_local_symbols_entry_64, keyed by address rather than file offset
     symtab: Mem<u8>,
     strtab: Mem<u8>,
     nlist_count: u32,
@@ -32,19 +40,26 @@ pub struct LocalSymbols {
 
 pub struct DyldCache {
     pub eb: ExecBase,
-    pub slide_info: Option<SlideInfo>,
+    pub slide_info: Vec<SlideInfo>, // newer caches have one per writable mapping
     pub image_info: Vec<ImageInfo>,
     pub uuid: Option<[u8; 16]>,
     pub cs_blob: Option<Mem<u8>>,
     pub local_symbols: Option<LocalSymbols>,
     pub have_images_text_offset: bool,
+    pub is_split: bool, // whole_buf has sub-caches after the main file
+    // relative method lists in the cache name selectors by offset from here
+    pub objc_relative_selector_base: Option<VMA>,
 }
 
+// where sub-caches go in the merged buffer; big enough for any page size
+const SUBCACHE_ALIGN: usize = 0x10000;
+
 const SLIDE_GRANULARITY: u64 = 4;
 pub struct SlideInfoV1 {
     pub toc: Mem<u8>,
     pub entries: Mem<u8>,
     pub entries_size: usize,
+    data_seg_idx: usize,
     data_addr: VMA,
     data_size: u64,
     endian: util::Endian,
@@ -57,15 +72,38 @@ pub struct SlideInfoV2 {
     pub delta_mask: u64,
     pub delta_shift: u32, // not including the -2
     pub value_add: u64,
+    data_seg_idx: usize,
+    rebase_list_by_slab: Vec<LazyBox<Vec<Reloc<'static>>>>,
+}
+// arm64e, with pointer authentication
+pub struct SlideInfoV3 {
+    pub page_size: u64,
+    pub page_starts: Mem<Unswapped<u16>>,
+    pub auth_value_add: u64,
+    data_seg_idx: usize,
+    rebase_list_by_slab: Vec<LazyBox<Vec<Reloc<'static>>>>,
+}
+// 32-bit (armv7k, arm64_32)
+pub struct SlideInfoV4 {
+    pub page_size: u64,
+    pub page_starts: Mem<Unswapped<u16>>,
+    pub page_extras: Mem<Unswapped<u16>>,
+    pub delta_mask: u64,
+    pub delta_shift: u32, // not including the -2
+    pub value_add: u64,
+    data_seg_idx: usize,
     rebase_list_by_slab: Vec<LazyBox<Vec<Reloc<'static>>>>,
 }
 pub enum SlideInfo {
     V1(SlideInfoV1),
     V2(SlideInfoV2),
+    V3(SlideInfoV3),
+    V4(SlideInfoV4),
 }
 
 impl SlideInfo {
-    pub fn new(blob: Mem<u8>, end: util::Endian, is64: bool, data_addr: VMA, data_size: u64) -> ExecResult<SlideInfo> {
+    // data_seg_idx is the index of the mapping this slide info covers in the DyldCache's segments
+    pub fn new(blob: Mem<u8>, end: util::Endian, is64: bool, data_seg_idx: usize, data_addr: VMA, data_size: u64) -> ExecResult<SlideInfo> {
         let slide_info_version: u32 = {
             let slice = blob.get();
             if slice.len() < 4 {
@@ -74,31 +112,65 @@ impl SlideInfo {
             util::copy_from_slice(&slice[..4], end)
         };
         match slide_info_version {
-            1 => Ok(SlideInfo::V1(try!(SlideInfoV1::new(blob, end, data_addr, data_size)))),
-            2 => Ok(SlideInfo::V2(try!(SlideInfoV2::new(blob, end, is64)))),
+            1 => Ok(SlideInfo::V1(try!(SlideInfoV1::new(blob, end, data_seg_idx, data_addr, data_size)))),
+            2 => Ok(SlideInfo::V2(try!(SlideInfoV2::new(blob, end, is64, data_seg_idx)))),
+            3 => Ok(SlideInfo::V3(try!(SlideInfoV3::new(blob, end, data_seg_idx)))),
+            4 => Ok(SlideInfo::V4(try!(SlideInfoV4::new(blob, end, data_seg_idx)))),
             _ => err(BadData, format!("unknown dyld slide info version {}", slide_info_version)),
         }
     }
     pub fn iter<F>(&self, eb: &ExecBase, range: Option<(VMA, u64)>, mut func: F) where F: FnMut(VMA) {
-        match self {
-            &SlideInfo::V1(ref v1) => v1.iter(range, func),
-            &SlideInfo::V2(ref v2) => {
-                let slabs = v2.save_rebase_list(eb, range);
-                for slab in slabs {
-                    let relocs = &slab.get().unwrap()[..];
-                    for reloc in relocs {
-                        if let Some((start_addr, size)) = range {
-                            if reloc.address.wrapping_sub(start_addr) >= size { continue; }
-                        }
-                        func(reloc.address);
-                    }
+        let slabs = match self {
+            &SlideInfo::V1(ref v1) => return v1.iter(range, func),
+            &SlideInfo::V2(ref v2) => v2.save_rebase_list(eb, range),
+            &SlideInfo::V3(ref v3) => v3.save_rebase_list(eb, range),
+            &SlideInfo::V4(ref v4) => v4.save_rebase_list(eb, range),
+        };
+        for slab in slabs {
+            let relocs = &slab.get().unwrap()[..];
+            for reloc in relocs {
+                if let Some((start_addr, size)) = range {
+                    if reloc.address.wrapping_sub(start_addr) >= size { continue; }
                 }
-            },
+                func(reloc.address);
+            }
+        }
+    }
+    // the chained versions; also un-chains the data
+    pub fn save_rebase_list(&self, eb: &ExecBase, range: Option<(VMA, u64)>) -> Option<&[LazyBox<Vec<Reloc<'static>>>]> {
+        match self {
+            &SlideInfo::V1(_) => None,
+            &SlideInfo::V2(ref v2) => Some(v2.save_rebase_list(eb, range)),
+            &SlideInfo::V3(ref v3) => Some(v3.save_rebase_list(eb, range)),
+            &SlideInfo::V4(ref v4) => Some(v4.save_rebase_list(eb, range)),
+        }
+    }
+    pub fn data_seg_idx(&self) -> usize {
+        match self {
+            &SlideInfo::V1(ref v1) => v1.data_seg_idx,
+            &SlideInfo::V2(ref v2) => v2.data_seg_idx,
+            &SlideInfo::V3(ref v3) => v3.data_seg_idx,
+            &SlideInfo::V4(ref v4) => v4.data_seg_idx,
         }
     }
 }
+
+// which slabs of pages cover range
+fn slabs_for_range(data_seg: &Segment, page_size: u64, page_count: usize, range: Option<(VMA, u64)>) -> Option<Range<usize>> {
+    let (mut page_start, mut page_end) = (0usize, page_count);
+    if let Some((raddr, rsize)) = range {
+        // need better utilities for ranges
+        let (raddr, rsize) = intersect_start_size((raddr, rsize), (data_seg.vmaddr, data_seg.vmsize));
+        page_start = max(page_start, ((raddr - data_seg.vmaddr) / page_size) as usize);
+        page_end = min(page_end, ((raddr + rsize - data_seg.vmaddr + page_size - 1) / page_size) as usize);
+    }
+    //println!("save_rebase_list: page_start={} page_end={}", page_start, page_end);
+    if page_start > page_end { return None; }
+    Some(page_start / SLAB_PAGES .. (page_end + SLAB_PAGES - 1) / SLAB_PAGES)
+}
+
 impl SlideInfoV1 {
-    pub fn new(blob: Mem<u8>, end: util::Endian, data_addr: VMA, data_size: u64) -> ExecResult<Self> {
+    pub fn new(blob: Mem<u8>, end: util::Endian, data_seg_idx: usize, data_addr: VMA, data_size: u64) -> ExecResult<Self> {
         let slice = blob.get();
         let size = size_of::<dyld_cache_slide_info>();
         if blob.len() < size {
@@ -120,6 +192,7 @@ impl SlideInfoV1 {
             endian: end,
             entries: entries,
             entries_size: entries_size,
+            data_seg_idx: data_seg_idx,
             data_addr: data_addr,
             data_size: data_size,
         })
@@ -174,7 +247,7 @@ impl SlideInfoV1 {
 }
 
 impl SlideInfoV2 {
-    pub fn new(blob: Mem<u8>, end: util::Endian, is64: bool) -> ExecResult<Self> {
+    pub fn new(blob: Mem<u8>, end: util::Endian, is64: bool, data_seg_idx: usize) -> ExecResult<Self> {
         let size = size_of::<dyld_cache_slide_info2>();
         if blob.len() < size {
             return err(BadData, "slide info blob too small for header");
@@ -212,12 +285,13 @@ impl SlideInfoV2 {
             delta_mask: slide_info.delta_mask,
             delta_shift: delta_shift,
             value_add: slide_info.value_add,
+            data_seg_idx: data_seg_idx,
             rebase_list_by_slab: (0..num_slabs).map(|_| LazyBox::new()).collect(),
         })
     }
     pub fn save_rebase_list(&self, eb: &ExecBase, range: Option<(VMA, u64)>) -> &[LazyBox<Vec<Reloc<'static>>>] {
         //let _sw = util::stopwatch("save_rebase_list");
-        let data_seg = some_or!(eb.segments.get(1), {
+        let data_seg = some_or!(eb.segments.get(self.data_seg_idx), {
             errln!("SlideInfoV2::save_rebase_list: no data segment");
             return &[];
         });
@@ -226,20 +300,11 @@ impl SlideInfoV2 {
             errln!("SlideInfoV2::save_rebase_list: not little endian; hardcoded just for optimization's sake");
             return &[];
         }
-        let (mut page_start, mut page_end) = (0usize, self.page_starts.len());
-        if let Some((raddr, rsize)) = range {
-            // need better utilities for ranges
-            let (raddr, rsize) = intersect_start_size((raddr, rsize), (data_seg.vmaddr, data_seg.vmsize));
-            page_start = max(page_start, ((raddr - data_seg.vmaddr) / self.page_size) as usize);
-            page_end = min(page_end, ((raddr + rsize - data_seg.vmaddr + self.page_size - 1) / self.page_size) as usize);
-        }
-        //println!("save_rebase_list: page_start={} page_end={}", page_start, page_end);
-        if page_start > page_end { return &[]; }
+        let slabs = some_or!(slabs_for_range(data_seg, self.page_size, self.page_starts.len(), range), { return &[]; });
         let delta_mask = self.delta_mask;
         let delta_shift = self.delta_shift;
         let value_add = self.value_add;
-        let slab_start = page_start / SLAB_PAGES;
-        let slab_end = (page_end + SLAB_PAGES - 1) / SLAB_PAGES;
+        let (slab_start, slab_end) = (slabs.start, slabs.end);
         for slab in slab_start..slab_end {
             let lazybox = &self.rebase_list_by_slab[slab];
             if lazybox.get().is_some() { continue; }
@@ -359,6 +424,226 @@ impl SlideInfoV2 {
     }
 }
 
+impl SlideInfoV3 {
+    pub fn new(blob: Mem<u8>, end: util::Endian, data_seg_idx: usize) -> ExecResult<Self> {
+        let size = size_of::<dyld_cache_slide_info3>();
+        if blob.len() < size {
+            return err(BadData, "slide info blob too small for header");
+        }
+        let slice = blob.get();
+        let slide_info: dyld_cache_slide_info3 = util::copy_from_slice(&slice[..size], end);
+        let (page_starts, _) =
+            file_array(&blob, "page starts",
+                       size as u64,
+                       slide_info.page_starts_count.ext(),
+                       2).cast();
+        if slide_info.page_size % 4096 != 0 ||
+           slide_info.page_size > 1048576 { // arbitrary
+            return err(BadData, "unreasonable slide info 3 page size");
+        }
+        let num_slabs = (page_starts.len() + SLAB_PAGES - 1) / SLAB_PAGES;
+        Ok(SlideInfoV3 {
+            page_size: slide_info.page_size.ext(),
+            page_starts: page_starts,
+            auth_value_add: slide_info.auth_value_add,
+            data_seg_idx: data_seg_idx,
+            rebase_list_by_slab: (0..num_slabs).map(|_| LazyBox::new()).collect(),
+        })
+    }
+    pub fn save_rebase_list(&self, eb: &ExecBase, range: Option<(VMA, u64)>) -> &[LazyBox<Vec<Reloc<'static>>>] {
+        let data_seg = some_or!(eb.segments.get(self.data_seg_idx), {
+            errln!("SlideInfoV3::save_rebase_list: no data segment");
+            return &[];
+        });
+        if eb.endian != LittleEndian || eb.pointer_size != 8 {
+            errln!("SlideInfoV3::save_rebase_list: expected a little endian 64-bit cache");
+            return &[];
+        }
+        let slabs = some_or!(slabs_for_range(data_seg, self.page_size, self.page_starts.len(), range), { return &[]; });
+        let auth_value_add = self.auth_value_add;
+        for slab in slabs.clone() {
+            let lazybox = &self.rebase_list_by_slab[slab];
+            if lazybox.get().is_some() { continue; }
+            let mut rebase_list: Vec<Reloc> = Vec::new();
+            let page_size = self.page_size as usize;
+            assert!(data_seg.name.is_none());
+            let data_data: &[Cell<u8>] = data_seg.data.as_ref().unwrap().get_mut();
+            let page_starts = &self.page_starts.get();
+            let page_starts = &page_starts[slab * SLAB_PAGES .. min((slab + 1) * SLAB_PAGES, page_starts.len())];
+            for (i, ps) in page_starts.iter().enumerate() {
+                let ps = ps.copy(LittleEndian);
+                if ps == (DYLD_CACHE_SLIDE_V3_PAGE_ATTR_NO_REBASE as u16) { continue; }
+                let offset: usize = (slab * SLAB_PAGES + i) * page_size;
+                let addr: VMA = data_seg.vmaddr.wrapping_add(offset as u64);
+                let mut offset_in_page = ps as usize;
+                loop {
+                    if offset_in_page > page_size - 8 {
+                        errln!("SlideInfoV3::save_rebase_list: offset-in-page past page size");
+                        break;
+                    }
+                    let slice = some_or!(data_data.slice_opt(offset + offset_in_page, offset + offset_in_page + 8), {
+                        errln!("SlideInfoV3::save_rebase_list: out of range of data segment");
+                        break;
+                    });
+                    let raw: u64 = util::copy_from_slice(slice, LittleEndian);
+                    let delta = (8 * ((raw >> 51) & 0x7ff)) as usize;
+                    let value = if raw >> 63 != 0 {
+                        // authenticated; just drop the signing info
+                        (raw & 0xffffffff).wrapping_add(auth_value_add)
+                    } else {
+                        let value51 = raw & 0x0007ffffffffffff;
+                        ((value51 & 0x0007f80000000000) << 13) | (value51 & 0x000007ffffffffff)
+                    };
+                    util::copy_to_slice(slice, &value, LittleEndian);
+                    rebase_list.push(Reloc {
+                        address: addr + offset_in_page.ext(),
+                        kind: RelocKind::Pointer,
+                        base: None,
+                        target: RelocTarget::ThisImageSlide,
+                    });
+                    if delta == 0 {
+                        break;
+                    }
+                    if delta > page_size - offset_in_page {
+                        errln!("SlideInfoV3::save_rebase_list: offset-in-page out of range");
+                        break;
+                    }
+                    offset_in_page += delta;
+                }
+            }
+            let _ = lazybox.store(Box::new(rebase_list)); // doesn't matter if this is a dupe
+        }
+        &self.rebase_list_by_slab[slabs]
+    }
+}
+
+impl SlideInfoV4 {
+    pub fn new(blob: Mem<u8>, end: util::Endian, data_seg_idx: usize) -> ExecResult<Self> {
+        let size = size_of::<dyld_cache_slide_info4>();
+        if blob.len() < size {
+            return err(BadData, "slide info blob too small for header");
+        }
+        let slice = blob.get();
+        let slide_info: dyld_cache_slide_info4 = util::copy_from_slice(&slice[..size], end);
+        let (page_starts, _) =
+            file_array(&blob, "page starts",
+                       slide_info.page_starts_offset.ext(),
+                       slide_info.page_starts_count.ext(),
+                       2).cast();
+        let (page_extras, _) =
+            file_array(&blob, "page extras",
+                       slide_info.page_extras_offset.ext(),
+                       slide_info.page_extras_count.ext(),
+                       2).cast();
+        if slide_info.page_size % 4096 != 0 ||
+           slide_info.page_size > 1048576 { // arbitrary
+            return err(BadData, "unreasonable slide info 4 page size");
+        }
+        if slide_info.delta_mask == 0 || slide_info.delta_mask >> 32 != 0 ||
+           slide_info.value_add >> 32 != 0 {
+            return err(BadData, format!("strange delta_mask 0x{:x} / value_add 0x{:x}", slide_info.delta_mask, slide_info.value_add));
+        }
+        let delta_shift = slide_info.delta_mask.trailing_zeros();
+        let num_slabs = (page_starts.len() + SLAB_PAGES - 1) / SLAB_PAGES;
+        Ok(SlideInfoV4 {
+            page_size: slide_info.page_size.ext(),
+            page_starts: page_starts,
+            page_extras: page_extras,
+            delta_mask: slide_info.delta_mask,
+            delta_shift: delta_shift,
+            value_add: slide_info.value_add,
+            data_seg_idx: data_seg_idx,
+            rebase_list_by_slab: (0..num_slabs).map(|_| LazyBox::new()).collect(),
+        })
+    }
+    pub fn save_rebase_list(&self, eb: &ExecBase, range: Option<(VMA, u64)>) -> &[LazyBox<Vec<Reloc<'static>>>] {
+        let data_seg = some_or!(eb.segments.get(self.data_seg_idx), {
+            errln!("SlideInfoV4::save_rebase_list: no data segment");
+            return &[];
+        });
+        if eb.endian != LittleEndian || eb.pointer_size != 4 {
+            errln!("SlideInfoV4::save_rebase_list: expected a little endian 32-bit cache");
+            return &[];
+        }
+        let slabs = some_or!(slabs_for_range(data_seg, self.page_size, self.page_starts.len(), range), { return &[]; });
+        let delta_mask = self.delta_mask as u32;
+        let delta_shift = self.delta_shift;
+        let value_add = self.value_add as u32;
+        for slab in slabs.clone() {
+            let lazybox = &self.rebase_list_by_slab[slab];
+            if lazybox.get().is_some() { continue; }
+            let mut rebase_list: Vec<Reloc> = Vec::new();
+            let page_extras = self.page_extras.get();
+            let page_size = self.page_size as usize;
+            assert!(data_seg.name.is_none());
+            let data_data: &[Cell<u8>] = data_seg.data.as_ref().unwrap().get_mut();
+            let page_starts = &self.page_starts.get();
+            let page_starts = &page_starts[slab * SLAB_PAGES .. min((slab + 1) * SLAB_PAGES, page_starts.len())];
+            for (i, ps) in page_starts.iter().enumerate() {
+                let ps = ps.copy(LittleEndian);
+                if ps == (DYLD_CACHE_SLIDE4_PAGE_NO_REBASE as u16) { continue; }
+                let offset: usize = (slab * SLAB_PAGES + i) * page_size;
+                let addr: VMA = data_seg.vmaddr.wrapping_add(offset as u64);
+                let mut do_chain = |mut offset_in_page: usize| {
+                    loop {
+                        if offset_in_page > page_size - 4 {
+                            errln!("SlideInfoV4::save_rebase_list: offset-in-page past page size");
+                            break;
+                        }
+                        let slice = some_or!(data_data.slice_opt(offset + offset_in_page, offset + offset_in_page + 4), {
+                            errln!("SlideInfoV4::save_rebase_list: out of range of data segment");
+                            break;
+                        });
+                        let raw: u32 = util::copy_from_slice(slice, LittleEndian);
+                        let delta = (4 * ((raw & delta_mask) >> delta_shift)) as usize;
+                        let mut value = raw & !delta_mask;
+                        if value & 0xffff8000 == 0 {
+                            // small positive non-pointer
+                        } else if value & 0x3fff8000 == 0x3fff8000 {
+                            // small negative non-pointer
+                            value |= 0xc0000000;
+                        } else {
+                            value = value.wrapping_add(value_add);
+                            rebase_list.push(Reloc {
+                                address: addr + offset_in_page.ext(),
+                                kind: RelocKind::Pointer,
+                                base: None,
+                                target: RelocTarget::ThisImageSlide,
+                            });
+                        }
+                        util::copy_to_slice(slice, &value, LittleEndian);
+                        if delta == 0 {
+                            break;
+                        }
+                        if delta > page_size - offset_in_page {
+                            errln!("SlideInfoV4::save_rebase_list: offset-in-page out of range");
+                            break;
+                        }
+                        offset_in_page += delta;
+                    }
+                };
+                if ps & (DYLD_CACHE_SLIDE4_PAGE_USE_EXTRA as u16) == 0 {
+                    do_chain((ps as usize) * 4);
+                } else {
+                    let mut extras_idx = (ps & (DYLD_CACHE_SLIDE4_PAGE_INDEX as u16)) as usize;
+                    loop {
+                        let pe = some_or!(page_extras.get(extras_idx), {
+                            errln!("SlideInfoV4::save_rebase_list: page_extras index out of range");
+                            break;
+                        });
+                        let pe = pe.copy(LittleEndian);
+                        do_chain(((pe & (DYLD_CACHE_SLIDE4_PAGE_INDEX as u16)) as usize) * 4);
+                        if pe & (DYLD_CACHE_SLIDE4_PAGE_EXTRA_END as u16) != 0 { break; }
+                        extras_idx += 1;
+                    }
+                }
+            }
+            let _ = lazybox.store(Box::new(rebase_list)); // doesn't matter if this is a dupe
+        }
+        &self.rebase_list_by_slab[slabs]
+    }
+}
+
 trait RangeCast {
     fn range_cast(self) -> Range<usize>;
 }
@@ -374,80 +659,200 @@ pub enum SlideMode {
     PristineToSlid,
 }
 
-impl DyldCache {
-    pub fn new(mc: Mem<u8>, inner_sects: bool, unslide: bool) -> ExecResult<DyldCache> {
+// the parts of a (main or sub-) cache file's header we care about
+#[derive(Clone)]
+struct CacheHeader {
+    arch: arch::Arch,
+    is64: bool,
+    hdr: dyld_cache_header,
+    min_low_offset: usize,
+}
+
+impl CacheHeader {
+    fn parse(mc: &Mem<u8>) -> ExecResult<CacheHeader> {
         // note - not all fields in older caches, but at least a page should be there, so don't worry about size calculation
         let hdr_size = size_of::<dyld_cache_header>();
-        let (arch, end, is64, hdr) = {
-            let buf = mc.get();
-            if buf.len() < hdr_size { return err(BadData, "truncated"); }
-            let top: [u8; 16] = util::copy_from_slice(&buf[..16], LittleEndian);
-            if &top[..7] != b"dyld_v1" {
-                return err(BadData, "bad magic");
-            }
-            let padded_arch = &top[7..16];
-            let (arch, is64) = if padded_arch == b"    i386\0" {
-                (arch::X86, false)
-            } else if padded_arch == b"  x86_64\0" {
-                (arch::X86_64, true)
-            } else if padded_arch == b"   armv7\0" ||
-                      padded_arch == b"   armv6\0" ||
-                      padded_arch == b"  armv7s\0" ||
-                      padded_arch == b"  armv7k\0" {
-                (arch::ARM, false)
-            } else if padded_arch == b"   arm64\0" {
-                (arch::AArch64, true)
-            } else {
-                return err(BadData, "unknown architecture, ergo can't determine endianness");
-            };
-            let end = LittleEndian;
-            let hdr: dyld_cache_header = util::copy_from_slice(&buf[..hdr_size], end);
-            (arch, end, is64, hdr)
+        let buf = mc.get();
+        if buf.len() < hdr_size { return err(BadData, "truncated"); }
+        let top: [u8; 16] = util::copy_from_slice(&buf[..16], LittleEndian);
+        if &top[..7] != b"dyld_v1" {
+            return err(BadData, "bad magic");
+        }
+        let padded_arch = &top[7..16];
+        let (arch, is64) = if padded_arch == b"    i386\0" {
+            (arch::X86, false)
+        } else if padded_arch == b"  x86_64\0" ||
+                  padded_arch == b" x86_64h\0" {
+            (arch::X86_64, true)
+        } else if padded_arch == b"   armv7\0" ||
+                  padded_arch == b"   armv6\0" ||
+                  padded_arch == b"  armv7s\0" ||
+                  padded_arch == b"  armv7k\0" {
+            (arch::ARM, false)
+        } else if padded_arch == b"   arm64\0" ||
+                  padded_arch == b"  arm64e\0" {
+            (arch::AArch64, true)
+        } else if padded_arch == b"arm64_32\0" {
+            (arch::AArch64, false)
+        } else {
+            return err(BadData, "unknown architecture, ergo can't determine endianness");
         };
-        let min_low_offset = min(min(hdr.mappingOffset, hdr.imagesOffset) as u64, hdr.slideInfoOffset) as usize;
+        let hdr: dyld_cache_header = util::copy_from_slice(&buf[..hdr_size], LittleEndian);
+        // newer caches leave the old imagesOffset and slideInfoOffset zero
+        let min_low_offset = [hdr.mappingOffset as u64, hdr.imagesOffsetOld as u64, hdr.slideInfoOffset]
+            .iter().cloned().filter(|&off| off != 0).min().unwrap_or(0) as usize;
+        Ok(CacheHeader { arch: arch, is64: is64, hdr: hdr, min_low_offset: min_low_offset })
+    }
+    // whether the header extends to field_end, i.e. covers every field before it
+    fn has(&self, field_end: usize) -> bool {
+        self.min_low_offset >= field_end
+    }
+    // (suffix, uuid) for each sub-cache file that goes with this main cache
+    fn subcaches(&self, mc: &Mem<u8>) -> Vec<(String, [u8; 16])> {
+        let hdr = &self.hdr;
+        if !has_field!(self, subCacheArrayCount) {
+            return Vec::new();
+        }
+        if has_field!(self, cacheSubType) {
+            let so = size_of::<dyld_subcache_entry>();
+            let entries = file_array(mc, "sub-cache entries", hdr.subCacheArrayOffset.ext(), hdr.subCacheArrayCount.ext(), so);
+            entries.get().chunks(so).map(|buf| {
+                let entry: dyld_subcache_entry = util::copy_from_slice(buf, LittleEndian);
+                (util::from_cstr(&entry.fileSuffix as &[i8]).lossy().into_owned(), entry.uuid)
+            }).collect()
+        } else {
+            // the first version just numbered them
+            let so = size_of::<dyld_subcache_entry_v1>();
+            let entries = file_array(mc, "sub-cache entries", hdr.subCacheArrayOffset.ext(), hdr.subCacheArrayCount.ext(), so);
+            entries.get().chunks(so).enumerate().map(|(i, buf)| {
+                let entry: dyld_subcache_entry_v1 = util::copy_from_slice(buf, LittleEndian);
+                (format!(".{}", i + 1), entry.uuid)
+            }).collect()
+        }
+    }
+}
+
+fn read_local_symbols(mc: &Mem<u8>, ch: &CacheHeader) -> Option<LocalSymbols> {
+    let hdr = &ch.hdr;
+    let end = LittleEndian;
+    let ls_mc = file_array(mc, "slide info blob", hdr.localSymbolsOffset, hdr.localSymbolsSize, 1);
+    let so = size_of::<dyld_cache_local_symbols_info>() as u64;
+    if hdr.localSymbolsSize < so {
+        if hdr.localSymbolsSize > 0 {
+            errln!("local symbols blob too small for header");
+        }
+        return None;
+    }
+    let ls_hdr: dyld_cache_local_symbols_info = util::copy_from_slice(&ls_mc.get()[..so as usize], end);
+    let nlist_size = if ch.is64 {
+        size_of::<macho_bind::nlist_64>()
+    } else {
+        size_of::<macho_bind::nlist>()
+    } as usize;
+    let symtab = file_array(&ls_mc, "dyld cache local symbols - nlist", ls_hdr.nlistOffset.ext(), ls_hdr.nlistCount.ext(), nlist_size);
+    let strtab = file_array(&ls_mc, "dyld cache local symbols - strtab", ls_hdr.stringsOffset.ext(), ls_hdr.stringsSize.ext(), 1);
+    // same cutoff dyld uses
+    let entries_64 = has_field!(ch, symbolFileUUID);
+    let entry_size = if entries_64 {
+        size_of::<dyld_cache_local_symbols_entry_64>()
+    } else {
+        size_of::<dyld_cache_local_symbols_entry>()
+    };
+    let entries = file_array(&ls_mc, "dyld cache local symbols - entries", ls_hdr.entriesOffset.ext(), ls_hdr.entriesCount.ext(), entry_size);
+    Some(LocalSymbols {
+        entries: entries,
+        entries_64: entries_64,
+        symtab: symtab,
+        strtab: strtab,
+        nlist_count: ls_hdr.nlistCount,
+    })
+}
+
+fn open_mmap(path: &Path) -> ExecResult<(File, Mem<u8>)> {
+    let fil = try!(File::open(path).map_err(|e| err_only(ErrorKind::Other, format!("open {:?} failed: {}", path, e))));
+    let mc = try!(util::memmap(&fil).map_err(|e| err_only(ErrorKind::Other, format!("mmap {:?} failed: {}", path, e))));
+    Ok((fil, mc))
+}
+
+impl DyldCache {
+    pub fn new(mc: Mem<u8>, inner_sects: bool, unslide: bool) -> ExecResult<DyldCache> {
+        DyldCache::new_split(mc, &[0], None, inner_sects, unslide)
+    }
+    // Open a cache by path, along with any sub-caches and .symbols file next to it.
+    pub fn open(path: &Path, inner_sects: bool, unslide: bool) -> ExecResult<DyldCache> {
+        let (main_fil, main_mc) = try!(open_mmap(path));
+        let main = try!(CacheHeader::parse(&main_mc));
+        let subcaches = main.subcaches(&main_mc);
+        let with_suffix = |suffix: &str| {
+            let mut p = path.as_os_str().to_owned();
+            p.push(suffix);
+            PathBuf::from(p)
+        };
+        let symbols = if has_field!(main, symbolFileUUID) &&
+                         main.hdr.symbolFileUUID != [0; 16] {
+            match open_mmap(&with_suffix(".symbols")) {
+                Ok((_, sym_mc)) => Some(sym_mc),
+                Err(e) => {
+                    errln!("warning: no local symbols: {}", e.message);
+                    None
+                },
+            }
+        } else { None };
+        if subcaches.is_empty() {
+            return DyldCache::new_split(main_mc, &[0], symbols, inner_sects, unslide);
+        }
+        drop(main_mc);
+        let mut fils = vec![main_fil];
+        for &(ref suffix, _) in &subcaches {
+            let sub_path = with_suffix(&suffix[..]);
+            fils.push(try!(File::open(&sub_path).map_err(|e| err_only(ErrorKind::Other, format!("open sub-cache {:?} failed: {}", sub_path, e)))));
+        }
+        let fil_refs: Vec<&File> = fils.iter().collect();
+        let (mc, offsets) = try!(util::memmap_concat(&fil_refs[..], SUBCACHE_ALIGN).map_err(|e| err_only(ErrorKind::Other, format!("mmap sub-caches failed: {}", e))));
+        for (&(ref suffix, ref uuid), &off) in subcaches.iter().zip(&offsets[1..]) {
+            // new_split will complain if it's outright broken
+            if let Ok(sub) = CacheHeader::parse(&mc.slice(off, mc.len()).unwrap()) {
+                if &sub.hdr.uuid != uuid {
+                    errln!("warning: sub-cache {} has the wrong UUID", suffix);
+                }
+            }
+        }
+        DyldCache::new_split(mc, &offsets[..], symbols, inner_sects, unslide)
+    }
+    // mc is the main cache followed by its sub-caches, starting at subcache_offsets (as
+    // util::memmap_concat gives); symbols is the separate .symbols file, if any.
+    pub fn new_split(mc: Mem<u8>, subcache_offsets: &[usize], symbols: Option<Mem<u8>>, inner_sects: bool, unslide: bool) -> ExecResult<DyldCache> {
+        let main = try!(CacheHeader::parse(&mc));
+        let (arch, is64, end) = (main.arch, main.is64, LittleEndian);
+        let min_low_offset = main.min_low_offset;
+        let hdr = &main.hdr;
         let cs_blob = if min_low_offset >= offset_of!(dyld_cache_header, codeSignatureSize) {
             Some(file_array(&mc, "code signature", hdr.codeSignatureOffset, hdr.codeSignatureSize, 1))
         } else { None };
-        let slide_info_blob = if min_low_offset >= offset_of!(dyld_cache_header, slideInfoSize) {
-            Some(file_array(&mc, "slide info", hdr.slideInfoOffset, hdr.slideInfoSize, 1))
-        } else { None };
         // TODO these checks should become bypassable
-        let local_symbols = if min_low_offset >= offset_of!(dyld_cache_header, localSymbolsSize) {
-            let ls_mc = file_array(&mc, "slide info blob", hdr.localSymbolsOffset, hdr.localSymbolsSize, 1);
-            let so = size_of::<dyld_cache_local_symbols_info>() as u64;
-            if hdr.localSymbolsSize < so {
-                if hdr.localSymbolsSize > 0 {
-                    errln!("local symbols blob too small for header");
-                }
-                None
-            } else {
-                let ls_hdr: dyld_cache_local_symbols_info = util::copy_from_slice(&ls_mc.get()[..so as usize], end);
-                let nlist_size = if is64 {
-                    size_of::<macho_bind::nlist_64>()
-                } else {
-                    size_of::<macho_bind::nlist>()
-                } as usize;
-                let symtab = file_array(&ls_mc, "dyld cache local symbols - nlist", ls_hdr.nlistOffset.ext(), ls_hdr.nlistCount.ext(), nlist_size);
-                let strtab = file_array(&ls_mc, "dyld cache local symbols - strtab", ls_hdr.stringsOffset.ext(), ls_hdr.stringsSize.ext(), 1);
-                let entry_size = size_of::<dyld_cache_local_symbols_entry>();
-                let entries = file_array(&ls_mc, "dyld cache local symbols - entries", ls_hdr.entriesOffset.ext(), ls_hdr.entriesCount.ext(), entry_size);
-                Some(LocalSymbols {
-                    entries: entries,
-                    symtab: symtab,
-                    strtab: strtab,
-                    nlist_count: ls_hdr.nlistCount,
-                })
+        let local_symbols = if let Some(ref sym_mc) = symbols {
+            let sym = try!(CacheHeader::parse(sym_mc));
+            if sym.hdr.uuid != hdr.symbolFileUUID {
+                errln!("warning: .symbols file has the wrong UUID");
             }
+            read_local_symbols(sym_mc, &sym)
+        } else if min_low_offset >= offset_of!(dyld_cache_header, localSymbolsSize) {
+            read_local_symbols(&mc, &main)
         } else { None };
         let uuid = if min_low_offset >= offset_of!(dyld_cache_header, cacheType) {
             Some(hdr.uuid)
         } else { None };
         // we don't actually care about the data
-        let have_images_text_offset = min_low_offset >= size_of::<dyld_cache_header>();
+        let have_images_text_offset = has_field!(main, imagesTextCount);
 
         let image_info = {
+            let (images_offset, images_count) = if hdr.imagesOffsetOld == 0 && has_field!(main, imagesCount) {
+                (hdr.imagesOffset, hdr.imagesCount)
+            } else {
+                (hdr.imagesOffsetOld, hdr.imagesCountOld)
+            };
             let so = size_of::<dyld_cache_image_info>();
-            let hdrmc = file_array(&mc, "images info", hdr.imagesOffset.ext(), hdr.imagesCount.ext(), so);
+            let hdrmc = file_array(&mc, "images info", images_offset.ext(), images_count.ext(), so);
             let hdrbuf = hdrmc.get();
             let buf = mc.get();
             hdrbuf.chunks(so).map(|ii_buf| {
@@ -460,34 +865,60 @@ impl DyldCache {
                 }
             }).collect()
         };
-        let segments: Vec<_> = {
+        let mut segments: Vec<Segment> = Vec::new();
+        // (segment index, blob)
+        let mut slide_info_blobs: Vec<(usize, Mem<u8>)> = Vec::new();
+        for (i, &base) in subcache_offsets.iter().enumerate() {
+            let sub_end = subcache_offsets.get(i + 1).cloned().unwrap_or(mc.len());
+            let sub_mc = some_or!(mc.slice(base, sub_end), {
+                return err(BadData, "sub-cache offset out of range");
+            });
+            let sub = if i == 0 { main.clone() } else { try!(CacheHeader::parse(&sub_mc)) };
+            if sub.arch != arch || sub.is64 != is64 {
+                errln!("warning: sub-cache {} has a different architecture from the main cache", i);
+            }
+            let seg_base = segments.len();
             let so = size_of::<dyld_cache_mapping_info>();
-            let mapping_mc = file_array(&mc, "mapping info", hdr.mappingOffset.ext(), hdr.mappingCount.ext(), so);
-            let len = mc.len() as u64;
-            mapping_mc.get().chunks(so).enumerate().map(|(i, mi_buf)| {
+            let mapping_mc = file_array(&sub_mc, "mapping info", sub.hdr.mappingOffset.ext(), sub.hdr.mappingCount.ext(), so);
+            let len = (sub_end - base) as u64;
+            segments.extend(mapping_mc.get().chunks(so).enumerate().map(|(j, mi_buf)| {
                 let mut mi: dyld_cache_mapping_info = util::copy_from_slice(mi_buf, end);
                 if mi.fileOffset >= len {
-                    errln!("warning: mapping_info {} in shared cache offset ({}) past end of file ({})", i, mi.fileOffset, len);
+                    errln!("warning: mapping_info {} in shared cache offset ({}) past end of file ({})", j, mi.fileOffset, len);
                     mi.size = 0;
                     mi.fileOffset = 0;
                 } else if mi.size > len - mi.fileOffset {
-                    errln!("warning: mapping_info {} in shared cache bounds ({}+{}) extend past end of file ({}); truncating", i, mi.fileOffset, mi.size, len);
+                    errln!("warning: mapping_info {} in shared cache bounds ({}+{}) extend past end of file ({}); truncating", j, mi.fileOffset, mi.size, len);
                     mi.size = len - mi.fileOffset;
                 }
+                let fileoff = base as u64 + mi.fileOffset;
 
                 Segment {
                     vmaddr: VMA(mi.address),
                     vmsize: mi.size,
-                    fileoff: mi.fileOffset,
+                    fileoff: fileoff,
                     filesize: mi.size,
                     name: None,
                     prot: ::u32_to_prot(mi.initProt),
-                    data: mc.slice(mi.fileOffset as usize, (mi.fileOffset + mi.size) as usize),
+                    data: mc.slice(fileoff as usize, (fileoff + mi.size) as usize),
                     seg_idx: None,
-                    private: hdr.mappingOffset as usize + i * so,
+                    private: base + sub.hdr.mappingOffset as usize + j * so,
                 }
-            }).collect()
-        };
+            }));
+            if has_field!(sub, mappingWithSlideCount) && sub.hdr.mappingWithSlideCount != 0 {
+                // one slide info per mapping
+                let so = size_of::<dyld_cache_mapping_and_slide_info>();
+                let mws_mc = file_array(&sub_mc, "mapping with slide info", sub.hdr.mappingWithSlideOffset.ext(), sub.hdr.mappingWithSlideCount.ext(), so);
+                for (j, mws_buf) in mws_mc.get().chunks(so).enumerate() {
+                    let mws: dyld_cache_mapping_and_slide_info = util::copy_from_slice(mws_buf, end);
+                    if mws.slideInfoFileSize != 0 && seg_base + j < segments.len() {
+                        slide_info_blobs.push((seg_base + j, file_array(&sub_mc, "slide info", mws.slideInfoFileOffset, mws.slideInfoFileSize, 1)));
+                    }
+                }
+            } else if i == 0 && min_low_offset >= offset_of!(dyld_cache_header, slideInfoSize) {
+                slide_info_blobs.push((1, file_array(&mc, "slide info", hdr.slideInfoOffset, hdr.slideInfoSize, 1)));
+            }
+        }
         let mut dc = DyldCache {
             eb: ExecBase {
                 arch: arch,
@@ -497,16 +928,21 @@ impl DyldCache {
                 sections: vec!(),
                 whole_buf: Some(mc),
             },
-            slide_info: None,
+            slide_info: Vec::new(),
             image_info: image_info,
             uuid: uuid,
             cs_blob: cs_blob,
             local_symbols: local_symbols,
             have_images_text_offset: have_images_text_offset,
+            is_split: subcache_offsets.len() > 1,
+            objc_relative_selector_base: None,
         };
-        if let Some(blob) = slide_info_blob {
-            match dc.make_slide_info(blob) {
-                Ok(x) => dc.slide_info = x,
+        if has_field!(main, objcOptsSize) && hdr.objcOptsOffset != 0 {
+            dc.objc_relative_selector_base = dc.read_objc_relative_selector_base(hdr.objcOptsOffset);
+        }
+        for (seg_idx, blob) in slide_info_blobs {
+            match dc.make_slide_info(seg_idx, blob) {
+                Ok(x) => dc.slide_info.push(x),
                 Err(e) => errln!("couldn't get slide info: {}", e),
             }
         }
@@ -530,18 +966,30 @@ impl DyldCache {
         }
         Ok(dc)
     }
+    // off is the file offset of the image's header, or for newer caches (including anything with
+    // a .symbols file), its address relative to the start of the cache
     pub fn get_ls_entry_for_offset(&self, off: u64) -> Option<::DscTabs> {
-        let entry_size = size_of::<dyld_cache_local_symbols_entry>();
         if let Some(ref ls) = self.local_symbols {
+            let entry_size = if ls.entries_64 {
+                size_of::<dyld_cache_local_symbols_entry_64>()
+            } else {
+                size_of::<dyld_cache_local_symbols_entry>()
+            };
             let entries = ls.entries.get();
             for entry_slice in entries.chunks(entry_size) {
-                let entry: dyld_cache_local_symbols_entry = util::copy_from_slice(entry_slice, self.eb.endian);
-                if entry.dylibOffset as u64 == off {
-                    if entry.nlistStartIndex > ls.nlist_count || entry.nlistCount > ls.nlist_count - entry.nlistStartIndex {
+                let (dylib_offset, nlist_start_index, nlist_count) = if ls.entries_64 {
+                    let entry: dyld_cache_local_symbols_entry_64 = util::copy_from_slice(entry_slice, self.eb.endian);
+                    (entry.dylibOffset, entry.nlistStartIndex, entry.nlistCount)
+                } else {
+                    let entry: dyld_cache_local_symbols_entry = util::copy_from_slice(entry_slice, self.eb.endian);
+                    (entry.dylibOffset as u64, entry.nlistStartIndex, entry.nlistCount)
+                };
+                if dylib_offset == off {
+                    if nlist_start_index > ls.nlist_count || nlist_count > ls.nlist_count - nlist_start_index {
                         errln!("warning: shared cache local symbols entry out of range");
                         return None;
                     } else {
-                        return Some(::DscTabs { symtab: ls.symtab.clone(), strtab: ls.strtab.clone(), start: entry.nlistStartIndex, count: entry.nlistCount });
+                        return Some(::DscTabs { symtab: ls.symtab.clone(), strtab: ls.strtab.clone(), start: nlist_start_index, count: nlist_count });
                     }
                 }
             }
@@ -557,8 +1005,15 @@ impl DyldCache {
         let mut mo = try!(MachO::new(buf, true, Some(MachODCInfo {
             hdr_offset: off as usize,
             have_images_text_offset: self.have_images_text_offset,
+            cache_segments: Some(Arc::new(self.eb.segments.clone())),
+            is_split: self.is_split,
+            objc_relative_selector_base: self.objc_relative_selector_base,
         })));
-        mo.dsc_tabs = self.get_ls_entry_for_offset(off);
+        let ls_off = match self.local_symbols {
+            Some(ref ls) if ls.entries_64 => VMA(ii.address).wrapping_sub(self.eb.segments[0].vmaddr),
+            _ => off,
+        };
+        mo.dsc_tabs = self.get_ls_entry_for_offset(ls_off);
         if fix_data {
             let _sw2 = util::stopwatch("DyldCache::load_single_image fix_data");
             for seg in &mo.eb.segments {
@@ -567,12 +1022,37 @@ impl DyldCache {
         }
         Ok(mo)
     }
-    fn make_slide_info(&self, blob: Mem<u8>) -> ExecResult<Option<SlideInfo>> {
-        let data_seg = some_or!(self.eb.segments.get(1), {
+    // ObjCOptimizationHeader: version, flags, then six u64 offsets from the cache base, the last
+    // being relativeMethodSelectorBaseAddressOffset.  (Older caches keep this in libobjc's
+    // __objc_opt_ro instead; not handled.)
+    fn read_objc_relative_selector_base(&self, opts_offset: u64) -> Option<VMA> {
+        let base = some_or!(self.eb.segments.first(), { return None; }).vmaddr;
+        let buf = some_or!(self.eb.get_sane(base.wrapping_add(opts_offset), 56), {
+            errln!("warning: couldn't read ObjC optimization header");
+            return None;
+        });
+        let offset: u64 = util::copy_from_slice(&buf[48..56], self.eb.endian);
+        if offset == 0 { None } else { Some(base.wrapping_add(offset)) }
+    }
+    fn make_slide_info(&self, seg_idx: usize, blob: Mem<u8>) -> ExecResult<SlideInfo> {
+        let data_seg = some_or!(self.eb.segments.get(seg_idx), {
             return err(BadData, "no data segment");
         });
         let (data_addr, data_size) = (data_seg.vmaddr, data_seg.vmsize);
-        Ok(Some(try!(SlideInfo::new(blob, self.eb.endian, self.eb.pointer_size == 8, data_addr, data_size))))
+        SlideInfo::new(blob, self.eb.endian, self.eb.pointer_size == 8, seg_idx, data_addr, data_size)
+    }
+    // the slide info covering addr, if any
+    pub fn slide_info_for_addr(&self, addr: VMA) -> Option<&SlideInfo> {
+        self.slide_info.iter().find(|si| {
+            let seg = &self.eb.segments[si.data_seg_idx()];
+            addr.wrapping_sub(seg.vmaddr) < seg.vmsize
+        })
+    }
+    // every rebase location in range, across all the slide infos
+    pub fn iter_rebases<F>(&self, range: Option<(VMA, u64)>, mut func: F) where F: FnMut(VMA) {
+        for si in &self.slide_info {
+            si.iter(&self.eb, range, &mut func);
+        }
     }
     pub fn auto_unslide(&mut self) {
         let slide = {
@@ -615,18 +1095,15 @@ impl DyldCache {
         }
     }
     pub fn fix_data(&self, range: Option<(VMA, u64)>) -> ExecResult<()> {
-        match self.slide_info {
-            Some(SlideInfo::V2(ref v2)) => {
-                let _ = v2.save_rebase_list(&self.eb, range);
-            },
-            _ => (),
+        for si in &self.slide_info {
+            let _ = si.save_rebase_list(&self.eb, range);
         }
         Ok(())
     }
 
     pub fn unslide_v1(&mut self, slide: u32) -> ExecResult<()> {
-        let v1 = match self.slide_info {
-            Some(SlideInfo::V1(ref v1)) => v1,
+        let v1 = match self.slide_info.first() {
+            Some(&SlideInfo::V1(ref v1)) => v1,
             _ => panic!("unslide_v1: not v1"),
         };
         if self.eb.endian != LittleEndian {
@@ -688,22 +1165,22 @@ impl Exec for DyldCache {
     }
     fn get_reloc_list<'a>(&'a self, specific: Option<&'a Any>) -> Vec<Reloc<'a>> {
         assert!(specific.is_none());
-        match self.slide_info {
-            Some(SlideInfo::V1(ref v1)) => {
-                let mut ret = Vec::new();
-                v1.iter(None, |addr| {
-                    ret.push(Reloc { address: addr, kind: RelocKind::_32Bit, base: None, target: RelocTarget::ThisImageSlide });
-                });
-                ret
-            },
-            Some(SlideInfo::V2(ref v2)) => {
-                v2.save_rebase_list(&self.eb, None).iter().flat_map(|slab| {
-                    let relocs = slab.get().unwrap();
-                    relocs.iter()
-                }).cloned().collect()
-            },
-            None => Vec::new(),
+        let mut ret = Vec::new();
+        for si in &self.slide_info {
+            match si {
+                &SlideInfo::V1(ref v1) => {
+                    v1.iter(None, |addr| {
+                        ret.push(Reloc { address: addr, kind: RelocKind::_32Bit, base: None, target: RelocTarget::ThisImageSlide });
+                    });
+                },
+                _ => {
+                    for slab in si.save_rebase_list(&self.eb, None).unwrap() {
+                        ret.extend(slab.get().unwrap().iter().cloned());
+                    }
+                },
+            }
         }
+        ret
     }
 
     fn as_any(&self) -> &Any { self as &Any }
@@ -855,3 +1332,35 @@ impl ImageCache {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_magic(magic: &[u8; 16]) -> ExecResult<CacheHeader> {
+        let mut buf = vec![0u8; size_of::<dyld_cache_header>()];
+        buf[..16].copy_from_slice(&magic[..]);
+        CacheHeader::parse(&Mem::with_vec(buf))
+    }
+
+    #[test]
+    fn test_header_archs() {
+        let cases: [(&[u8; 16], arch::Arch, bool); 10] = [
+            (b"dyld_v1    i386\0", arch::X86, false),
+            (b"dyld_v1  x86_64\0", arch::X86_64, true),
+            (b"dyld_v1 x86_64h\0", arch::X86_64, true),
+            (b"dyld_v1   armv7\0", arch::ARM, false),
+            (b"dyld_v1   armv6\0", arch::ARM, false),
+            (b"dyld_v1  armv7s\0", arch::ARM, false),
+            (b"dyld_v1  armv7k\0", arch::ARM, false),
+            (b"dyld_v1   arm64\0", arch::AArch64, true),
+            (b"dyld_v1  arm64e\0", arch::AArch64, true),
+            (b"dyld_v1arm64_32\0", arch::AArch64, false),
+        ];
+        for &(magic, arch, is64) in &cases {
+            let ch = parse_magic(magic).unwrap_or_else(|e| panic!("{:?}: {}", &magic[..], e.message));
+            assert_eq!((ch.arch, ch.is64), (arch, is64));
+        }
+        assert!(parse_magic(b"dyld_v1   sparc\0").is_err());
+        assert!(parse_magic(b"dyld_v2   arm64\0").is_err());
+    }
+}
@@ -0,0 +1,427 @@
+// Typed views of the raw load commands kept in MachO::load_commands.  This is just for looking at
+// them; parse_load_commands is still what fills in segments, linkedit data and so on.
+use std::mem::size_of;
+use std::fmt;
+use util;
+use util::{ByteStr, ByteString, Endian, ReadCell, Swap, SliceExt};
+use macho_bind::*;
+use {MachO, PackedVersion};
+
+// the loader.h we bind has LC_BUILD_VERSION, but not build_version_command or these
+pub const PLATFORM_MACOS: u32 = 1;
+pub const PLATFORM_IOS: u32 = 2;
+pub const PLATFORM_TVOS: u32 = 3;
+pub const PLATFORM_WATCHOS: u32 = 4;
+pub const PLATFORM_BRIDGEOS: u32 = 5;
+pub const PLATFORM_MACCATALYST: u32 = 6;
+pub const PLATFORM_IOSSIMULATOR: u32 = 7;
+pub const PLATFORM_TVOSSIMULATOR: u32 = 8;
+pub const PLATFORM_WATCHOSSIMULATOR: u32 = 9;
+pub const PLATFORM_DRIVERKIT: u32 = 10;
+pub const PLATFORM_VISIONOS: u32 = 11;
+pub const PLATFORM_VISIONOSSIMULATOR: u32 = 12;
+
+pub const TOOL_CLANG: u32 = 1;
+pub const TOOL_SWIFT: u32 = 2;
+pub const TOOL_LD: u32 = 3;
+pub const TOOL_LLD: u32 = 4;
+
+pub fn platform_name(platform: u32) -> Option<&'static str> {
+    Some(match platform {
+        PLATFORM_MACOS => "macos",
+        PLATFORM_IOS => "ios",
+        PLATFORM_TVOS => "tvos",
+        PLATFORM_WATCHOS => "watchos",
+        PLATFORM_BRIDGEOS => "bridgeos",
+        PLATFORM_MACCATALYST => "maccatalyst",
+        PLATFORM_IOSSIMULATOR => "iossimulator",
+        PLATFORM_TVOSSIMULATOR => "tvossimulator",
+        PLATFORM_WATCHOSSIMULATOR => "watchossimulator",
+        PLATFORM_DRIVERKIT => "driverkit",
+        PLATFORM_VISIONOS => "visionos",
+        PLATFORM_VISIONOSSIMULATOR => "visionossimulator",
+        _ => return None,
+    })
+}
+
+pub fn tool_name(tool: u32) -> Option<&'static str> {
+    Some(match tool {
+        TOOL_CLANG => "clang",
+        TOOL_SWIFT => "swift",
+        TOOL_LD => "ld",
+        TOOL_LLD => "lld",
+        _ => return None,
+    })
+}
+
+pub fn lc_name(cmd: u32) -> Option<&'static str> {
+    Some(match cmd {
+        LC_SEGMENT => "LC_SEGMENT",
+        LC_SYMTAB => "LC_SYMTAB",
+        LC_SYMSEG => "LC_SYMSEG",
+        LC_THREAD => "LC_THREAD",
+        LC_UNIXTHREAD => "LC_UNIXTHREAD",
+        LC_LOADFVMLIB => "LC_LOADFVMLIB",
+        LC_IDFVMLIB => "LC_IDFVMLIB",
+        LC_IDENT => "LC_IDENT",
+        LC_FVMFILE => "LC_FVMFILE",
+        LC_PREPAGE => "LC_PREPAGE",
+        LC_DYSYMTAB => "LC_DYSYMTAB",
+        LC_LOAD_DYLIB => "LC_LOAD_DYLIB",
+        LC_ID_DYLIB => "LC_ID_DYLIB",
+        LC_LOAD_DYLINKER => "LC_LOAD_DYLINKER",
+        LC_ID_DYLINKER => "LC_ID_DYLINKER",
+        LC_PREBOUND_DYLIB => "LC_PREBOUND_DYLIB",
+        LC_ROUTINES => "LC_ROUTINES",
+        LC_SUB_FRAMEWORK => "LC_SUB_FRAMEWORK",
+        LC_SUB_UMBRELLA => "LC_SUB_UMBRELLA",
+        LC_SUB_CLIENT => "LC_SUB_CLIENT",
+        LC_SUB_LIBRARY => "LC_SUB_LIBRARY",
+        LC_TWOLEVEL_HINTS => "LC_TWOLEVEL_HINTS",
+        LC_PREBIND_CKSUM => "LC_PREBIND_CKSUM",
+        LC_LOAD_WEAK_DYLIB => "LC_LOAD_WEAK_DYLIB",
+        LC_SEGMENT_64 => "LC_SEGMENT_64",
+        LC_ROUTINES_64 => "LC_ROUTINES_64",
+        LC_UUID => "LC_UUID",
+        LC_RPATH => "LC_RPATH",
+        LC_CODE_SIGNATURE => "LC_CODE_SIGNATURE",
+        LC_SEGMENT_SPLIT_INFO => "LC_SEGMENT_SPLIT_INFO",
+        LC_REEXPORT_DYLIB => "LC_REEXPORT_DYLIB",
+        LC_LAZY_LOAD_DYLIB => "LC_LAZY_LOAD_DYLIB",
+        LC_ENCRYPTION_INFO => "LC_ENCRYPTION_INFO",
+        LC_DYLD_INFO => "LC_DYLD_INFO",
+        LC_DYLD_INFO_ONLY => "LC_DYLD_INFO_ONLY",
+        LC_LOAD_UPWARD_DYLIB => "LC_LOAD_UPWARD_DYLIB",
+        LC_VERSION_MIN_MACOSX => "LC_VERSION_MIN_MACOSX",
+        LC_VERSION_MIN_IPHONEOS => "LC_VERSION_MIN_IPHONEOS",
+        LC_FUNCTION_STARTS => "LC_FUNCTION_STARTS",
+        LC_DYLD_ENVIRONMENT => "LC_DYLD_ENVIRONMENT",
+        LC_MAIN => "LC_MAIN",
+        LC_DATA_IN_CODE => "LC_DATA_IN_CODE",
+        LC_SOURCE_VERSION => "LC_SOURCE_VERSION",
+        LC_DYLIB_CODE_SIGN_DRS => "LC_DYLIB_CODE_SIGN_DRS",
+        LC_ENCRYPTION_INFO_64 => "LC_ENCRYPTION_INFO_64",
+        LC_LINKER_OPTION => "LC_LINKER_OPTION",
+        LC_LINKER_OPTIMIZATION_HINT => "LC_LINKER_OPTIMIZATION_HINT",
+        LC_VERSION_MIN_TVOS => "LC_VERSION_MIN_TVOS",
+        LC_VERSION_MIN_WATCHOS => "LC_VERSION_MIN_WATCHOS",
+        LC_NOTE => "LC_NOTE",
+        LC_BUILD_VERSION => "LC_BUILD_VERSION",
+        LC_DYLD_EXPORTS_TRIE => "LC_DYLD_EXPORTS_TRIE",
+        LC_DYLD_CHAINED_FIXUPS => "LC_DYLD_CHAINED_FIXUPS",
+        _ => return None,
+    })
+}
+
+// a.b.c.d.e packed as a24.b10.c10.d10.e10
+#[derive(Clone, Copy)]
+pub struct SourceVersion(pub u64);
+impl fmt::Display for SourceVersion {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let v = self.0;
+        write!(fmt, "{}.{}.{}.{}.{}", v >> 40, (v >> 30) & 0x3ff, (v >> 20) & 0x3ff, (v >> 10) & 0x3ff, v & 0x3ff)
+    }
+}
+
+#[derive(Clone)]
+pub struct SectionInfo {
+    pub sectname: ByteString,
+    pub segname: ByteString,
+    pub addr: u64,
+    pub size: u64,
+    pub offset: u32,
+    pub align: u32,
+    pub reloff: u32,
+    pub nreloc: u32,
+    pub flags: u32,
+    pub reserved1: u32,
+    pub reserved2: u32,
+}
+
+#[derive(Clone)]
+pub struct SegmentInfo {
+    pub is64: bool,
+    pub segname: ByteString,
+    pub vmaddr: u64,
+    pub vmsize: u64,
+    pub fileoff: u64,
+    pub filesize: u64,
+    pub maxprot: u32,
+    pub initprot: u32,
+    pub flags: u32,
+    pub sections: Vec<SectionInfo>,
+}
+
+#[derive(Clone)]
+pub struct BuildVersion {
+    pub platform: u32,
+    pub minos: PackedVersion,
+    pub sdk: PackedVersion,
+    pub tools: Vec<(u32, PackedVersion)>,
+}
+
+#[derive(Clone, Copy)]
+pub struct VersionMin {
+    pub cmd: u32, // LC_VERSION_MIN_*
+    pub version: PackedVersion,
+    pub sdk: PackedVersion,
+}
+
+#[derive(Clone, Copy)]
+pub struct EntryPoint {
+    pub entryoff: u64,
+    pub stacksize: u64,
+}
+
+#[derive(Clone, Copy)]
+pub struct EncryptionInfo {
+    pub is64: bool,
+    pub cryptoff: u32,
+    pub cryptsize: u32,
+    pub cryptid: u32,
+}
+
+#[derive(Clone)]
+pub enum LoadCommand<'a> {
+    Segment(SegmentInfo),
+    Symtab(symtab_command),
+    Dysymtab(dysymtab_command),
+    DyldInfo(dyld_info_command),
+    // LC_CODE_SIGNATURE, LC_FUNCTION_STARTS and the like
+    LinkeditData(linkedit_data_command),
+    // LC_ID_DYLIB, LC_LOAD_DYLIB and variants
+    Dylib { cmd: u32, name: &'a ByteStr, timestamp: u32, current_version: PackedVersion, compatibility_version: PackedVersion },
+    // LC_LOAD_DYLINKER, LC_ID_DYLINKER
+    Dylinker { cmd: u32, name: &'a ByteStr },
+    DyldEnvironment(&'a ByteStr),
+    Rpath(&'a ByteStr),
+    Uuid([u8; 16]),
+    BuildVersion(BuildVersion),
+    VersionMin(VersionMin),
+    Main(EntryPoint),
+    SourceVersion(SourceVersion),
+    EncryptionInfo(EncryptionInfo),
+    // anything else, or anything too short to be what it claims
+    Other { cmd: u32, data: &'a [ReadCell<u8>] },
+}
+
+impl<'a> LoadCommand<'a> {
+    pub fn cmd(&self) -> u32 {
+        match *self {
+            LoadCommand::Segment(ref seg) => if seg.is64 { LC_SEGMENT_64 } else { LC_SEGMENT },
+            LoadCommand::Symtab(ref c) => c.cmd,
+            LoadCommand::Dysymtab(ref c) => c.cmd,
+            LoadCommand::DyldInfo(ref c) => c.cmd,
+            LoadCommand::LinkeditData(ref c) => c.cmd,
+            LoadCommand::Dylib { cmd, .. } => cmd,
+            LoadCommand::Dylinker { cmd, .. } => cmd,
+            LoadCommand::DyldEnvironment(_) => LC_DYLD_ENVIRONMENT,
+            LoadCommand::Rpath(_) => LC_RPATH,
+            LoadCommand::Uuid(_) => LC_UUID,
+            LoadCommand::BuildVersion(_) => LC_BUILD_VERSION,
+            LoadCommand::VersionMin(ref vm) => vm.cmd,
+            LoadCommand::Main(_) => LC_MAIN,
+            LoadCommand::SourceVersion(_) => LC_SOURCE_VERSION,
+            LoadCommand::EncryptionInfo(ref ei) => if ei.is64 { LC_ENCRYPTION_INFO_64 } else { LC_ENCRYPTION_INFO },
+            LoadCommand::Other { cmd, .. } => cmd,
+        }
+    }
+}
+
+fn read_struct<T: Copy + Swap>(buf: &[ReadCell<u8>], end: Endian) -> Option<T> {
+    Some(util::copy_from_slice(try_opt!(buf.slice_opt(0, size_of::<T>())), end))
+}
+
+fn read_u32(buf: &[ReadCell<u8>], off: usize, end: Endian) -> Option<u32> {
+    Some(util::copy_from_slice(try_opt!(buf.slice_opt(off, off + 4)), end))
+}
+
+// an lc_str: offset from the start of the command, stored at off
+fn read_lc_str<'a>(buf: &'a [ReadCell<u8>], off: usize, end: Endian) -> Option<&'a ByteStr> {
+    let str_off = try_opt!(read_u32(buf, off, end)) as usize;
+    if str_off > buf.len() { return None; }
+    Some(util::from_cstr(&buf[str_off..]))
+}
+
+fn parse_segment(buf: &[ReadCell<u8>], is64: bool, end: Endian) -> Option<SegmentInfo> {
+    branch!(if (is64) {
+        type segment_command_x = segment_command_64;
+        type section_x = section_64;
+    } else {
+        type segment_command_x = segment_command;
+        type section_x = section;
+    } then {
+        let sc: segment_command_x = try_opt!(read_struct(buf, end));
+        let mut sections = Vec::new();
+        let mut off = size_of::<segment_command_x>();
+        for _ in 0..sc.nsects {
+            let s: section_x = try_opt!(read_struct(try_opt!(buf.slice_opt(off, buf.len())), end));
+            sections.push(SectionInfo {
+                sectname: util::from_cstr(&s.sectname as &[i8]).to_owned(),
+                segname: util::from_cstr(&s.segname as &[i8]).to_owned(),
+                addr: s.addr as u64,
+                size: s.size as u64,
+                offset: s.offset,
+                align: s.align,
+                reloff: s.reloff,
+                nreloc: s.nreloc,
+                flags: s.flags,
+                reserved1: s.reserved1,
+                reserved2: s.reserved2,
+            });
+            off += size_of::<section_x>();
+        }
+        Some(SegmentInfo {
+            is64: is64,
+            segname: util::from_cstr(&sc.segname as &[i8]).to_owned(),
+            vmaddr: sc.vmaddr as u64,
+            vmsize: sc.vmsize as u64,
+            fileoff: sc.fileoff as u64,
+            filesize: sc.filesize as u64,
+            maxprot: sc.maxprot as u32,
+            initprot: sc.initprot as u32,
+            flags: sc.flags,
+            sections: sections,
+        })
+    })
+}
+
+fn parse_build_version(buf: &[ReadCell<u8>], end: Endian) -> Option<BuildVersion> {
+    // cmd, cmdsize, platform, minos, sdk, ntools, then (tool, version) pairs
+    let ntools = try_opt!(read_u32(buf, 20, end));
+    let mut tools = Vec::new();
+    for i in 0..ntools as usize {
+        let tool = try_opt!(read_u32(buf, 24 + 8 * i, end));
+        let version = try_opt!(read_u32(buf, 28 + 8 * i, end));
+        tools.push((tool, PackedVersion(version)));
+    }
+    Some(BuildVersion {
+        platform: try_opt!(read_u32(buf, 8, end)),
+        minos: PackedVersion(try_opt!(read_u32(buf, 12, end))),
+        sdk: PackedVersion(try_opt!(read_u32(buf, 16, end))),
+        tools: tools,
+    })
+}
+
+// None if the command is too small or malformed.
+fn try_parse_load_command<'a>(buf: &'a [ReadCell<u8>], end: Endian) -> Option<LoadCommand<'a>> {
+    let cmd = read_u32(buf, 0, end).unwrap_or(0);
+    match cmd {
+        LC_SEGMENT => parse_segment(buf, false, end).map(LoadCommand::Segment),
+        LC_SEGMENT_64 => parse_segment(buf, true, end).map(LoadCommand::Segment),
+        LC_SYMTAB => read_struct(buf, end).map(LoadCommand::Symtab),
+        LC_DYSYMTAB => read_struct(buf, end).map(LoadCommand::Dysymtab),
+        LC_DYLD_INFO | LC_DYLD_INFO_ONLY => read_struct(buf, end).map(LoadCommand::DyldInfo),
+        LC_CODE_SIGNATURE | LC_SEGMENT_SPLIT_INFO | LC_FUNCTION_STARTS | LC_DATA_IN_CODE |
+        LC_DYLIB_CODE_SIGN_DRS | LC_LINKER_OPTIMIZATION_HINT | LC_DYLD_EXPORTS_TRIE |
+        LC_DYLD_CHAINED_FIXUPS => read_struct(buf, end).map(LoadCommand::LinkeditData),
+        LC_ID_DYLIB | LC_LOAD_DYLIB | LC_LOAD_WEAK_DYLIB | LC_REEXPORT_DYLIB |
+        LC_LAZY_LOAD_DYLIB | LC_LOAD_UPWARD_DYLIB => {
+            read_struct::<dylib_command>(buf, end).and_then(|dc| {
+                Some(LoadCommand::Dylib {
+                    cmd: cmd,
+                    name: try_opt!(read_lc_str(buf, 8, end)),
+                    timestamp: dc.dylib.timestamp,
+                    current_version: PackedVersion(dc.dylib.current_version),
+                    compatibility_version: PackedVersion(dc.dylib.compatibility_version),
+                })
+            })
+        },
+        LC_LOAD_DYLINKER | LC_ID_DYLINKER => {
+            read_lc_str(buf, 8, end).map(|name| LoadCommand::Dylinker { cmd: cmd, name: name })
+        },
+        LC_DYLD_ENVIRONMENT => read_lc_str(buf, 8, end).map(LoadCommand::DyldEnvironment),
+        LC_RPATH => read_lc_str(buf, 8, end).map(LoadCommand::Rpath),
+        LC_UUID => read_struct::<uuid_command>(buf, end).map(|uc| LoadCommand::Uuid(uc.uuid)),
+        LC_BUILD_VERSION => parse_build_version(buf, end).map(LoadCommand::BuildVersion),
+        LC_VERSION_MIN_MACOSX | LC_VERSION_MIN_IPHONEOS | LC_VERSION_MIN_TVOS | LC_VERSION_MIN_WATCHOS => {
+            read_struct::<version_min_command>(buf, end).map(|vm| LoadCommand::VersionMin(VersionMin {
+                cmd: cmd,
+                version: PackedVersion(vm.version),
+                sdk: PackedVersion(vm.sdk),
+            }))
+        },
+        LC_MAIN => read_struct::<entry_point_command>(buf, end).map(|ep| LoadCommand::Main(EntryPoint {
+            entryoff: ep.entryoff,
+            stacksize: ep.stacksize,
+        })),
+        LC_SOURCE_VERSION => {
+            read_struct::<source_version_command>(buf, end).map(|sv| LoadCommand::SourceVersion(SourceVersion(sv.version)))
+        },
+        LC_ENCRYPTION_INFO | LC_ENCRYPTION_INFO_64 => {
+            // the 64-bit one just adds padding
+            read_struct::<encryption_info_command>(buf, end).map(|ei| LoadCommand::EncryptionInfo(EncryptionInfo {
+                is64: cmd == LC_ENCRYPTION_INFO_64,
+                cryptoff: ei.cryptoff,
+                cryptsize: ei.cryptsize,
+                cryptid: ei.cryptid,
+            }))
+        },
+        _ => Some(LoadCommand::Other { cmd: cmd, data: buf }),
+    }
+}
+
+pub fn parse_load_command<'a>(buf: &'a [ReadCell<u8>], end: Endian) -> LoadCommand<'a> {
+    try_parse_load_command(buf, end).unwrap_or_else(|| {
+        let cmd = read_u32(buf, 0, end).unwrap_or(0);
+        errln!("warning: {} load command too small or malformed", lc_name(cmd).unwrap_or("?"));
+        LoadCommand::Other { cmd: cmd, data: buf }
+    })
+}
+
+impl MachO {
+    pub fn typed_load_commands(&self) -> Vec<LoadCommand> {
+        self.load_commands.iter().map(|lc| parse_load_command(lc.get(), self.eb.endian)).collect()
+    }
+
+    // Unlike typed_load_commands, these skip malformed commands without complaining, since
+    // they get called over and over.
+    fn filter_load_commands<'a, T, F>(&'a self, f: F) -> Vec<T> where F: FnMut(LoadCommand<'a>) -> Option<T> {
+        let end = self.eb.endian;
+        self.load_commands.iter().filter_map(|lc| try_parse_load_command(lc.get(), end)).filter_map(f).collect()
+    }
+
+    fn find_load_command<'a, T, F>(&'a self, f: F) -> Option<T> where F: FnMut(LoadCommand<'a>) -> Option<T> {
+        let end = self.eb.endian;
+        self.load_commands.iter().filter_map(|lc| try_parse_load_command(lc.get(), end)).filter_map(f).next()
+    }
+
+    pub fn install_name(&self) -> Option<&ByteStr> {
+        self.find_load_command(|lc| match lc {
+            LoadCommand::Dylib { cmd: LC_ID_DYLIB, name, .. } => Some(name),
+            _ => None,
+        })
+    }
+
+    pub fn uuid(&self) -> Option<[u8; 16]> {
+        self.find_load_command(|lc| if let LoadCommand::Uuid(uuid) = lc { Some(uuid) } else { None })
+    }
+
+    pub fn build_version(&self) -> Option<BuildVersion> {
+        self.find_load_command(|lc| if let LoadCommand::BuildVersion(bv) = lc { Some(bv) } else { None })
+    }
+
+    pub fn version_min(&self) -> Option<VersionMin> {
+        self.find_load_command(|lc| if let LoadCommand::VersionMin(vm) = lc { Some(vm) } else { None })
+    }
+
+    pub fn entry_point(&self) -> Option<EntryPoint> {
+        self.find_load_command(|lc| if let LoadCommand::Main(ep) = lc { Some(ep) } else { None })
+    }
+
+    pub fn source_version(&self) -> Option<SourceVersion> {
+        self.find_load_command(|lc| if let LoadCommand::SourceVersion(sv) = lc { Some(sv) } else { None })
+    }
+
+    pub fn encryption_info(&self) -> Option<EncryptionInfo> {
+        self.find_load_command(|lc| if let LoadCommand::EncryptionInfo(ei) = lc { Some(ei) } else { None })
+    }
+
+    pub fn rpaths(&self) -> Vec<&ByteStr> {
+        self.filter_load_commands(|lc| if let LoadCommand::Rpath(path) = lc { Some(path) } else { None })
+    }
+
+    pub fn dyld_environment(&self) -> Vec<&ByteStr> {
+        self.filter_load_commands(|lc| if let LoadCommand::DyldEnvironment(env) = lc { Some(env) } else { None })
+    }
+}
@@ -15,15 +15,23 @@ use std::mem::{replace, size_of, transmute};
 use std::str::FromStr;
 use std::cmp::max;
 use macho_bind::*;
-use exec::{arch, VMA, SymbolValue, ByteSliceIterator, DepLib, SourceLib, ErrorKind, err, SymbolSource, Symbol};
+use exec::{arch, VMA, SymbolValue, ByteSliceIterator, DepLib, SourceLib, ErrorKind, err, SymbolSource, Symbol, Reloc, RelocKind, RelocTarget};
 use std::{u64, u32, usize};
 use vec_map::VecMap;
 use std::collections::{HashSet};
 use std::borrow::Cow;
 use std::any::Any;
-use util::{VecStrExt, Mem, Swap, SliceExt, OptionExt, copy_memory, into_cow, IntStuff, Endian, ByteString, ByteStr, FieldLens, Ext, Narrow, CheckAdd, CheckSub, TrivialState, ReadCell, stopwatch, vec_extend_from_slice, fast_slice_to_owned};
+use std::cell::Cell;
+use std::sync::Arc;
+use util::{VecStrExt, Mem, Lazy, Swap, SliceExt, OptionExt, copy_memory, into_cow, IntStuff, Endian, ByteString, ByteStr, FieldLens, Ext, Narrow, CheckAdd, CheckSub, TrivialState, ReadCell, stopwatch, vec_extend_from_slice, fast_slice_to_owned};
 
 pub mod dyldcache;
+pub mod codesign;
+pub mod objc;
+pub mod swift;
+pub mod swift_demangle;
+pub mod load_commands;
+mod reader;
 use dyldcache::{DyldCache, ImageCache, SlideInfo};
 
 pub const VM_PROT_WRITE: u32 = 2;
@@ -48,6 +56,8 @@ pub const CPU_TYPE_SPARC: u32 = 14;
 pub const CPU_TYPE_I860: u32 = 15;
 pub const CPU_TYPE_POWERPC: u32 = 18;
 pub const CPU_TYPE_POWERPC64: u32 = 0x01000000 | 18;
+// not in our copy of machine.h
+pub const CPU_TYPE_RISCV: u32 = 24;
 pub const CPU_SUBTYPE_I386_ALL: u32 = (0 * 0x10 + 3);
 pub const CPU_SUBTYPE_X86_64_ALL: u32 = 3;
 pub const CPU_SUBTYPE_386: u32 = (0 * 0x10 + 3);
@@ -138,6 +148,27 @@ pub const CPU_SUBTYPE_ARM_V7EM: u32 = 16;
 pub const CPU_SUBTYPE_ARM_V8: u32 = 13;
 pub const CPU_SUBTYPE_ARM64_ALL: u32 = 0;
 pub const CPU_SUBTYPE_ARM64_V8: u32 = 1;
+pub const CPU_SUBTYPE_RISCV_ALL: u32 = 0;
+
+// the per-arch reloc.h enums; only the ones we look at
+pub const GENERIC_RELOC_VANILLA: u32 = 0;
+pub const GENERIC_RELOC_PB_LA_PTR: u32 = 3;
+pub const X86_64_RELOC_UNSIGNED: u32 = 0;
+pub const X86_64_RELOC_SIGNED: u32 = 1;
+pub const X86_64_RELOC_BRANCH: u32 = 2;
+pub const X86_64_RELOC_GOT_LOAD: u32 = 3;
+pub const X86_64_RELOC_GOT: u32 = 4;
+pub const X86_64_RELOC_SIGNED_1: u32 = 6;
+pub const X86_64_RELOC_SIGNED_2: u32 = 7;
+pub const X86_64_RELOC_SIGNED_4: u32 = 8;
+pub const ARM_RELOC_VANILLA: u32 = 0;
+pub const ARM_RELOC_PB_LA_PTR: u32 = 4;
+pub const ARM_RELOC_BR24: u32 = 5;
+pub const ARM_THUMB_RELOC_BR22: u32 = 6;
+pub const ARM64_RELOC_UNSIGNED: u32 = 0;
+pub const ARM64_RELOC_BRANCH26: u32 = 2;
+pub const PPC_RELOC_VANILLA: u32 = 0;
+pub const PPC_RELOC_PB_LA_PTR: u32 = 7;
 
 // dont bother with the unions
 #[repr(C)]
@@ -365,6 +396,13 @@ pub struct SectPrivate {
     pub reserved2: u32,
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct DataInCodeEntry {
+    pub addr: VMA,
+    pub length: u64,
+    pub kind: u16, // DICE_KIND_*
+}
+
 #[derive(Default, Clone)]
 pub struct MachO {
     pub eb: exec::ExecBase,
@@ -397,6 +435,8 @@ pub struct MachO {
     pub dyld_lazy_bind: Mem<u8>,
     pub dyld_export: Mem<u8>,
     // linkedit_data_commands
+    pub dyld_chained_fixups: Mem<u8>,
+    pub dyld_exports_trie: Mem<u8>,
     pub segment_split_info: Mem<u8>,
     pub function_starts: Mem<u8>,
     pub data_in_code: Mem<u8>,
@@ -404,12 +444,21 @@ pub struct MachO {
     pub dylib_code_sign_drs: Mem<u8>,
     pub code_signature: Mem<u8>,
 
-    _linkedit_bits: Option<[LinkeditBit; 22]>,
+    _linkedit_bits: Option<[LinkeditBit; 24]>,
+    reloc_syms_cache: Lazy<RelocSyms>,
 
     // from dyld cache
     pub dc_info: MachODCInfo,
 }
 
+// owned copies of symbols so that relocs can point at them
+#[derive(Clone)]
+struct RelocSyms {
+    nlist: Vec<Symbol<'static>>, // by index in symtab
+    binds: Vec<(VMA, u8, i64, Symbol<'static>)>, // (address, bind type, addend, import) for each bind
+    chained: Vec<(Symbol<'static>, i64)>, // by import ordinal, with addend
+}
+
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub enum WhichBind {
     Bind = 0,
@@ -443,7 +492,7 @@ macro_rules! lbit {
     ($self_field:ident, $cmd_id:ident, $cmd_type:ty, $off_field:ident, $size_field:ident, $divi:expr) => { lbit!($self_field, $cmd_id, $cmd_type, $off_field, $size_field, $divi, false) }
 }
 
-fn make_linkedit_bits(is64: bool) -> [LinkeditBit; 22] {
+fn make_linkedit_bits(is64: bool) -> [LinkeditBit; 24] {
     let nlist_size = if is64 { size_of::<nlist_64>() } else { size_of::<nlist>() };
     [
         // section relocations here?
@@ -452,6 +501,9 @@ fn make_linkedit_bits(is64: bool) -> [LinkeditBit; 22] {
         lbit!(dyld_weak_bind, LC_DYLD_INFO, dyld_info_command, weak_bind_off, weak_bind_size, 1),
         lbit!(dyld_lazy_bind, LC_DYLD_INFO, dyld_info_command, lazy_bind_off, lazy_bind_size, 1),
         lbit!(dyld_export, LC_DYLD_INFO, dyld_info_command, export_off, export_size, 1),
+        // the replacement for the above
+        lbit!(dyld_chained_fixups, LC_DYLD_CHAINED_FIXUPS, linkedit_data_command, dataoff, datasize, 1),
+        lbit!(dyld_exports_trie, LC_DYLD_EXPORTS_TRIE, linkedit_data_command, dataoff, datasize, 1),
 
         lbit!(locrel, LC_DYSYMTAB, dysymtab_command, locreloff, nlocrel, size_of::<relocation_info>()),
         lbit!(segment_split_info, LC_SEGMENT_SPLIT_INFO, linkedit_data_command, dataoff, datasize, 1),
@@ -512,18 +564,19 @@ impl exec::Exec for MachO {
                 let mut out = Vec::new();
                 self.parse_each_dyld_bind(&mut |state: &ParseDyldBindState<'a>| {
                     if state.already_bound_this_symbol { return true; }
+                    out.extend(bind_state_symbol(state));
+                    true
+                });
+                for imp in self.get_chained_imports() {
                     out.push(Symbol {
-                        name: some_or!(state.symbol, { return true; }).into(),
+                        name: imp.name.into(),
                         is_public: true,
-                        // XXX what about BIND_SYMBOL_FLAGS_NON_WEAK_DEFINITION?
-                        is_weak: state.which == WhichBind::WeakBind ||
-                                 state.flags & BIND_SYMBOL_FLAGS_WEAK_IMPORT != 0,
-                        val: SymbolValue::Undefined(state.source_dylib),
+                        is_weak: imp.is_weak,
+                        val: SymbolValue::Undefined(imp.source_dylib),
                         size: None,
                         private: 0,
                     });
-                    true
-                });
+                }
                 out
             },
             SymbolSource::Exported => self.get_exported_symbol_list(None),
@@ -555,6 +608,69 @@ impl exec::Exec for MachO {
         res
     }
 
+    fn get_reloc_list<'a>(&'a self, specific: Option<&'a Any>) -> Vec<Reloc<'a>> {
+        assert!(specific.is_none());
+        let syms = self.get_reloc_syms();
+        let mut out = Vec::new();
+        let mut pcrel = 0;
+        self.parse_dyld_rebase(&mut |addr: VMA, typ: u8| {
+            let kind = match typ as u32 {
+                REBASE_TYPE_POINTER => RelocKind::Pointer,
+                REBASE_TYPE_TEXT_ABSOLUTE32 => RelocKind::_32Bit,
+                _ => { pcrel += 1; return true; },
+            };
+            out.push(Reloc {
+                address: addr,
+                kind: kind,
+                base: None,
+                target: RelocTarget::ThisImageSlide,
+            });
+            true
+        });
+        for &(addr, typ, addend, ref sym) in &syms.binds {
+            let kind = match typ as u32 {
+                BIND_TYPE_POINTER => RelocKind::Pointer,
+                BIND_TYPE_TEXT_ABSOLUTE32 => RelocKind::_32Bit,
+                _ => { pcrel += 1; continue; },
+            };
+            out.push(Reloc {
+                address: addr,
+                kind: kind,
+                base: Some(VMA(addend as u64)),
+                target: RelocTarget::Import(sym),
+            });
+        }
+        if pcrel > 0 {
+            errln!("warning: skipped {} pc-relative rebases/binds", pcrel);
+        }
+        self.parse_chained_fixups(&mut |fixup: &ChainedFixup| {
+            out.push(match fixup.target {
+                ChainedFixupTarget::Rebase(target) => Reloc {
+                    address: fixup.addr,
+                    kind: RelocKind::Pointer,
+                    base: Some(target),
+                    target: RelocTarget::ThisImageSlide,
+                },
+                ChainedFixupTarget::Bind { import, addend } => {
+                    let &(ref sym, imp_addend) = some_or!(syms.chained.get(import), {
+                        errln!("warning: chained fixup at {} has out-of-range import index {}", fixup.addr, import);
+                        return true;
+                    });
+                    Reloc {
+                        address: fixup.addr,
+                        kind: RelocKind::Pointer,
+                        base: Some(VMA(addend.wrapping_add(imp_addend) as u64)),
+                        target: RelocTarget::Import(sym),
+                    }
+                },
+            });
+            true
+        });
+        self.push_classic_relocs(self.extrel.get(), "extrel", &syms.nlist, &mut out);
+        self.push_classic_relocs(self.locrel.get(), "locrel", &syms.nlist, &mut out);
+        out
+    }
+
     fn get_dep_libs(&self) -> Cow<[DepLib]> {
         let dls = self.load_dylib.iter().enumerate().map(|(i, ld)| DepLib {
             path: (&*ld.path).into(),
@@ -626,12 +742,28 @@ fn mach_arch_desc(cputype: i32, cpusubtype: i32) -> Option<&'static str> {
         (CPU_TYPE_ARM, CPU_SUBTYPE_ARM_V7K) => "armv7k",
         (CPU_TYPE_ARM64, CPU_SUBTYPE_ARM64_ALL) => "arm64",
         (CPU_TYPE_ARM64, CPU_SUBTYPE_ARM64_V8) => "arm64v8",
+        (CPU_TYPE_RISCV, CPU_SUBTYPE_RISCV_ALL) => "riscv",
         (CPU_TYPE_ANY, CPU_SUBTYPE_LITTLE_ENDIAN) => "little",
         (CPU_TYPE_ANY, CPU_SUBTYPE_BIG_ENDIAN) => "big",
         _ => return None,
     })
 }
 
+fn mach_cputype_arch(cputype: i32) -> arch::Arch {
+    match cputype as u32 {
+        CPU_TYPE_X86 => arch::X86,
+        CPU_TYPE_X86_64 => arch::X86_64,
+        CPU_TYPE_ARM => arch::ARM,
+        CPU_TYPE_ARM64 => arch::AArch64,
+        CPU_TYPE_POWERPC | CPU_TYPE_POWERPC64 => arch::PowerPC,
+        CPU_TYPE_SPARC => arch::Sparc,
+        CPU_TYPE_MC680x0 => arch::M68k,
+        CPU_TYPE_RISCV => arch::RiscV,
+        // Even if we don't know the arch, we can at least do something.
+        _ => arch::UnknownArch,
+    }
+}
+
 fn fixup_segment_overflow(seg: &mut exec::Segment, sixtyfour: bool) {
     if sixtyfour {
         if seg.vmsize > exec::VMA(u64::MAX) - seg.vmaddr {
@@ -671,6 +803,20 @@ impl<T> OKOrTruncated<T> for Option<T> {
     }
 }
 
+// The import a bind opcode state refers to.
+fn bind_state_symbol<'s>(state: &ParseDyldBindState<'s>) -> Option<Symbol<'s>> {
+    Some(Symbol {
+        name: some_or!(state.symbol, { return None; }).into(),
+        is_public: true,
+        // XXX what about BIND_SYMBOL_FLAGS_NON_WEAK_DEFINITION?
+        is_weak: state.which == WhichBind::WeakBind ||
+                 state.flags & BIND_SYMBOL_FLAGS_WEAK_IMPORT != 0,
+        val: SymbolValue::Undefined(state.source_dylib),
+        size: None,
+        private: 0,
+    })
+}
+
 pub struct ParseDyldBindState<'s> {
     pub source_dylib: SourceLib,
     pub seg: Option<&'s exec::Segment>,
@@ -693,6 +839,98 @@ struct ParseDyldExportState<'a> {
     offset: usize,
 }
 
+pub struct ChainedImport<'a> {
+    pub source_dylib: SourceLib,
+    pub is_weak: bool,
+    pub name: &'a ByteStr,
+    pub addend: i64,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum ChainedFixupTarget {
+    Rebase(VMA),
+    Bind { import: usize, addend: i64 },
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ChainedFixup {
+    pub addr: VMA,
+    pub pointer_format: u16,
+    pub is_auth: bool,
+    pub target: ChainedFixupTarget,
+}
+
+// (stride, is 64-bit)
+fn chained_ptr_format_info(fmt: u32) -> Option<(u64, bool)> {
+    match fmt {
+        DYLD_CHAINED_PTR_ARM64E | DYLD_CHAINED_PTR_ARM64E_USERLAND |
+        DYLD_CHAINED_PTR_ARM64E_USERLAND24 | DYLD_CHAINED_PTR_ARM64E_SHARED_CACHE => Some((8, true)),
+        DYLD_CHAINED_PTR_64 | DYLD_CHAINED_PTR_64_OFFSET | DYLD_CHAINED_PTR_ARM64E_KERNEL |
+        DYLD_CHAINED_PTR_64_KERNEL_CACHE | DYLD_CHAINED_PTR_ARM64E_FIRMWARE => Some((4, true)),
+        DYLD_CHAINED_PTR_X86_64_KERNEL_CACHE => Some((1, true)),
+        DYLD_CHAINED_PTR_32 | DYLD_CHAINED_PTR_32_CACHE | DYLD_CHAINED_PTR_32_FIRMWARE => Some((4, false)),
+        _ => None,
+    }
+}
+
+// returns (next, is_auth, target); target is None for the 32-bit non-pointer hack
+fn decode_chained_ptr(fmt: u32, raw: u64, max_valid_pointer: u32, base: VMA) -> (u64, bool, Option<ChainedFixupTarget>) {
+    use ChainedFixupTarget::*;
+    let bits = |lo: u32, width: u32| (raw >> lo) & ((1u64 << width) - 1);
+    match fmt {
+        DYLD_CHAINED_PTR_ARM64E | DYLD_CHAINED_PTR_ARM64E_KERNEL | DYLD_CHAINED_PTR_ARM64E_USERLAND |
+        DYLD_CHAINED_PTR_ARM64E_FIRMWARE | DYLD_CHAINED_PTR_ARM64E_USERLAND24 => {
+            let is_auth = bits(63, 1) != 0;
+            let target = if bits(62, 1) != 0 {
+                let ord_width = if fmt == DYLD_CHAINED_PTR_ARM64E_USERLAND24 { 24 } else { 16 };
+                // 19-bit signed addend, only for non-auth
+                let addend = if is_auth { 0 } else { ((bits(32, 19) << 45) as i64) >> 45 };
+                Bind { import: bits(0, ord_width) as usize, addend: addend }
+            } else if is_auth {
+                Rebase(base + bits(0, 32))
+            } else {
+                let target = bits(0, 43);
+                let high8 = bits(43, 8) << 56;
+                let is_vmaddr = fmt == DYLD_CHAINED_PTR_ARM64E || fmt == DYLD_CHAINED_PTR_ARM64E_FIRMWARE;
+                Rebase(VMA((if is_vmaddr { target } else { base.0.wrapping_add(target) }) | high8))
+            };
+            (bits(51, 11), is_auth, Some(target))
+        },
+        DYLD_CHAINED_PTR_64 | DYLD_CHAINED_PTR_64_OFFSET => {
+            let target = if bits(63, 1) != 0 {
+                Bind { import: bits(0, 24) as usize, addend: bits(24, 8) as i64 }
+            } else {
+                let target = bits(0, 36);
+                let high8 = bits(36, 8) << 56;
+                let is_vmaddr = fmt == DYLD_CHAINED_PTR_64;
+                Rebase(VMA((if is_vmaddr { target } else { base.0.wrapping_add(target) }) | high8))
+            };
+            (bits(51, 12), false, Some(target))
+        },
+        // rebase only; we don't care about cacheLevel
+        DYLD_CHAINED_PTR_64_KERNEL_CACHE | DYLD_CHAINED_PTR_X86_64_KERNEL_CACHE =>
+            (bits(51, 12), bits(63, 1) != 0, Some(Rebase(base + bits(0, 30)))),
+        DYLD_CHAINED_PTR_ARM64E_SHARED_CACHE => {
+            let is_auth = bits(63, 1) != 0;
+            let high8 = if is_auth { 0 } else { bits(34, 8) << 56 };
+            (bits(52, 11), is_auth, Some(Rebase(VMA(base.0.wrapping_add(bits(0, 34)) | high8))))
+        },
+        DYLD_CHAINED_PTR_32 => {
+            let target = if bits(31, 1) != 0 {
+                Some(Bind { import: bits(0, 20) as usize, addend: bits(20, 6) as i64 })
+            } else if bits(0, 26) > max_valid_pointer as u64 {
+                None
+            } else {
+                Some(Rebase(VMA(bits(0, 26))))
+            };
+            (bits(26, 5), false, target)
+        },
+        DYLD_CHAINED_PTR_32_CACHE => (bits(30, 2), false, Some(Rebase(base + bits(0, 30)))),
+        DYLD_CHAINED_PTR_32_FIRMWARE => (bits(26, 6), false, Some(Rebase(VMA(bits(0, 26))))),
+        _ => unreachable!(),
+    }
+}
+
 pub enum GuessBrokenCacheSlideResult {
     Guess(u64),
     Inconsistent,
@@ -701,18 +939,24 @@ pub enum GuessBrokenCacheSlideResult {
 }
 
 // info about the dyld cache containing this macho
-#[derive(Default, Copy, Clone, Debug)]
+#[derive(Default, Clone, Debug)]
 pub struct MachODCInfo {
     hdr_offset: usize,
     have_images_text_offset: bool,
+    // all of the cache's mappings, for following pointers into other images
+    cache_segments: Option<Arc<Vec<exec::Segment>>>,
+    // split caches: the image's file offsets are relative to whichever sub-cache holds each
+    // segment, so go by address using cache_segments instead
+    is_split: bool,
+    objc_relative_selector_base: Option<VMA>,
 }
 
 impl MachO {
     pub fn new(mc: Mem<u8>, do_lcs: bool, dc_info: Option<MachODCInfo>) -> exec::ExecResult<MachO> {
         let mut me: MachO = Default::default();
         let dc_info = dc_info.unwrap_or(Default::default());
-        me.dc_info = dc_info;
         let hdr_offset = dc_info.hdr_offset;
+        me.dc_info = dc_info;
         let mut lc_off = try!(hdr_offset.checked_add(size_of::<mach_header>()).ok_or_truncated());
         {
             let buf = mc.get();
@@ -770,16 +1014,7 @@ impl MachO {
     }
 
     fn parse_header(&mut self) {
-        self.eb.arch = match self.mh.cputype as u32 {
-            CPU_TYPE_X86 => arch::X86,
-            CPU_TYPE_X86_64 => arch::X86_64,
-            CPU_TYPE_ARM => arch::ARM,
-            CPU_TYPE_ARM64 => arch::AArch64,
-            CPU_TYPE_POWERPC => arch::PowerPC,
-            CPU_TYPE_POWERPC64 => arch::PowerPC,
-            // Even if we don't know the arch, we can at least do something.
-            _ => arch::UnknownArch,
-        }
+        self.eb.arch = mach_cputype_arch(self.mh.cputype);
         // we don't really care about cpusubtype but could fill it in
     }
 
@@ -788,6 +1023,9 @@ impl MachO {
         self.nlist_size = if self.is64 { size_of::<nlist_64>() } else { size_of::<nlist>() };
         let end = self.eb.endian;
         let hdr_offset = self.dc_info.hdr_offset as u64;
+        let cache_segments = if self.dc_info.is_split { self.dc_info.cache_segments.clone() } else { None };
+        // (original fileoff, new fileoff) of __LINKEDIT, if we had to move it
+        let linkedit_adj: Cell<Option<(u64, u64)>> = Cell::new(None);
         let whole = mc.get();
         let mut segi: usize = 0;
         for lci in 0..self.mh.ncmds {
@@ -818,7 +1056,16 @@ impl MachO {
                         // cache, the offset isn't even consistent between __TEXT's fileoff and
                         // __text's.  So just do it based on the address.
                         fileoff = hdr_offset;
+                    } else if let Some(ref cache_segments) = cache_segments {
+                        fileoff = some_or!(exec::addr_to_off(cache_segments, VMA(sc.vmaddr as u64), sc.filesize as u64), {
+                            errln!("warning: shared cache library segment {} isn't in any cache mapping", name);
+                            fileoff
+                        });
+                        if &name == "__LINKEDIT" {
+                            linkedit_adj.set(Some((sc.fileoff as u64, fileoff)));
+                        }
                     }
+                    let seg_fileoff = fileoff;
                     //let was_0 = sc.fileoff == 0;
                     //let fileoff = if was_0 { hdr_offset as u64 } else { sc.fileoff as u64 };
                     let data: Option<Mem<u8>> = mc.slice(fileoff as usize, (fileoff + (sc.filesize as u64)) as usize);
@@ -843,6 +1090,8 @@ impl MachO {
                                 errln!("warning: integer overflow in shared cache library __TEXT offset recalculation");
                                 fileoff
                             });
+                        } else if cache_segments.is_some() && s.offset != 0 {
+                            fileoff = fileoff.wrapping_sub(sc.fileoff as u64).wrapping_add(seg_fileoff);
                         }
                         let mut seg = exec::Segment {
                             vmaddr: VMA(s.addr as u64),
@@ -874,7 +1123,8 @@ impl MachO {
                 LC_SEGMENT_64 => do_segment(true, &mut self.eb.segments, &mut self.eb.sections, &mut self.sect_private),
                 LC_DYLD_INFO | LC_DYLD_INFO_ONLY | LC_SYMTAB | LC_DYSYMTAB |
                 LC_FUNCTION_STARTS | LC_DATA_IN_CODE | LC_DYLIB_CODE_SIGN_DRS |
-                LC_SEGMENT_SPLIT_INFO | LC_LINKER_OPTIMIZATION_HINT | LC_CODE_SIGNATURE => {
+                LC_SEGMENT_SPLIT_INFO | LC_LINKER_OPTIMIZATION_HINT | LC_CODE_SIGNATURE |
+                LC_DYLD_CHAINED_FIXUPS | LC_DYLD_EXPORTS_TRIE => {
                     for fb in self.linkedit_bits() {
                         if lc.cmd == fb.cmd_id || (lc.cmd == LC_DYLD_INFO_ONLY && fb.cmd_id == LC_DYLD_INFO) {
                             let mcref: &mut Mem<u8> = unsafe { fb.self_field.get_mut_unsafe(self_) };
@@ -882,12 +1132,16 @@ impl MachO {
                                 some_or!(         lc_buf.slice_opt(fb.cmd_off_field_off, fb.cmd_off_field_off+4)
                                          .and_tup(lc_buf.slice_opt(fb.cmd_count_field_off, fb.cmd_count_field_off+4)),
                                          { errln!("warning: load command too small for offset/size of {}", fb.name); continue; });
-                            let mut off: u32 = util::copy_from_slice(off_data, end);
+                            let off: u32 = util::copy_from_slice(off_data, end);
                             let count: u32 = util::copy_from_slice(count_data, end);
+                            let mut off: u64 = off.ext();
                             let buf = if fb.is_symtab {
-                                off *= fb.elm_size as u32;
+                                off *= fb.elm_size as u64;
                                 &self.symtab
                             } else {
+                                if let Some((orig, new)) = linkedit_adj.get() {
+                                    off = off.wrapping_sub(orig).wrapping_add(new);
+                                }
                                 self.eb.whole_buf.as_ref().unwrap()
                             };
                             let mut ignore = false;
@@ -904,7 +1158,7 @@ impl MachO {
                                 }
                             }
                             if !ignore {
-                                *mcref = file_array(buf, fb.name, off.ext(), count.ext(), fb.elm_size);
+                                *mcref = file_array(buf, fb.name, off, count.ext(), fb.elm_size);
                             }
                         }
                     }
@@ -1038,6 +1292,45 @@ impl MachO {
         if self.eb.arch == arch::AArch64 { 0x4000 } else { 0x1000 }
     }
 
+    pub fn sect_has_code(&self, sect: &exec::Segment) -> bool {
+        if sect.private == !0 { return false; }
+        self.sect_private[sect.private].flags & (S_ATTR_SOME_INSTRUCTIONS | S_ATTR_PURE_INSTRUCTIONS) != 0
+    }
+
+    // LC_FUNCTION_STARTS: uleb deltas from the start of __TEXT, 0-terminated
+    pub fn parse_function_starts(&self) -> Vec<VMA> {
+        let mut res = Vec::new();
+        let mut addr = some_or!(self.dyld_base, { return res; });
+        let mut slice = self.function_starts.get();
+        while !slice.is_empty() {
+            let mut it = ByteSliceIterator(&mut slice);
+            let delta = some_or!(exec::read_leb128_inner_noisy(&mut it, false, "parse_function_starts"), { break; });
+            if delta == 0 { break; }
+            addr = addr.wrapping_add(delta);
+            res.push(addr);
+        }
+        res
+    }
+
+    pub fn parse_data_in_code(&self) -> Vec<DataInCodeEntry> {
+        let mut res = Vec::new();
+        let end = self.eb.endian;
+        let text_fileoff = self.text_fileoff();
+        for chunk in self.data_in_code.get().chunks(8) {
+            if chunk.len() < 8 { break; }
+            let offset: u32 = util::copy_from_slice(&chunk[0..4], end);
+            let length: u16 = util::copy_from_slice(&chunk[4..6], end);
+            let kind: u16 = util::copy_from_slice(&chunk[6..8], end);
+            // offsets are from the mach header
+            let addr = some_or!(exec::off_to_addr(&self.eb.segments, text_fileoff + (offset as u64), 0), {
+                errln!("warning: parse_data_in_code: offset 0x{:x} not in any segment", offset);
+                continue;
+            });
+            res.push(DataInCodeEntry { addr: addr, length: length as u64, kind: kind });
+        }
+        res
+    }
+
     pub fn rewhole(&mut self) {
         let _sw = stopwatch("rewhole");
         let new_size = self.eb.segments.iter().map(|seg| seg.fileoff + seg.filesize).max().unwrap_or(0);
@@ -1054,8 +1347,14 @@ impl MachO {
     }
 
     pub fn reallocate(&mut self) -> exec::ExecResult<()> {
-        let _sw = stopwatch("reallocate");
+        // the old signature won't match anymore; see codesign::adhoc_sign
         self.code_signature = Mem::<u8>::default();
+        self.reallocate_keeping_signature()
+    }
+
+    // Like reallocate, but keeps whatever is in code_signature (at the end of __LINKEDIT)
+    pub fn reallocate_keeping_signature(&mut self) -> exec::ExecResult<()> {
+        let _sw = stopwatch("reallocate");
         self.xsym_to_symtab();
         let page_size = self.page_size();
 
@@ -1157,6 +1456,11 @@ impl MachO {
                     allocs.push((mcref.byte_offset_in(&self.symtab).unwrap(), buf.len()));
                 }
             } else {
+                if fb.cmd_id == LC_CODE_SIGNATURE && buf.len() > 0 {
+                    // codesign wants it 16-byte aligned
+                    let aligned = linkedit.len().align_up_to(16);
+                    linkedit.resize(aligned, 0);
+                }
                 allocs.push((linkedit.len(), buf.len()));
                 vec_extend_from_slice(&mut linkedit, buf);
             }
@@ -1234,6 +1538,8 @@ impl MachO {
             (LC_LINKER_OPTIMIZATION_HINT, size_of::<linkedit_data_command>()),
             // for consistency, code_signature is here, but reallocate() nukes it
             (LC_CODE_SIGNATURE, size_of::<linkedit_data_command>()),
+            (LC_DYLD_CHAINED_FIXUPS, size_of::<linkedit_data_command>()),
+            (LC_DYLD_EXPORTS_TRIE, size_of::<linkedit_data_command>()),
         ].iter().map(|&(cmd, cmdsize)| {
             let mut buf: Vec<u8> = Vec::new();
             buf.resize(cmdsize, 0);
@@ -1285,6 +1591,8 @@ impl MachO {
                         LC_DATA_IN_CODE => Some(6),
                         LC_LINKER_OPTIMIZATION_HINT => Some(7),
                         LC_CODE_SIGNATURE => Some(8),
+                        LC_DYLD_CHAINED_FIXUPS => Some(9),
+                        LC_DYLD_EXPORTS_TRIE => Some(10),
                         _ => None
                     } {
                         if let Some(new_cmd) = bit_cmds[idx].take() {
@@ -1383,6 +1691,418 @@ impl MachO {
         (existing_segs, extra_segs)
     }
 
+    fn get_reloc_syms(&self) -> &RelocSyms {
+        self.reloc_syms_cache.get(|| {
+            let mut nlist = Vec::new();
+            if self.nlist_size != 0 {
+                self.push_nlist_symbols(self.symtab.get(), self.strtab.get(), 0, self.symtab.len() / self.nlist_size, false, &mut nlist);
+            }
+            let mut binds = Vec::new();
+            self.parse_each_dyld_bind(&mut |state: &ParseDyldBindState| {
+                let seg_off = some_or!(state.seg_off, return true);
+                let sym = some_or!(bind_state_symbol(state), return true);
+                binds.push((state.seg.unwrap().vmaddr + seg_off, state.typ, state.addend, sym.into_owned()));
+                true
+            });
+            let chained = self.get_chained_imports().into_iter().map(|imp| (Symbol {
+                name: imp.name.to_owned().into(),
+                is_public: true,
+                is_weak: imp.is_weak,
+                val: SymbolValue::Undefined(imp.source_dylib),
+                size: None,
+                private: 0,
+            }, imp.addend)).collect();
+            RelocSyms {
+                nlist: nlist.into_iter().map(|s| s.into_owned()).collect(),
+                binds: binds,
+                chained: chained,
+            }
+        })
+    }
+
+    // r_address in extrel/locrel is relative to this; see getRelocBase in dyld
+    fn classic_reloc_base(&self) -> VMA {
+        let segs = &self.eb.segments;
+        if self.eb.arch == arch::X86_64 || self.mh.flags & MH_SPLIT_SEGS != 0 {
+            if let Some(seg) = segs.iter().find(|seg| seg.prot.w) {
+                return seg.vmaddr;
+            }
+        }
+        segs.first().map_or(VMA(0), |seg| seg.vmaddr)
+    }
+
+    fn push_classic_relocs<'a>(&'a self, rels: &[ReadCell<u8>], what: &str, nlist_syms: &'a [Symbol<'static>], out: &mut Vec<Reloc<'a>>) {
+        let end = self.eb.endian;
+        let base = self.classic_reloc_base();
+        let ptr_length = if self.is64 { 3 } else { 2 };
+        let mut unknown = 0;
+        for ent in rels.chunks(size_of::<relocation_info>()) {
+            if ent.len() < 8 { break; }
+            let word0: u32 = util::copy_from_slice(&ent[..4], end);
+            let word1: u32 = util::copy_from_slice(&ent[4..8], end);
+            let (offset, symbolnum, pcrel, length, is_extern, typ);
+            let scattered = word0 & R_SCATTERED != 0;
+            if scattered {
+                // the mask hack means this is the same for either endianness;
+                // r_value is the target address, which doesn't matter for a rebase
+                offset = (word0 & 0xffffff) as u64;
+                typ = (word0 >> 24) & 0xf;
+                length = (word0 >> 28) & 3;
+                pcrel = (word0 >> 30) & 1 != 0;
+                is_extern = false;
+                symbolnum = 0;
+            } else {
+                // ordinary bitfields, which are allocated from the other end on big endian
+                offset = word0 as i32 as i64 as u64;
+                if end == util::BigEndian {
+                    symbolnum = word1 >> 8;
+                    pcrel = (word1 >> 7) & 1 != 0;
+                    length = (word1 >> 5) & 3;
+                    is_extern = (word1 >> 4) & 1 != 0;
+                    typ = word1 & 0xf;
+                } else {
+                    symbolnum = word1 & 0xffffff;
+                    pcrel = (word1 >> 24) & 1 != 0;
+                    length = (word1 >> 25) & 3;
+                    is_extern = (word1 >> 27) & 1 != 0;
+                    typ = word1 >> 28;
+                }
+            }
+            let address = base.wrapping_add(offset);
+            let plain = match (self.eb.arch, typ) {
+                (arch::X86, GENERIC_RELOC_VANILLA) | (arch::X86, GENERIC_RELOC_PB_LA_PTR) |
+                (arch::X86_64, X86_64_RELOC_UNSIGNED) |
+                (arch::ARM, ARM_RELOC_VANILLA) | (arch::ARM, ARM_RELOC_PB_LA_PTR) |
+                (arch::AArch64, ARM64_RELOC_UNSIGNED) |
+                (arch::PowerPC, PPC_RELOC_VANILLA) | (arch::PowerPC, PPC_RELOC_PB_LA_PTR) => true,
+                _ => false,
+            };
+            let branch = match (self.eb.arch, typ) {
+                (arch::AArch64, ARM64_RELOC_BRANCH26) => Some(RelocKind::Arm64Br26),
+                (arch::ARM, ARM_RELOC_BR24) => Some(RelocKind::ArmBr24),
+                (arch::ARM, ARM_THUMB_RELOC_BR22) => Some(RelocKind::ThumbBr22),
+                (arch::X86, GENERIC_RELOC_VANILLA) |
+                (arch::X86_64, X86_64_RELOC_SIGNED) |
+                // same, but the instruction has 1, 2 or 4 bytes of immediate after the field
+                (arch::X86_64, X86_64_RELOC_SIGNED_1) |
+                (arch::X86_64, X86_64_RELOC_SIGNED_2) |
+                (arch::X86_64, X86_64_RELOC_SIGNED_4) |
+                (arch::X86_64, X86_64_RELOC_BRANCH) => Some(RelocKind::Rel32),
+                (arch::X86_64, X86_64_RELOC_GOT_LOAD) |
+                (arch::X86_64, X86_64_RELOC_GOT) => Some(RelocKind::GotPcRel),
+                _ => None,
+            };
+            let kind = if plain && !pcrel && length == ptr_length {
+                RelocKind::Pointer
+            } else if plain && !pcrel && length == 2 {
+                RelocKind::_32Bit
+            } else if branch.is_some() && pcrel && length == 2 && is_extern {
+                branch.unwrap()
+            } else {
+                unknown += 1;
+                continue;
+            };
+            let target = if is_extern {
+                RelocTarget::Import(some_or!(nlist_syms.get(symbolnum as usize), {
+                    errln!("warning: {} entry at {} has out-of-range symbol index {}", what, address, symbolnum);
+                    continue;
+                }))
+            } else if !scattered && symbolnum == R_ABS {
+                // absolute, nothing to do
+                continue;
+            } else {
+                RelocTarget::ThisImageSlide
+            };
+            out.push(Reloc {
+                address: address,
+                kind: kind,
+                base: None,
+                target: target,
+            });
+        }
+        if unknown > 0 {
+            errln!("warning: skipped {} {} entries of unknown type", unknown, what);
+        }
+    }
+
+    pub fn parse_dyld_rebase(&self, cb: &mut FnMut(VMA, u8) -> bool) {
+        struct RebaseState<'s> {
+            seg: Option<&'s exec::Segment>,
+            seg_off: Option<u64>,
+            typ: u8,
+        }
+        let mut slice = self.dyld_rebase.get();
+        let pointer_size = self.eb.pointer_size as u64;
+        let leb = |slice_: &mut &[ReadCell<u8>]| -> Option<u64> {
+            let mut it = ByteSliceIterator(slice_);
+            exec::read_leb128_inner_noisy(&mut it, false, "parse_dyld_rebase")
+        };
+        macro_rules! leb { () => {
+            if let Some(num) = leb(&mut slice) { num } else { return }
+        } }
+        let advance = |state: &mut RebaseState, amount: u64| {
+            if let Some(off) = state.seg_off {
+                let seg = state.seg.unwrap();
+                let new = off.wrapping_add(amount);
+                if new > seg.vmsize {
+                    errln!("warning: parse_dyld_rebase: going out of range of segment (off={:x}, size={:x}, adv={:x}), addr={}",
+                           off, seg.vmsize, amount, seg.vmaddr);
+                    state.seg_off = None;
+                } else {
+                    state.seg_off = Some(new);
+                }
+            }
+        };
+        let mut do_rebase = |state: &mut RebaseState, amount: u64| -> bool {
+            if let Some(off) = state.seg_off {
+                let seg = state.seg.unwrap();
+                let size = if state.typ == (REBASE_TYPE_POINTER as u8) { pointer_size } else { 4 };
+                if seg.vmsize - off < size {
+                    errln!("warning: parse_dyld_rebase: rebase reaches off end");
+                    state.seg_off = None;
+                } else if !cb(seg.vmaddr + off, state.typ) {
+                    return false;
+                }
+            }
+            advance(state, amount);
+            true
+        };
+        let mut state = RebaseState { seg: None, seg_off: None, typ: 0 };
+        while !slice.is_empty() {
+            let byte = slice[0].get();
+            slice = &slice[1..];
+            let immediate = byte & (REBASE_IMMEDIATE_MASK as u8);
+            let opcode = byte & (REBASE_OPCODE_MASK as u8);
+            match opcode as u32 {
+                REBASE_OPCODE_DONE => break,
+                REBASE_OPCODE_SET_TYPE_IMM => {
+                    state.typ = immediate;
+                    if immediate < 1 || immediate > 3 {
+                        errln!("warning: parse_dyld_rebase: unknown REBASE_OPCODE_SET_TYPE_IMM type {}", immediate);
+                    }
+                },
+                REBASE_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB => {
+                    let offset = leb!();
+                    let seg = some_or!(self.eb.segments.get(immediate as usize), {
+                        errln!("parse_dyld_rebase: REBASE_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB: bad segment index {}", immediate);
+                        state.seg = None;
+                        state.seg_off = None;
+                        continue;
+                    });
+                    state.seg = Some(seg);
+                    state.seg_off = Some(0);
+                    advance(&mut state, offset);
+                },
+                REBASE_OPCODE_ADD_ADDR_ULEB => advance(&mut state, leb!()),
+                REBASE_OPCODE_ADD_ADDR_IMM_SCALED => advance(&mut state, (immediate as u64) * pointer_size),
+                REBASE_OPCODE_DO_REBASE_IMM_TIMES => {
+                    for _ in 0..immediate {
+                        if !do_rebase(&mut state, pointer_size) { return }
+                    }
+                },
+                REBASE_OPCODE_DO_REBASE_ULEB_TIMES => {
+                    let count = leb!();
+                    for _ in 0..count {
+                        if state.seg_off.is_none() { break; }
+                        if !do_rebase(&mut state, pointer_size) { return }
+                    }
+                },
+                REBASE_OPCODE_DO_REBASE_ADD_ADDR_ULEB => {
+                    let add = leb!();
+                    if !do_rebase(&mut state, add.wrapping_add(pointer_size)) { return }
+                },
+                REBASE_OPCODE_DO_REBASE_ULEB_TIMES_SKIPPING_ULEB => {
+                    let count = leb!();
+                    let skip = leb!().wrapping_add(pointer_size);
+                    for _ in 0..count {
+                        if state.seg_off.is_none() { break; }
+                        if !do_rebase(&mut state, skip) { return }
+                    }
+                },
+                _ => {
+                    errln!("parse_dyld_rebase: unknown rebase opcode (byte=0x{:x})", byte);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn chained_fixups_header(&self) -> Option<dyld_chained_fixups_header> {
+        let data = self.dyld_chained_fixups.get();
+        if data.is_empty() { return None; }
+        let hdr: dyld_chained_fixups_header = util::copy_from_slice(
+            some_or!(data.slice_opt(0, size_of::<dyld_chained_fixups_header>()), {
+                errln!("warning: chained fixups: truncated header");
+                return None;
+            }), self.eb.endian);
+        if hdr.fixups_version != 0 {
+            errln!("warning: chained fixups: unknown fixups_version {}", hdr.fixups_version);
+            return None;
+        }
+        Some(hdr)
+    }
+
+    fn chained_ordinal_to_source_lib(&self, ord: i64) -> SourceLib {
+        let count = self.load_dylib.len() as i64;
+        match ord {
+            0 => SourceLib::Self_,
+            -1 => SourceLib::MainExecutable,
+            // -3 is weak lookup, which is close enough
+            -2 | -3 => SourceLib::Flat,
+            _ if ord > 0 && ord <= count => SourceLib::Ordinal((ord - 1) as u32),
+            _ => {
+                errln!("warning: chained fixups: dylib ordinal out of range (ord={}, count={})", ord, count);
+                SourceLib::None
+            },
+        }
+    }
+
+    // indexed by the import ordinal in binds, so bad entries are still included
+    pub fn get_chained_imports(&self) -> Vec<ChainedImport> {
+        let mut out = Vec::new();
+        let hdr = some_or!(self.chained_fixups_header(), { return out; });
+        let data = self.dyld_chained_fixups.get();
+        let end = self.eb.endian;
+        if hdr.symbols_format != DYLD_CHAINED_SYMBOL_UNCOMPRESSED {
+            errln!("warning: chained fixups: unsupported symbols_format {}", hdr.symbols_format);
+            return out;
+        }
+        let entsize = match hdr.imports_format {
+            DYLD_CHAINED_IMPORT => 4,
+            DYLD_CHAINED_IMPORT_ADDEND => 8,
+            DYLD_CHAINED_IMPORT_ADDEND64 => 16,
+            _ => {
+                errln!("warning: chained fixups: unknown imports_format {}", hdr.imports_format);
+                return out;
+            },
+        };
+        let imports_off = hdr.imports_offset as usize;
+        let imports = some_or!(data.slice_opt(imports_off, imports_off.saturating_add((hdr.imports_count as usize).saturating_mul(entsize))), {
+            errln!("warning: chained fixups: imports table out of range (offset={}, count={})", hdr.imports_offset, hdr.imports_count);
+            return out;
+        });
+        let symbols = data.slice_opt(hdr.symbols_offset as usize, data.len()).unwrap_or(&[]);
+        for ent in imports.chunks(entsize) {
+            let (ord, is_weak, name_off, addend) = if entsize == 16 {
+                let raw: u64 = util::copy_from_slice(&ent[..8], end);
+                let addend: u64 = util::copy_from_slice(&ent[8..16], end);
+                ((raw & 0xffff) as u16 as i16 as i64, (raw >> 16) & 1 != 0, (raw >> 32) as usize, addend as i64)
+            } else {
+                let raw: u32 = util::copy_from_slice(&ent[..4], end);
+                let addend: u32 = if entsize == 8 { util::copy_from_slice(&ent[4..8], end) } else { 0 };
+                ((raw & 0xff) as u8 as i8 as i64, (raw >> 8) & 1 != 0, (raw >> 9) as usize, addend as i32 as i64)
+            };
+            let name = some_or!(symbols.slice_opt(name_off, symbols.len()).and_then(|s| util::from_cstr_strict(s)), {
+                errln!("warning: chained fixups: bad import name offset {}", name_off);
+                ByteStr::from_str("<err>")
+            });
+            out.push(ChainedImport {
+                source_dylib: self.chained_ordinal_to_source_lib(ord),
+                is_weak: is_weak,
+                name: name,
+                addend: addend,
+            });
+        }
+        out
+    }
+
+    pub fn parse_chained_fixups(&self, cb: &mut FnMut(&ChainedFixup) -> bool) {
+        let hdr = some_or!(self.chained_fixups_header(), { return; });
+        let data = self.dyld_chained_fixups.get();
+        let end = self.eb.endian;
+        let base = some_or!(self.dyld_base, {
+            errln!("warning: chained fixups: no load command segment");
+            return;
+        });
+        let read_u32 = |off: usize| -> Option<u32> {
+            off.checked_add(4).and_then(|e| data.slice_opt(off, e)).map(|s| util::copy_from_slice(s, end))
+        };
+        let read_u16 = |off: usize| -> Option<u16> {
+            off.checked_add(2).and_then(|e| data.slice_opt(off, e)).map(|s| util::copy_from_slice(s, end))
+        };
+        let starts_off = hdr.starts_offset as usize;
+        let seg_count = some_or!(read_u32(starts_off), {
+            errln!("warning: chained fixups: starts_offset out of range");
+            return;
+        });
+        for segi in 0..seg_count as usize {
+            let info_off = some_or!(read_u32(starts_off + 4 + segi * 4), {
+                errln!("warning: chained fixups: seg_info_offset table truncated");
+                return;
+            });
+            if info_off == 0 { continue; }
+            let sis_off = starts_off.saturating_add(info_off as usize);
+            let sis: dyld_chained_starts_in_segment = util::copy_from_slice(
+                some_or!(data.slice_opt(sis_off, sis_off.saturating_add(size_of::<dyld_chained_starts_in_segment>())), {
+                    errln!("warning: chained fixups: starts for segment {} out of range", segi);
+                    continue;
+                }), end);
+            let seg = some_or!(self.eb.segments.get(segi), {
+                errln!("warning: chained fixups: bad segment index {}", segi);
+                continue;
+            });
+            let fmt = sis.pointer_format as u32;
+            let (stride, is64) = some_or!(chained_ptr_format_info(fmt), {
+                errln!("warning: chained fixups: unsupported pointer_format {} in segment {}", fmt, segi);
+                continue;
+            });
+            let seg_data = seg.get_data();
+            let page_starts_off = sis_off + offset_of!(dyld_chained_starts_in_segment, page_start);
+            let mut walk_chain = |start: u64| -> bool {
+                let mut addr = (base + sis.segment_offset).wrapping_add(start);
+                loop {
+                    let off = addr.wrapping_sub(seg.vmaddr);
+                    let size = if is64 { 8 } else { 4 };
+                    if off > seg_data.len() as u64 || seg_data.len() as u64 - off < size {
+                        errln!("warning: chained fixups: chain runs off end of segment at {}", addr);
+                        return true;
+                    }
+                    let off = off as usize;
+                    let raw: u64 = if is64 {
+                        util::copy_from_slice(&seg_data[off..off+8], end)
+                    } else {
+                        let raw: u32 = util::copy_from_slice(&seg_data[off..off+4], end);
+                        raw as u64
+                    };
+                    let (next, is_auth, target) = decode_chained_ptr(fmt, raw, sis.max_valid_pointer, base);
+                    if let Some(target) = target {
+                        if !cb(&ChainedFixup {
+                            addr: addr,
+                            pointer_format: sis.pointer_format,
+                            is_auth: is_auth,
+                            target: target,
+                        }) { return false; }
+                    }
+                    if next == 0 { return true; }
+                    addr = addr + next * stride;
+                }
+            };
+            for pagei in 0..sis.page_count as usize {
+                let page_start = some_or!(read_u16(page_starts_off + pagei * 2), {
+                    errln!("warning: chained fixups: page_start array truncated");
+                    break;
+                }) as u32;
+                if page_start == DYLD_CHAINED_PTR_START_NONE { continue; }
+                let page_off = (pagei as u64) * (sis.page_size as u64);
+                if page_start & DYLD_CHAINED_PTR_START_MULTI != 0 && !is64 {
+                    // index of a list of starts
+                    let mut idx = (page_start & !DYLD_CHAINED_PTR_START_MULTI) as usize;
+                    loop {
+                        let start = some_or!(read_u16(page_starts_off + idx * 2), {
+                            errln!("warning: chained fixups: chain_starts array truncated");
+                            break;
+                        }) as u32;
+                        if !walk_chain(page_off + (start & !DYLD_CHAINED_PTR_START_LAST) as u64) { return; }
+                        if start & DYLD_CHAINED_PTR_START_LAST != 0 { break; }
+                        idx += 1;
+                    }
+                } else {
+                    if !walk_chain(page_off + page_start as u64) { return; }
+                }
+            }
+        }
+    }
+
     pub fn parse_each_dyld_bind<'a>(&'a self, cb: &mut FnMut(&ParseDyldBindState<'a>) -> bool) {
         self.parse_dyld_bind(self.dyld_bind.get(), WhichBind::Bind, cb);
         self.parse_dyld_bind(self.dyld_weak_bind.get(), WhichBind::WeakBind, cb);
@@ -1519,6 +2239,11 @@ impl MachO {
             }
         }
     }
+    // LC_DYLD_EXPORTS_TRIE has the same format as the LC_DYLD_INFO one
+    fn export_trie(&self) -> &[ReadCell<u8>] {
+        if self.dyld_export.len() != 0 { self.dyld_export.get() } else { self.dyld_exports_trie.get() }
+    }
+
     fn parse_dyld_export<'a>(&'a self, dyld_export: &'a [ReadCell<u8>], search_for: Option<&'a ByteStr>, cb: &mut for<'b> FnMut(&'b ParseDyldExportState<'b>) -> bool) {
         if dyld_export.is_empty() { return; }
         enum State<'x> {
@@ -1664,7 +2389,7 @@ impl MachO {
 
     pub fn guess_broken_cache_slide(&self, dc: &DyldCache) -> GuessBrokenCacheSlideResult {
         let mut result = GuessBrokenCacheSlideResult::GotNoBindSelf;
-        let dyld_export = self.dyld_export.get();
+        let dyld_export = self.export_trie();
         self.parse_dyld_bind(self.dyld_lazy_bind.get(), WhichBind::LazyBind, &mut |bind_state: &ParseDyldBindState| {
             if bind_state.source_dylib == SourceLib::Self_ {
                 let symbol = some_or!(bind_state.symbol.as_ref(), return true);
@@ -1672,13 +2397,14 @@ impl MachO {
                 self.parse_dyld_export(dyld_export, Some(symbol), &mut |export_state: &ParseDyldExportState| -> bool {
                     debug_assert_eq!(&export_state.name, symbol);
                     let true_addr = export_state.addr;
-                    let seg_data = some_or!(bind_state.seg, return true).get_data();
-                    let seg_off = some_or!(bind_state.seg_off, return true) as usize;
+                    let seg = some_or!(bind_state.seg, return true);
+                    let seg_data = seg.get_data();
+                    let seg_off = some_or!(bind_state.seg_off, return true);
                     let broken_addr = VMA(dc.eb.ptr_from_slice(
-                        &seg_data[seg_off..seg_off + (dc.eb.pointer_size as usize)]));
-                    let this_guess = match dc.slide_info {
-                        None | Some(SlideInfo::V1(_)) => broken_addr.wrapping_sub(true_addr),
-                        Some(SlideInfo::V2(ref v2)) => {
+                        &seg_data[seg_off as usize..seg_off as usize + (dc.eb.pointer_size as usize)]));
+                    let this_guess = match dc.slide_info_for_addr(seg.vmaddr + seg_off) {
+                        None | Some(&SlideInfo::V1(_)) => broken_addr.wrapping_sub(true_addr),
+                        Some(&SlideInfo::V2(ref v2)) => {
                             let delta_mask = v2.delta_mask;
                             if broken_addr.0.wrapping_add(v2.value_add) & !delta_mask == true_addr.0 & !delta_mask {
                                 0
@@ -1689,7 +2415,16 @@ impl MachO {
                                 result = GuessBrokenCacheSlideResult::Inconsistent;
                                 return false;
                             }
-                        }
+                        },
+                        Some(&SlideInfo::V3(_)) | Some(&SlideInfo::V4(_)) => {
+                            // fix_data already un-chained these, so they should match exactly
+                            if broken_addr == true_addr {
+                                0
+                            } else {
+                                result = GuessBrokenCacheSlideResult::Inconsistent;
+                                return false;
+                            }
+                        },
                     };
                     match result {
                         GuessBrokenCacheSlideResult::GotNoBindSelf => result = GuessBrokenCacheSlideResult::Guess(this_guess),
@@ -1716,7 +2451,7 @@ impl MachO {
     }
     pub fn get_exported_symbol_list(&self, search_for: Option<&ByteStr>) -> Vec<Symbol<'static>> {
         let mut out = Vec::new();
-        self.parse_dyld_export(self.dyld_export.get(), search_for, &mut |state: &ParseDyldExportState| {
+        self.parse_dyld_export(self.export_trie(), search_for, &mut |state: &ParseDyldExportState| {
             out.push(Symbol {
                 name: state.name.to_owned().into(),
                 is_public: true,
@@ -0,0 +1,378 @@
+// Reading ObjC runtime metadata (the objc2 ABI: __objc_classlist and friends).  Works on
+// standalone binaries (following binds and chained fixups) and on images loaded out of a dyld
+// cache, where pointers may lead into other images.
+use util;
+use util::{ByteStr, ByteString};
+use exec::VMA;
+use MachO;
+use reader::ImageReader;
+
+const RO_META: u32 = 1;
+const METHOD_LIST_IS_RELATIVE: u32 = 0x80000000;
+const METHOD_LIST_SELECTORS_ARE_DIRECT: u32 = 0x40000000;
+const METHOD_LIST_ENTSIZE_MASK: u32 = 0xfffc;
+
+#[derive(Clone, Debug)]
+pub struct ObjCMethod {
+    pub name: ByteString,
+    pub types: ByteString,
+    pub imp: Option<VMA>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ObjCIvar {
+    pub name: ByteString,
+    pub type_: ByteString,
+    pub offset: Option<u32>,
+    pub size: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct ObjCProperty {
+    pub name: ByteString,
+    pub attributes: ByteString,
+}
+
+#[derive(Clone, Debug)]
+pub struct ObjCClass {
+    pub addr: VMA,
+    pub name: ByteString,
+    pub superclass: Option<ByteString>,
+    pub is_swift: bool,
+    pub flags: u32,
+    pub instance_size: u32,
+    pub protocols: Vec<ByteString>,
+    pub ivars: Vec<ObjCIvar>,
+    pub properties: Vec<ObjCProperty>,
+    pub instance_methods: Vec<ObjCMethod>,
+    pub class_methods: Vec<ObjCMethod>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ObjCCategory {
+    pub addr: VMA,
+    pub name: ByteString,
+    pub class_name: Option<ByteString>,
+    pub protocols: Vec<ByteString>,
+    pub properties: Vec<ObjCProperty>,
+    pub instance_methods: Vec<ObjCMethod>,
+    pub class_methods: Vec<ObjCMethod>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ObjCProtocol {
+    pub addr: VMA,
+    pub name: ByteString,
+    pub protocols: Vec<ByteString>,
+    pub properties: Vec<ObjCProperty>,
+    pub instance_methods: Vec<ObjCMethod>,
+    pub class_methods: Vec<ObjCMethod>,
+    pub optional_instance_methods: Vec<ObjCMethod>,
+    pub optional_class_methods: Vec<ObjCMethod>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ObjCInfo {
+    pub classes: Vec<ObjCClass>,
+    pub categories: Vec<ObjCCategory>,
+    pub protocols: Vec<ObjCProtocol>,
+}
+
+// _OBJC_CLASS_$_NSObject -> NSObject, etc.
+fn strip_symbol_prefix(sym: &ByteStr) -> ByteString {
+    for prefix in &["_OBJC_CLASS_$_", "_OBJC_METACLASS_$_", "__OBJC_PROTOCOL_$_"] {
+        if sym.starts_with(prefix.as_bytes()) {
+            return ByteString::from_bytes(&sym[prefix.len()..]);
+        }
+    }
+    sym.to_owned()
+}
+
+struct ObjCReader<'a> {
+    r: ImageReader<'a>,
+    mo: &'a MachO,
+    ps: u64,
+    data_mask: u64,
+    // lists flagged METHOD_LIST_SELECTORS_ARE_DIRECT use offsets from this rather than selrefs
+    sel_base: Option<VMA>,
+}
+
+impl<'a> ObjCReader<'a> {
+    fn new(mo: &'a MachO) -> ObjCReader<'a> {
+        let r = ImageReader::new(mo);
+        let ps = r.ps;
+        ObjCReader {
+            r: r,
+            mo: mo,
+            ps: ps,
+            data_mask: if ps == 8 { !7 } else { !3 },
+            sel_base: mo.dc_info.objc_relative_selector_base,
+        }
+    }
+
+    fn cstr_at_ptr(&self, field: VMA) -> ByteString {
+        let addr = some_or!(self.r.read_ptr(field), { return ByteString::from_str("?"); });
+        if addr.0 == 0 { return ByteString::from_str(""); }
+        self.r.read_cstr(addr).unwrap_or_else(|| {
+            errln!("warning: objc: can't read string at {} (from {})", addr, field);
+            ByteString::from_str("?")
+        })
+    }
+
+    fn list_header(&self, list: VMA, what: &str) -> Option<(u32, u32)> {
+        let hdr = some_or!(self.r.read(list, 8), {
+            errln!("warning: objc: can't read {} at {}", what, list);
+            return None;
+        });
+        Some(util::copy_from_slice(hdr, self.mo.eb.endian))
+    }
+
+    fn method_list(&self, list: VMA) -> Vec<ObjCMethod> {
+        let mut out = Vec::new();
+        if list.0 == 0 { return out; }
+        let (flags, count) = some_or!(self.list_header(list, "method list"), { return out; });
+        let relative = flags & METHOD_LIST_IS_RELATIVE != 0;
+        let direct = relative && flags & METHOD_LIST_SELECTORS_ARE_DIRECT != 0;
+        if direct && self.sel_base.is_none() {
+            errln!("warning: objc: method list at {} has direct selectors, but there's no selector base", list);
+            return out;
+        }
+        let entsize = (flags & METHOD_LIST_ENTSIZE_MASK) as u64;
+        if entsize < if relative { 12 } else { 3 * self.ps } {
+            errln!("warning: objc: method list at {} has bad entsize {}", list, entsize);
+            return out;
+        }
+        for i in 0..count as u64 {
+            let ent = list + 8 + i * entsize;
+            let method = if relative { self.relative_method(ent, direct) } else { self.method(ent) };
+            out.push(some_or!(method, {
+                errln!("warning: objc: can't read method at {}", ent);
+                break;
+            }));
+        }
+        out
+    }
+
+    fn method(&self, ent: VMA) -> Option<ObjCMethod> {
+        let imp = try_opt!(self.r.read_ptr(ent + 2 * self.ps));
+        Some(ObjCMethod {
+            name: self.cstr_at_ptr(ent),
+            types: self.cstr_at_ptr(ent + self.ps),
+            imp: if imp.0 == 0 { None } else { Some(imp) },
+        })
+    }
+
+    // three int32 offsets, each relative to itself: selector ref, types, imp
+    // (or, if direct, the selector itself relative to sel_base)
+    fn relative_method(&self, ent: VMA, direct: bool) -> Option<ObjCMethod> {
+        let rel = |field: u64| -> Option<(VMA, i32)> {
+            let off = try_opt!(self.r.read_u32(ent + field)) as i32;
+            Some(((ent + field).wrapping_add(off as i64 as u64), off))
+        };
+        let (selref, name_off) = try_opt!(rel(0));
+        let name = if direct {
+            self.r.read_cstr(self.sel_base.unwrap().wrapping_add(name_off as i64 as u64))
+        } else {
+            let sel = try_opt!(self.r.read_ptr(selref));
+            self.r.read_cstr(sel)
+        };
+        let (types, _) = try_opt!(rel(4));
+        let (imp, imp_off) = try_opt!(rel(8));
+        Some(ObjCMethod {
+            name: name.unwrap_or_else(|| ByteString::from_str("?")),
+            types: self.r.read_cstr(types).unwrap_or_else(|| ByteString::from_str("?")),
+            imp: if imp_off == 0 { None } else { Some(imp) },
+        })
+    }
+
+    fn ivar_list(&self, list: VMA) -> Vec<ObjCIvar> {
+        let mut out = Vec::new();
+        if list.0 == 0 { return out; }
+        let (entsize, count) = some_or!(self.list_header(list, "ivar list"), { return out; });
+        let entsize = entsize as u64;
+        if entsize < 3 * self.ps + 8 {
+            errln!("warning: objc: ivar list at {} has bad entsize {}", list, entsize);
+            return out;
+        }
+        for i in 0..count as u64 {
+            let ent = list + 8 + i * entsize;
+            let offset_ptr = some_or!(self.r.read_ptr(ent), break);
+            out.push(ObjCIvar {
+                name: self.cstr_at_ptr(ent + self.ps),
+                type_: self.cstr_at_ptr(ent + 2 * self.ps),
+                offset: if offset_ptr.0 == 0 { None } else { self.r.read_u32(offset_ptr) },
+                size: self.r.read_u32(ent + 3 * self.ps + 4).unwrap_or(0),
+            });
+        }
+        out
+    }
+
+    fn property_list(&self, list: VMA) -> Vec<ObjCProperty> {
+        let mut out = Vec::new();
+        if list.0 == 0 { return out; }
+        let (entsize, count) = some_or!(self.list_header(list, "property list"), { return out; });
+        let entsize = entsize as u64;
+        if entsize < 2 * self.ps {
+            errln!("warning: objc: property list at {} has bad entsize {}", list, entsize);
+            return out;
+        }
+        for i in 0..count as u64 {
+            let ent = list + 8 + i * entsize;
+            if self.r.read(ent, 2 * self.ps).is_none() {
+                errln!("warning: objc: can't read property at {}", ent);
+                break;
+            }
+            out.push(ObjCProperty {
+                name: self.cstr_at_ptr(ent),
+                attributes: self.cstr_at_ptr(ent + self.ps),
+            });
+        }
+        out
+    }
+
+    // pointer-sized count, then pointers to protocol_t
+    fn protocol_list(&self, list: VMA) -> Vec<ByteString> {
+        let mut out = Vec::new();
+        if list.0 == 0 { return out; }
+        let count = some_or!(self.r.read_ptr(list), { return out; }).0;
+        for i in 0..count {
+            let field = list + (i + 1) * self.ps;
+            if self.r.read(field, self.ps).is_none() {
+                errln!("warning: objc: can't read protocol list entry at {}", field);
+                break;
+            }
+            out.push(self.protocol_ref_name(field));
+        }
+        out
+    }
+
+    fn protocol_ref_name(&self, field: VMA) -> ByteString {
+        if let Some(sym) = self.r.bind_at(field) {
+            return strip_symbol_prefix(sym);
+        }
+        match self.r.read_ptr(field) {
+            Some(proto) if proto.0 != 0 => self.cstr_at_ptr(proto + self.ps),
+            _ => ByteString::from_str("?"),
+        }
+    }
+
+    fn class_ro(&self, cls: VMA) -> Option<(VMA, bool)> {
+        let data = try_opt!(self.r.read_ptr(cls + 4 * self.ps));
+        // low bits mark Swift classes
+        Some((VMA(data.0 & self.data_mask), data.0 & 3 != 0))
+    }
+
+    fn class_name(&self, cls: VMA) -> Option<ByteString> {
+        let (ro, _) = try_opt!(self.class_ro(cls));
+        Some(self.cstr_at_ptr(ro + 8 + 2 * self.ps))
+    }
+
+    // the name of the class pointed to by field, which might be an import
+    fn class_ref_name(&self, field: VMA) -> Option<ByteString> {
+        if let Some(sym) = self.r.bind_at(field) {
+            return Some(strip_symbol_prefix(sym));
+        }
+        let cls = try_opt!(self.r.read_ptr(field));
+        if cls.0 == 0 { return None; }
+        self.class_name(cls)
+    }
+
+    fn class(&self, cls: VMA) -> Option<ObjCClass> {
+        let (ro, is_swift) = try_opt!(self.class_ro(cls));
+        let flags = try_opt!(self.r.read_u32(ro));
+        if flags & RO_META != 0 {
+            errln!("warning: objc: class at {} is a metaclass", cls);
+        }
+        let field = |i: u64| self.r.read_ptr(ro + 8 + i * self.ps).unwrap_or(VMA(0));
+        let class_methods = match self.r.read_ptr(cls) {
+            Some(isa) if isa.0 != 0 && self.r.bind_at(cls).is_none() => {
+                match self.class_ro(isa) {
+                    Some((meta_ro, _)) => self.method_list(self.r.read_ptr(meta_ro + 8 + 3 * self.ps).unwrap_or(VMA(0))),
+                    None => Vec::new(),
+                }
+            },
+            _ => Vec::new(),
+        };
+        Some(ObjCClass {
+            addr: cls,
+            name: self.cstr_at_ptr(ro + 8 + 2 * self.ps),
+            superclass: self.class_ref_name(cls + self.ps),
+            is_swift: is_swift,
+            flags: flags,
+            instance_size: self.r.read_u32(ro + 8).unwrap_or(0),
+            protocols: self.protocol_list(field(4)),
+            ivars: self.ivar_list(field(5)),
+            properties: self.property_list(field(7)),
+            instance_methods: self.method_list(field(3)),
+            class_methods: class_methods,
+        })
+    }
+
+    fn category(&self, cat: VMA) -> Option<ObjCCategory> {
+        let field = |i: u64| self.r.read_ptr(cat + i * self.ps);
+        Some(ObjCCategory {
+            addr: cat,
+            name: self.cstr_at_ptr(cat),
+            class_name: self.class_ref_name(cat + self.ps),
+            protocols: self.protocol_list(try_opt!(field(4))),
+            properties: self.property_list(try_opt!(field(5))),
+            instance_methods: self.method_list(try_opt!(field(2))),
+            class_methods: self.method_list(try_opt!(field(3))),
+        })
+    }
+
+    fn protocol(&self, proto: VMA) -> Option<ObjCProtocol> {
+        let field = |i: u64| self.r.read_ptr(proto + i * self.ps);
+        Some(ObjCProtocol {
+            addr: proto,
+            name: self.cstr_at_ptr(proto + self.ps),
+            protocols: self.protocol_list(try_opt!(field(2))),
+            instance_methods: self.method_list(try_opt!(field(3))),
+            class_methods: self.method_list(try_opt!(field(4))),
+            optional_instance_methods: self.method_list(try_opt!(field(5))),
+            optional_class_methods: self.method_list(try_opt!(field(6))),
+            properties: self.property_list(try_opt!(field(7))),
+        })
+    }
+
+    // pointers in every section with this name (there can be one per __DATA* segment)
+    fn section_pointers(&self, sectname: &str) -> Vec<VMA> {
+        let mut out = Vec::new();
+        for sect in self.r.sections_named(sectname) {
+            for i in 0..sect.vmsize / self.ps {
+                let ptr = some_or!(self.r.read_ptr(sect.vmaddr + i * self.ps), {
+                    errln!("warning: objc: can't read {}", sectname);
+                    break;
+                });
+                if ptr.0 != 0 { out.push(ptr); }
+            }
+        }
+        out
+    }
+}
+
+impl MachO {
+    pub fn parse_objc(&self) -> ObjCInfo {
+        let reader = ObjCReader::new(self);
+        let mut info = ObjCInfo::default();
+        for cls in reader.section_pointers("__objc_classlist") {
+            match reader.class(cls) {
+                Some(c) => info.classes.push(c),
+                None => errln!("warning: objc: can't read class at {}", cls),
+            }
+        }
+        for cat in reader.section_pointers("__objc_catlist") {
+            match reader.category(cat) {
+                Some(c) => info.categories.push(c),
+                None => errln!("warning: objc: can't read category at {}", cat),
+            }
+        }
+        for proto in reader.section_pointers("__objc_protolist") {
+            match reader.protocol(proto) {
+                Some(p) => info.protocols.push(p),
+                None => errln!("warning: objc: can't read protocol at {}", proto),
+            }
+        }
+        info
+    }
+}
@@ -0,0 +1,105 @@
+// Following pointers around an image without relocating it first: binds and chained fixups are
+// looked up by address, and for images loaded out of a dyld cache, anything outside the image is
+// read from the rest of the cache.  Used by the objc and swift modules.
+use util;
+use util::{ByteStr, ByteString, Fnv, SliceExt};
+use exec;
+use exec::{VMA, ReadVMA};
+use std::collections::HashMap;
+use std::cell::Cell;
+use ::{MachO, ChainedFixupTarget};
+
+fn get_sane_in(segs: &[exec::Segment], addr: VMA, size: u64) -> Option<&[Cell<u8>]> {
+    let (seg, off, avail) = try_opt!(exec::addr_to_seg_off_range(segs, addr));
+    if size > avail { return None; }
+    let data = try_opt!(seg.data.as_ref());
+    data.get_mut().slice_opt(off as usize, (off + size) as usize)
+}
+
+pub struct ImageReader<'a> {
+    pub mo: &'a MachO,
+    pub ps: u64,
+    // pointers that are fixups rather than plain values
+    rebases: HashMap<VMA, VMA, Fnv>,
+    binds: HashMap<VMA, &'a ByteStr, Fnv>,
+}
+
+impl<'a> ImageReader<'a> {
+    pub fn new(mo: &'a MachO) -> ImageReader<'a> {
+        let mut rebases = util::new_fnv_hashmap();
+        let mut binds = util::new_fnv_hashmap();
+        mo.parse_each_dyld_bind(&mut |state| {
+            if let (Some(seg), Some(seg_off), Some(sym)) = (state.seg, state.seg_off, state.symbol) {
+                binds.insert(seg.vmaddr + seg_off, sym);
+            }
+            true
+        });
+        let imports = mo.get_chained_imports();
+        mo.parse_chained_fixups(&mut |fixup| {
+            match fixup.target {
+                ChainedFixupTarget::Rebase(target) => { rebases.insert(fixup.addr, target); },
+                ChainedFixupTarget::Bind { import, .. } => {
+                    if let Some(imp) = imports.get(import) {
+                        binds.insert(fixup.addr, imp.name);
+                    }
+                },
+            }
+            true
+        });
+        ImageReader {
+            mo: mo,
+            ps: mo.eb.pointer_size as u64,
+            rebases: rebases,
+            binds: binds,
+        }
+    }
+
+    pub fn read(&self, addr: VMA, size: u64) -> Option<&'a [Cell<u8>]> {
+        self.mo.eb.get_sane(addr, size).or_else(|| {
+            let segs = try_opt!(self.mo.dc_info.cache_segments.as_ref());
+            get_sane_in(segs, addr, size)
+        })
+    }
+
+    pub fn read_u32(&self, addr: VMA) -> Option<u32> {
+        Some(util::copy_from_slice(try_opt!(self.read(addr, 4)), self.mo.eb.endian))
+    }
+
+    // The name of the symbol bound at addr, if any.
+    pub fn bind_at(&self, addr: VMA) -> Option<&'a ByteStr> {
+        self.binds.get(&addr).map(|&sym| sym)
+    }
+
+    // Returns VMA(0) for binds, since whatever's in the file isn't meaningful.
+    pub fn read_ptr(&self, addr: VMA) -> Option<VMA> {
+        if let Some(&target) = self.rebases.get(&addr) {
+            return Some(target);
+        }
+        if self.binds.contains_key(&addr) {
+            return Some(VMA(0));
+        }
+        let mut val = self.mo.eb.ptr_from_slice(try_opt!(self.read(addr, self.ps)));
+        if self.mo.eb.arch == exec::arch::AArch64 {
+            // top byte ignore / leftover pointer auth bits
+            val &= !(0xffu64 << 56);
+        }
+        Some(VMA(val))
+    }
+
+    pub fn read_cstr(&self, addr: VMA) -> Option<ByteString> {
+        if let Some(s) = exec::read_cstr(&self.mo.eb, addr) {
+            return Some(s);
+        }
+        let segs = try_opt!(self.mo.dc_info.cache_segments.as_ref());
+        let (seg, off, _) = try_opt!(exec::addr_to_seg_off_range(segs, addr));
+        let data = try_opt!(seg.data.as_ref());
+        util::from_cstr_strict(&data.get()[off as usize..]).map(|s| s.to_owned())
+    }
+
+    // Every section with this name (there can be one per __DATA* segment).
+    pub fn sections_named(&self, sectname: &str) -> Vec<&'a exec::Segment> {
+        self.mo.eb.sections.iter().filter(|sect| {
+            sect.name.as_ref().map(|n| &n[..]) == Some(ByteStr::from_str(sectname))
+        }).collect()
+    }
+}
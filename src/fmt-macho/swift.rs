@@ -0,0 +1,349 @@
+// Reading Swift reflection metadata: nominal type descriptors (__swift5_types), protocol
+// conformance records (__swift5_proto) and the field descriptors the type descriptors point to
+// (__swift5_fieldmd, with names in __swift5_reflstr).  All of it is linked together with 32-bit
+// offsets relative to the field they're stored in; some of those can be "indirect", pointing at
+// a pointer (often a bind) instead of at the target.
+use util::{ByteStr, ByteString};
+use exec::VMA;
+use MachO;
+use reader::ImageReader;
+use swift_demangle;
+
+pub const CONTEXT_KIND_MODULE: u32 = 0;
+pub const CONTEXT_KIND_EXTENSION: u32 = 1;
+pub const CONTEXT_KIND_ANONYMOUS: u32 = 2;
+pub const CONTEXT_KIND_PROTOCOL: u32 = 3;
+pub const CONTEXT_KIND_OPAQUE_TYPE: u32 = 4;
+pub const CONTEXT_KIND_CLASS: u32 = 16;
+pub const CONTEXT_KIND_STRUCT: u32 = 17;
+pub const CONTEXT_KIND_ENUM: u32 = 18;
+
+// TypeReferenceKind, in the low bits of __swift5_types entries and in conformance flags
+const TYPE_REF_DIRECT_DESCRIPTOR: u32 = 0;
+const TYPE_REF_INDIRECT_DESCRIPTOR: u32 = 1;
+const TYPE_REF_DIRECT_OBJC_CLASS_NAME: u32 = 2;
+const TYPE_REF_INDIRECT_OBJC_CLASS: u32 = 3;
+
+pub const FIELD_IS_INDIRECT_CASE: u32 = 1;
+pub const FIELD_IS_VAR: u32 = 2;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SwiftTypeKind {
+    Class,
+    Struct,
+    Enum,
+}
+
+impl SwiftTypeKind {
+    pub fn keyword(self) -> &'static str {
+        match self {
+            SwiftTypeKind::Class => "class",
+            SwiftTypeKind::Struct => "struct",
+            SwiftTypeKind::Enum => "enum",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SwiftField {
+    pub name: ByteString,
+    // None for enum cases without a payload
+    pub type_name: Option<String>,
+    pub flags: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct SwiftType {
+    pub addr: VMA,
+    pub kind: SwiftTypeKind,
+    pub name: String,
+    pub superclass: Option<String>,
+    pub fields: Vec<SwiftField>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SwiftConformance {
+    pub addr: VMA,
+    pub type_name: String,
+    pub protocol: String,
+    pub flags: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SwiftInfo {
+    pub types: Vec<SwiftType>,
+    pub conformances: Vec<SwiftConformance>,
+}
+
+enum RelTarget<'a> {
+    Addr(VMA),
+    Symbol(&'a ByteStr),
+}
+
+// "nominal type descriptor for Swift.Int" -> "Swift.Int"
+fn symbol_target_name(sym: &ByteStr) -> String {
+    let sym = sym.lossy();
+    if sym.starts_with("_OBJC_CLASS_$_") {
+        return sym["_OBJC_CLASS_$_".len()..].to_owned();
+    }
+    let demangled = some_or!(swift_demangle::demangle(&sym), { return sym.into_owned(); });
+    for prefix in &["nominal type descriptor for ", "protocol descriptor for ", "type metadata for "] {
+        if demangled.starts_with(prefix) {
+            return demangled[prefix.len()..].to_owned();
+        }
+    }
+    demangled
+}
+
+struct SwiftReader<'a> {
+    r: ImageReader<'a>,
+}
+
+impl<'a> SwiftReader<'a> {
+    fn read_i32(&self, field: VMA) -> Option<i32> {
+        self.r.read_u32(field).map(|x| x as i32)
+    }
+
+    fn rel_direct(&self, field: VMA) -> Option<VMA> {
+        let off = try_opt!(self.read_i32(field));
+        if off == 0 { return None; }
+        Some(field.wrapping_add(off as i64 as u64))
+    }
+
+    // the low bit means the target is a pointer to the real target
+    fn rel_indirectable(&self, field: VMA) -> Option<RelTarget<'a>> {
+        let off = try_opt!(self.read_i32(field));
+        if off == 0 { return None; }
+        let target = field.wrapping_add((off & !1) as i64 as u64);
+        if off & 1 == 0 { return Some(RelTarget::Addr(target)); }
+        self.deref(target)
+    }
+
+    fn deref(&self, ptr: VMA) -> Option<RelTarget<'a>> {
+        if let Some(sym) = self.r.bind_at(ptr) {
+            return Some(RelTarget::Symbol(sym));
+        }
+        let target = try_opt!(self.r.read_ptr(ptr));
+        if target.0 == 0 { None } else { Some(RelTarget::Addr(target)) }
+    }
+
+    fn target_name(&self, target: RelTarget) -> String {
+        match target {
+            RelTarget::Addr(desc) => self.context_name(desc, 0),
+            RelTarget::Symbol(sym) => symbol_target_name(sym),
+        }
+    }
+
+    fn cstr_at_rel(&self, field: VMA) -> String {
+        self.rel_direct(field).and_then(|addr| self.r.read_cstr(addr))
+            .map_or_else(|| "?".to_owned(), |s| s.lossy().into_owned())
+    }
+
+    // The full dotted name of a context descriptor, following parents up to the module.
+    fn context_name(&self, desc: VMA, depth: usize) -> String {
+        let flags = some_or!(self.r.read_u32(desc), {
+            errln!("warning: swift: can't read context descriptor at {}", desc);
+            return "?".to_owned();
+        });
+        let parent = if depth > 32 { None } else { self.rel_indirectable(desc + 4) };
+        let parent_name = parent.map(|p| match p {
+            RelTarget::Addr(p) => self.context_name(p, depth + 1),
+            RelTarget::Symbol(sym) => symbol_target_name(sym),
+        });
+        let name = match flags & 0x1f {
+            CONTEXT_KIND_MODULE | CONTEXT_KIND_PROTOCOL | CONTEXT_KIND_CLASS |
+            CONTEXT_KIND_STRUCT | CONTEXT_KIND_ENUM => self.cstr_at_rel(desc + 8),
+            CONTEXT_KIND_EXTENSION => {
+                // named after the extended type, not the module it's in
+                if let Some(mangled) = self.rel_direct(desc + 8) {
+                    return self.mangled_type_name(mangled);
+                }
+                return parent_name.unwrap_or_else(|| "?".to_owned());
+            },
+            CONTEXT_KIND_ANONYMOUS | CONTEXT_KIND_OPAQUE_TYPE => {
+                return parent_name.unwrap_or_else(|| "?".to_owned());
+            },
+            kind => format!("<context kind {}>", kind),
+        };
+        match parent_name {
+            Some(parent) => format!("{}.{}", parent, name),
+            None => name,
+        }
+    }
+
+    // Mangled names are NUL-terminated, but can contain symbolic references: a byte from 1 to
+    // 0x17 followed by an int32 offset, or 0x18 to 0x1f followed by a pointer.
+    fn read_mangled(&self, addr: VMA) -> Option<Vec<u8>> {
+        let mut out = Vec::new();
+        loop {
+            let c = try_opt!(self.r.read(addr + out.len() as u64, 1));
+            let c = c[0].get();
+            let extra = match c {
+                0 => return Some(out),
+                0x01...0x17 => 4,
+                0x18...0x1f => self.r.ps,
+                _ => 0,
+            };
+            out.push(c);
+            if extra != 0 {
+                let rest = try_opt!(self.r.read(addr + out.len() as u64, extra));
+                out.extend(rest.iter().map(|c| c.get()));
+            }
+        }
+    }
+
+    fn symbolic_reference(&self, addr: VMA, kind: u8, at: usize) -> Option<String> {
+        let target = try_opt!(self.rel_direct(addr + at as u64 + 1));
+        match kind {
+            // context descriptor
+            1 => Some(self.context_name(target, 0)),
+            2 => self.deref(target).map(|t| self.target_name(t)),
+            _ => None,
+        }
+    }
+
+    fn mangled_type_name(&self, addr: VMA) -> String {
+        let mangled = some_or!(self.read_mangled(addr), {
+            errln!("warning: swift: can't read mangled name at {}", addr);
+            return "?".to_owned();
+        });
+        let resolve = |kind: u8, at: usize| self.symbolic_reference(addr, kind, at);
+        swift_demangle::demangle_type(&mangled, &resolve).unwrap_or_else(|| {
+            // leave it mangled, minus the unprintable bits
+            let mut out = String::new();
+            let mut i = 0;
+            while i < mangled.len() {
+                match mangled[i] {
+                    0x01...0x17 => { out.push_str("{symbolic}"); i += 5; },
+                    0x18...0x1f => { out.push_str("{symbolic}"); i += 1 + self.r.ps as usize; },
+                    c => { out.push(c as char); i += 1; },
+                }
+            }
+            out
+        })
+    }
+
+    fn fields(&self, fd: VMA) -> Option<(Option<String>, Vec<SwiftField>)> {
+        let superclass = self.rel_direct(fd + 4).map(|m| self.mangled_type_name(m));
+        // u16 kind, u16 record size
+        let record_size = try_opt!(self.r.read_u32(fd + 8)) >> 16;
+        let count = try_opt!(self.r.read_u32(fd + 12));
+        if record_size < 12 {
+            errln!("warning: swift: field descriptor at {} has bad record size {}", fd, record_size);
+            return None;
+        }
+        let mut fields = Vec::new();
+        for i in 0..count as u64 {
+            let rec = fd + 16 + i * record_size as u64;
+            let flags = some_or!(self.r.read_u32(rec), {
+                errln!("warning: swift: can't read field record at {}", rec);
+                break;
+            });
+            let name = self.rel_direct(rec + 8).and_then(|addr| self.r.read_cstr(addr));
+            fields.push(SwiftField {
+                name: name.unwrap_or_else(|| ByteString::from_str("?")),
+                type_name: self.rel_direct(rec + 4).map(|m| self.mangled_type_name(m)),
+                flags: flags,
+            });
+        }
+        Some((superclass, fields))
+    }
+
+    fn type_(&self, desc: VMA) -> Option<SwiftType> {
+        let flags = try_opt!(self.r.read_u32(desc));
+        let kind = match flags & 0x1f {
+            CONTEXT_KIND_CLASS => SwiftTypeKind::Class,
+            CONTEXT_KIND_STRUCT => SwiftTypeKind::Struct,
+            CONTEXT_KIND_ENUM => SwiftTypeKind::Enum,
+            other => {
+                errln!("warning: swift: type descriptor at {} has unexpected kind {}", desc, other);
+                return None;
+            },
+        };
+        let (superclass, fields) = match self.rel_direct(desc + 16) {
+            Some(fd) => self.fields(fd).unwrap_or((None, Vec::new())),
+            None => (None, Vec::new()),
+        };
+        Some(SwiftType {
+            addr: desc,
+            kind: kind,
+            name: self.context_name(desc, 0),
+            superclass: superclass,
+            fields: fields,
+        })
+    }
+
+    // field is the int32 offset; kind says what it points to
+    fn type_ref_name(&self, field: VMA, kind: u32) -> Option<String> {
+        let target = try_opt!(self.rel_direct(field));
+        Some(match kind {
+            TYPE_REF_DIRECT_DESCRIPTOR => self.context_name(target, 0),
+            TYPE_REF_INDIRECT_DESCRIPTOR => self.target_name(try_opt!(self.deref(target))),
+            TYPE_REF_DIRECT_OBJC_CLASS_NAME => try_opt!(self.r.read_cstr(target)).lossy().into_owned(),
+            TYPE_REF_INDIRECT_OBJC_CLASS => {
+                match try_opt!(self.deref(target)) {
+                    RelTarget::Symbol(sym) => symbol_target_name(sym),
+                    RelTarget::Addr(cls) => format!("<objc class at {}>", cls),
+                }
+            },
+            _ => return None,
+        })
+    }
+
+    fn conformance(&self, desc: VMA) -> Option<SwiftConformance> {
+        let flags = try_opt!(self.r.read_u32(desc + 12));
+        let protocol = match self.rel_indirectable(desc) {
+            Some(target) => self.target_name(target),
+            None => "?".to_owned(),
+        };
+        Some(SwiftConformance {
+            addr: desc,
+            type_name: self.type_ref_name(desc + 4, (flags >> 3) & 7).unwrap_or_else(|| "?".to_owned()),
+            protocol: protocol,
+            flags: flags,
+        })
+    }
+
+    // targets of the int32 offsets in every section with this name
+    fn section_entries(&self, sectname: &str, mask: u32) -> Vec<(VMA, u32)> {
+        let mut out = Vec::new();
+        for sect in self.r.sections_named(sectname) {
+            for i in 0..sect.vmsize / 4 {
+                let field = sect.vmaddr + i * 4;
+                let raw = some_or!(self.r.read_u32(field), {
+                    errln!("warning: swift: can't read {}", sectname);
+                    break;
+                });
+                let off = (raw & !mask) as i32;
+                if off == 0 { continue; }
+                out.push((field.wrapping_add(off as i64 as u64), raw & mask));
+            }
+        }
+        out
+    }
+}
+
+impl MachO {
+    pub fn parse_swift(&self) -> SwiftInfo {
+        let reader = SwiftReader { r: ImageReader::new(self) };
+        let mut info = SwiftInfo::default();
+        for (target, kind) in reader.section_entries("__swift5_types", 3) {
+            let desc = match kind {
+                TYPE_REF_DIRECT_DESCRIPTOR => Some(target),
+                TYPE_REF_INDIRECT_DESCRIPTOR => reader.r.read_ptr(target),
+                _ => None,
+            };
+            match desc.and_then(|desc| reader.type_(desc)) {
+                Some(ty) => info.types.push(ty),
+                None => errln!("warning: swift: can't read type at {}", target),
+            }
+        }
+        for (desc, _) in reader.section_entries("__swift5_proto", 0) {
+            match reader.conformance(desc) {
+                Some(conf) => info.conformances.push(conf),
+                None => errln!("warning: swift: can't read conformance at {}", desc),
+            }
+        }
+        info
+    }
+}
@@ -0,0 +1,754 @@
+// A demangler for Swift names (the Swift 5 '$s' scheme; '$S' and '_T0' are close enough that
+// the same code mostly works).  It handles what tends to show up in the symbol tables of
+// frameworks - types, functions, properties, initializers, metadata, descriptors, witness tables
+// and protocol witnesses - and gives up (returning None) on anything fancier, such as generic
+// requirements, closures, specializations and punycode.
+
+// Based on swift/lib/Demangling/Demangler.cpp, which works the same way: each operator pops its
+// operands off a stack of already-demangled nodes.
+#[derive(Clone, Debug)]
+enum Node {
+    Ident(String),
+    Module(String),
+    Type(String),
+    Tuple(Vec<String>),
+    Func(FuncSig),
+    Entity(String),
+    GenericSig(String),
+    EmptyList,
+    FirstElementMarker,
+    VariadicMarker,
+    ThrowsMarker,
+    AsyncMarker,
+}
+
+#[derive(Clone, Debug)]
+struct FuncSig {
+    params: Vec<String>,
+    params_is_tuple: bool,
+    result: String,
+    is_async: bool,
+    throws: bool,
+}
+
+impl FuncSig {
+    fn describe(&self, labels: Option<&[Option<String>]>) -> String {
+        let params: Vec<String> = self.params.iter().enumerate().map(|(i, param)| {
+            match labels.and_then(|labels| labels.get(i)) {
+                Some(&Some(ref label)) => format!("{}: {}", label, param),
+                _ => param.clone(),
+            }
+        }).collect();
+        format!("({}){}{} -> {}", params.join(", "),
+                if self.is_async { " async" } else { "" },
+                if self.throws { " throws" } else { "" },
+                self.result)
+    }
+}
+
+fn standard_type(c: u8) -> Option<&'static str> {
+    Some(match c {
+        b'A' => "AutoreleasingUnsafeMutablePointer",
+        b'a' => "Array",
+        b'b' => "Bool",
+        b'c' => "UnicodeScalar",
+        b'D' => "Dictionary",
+        b'd' => "Double",
+        b'f' => "Float",
+        b'h' => "Set",
+        b'I' => "DefaultIndices",
+        b'i' => "Int",
+        b'J' => "Character",
+        b'N' => "ClosedRange",
+        b'n' => "Range",
+        b'O' => "ObjectIdentifier",
+        b'P' => "UnsafePointer",
+        b'p' => "UnsafeMutablePointer",
+        b'R' => "UnsafeBufferPointer",
+        b'r' => "UnsafeMutableBufferPointer",
+        b'S' => "String",
+        b's' => "Substring",
+        b'u' => "UInt",
+        b'V' => "UnsafeRawPointer",
+        b'v' => "UnsafeMutableRawPointer",
+        b'W' => "UnsafeRawBufferPointer",
+        b'w' => "UnsafeMutableRawBufferPointer",
+        b'q' => "Optional",
+        b'B' => "BinaryFloatingPoint",
+        b'E' => "Encodable",
+        b'e' => "Decodable",
+        b'F' => "FloatingPoint",
+        b'G' => "RandomNumberGenerator",
+        b'H' => "Hashable",
+        b'j' => "Numeric",
+        b'K' => "BidirectionalCollection",
+        b'k' => "RandomAccessCollection",
+        b'L' => "Comparable",
+        b'l' => "Collection",
+        b'M' => "MutableCollection",
+        b'm' => "RangeReplaceableCollection",
+        b'Q' => "Equatable",
+        b'T' => "Sequence",
+        b't' => "IteratorProtocol",
+        b'U' => "UnsignedInteger",
+        b'X' => "RangeExpression",
+        b'x' => "Strideable",
+        b'Y' => "RawRepresentable",
+        b'y' => "StringProtocol",
+        b'Z' => "SignedInteger",
+        b'z' => "BinaryInteger",
+        _ => return None,
+    })
+}
+
+fn generic_param_name(depth: usize, mut index: usize) -> String {
+    let mut name = String::new();
+    while index >= 26 {
+        name.push((b'A' + (index % 26) as u8) as char);
+        index /= 26;
+    }
+    name.push((b'A' + index as u8) as char);
+    if depth != 0 { name.push_str(&depth.to_string()); }
+    name
+}
+
+fn tuple_str(elems: &[String]) -> String {
+    format!("({})", elems.join(", "))
+}
+
+fn is_word_start(c: u8) -> bool {
+    !(c >= b'0' && c <= b'9') && c != b'_' && c != 0
+}
+
+fn is_word_end(c: u8, prev: u8) -> bool {
+    c == b'_' || c == 0 || (!(prev >= b'A' && prev <= b'Z') && c >= b'A' && c <= b'Z')
+}
+
+const MAX_WORDS: usize = 26;
+const MAX_REPEAT_COUNT: usize = 2048;
+
+struct Demangler<'a> {
+    text: &'a [u8],
+    pos: usize,
+    stack: Vec<Node>,
+    substs: Vec<Node>,
+    words: Vec<String>,
+    // resolves symbolic references (kind byte, offset of the kind byte in text)
+    resolve: &'a Fn(u8, usize) -> Option<String>,
+}
+
+impl<'a> Demangler<'a> {
+    fn new(text: &'a [u8], resolve: &'a Fn(u8, usize) -> Option<String>) -> Demangler<'a> {
+        Demangler {
+            text: text,
+            pos: 0,
+            stack: Vec::new(),
+            substs: Vec::new(),
+            words: Vec::new(),
+            resolve: resolve,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek();
+        if c.is_some() { self.pos += 1; }
+        c
+    }
+
+    fn next_if(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) { self.pos += 1; true } else { false }
+    }
+
+    fn natural(&mut self) -> Option<usize> {
+        let start = self.pos;
+        let mut n: usize = 0;
+        while let Some(c @ b'0'...b'9') = self.peek() {
+            n = try_opt!(n.checked_mul(10).and_then(|n| n.checked_add((c - b'0') as usize)));
+            self.pos += 1;
+        }
+        if self.pos == start { None } else { Some(n) }
+    }
+
+    // '_' is 0, N '_' is N + 1
+    fn index(&mut self) -> Option<usize> {
+        if self.next_if(b'_') { return Some(0); }
+        let n = try_opt!(self.natural());
+        if self.next_if(b'_') { n.checked_add(1) } else { None }
+    }
+
+    fn push(&mut self, node: Node) {
+        self.stack.push(node);
+    }
+
+    fn push_subst(&mut self, node: Node) {
+        self.substs.push(node.clone());
+        self.stack.push(node);
+    }
+
+    fn pop_if<F>(&mut self, pred: F) -> Option<Node> where F: FnOnce(&Node) -> bool {
+        if self.stack.last().map_or(false, pred) { self.stack.pop() } else { None }
+    }
+
+    fn pop_type(&mut self) -> Option<String> {
+        match self.stack.pop() {
+            Some(Node::Type(s)) => Some(s),
+            Some(Node::Tuple(elems)) => Some(tuple_str(&elems)),
+            Some(Node::Func(sig)) => Some(sig.describe(None)),
+            _ => None,
+        }
+    }
+
+    fn pop_ident(&mut self) -> Option<String> {
+        match self.stack.pop() {
+            Some(Node::Ident(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn pop_module(&mut self) -> Option<String> {
+        match self.stack.pop() {
+            Some(Node::Ident(s)) | Some(Node::Module(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn pop_context(&mut self) -> Option<String> {
+        match self.stack.pop() {
+            Some(Node::Ident(s)) | Some(Node::Module(s)) | Some(Node::Type(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn pop_entity(&mut self) -> Option<String> {
+        match self.stack.pop() {
+            Some(Node::Entity(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    // type protocol module
+    fn pop_conformance(&mut self) -> Option<String> {
+        let module = try_opt!(self.pop_module());
+        let proto = try_opt!(self.pop_type());
+        let ty = try_opt!(self.pop_type());
+        Some(format!("{} : {} in {}", ty, proto, module))
+    }
+
+    fn pop_generic_sig(&mut self) -> String {
+        match self.pop_if(|n| if let Node::GenericSig(_) = *n { true } else { false }) {
+            Some(Node::GenericSig(s)) => s,
+            _ => String::new(),
+        }
+    }
+
+    // returns (params, is_tuple)
+    fn pop_params(&mut self) -> Option<(Vec<String>, bool)> {
+        match self.stack.pop() {
+            Some(Node::EmptyList) => Some((Vec::new(), false)),
+            Some(Node::Tuple(elems)) => Some((elems, true)),
+            Some(Node::Type(s)) => Some((vec![s], false)),
+            Some(Node::Func(sig)) => Some((vec![sig.describe(None)], false)),
+            _ => None,
+        }
+    }
+
+    // result type, then parameters, then async/throws
+    fn pop_function_type(&mut self) -> Option<FuncSig> {
+        let throws = self.pop_if(|n| if let Node::ThrowsMarker = *n { true } else { false }).is_some();
+        let is_async = self.pop_if(|n| if let Node::AsyncMarker = *n { true } else { false }).is_some();
+        let (params, params_is_tuple) = try_opt!(self.pop_params());
+        let (results, result_is_tuple) = try_opt!(self.pop_params());
+        Some(FuncSig {
+            params: params,
+            params_is_tuple: params_is_tuple,
+            result: if results.len() == 1 && !result_is_tuple { results[0].clone() } else { tuple_str(&results) },
+            is_async: is_async,
+            throws: throws,
+        })
+    }
+
+    // Either 'y' (no labels) or, if the parameters are a tuple, one identifier or '_' per
+    // parameter.
+    fn pop_labels(&mut self, sig: &FuncSig) -> Option<Vec<Option<String>>> {
+        if self.pop_if(|n| if let Node::EmptyList = *n { true } else { false }).is_some() {
+            return None;
+        }
+        if !sig.params_is_tuple || sig.params.is_empty() { return None; }
+        let mut labels = Vec::new();
+        for _ in 0..sig.params.len() {
+            match self.stack.last() {
+                Some(&Node::Ident(ref s)) => labels.push(Some(s.clone())),
+                Some(&Node::FirstElementMarker) => labels.push(None),
+                _ => return None,
+            }
+            self.stack.pop();
+        }
+        labels.reverse();
+        Some(labels)
+    }
+
+    fn identifier(&mut self) -> Option<()> {
+        let mut has_word_substs = false;
+        if self.next_if(b'0') {
+            if self.peek() == Some(b'0') {
+                // punycode
+                return None;
+            }
+            has_word_substs = true;
+        }
+        let mut ident = String::new();
+        loop {
+            while has_word_substs {
+                let c = some_or!(self.peek(), break);
+                let idx = match c {
+                    b'a'...b'z' => (c - b'a') as usize,
+                    b'A'...b'Z' => { has_word_substs = false; (c - b'A') as usize },
+                    _ => break,
+                };
+                self.pos += 1;
+                ident.push_str(try_opt!(self.words.get(idx)));
+            }
+            if self.next_if(b'0') { break; }
+            let len = try_opt!(self.natural());
+            if len == 0 || self.pos + len > self.text.len() { return None; }
+            let slice = &self.text[self.pos..self.pos + len];
+            ident.push_str(&String::from_utf8_lossy(slice));
+            let mut word_start: Option<usize> = None;
+            for i in 0..len + 1 {
+                let c = if i < len { slice[i] } else { 0 };
+                if let Some(start) = word_start {
+                    if is_word_end(c, slice[i - 1]) {
+                        if i - start >= 2 && self.words.len() < MAX_WORDS {
+                            self.words.push(String::from_utf8_lossy(&slice[start..i]).into_owned());
+                        }
+                        word_start = None;
+                    }
+                }
+                if word_start.is_none() && is_word_start(c) { word_start = Some(i); }
+            }
+            self.pos += len;
+            if !has_word_substs { break; }
+        }
+        self.push_subst(Node::Ident(ident));
+        Some(())
+    }
+
+    fn multi_substitution(&mut self) -> Option<()> {
+        let mut repeat: Option<usize> = None;
+        loop {
+            let c = try_opt!(self.next());
+            match c {
+                b'a'...b'z' | b'A'...b'Z' => {
+                    let idx = (if c >= b'a' { c - b'a' } else { c - b'A' }) as usize;
+                    let node = try_opt!(self.substs.get(idx)).clone();
+                    for _ in 0..repeat.unwrap_or(1) { self.push(node.clone()); }
+                    if c <= b'Z' { return Some(()); }
+                    repeat = None;
+                },
+                b'_' => {
+                    let idx = repeat.map_or(26, |r| r + 27);
+                    let node = try_opt!(self.substs.get(idx)).clone();
+                    self.push(node);
+                    return Some(());
+                },
+                b'0'...b'9' => {
+                    self.pos -= 1;
+                    let r = try_opt!(self.natural());
+                    if r > MAX_REPEAT_COUNT { return None; }
+                    repeat = Some(r);
+                },
+                _ => return None,
+            }
+        }
+    }
+
+    fn standard_substitution(&mut self) -> Option<()> {
+        match try_opt!(self.next()) {
+            b'o' => self.push(Node::Module("__C".to_owned())),
+            b'C' => self.push(Node::Module("__C_Synthesized".to_owned())),
+            b'g' => {
+                let ty = try_opt!(self.pop_type());
+                self.push_subst(Node::Type(format!("{}?", ty)));
+            },
+            b'0'...b'9' => {
+                self.pos -= 1;
+                let repeat = try_opt!(self.natural());
+                if repeat > MAX_REPEAT_COUNT { return None; }
+                let ty = format!("Swift.{}", try_opt!(self.next().and_then(standard_type)));
+                for _ in 0..repeat { self.push(Node::Type(ty.clone())); }
+            },
+            c => self.push(Node::Type(format!("Swift.{}", try_opt!(standard_type(c))))),
+        }
+        Some(())
+    }
+
+    fn bound_generic(&mut self) -> Option<()> {
+        let mut args = Vec::new();
+        loop {
+            let mut list = Vec::new();
+            loop {
+                match self.stack.last() {
+                    Some(&Node::Type(_)) | Some(&Node::Tuple(_)) | Some(&Node::Func(_)) => (),
+                    _ => break,
+                }
+                list.push(try_opt!(self.pop_type()));
+            }
+            args.extend(list);
+            match self.stack.pop() {
+                Some(Node::EmptyList) => break,
+                Some(Node::FirstElementMarker) => continue,
+                _ => return None,
+            }
+        }
+        args.reverse();
+        let base = try_opt!(self.pop_type());
+        let ty = match (&base[..], args.len()) {
+            ("Swift.Array", 1) => format!("[{}]", args[0]),
+            ("Swift.Dictionary", 2) => format!("[{} : {}]", args[0], args[1]),
+            ("Swift.Optional", 1) => format!("{}?", args[0]),
+            _ => format!("{}<{}>", base, args.join(", ")),
+        };
+        self.push_subst(Node::Type(ty));
+        Some(())
+    }
+
+    fn tuple(&mut self) -> Option<()> {
+        let mut elems = Vec::new();
+        if self.pop_if(|n| if let Node::EmptyList = *n { true } else { false }).is_none() {
+            loop {
+                let first = self.pop_if(|n| if let Node::FirstElementMarker = *n { true } else { false }).is_some();
+                let variadic = self.pop_if(|n| if let Node::VariadicMarker = *n { true } else { false }).is_some();
+                let label = match self.pop_if(|n| if let Node::Ident(_) = *n { true } else { false }) {
+                    Some(Node::Ident(s)) => Some(s),
+                    _ => None,
+                };
+                let mut elem = try_opt!(self.pop_type());
+                if variadic { elem.push_str("..."); }
+                if let Some(label) = label { elem = format!("{}: {}", label, elem); }
+                elems.push(elem);
+                if first { break; }
+            }
+            elems.reverse();
+        }
+        self.push(Node::Tuple(elems));
+        Some(())
+    }
+
+    fn nominal(&mut self) -> Option<()> {
+        let name = try_opt!(self.pop_ident());
+        let ctx = try_opt!(self.pop_context());
+        self.push_subst(Node::Type(format!("{}.{}", ctx, name)));
+        Some(())
+    }
+
+    // type module 'E'
+    fn extension(&mut self) -> Option<()> {
+        let module = try_opt!(self.pop_module());
+        let ty = try_opt!(self.pop_type());
+        if ty.split('.').next() == Some(&module[..]) {
+            self.push(Node::Type(ty));
+        } else {
+            self.push(Node::Type(format!("(extension in {}):{}", module, ty)));
+        }
+        Some(())
+    }
+
+    fn function(&mut self) -> Option<()> {
+        let generics = self.pop_generic_sig();
+        let sig = try_opt!(self.pop_function_type());
+        let labels = self.pop_labels(&sig);
+        let name = try_opt!(self.pop_ident());
+        let ctx = try_opt!(self.pop_context());
+        self.push(Node::Entity(format!("{}.{}{}{}", ctx, name, generics,
+                                       sig.describe(labels.as_ref().map(|l| &l[..])))));
+        Some(())
+    }
+
+    fn variable(&mut self) -> Option<()> {
+        let accessor = match try_opt!(self.next()) {
+            b'p' => "",
+            b'g' => ".getter",
+            b's' => ".setter",
+            b'M' => ".modify",
+            b'r' => ".read",
+            b'w' => ".willset",
+            b'W' => ".didset",
+            b'm' => ".materializeForSet",
+            b'a' => ".unsafeMutableAddressor",
+            b'l' => ".unsafeAddressor",
+            b'i' => ".init",
+            _ => return None,
+        };
+        let ty = try_opt!(self.pop_type());
+        let name = try_opt!(self.pop_ident());
+        let ctx = try_opt!(self.pop_context());
+        self.push(Node::Entity(format!("{}.{}{} : {}", ctx, name, accessor, ty)));
+        Some(())
+    }
+
+    fn function_entity(&mut self) -> Option<()> {
+        let c = try_opt!(self.next());
+        let name = match c {
+            b'D' => "__deallocating_deinit",
+            b'd' => "deinit",
+            b'E' => "__ivar_destroyer",
+            b'e' => "__ivar_initializer",
+            b'C' => "__allocating_init",
+            b'c' => "init",
+            _ => return None,
+        };
+        let entity = if c == b'C' || c == b'c' {
+            let sig = match self.stack.pop() {
+                Some(Node::Func(sig)) => sig,
+                _ => return None,
+            };
+            let labels = self.pop_labels(&sig);
+            let ctx = try_opt!(self.pop_context());
+            format!("{}.{}{}", ctx, name, sig.describe(labels.as_ref().map(|l| &l[..])))
+        } else {
+            format!("{}.{}", try_opt!(self.pop_context()), name)
+        };
+        self.push(Node::Entity(entity));
+        Some(())
+    }
+
+    fn metadata(&mut self) -> Option<()> {
+        let what = match try_opt!(self.next()) {
+            b'n' => "nominal type descriptor for ",
+            b'a' => "type metadata accessor for ",
+            b'p' => "protocol descriptor for ",
+            b'm' => "metaclass for ",
+            b'f' => "full type metadata for ",
+            b'o' => "class metadata base offset for ",
+            b'u' => "method lookup function for ",
+            b'F' => "reflection metadata field descriptor ",
+            b'V' => {
+                let entity = try_opt!(self.pop_entity());
+                self.push(Node::Entity(format!("property descriptor for {}", entity)));
+                return Some(());
+            },
+            b'c' => {
+                let conf = try_opt!(self.pop_conformance());
+                self.push(Node::Entity(format!("protocol conformance descriptor for {}", conf)));
+                return Some(());
+            },
+            _ => return None,
+        };
+        let ty = try_opt!(self.pop_type());
+        self.push(Node::Entity(format!("{}{}", what, ty)));
+        Some(())
+    }
+
+    fn witness(&mut self) -> Option<()> {
+        let entity = match try_opt!(self.next()) {
+            b'V' => format!("value witness table for {}", try_opt!(self.pop_type())),
+            b'P' => format!("protocol witness table for {}", try_opt!(self.pop_conformance())),
+            b'p' => format!("protocol witness table pattern for {}", try_opt!(self.pop_conformance())),
+            b'a' => format!("protocol witness table accessor for {}", try_opt!(self.pop_conformance())),
+            b'v' => {
+                let direct = match try_opt!(self.next()) {
+                    b'd' => "direct",
+                    b'i' => "indirect",
+                    _ => return None,
+                };
+                format!("{} field offset for {}", direct, try_opt!(self.pop_entity()))
+            },
+            _ => return None,
+        };
+        self.push(Node::Entity(entity));
+        Some(())
+    }
+
+    fn thunk(&mut self) -> Option<()> {
+        let entity = match try_opt!(self.next()) {
+            b'q' => format!("method descriptor for {}", try_opt!(self.pop_entity())),
+            b'j' => format!("dispatch thunk of {}", try_opt!(self.pop_entity())),
+            b'W' => {
+                let conf = try_opt!(self.pop_conformance());
+                format!("protocol witness for {} in conformance {}", try_opt!(self.pop_entity()), conf)
+            },
+            _ => return None,
+        };
+        self.push(Node::Entity(entity));
+        Some(())
+    }
+
+    fn symbolic_reference(&mut self, kind: u8) -> Option<()> {
+        let at = self.pos - 1;
+        if self.pos + 4 > self.text.len() { return None; }
+        self.pos += 4;
+        let name = try_opt!((self.resolve)(kind, at));
+        self.push_subst(Node::Type(name));
+        Some(())
+    }
+
+    fn operator(&mut self) -> Option<()> {
+        let c = try_opt!(self.next());
+        match c {
+            0x01...0x17 => return self.symbolic_reference(c),
+            b'0'...b'9' => { self.pos -= 1; return self.identifier(); },
+            b'A' => return self.multi_substitution(),
+            b'S' => return self.standard_substitution(),
+            b'C' | b'V' | b'O' | b'P' | b'a' => return self.nominal(),
+            b'G' => return self.bound_generic(),
+            b't' => return self.tuple(),
+            b'E' => return self.extension(),
+            b'F' => return self.function(),
+            b'v' => return self.variable(),
+            b'f' => return self.function_entity(),
+            b'M' => return self.metadata(),
+            b'W' => return self.witness(),
+            b'T' => return self.thunk(),
+            b's' => self.push(Node::Module("Swift".to_owned())),
+            b'y' => self.push(Node::EmptyList),
+            b'_' => self.push(Node::FirstElementMarker),
+            b'd' => self.push(Node::VariadicMarker),
+            b'K' => self.push(Node::ThrowsMarker),
+            b'Y' => {
+                if !self.next_if(b'a') { return None; }
+                self.push(Node::AsyncMarker);
+            },
+            b'c' => {
+                let sig = try_opt!(self.pop_function_type());
+                self.push(Node::Func(sig));
+            },
+            b'N' => {
+                let ty = try_opt!(self.pop_type());
+                self.push(Node::Entity(format!("type metadata for {}", ty)));
+            },
+            b'D' => {
+                let ty = try_opt!(self.pop_type());
+                self.push(Node::Entity(ty));
+            },
+            b'L' => {
+                // private decl name: name discriminator 'LL'
+                if !self.next_if(b'L') { return None; }
+                let discriminator = try_opt!(self.pop_ident());
+                let name = try_opt!(self.pop_ident());
+                self.push(Node::Ident(format!("({} in {})", name, discriminator)));
+            },
+            b'l' => {
+                // a generic signature with no requirements: just <A>
+                self.push(Node::GenericSig("<A>".to_owned()));
+            },
+            b'x' => self.push(Node::Type(generic_param_name(0, 0))),
+            b'q' => {
+                let (depth, index) = if self.next_if(b'd') {
+                    let depth = try_opt!(self.index()) + 1;
+                    (depth, try_opt!(self.index()))
+                } else {
+                    (0, try_opt!(self.index()) + 1)
+                };
+                self.push(Node::Type(generic_param_name(depth, index)));
+            },
+            b'z' => {
+                let ty = try_opt!(self.pop_type());
+                self.push(Node::Type(format!("inout {}", ty)));
+            },
+            b'h' => {
+                let ty = try_opt!(self.pop_type());
+                self.push(Node::Type(format!("__shared {}", ty)));
+            },
+            b'n' => {
+                let ty = try_opt!(self.pop_type());
+                self.push(Node::Type(format!("__owned {}", ty)));
+            },
+            b'm' => {
+                let ty = try_opt!(self.pop_type());
+                self.push(Node::Type(format!("{}.Type", ty)));
+            },
+            b'p' => {
+                // protocol list: empty list for Any, otherwise protocols with a '_' after the first
+                let mut protos = Vec::new();
+                if self.pop_if(|n| if let Node::EmptyList = *n { true } else { false }).is_none() {
+                    loop {
+                        let first = self.pop_if(|n| if let Node::FirstElementMarker = *n { true } else { false }).is_some();
+                        protos.push(try_opt!(self.pop_type()));
+                        if first { break; }
+                    }
+                    protos.reverse();
+                }
+                self.push(Node::Type(if protos.is_empty() { "Any".to_owned() } else { protos.join(" & ") }));
+            },
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn run(&mut self) -> Option<String> {
+        while self.pos < self.text.len() {
+            try_opt!(self.operator());
+        }
+        if self.stack.len() != 1 { return None; }
+        match self.stack.pop() {
+            Some(Node::Entity(s)) => Some(s),
+            Some(node) => { self.stack.push(node); self.pop_type() },
+            None => None,
+        }
+    }
+}
+
+fn no_symbolic_references(_: u8, _: usize) -> Option<String> { None }
+
+// Demangle a symbol name, with or without the leading underscore.
+pub fn demangle(sym: &str) -> Option<String> {
+    let sym = if sym.starts_with("_") { &sym[1..] } else { sym };
+    let body = if sym.starts_with("$s") || sym.starts_with("$S") {
+        &sym[2..]
+    } else if sym.starts_with("T0") {
+        &sym[2..]
+    } else {
+        return None;
+    };
+    Demangler::new(body.as_bytes(), &no_symbolic_references).run()
+}
+
+// Demangle a bare type mangling, as found in reflection metadata.  These can contain symbolic
+// references (a kind byte from 1 to 0x17 followed by a 4-byte relative offset), which resolve is
+// called to name, given the kind and the offset of the kind byte in mangled.
+pub fn demangle_type(mangled: &[u8], resolve: &Fn(u8, usize) -> Option<String>) -> Option<String> {
+    Demangler::new(mangled, resolve).run()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dm(sym: &str) -> String {
+        demangle(sym).unwrap_or_else(|| panic!("failed to demangle {}", sym))
+    }
+
+    #[test]
+    fn test_metadata() {
+        assert_eq!(dm("$sSiN"), "type metadata for Swift.Int");
+        assert_eq!(dm("_$sSiN"), "type metadata for Swift.Int");
+    }
+
+    #[test]
+    fn test_word_substitution() {
+        assert_eq!(dm("$s4main12SomeLongNameV0B4TypeVMn"),
+                   "nominal type descriptor for main.SomeLongName.SomeType");
+    }
+
+    #[test]
+    fn test_bound_generic() {
+        assert_eq!(dm("$sSaySiGD"), "[Swift.Int]");
+        assert_eq!(dm("$sSDySSSiGD"), "[Swift.String : Swift.Int]");
+    }
+
+    #[test]
+    fn test_init_with_labels() {
+        assert_eq!(dm("$s4main3FooC1x1yACSi_SStcfC"),
+                   "main.Foo.__allocating_init(x: Swift.Int, y: Swift.String) -> main.Foo");
+    }
+
+    #[test]
+    fn test_repeat_count() {
+        assert_eq!(dm("$sSi_S2itD"), "(Swift.Int, Swift.Int, Swift.Int)");
+        assert_eq!(demangle("$sSi_S3000itD"), None);
+    }
+}
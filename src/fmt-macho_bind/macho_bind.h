@@ -3,4 +3,5 @@
 #include "mach-o/fat.h"
 #include "mach-o/nlist.h"
 #include "mach-o/reloc.h"
+#include "mach-o/fixup-chains.h"
 #include "dyld_cache_format.h"
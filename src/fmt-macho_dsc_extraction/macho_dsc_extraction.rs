@@ -11,7 +11,7 @@ use std::mem::{replace, transmute};
 use util::{Mem, SliceExt, OptionExt, Endian, LittleEndian, Lazy, Fnv};
 use macho_bind::*;
 use exec::{arch, VMA, SymbolValue, SourceLib, SymbolSource, Exec, SegmentWriter, SWGetSaneError, RelocKind, RelocContext, ReadVMA, Symbol, UlebWriter};
-use exec::arch::Arch;
+use exec::arch::{Arch, ArchAndOptions, CodeMode};
 use std::collections::{HashSet, HashMap};
 use std::cell::Cell;
 use std::any::Any;
@@ -21,6 +21,10 @@ extern crate dis_generated_jump_dis;
 use dis_generated_jump_dis::AArch64Handler;
 extern crate dis_simple_trawl;
 use dis_simple_trawl::CodeMap;
+extern crate dis;
+use dis::{Disassembler, DisassemblerStatics, DisassemblerInput};
+extern crate dis_simpledis;
+use dis_simpledis::SimpleDisassembler;
 
 struct ReaggregatedSyms {
     localsym: Vec<u8>,
@@ -38,7 +42,9 @@ trait MachODscExtraction {
     fn fix_objc_from_cache<'dc>(&mut self, dc: &'dc DyldCache);
     fn check_no_other_lib_refs<'a>(&'a self, dc: &'a DyldCache);
     fn guess_text_relocs(&self, stubs_by_name: &HashMap<&ByteStr, VMA, Fnv>) -> Vec<(VMA, RelocKind, VMA)>;
-    fn stub_name_list(&self) -> Vec<(&ByteStr, VMA)>;
+    fn guess_text_relocs_arm64(&self, stubs_by_name: &HashMap<&ByteStr, VMA, Fnv>) -> Vec<(VMA, RelocKind, VMA)>;
+    fn guess_text_relocs_x86_64(&self) -> Vec<(VMA, RelocKind, VMA)>;
+    fn indirect_name_list(&self, sect_type: u32) -> Vec<(&ByteStr, VMA)>;
     fn fix_text_relocs_from_cache(&mut self, ic: &ImageCache, dc: &DyldCache);
     fn backwards_reexport_map<'a>(&'a self, ic: &'a ImageCache) -> HashMap<ByteString, &'a ByteStr, Fnv>;
     fn reconstruct_rebase(&self, dc: &DyldCache) -> Vec<u8>;
@@ -557,15 +563,15 @@ impl MachODscExtraction for MachO {
              } else { ByteStr::from_str("??") }
         }
 
-        let sli = some_or!(dc.slide_info.as_ref(), {
+        if dc.slide_info.is_empty() {
             // no slide info so can't do it, oh well...
             return;
-        });
+        }
         let arch = self.eb.arch;
         for segment in &self.eb.segments {
             let content = segment.data.as_ref().unwrap().get();
             let pointer_size = self.eb.pointer_size;
-            sli.iter(&dc.eb, Some((segment.vmaddr, segment.vmsize)), |ptr| {
+            dc.iter_rebases(Some((segment.vmaddr, segment.vmsize)), |ptr| {
                 let offset = (ptr - segment.vmaddr) as usize;
                 let mut val: u64 = self.eb.ptr_from_slice(some_or!(content.slice_opt(offset, offset+pointer_size), {
                     return;
@@ -585,14 +591,18 @@ impl MachODscExtraction for MachO {
             });
         }
     }
-    // currently for cache extraction on arm64 only
+    // currently for cache extraction on arm64 and x86_64 only
     fn guess_text_relocs(&self, stubs_by_name: &HashMap<&ByteStr, VMA, Fnv>) -> Vec<(VMA, RelocKind, VMA)> {
         let _sw = stopwatch("guess_text_relocs");
+        match self.eb.arch {
+            arch::AArch64 => self.guess_text_relocs_arm64(stubs_by_name),
+            arch::X86_64 => self.guess_text_relocs_x86_64(),
+            _ => Vec::new(),
+        }
+    }
+    fn guess_text_relocs_arm64(&self, stubs_by_name: &HashMap<&ByteStr, VMA, Fnv>) -> Vec<(VMA, RelocKind, VMA)> {
         let strtab = self.strtab.get();
         let mut relocs = Vec::new();
-        if self.eb.arch != arch::AArch64 {
-            return relocs;
-        }
         let end = self.eb.endian;
         let pointer_size = self.eb.pointer_size;
         let stack_chk_fail = ByteStr::from_str("___stack_chk_fail");
@@ -607,7 +617,7 @@ impl MachODscExtraction for MachO {
             });
             let grain_shift: u8 = 2; // xxx
             let start_addr = sect.vmaddr;
-            let mut codemap = CodeMap::new(start_addr, grain_shift, util::downgrade(sectdata), end, &self.eb.segments);
+            let mut codemap = CodeMap::new(start_addr, grain_shift, util::downgrade(sectdata), end);
             if let Some(stack_chk_fail_stub) = stack_chk_fail_stub {
                 codemap.mark_noreturn_addr(stack_chk_fail_stub);
             }
@@ -646,7 +656,68 @@ impl MachODscExtraction for MachO {
         }
         relocs
     }
-    fn stub_name_list(&self) -> Vec<(&ByteStr, VMA)> {
+    // No code walker for x86, so just sweep linearly, restarting at each symbol
+    // in case of data or something the decoder doesn't know.
+    fn guess_text_relocs_x86_64(&self) -> Vec<(VMA, RelocKind, VMA)> {
+        let mut relocs = Vec::new();
+        let end = self.eb.endian;
+        let dis = SimpleDisassembler::new_with_args(ArchAndOptions::new_default(arch::X86_64), &[]).unwrap();
+        let mut starts: Vec<VMA> = Vec::new();
+        for group in &[self.localsym.get(), self.extdefsym.get()] {
+            for chunk in group.chunks(self.nlist_size) {
+                starts.push(VMA(copy_nlist_from_slice(chunk, end).n_value as u64));
+            }
+        }
+        starts.sort();
+        starts.dedup();
+        for sect in &self.eb.sections {
+            if self.sect_private[sect.private].flags & S_ATTR_SOME_INSTRUCTIONS == 0 {
+                continue;
+            }
+            let sectdata = some_or!(self.eb.get_sane(sect.vmaddr, sect.filesize), {
+                errln!("warning: guess_text_relocs: couldn't read section named {}", sect.name.as_ref().unwrap());
+                continue;
+            });
+            let bytes: Vec<u8> = sectdata.iter().map(|c| c.get()).collect();
+            let sect_end = sect.vmaddr + sect.filesize;
+            let mut resync = starts.iter().cloned().filter(|&a| a > sect.vmaddr && a < sect_end).peekable();
+            let mut addr = sect.vmaddr;
+            while addr < sect_end {
+                while resync.peek().map_or(false, |&a| a <= addr) { resync.next(); }
+                let limit = resync.peek().cloned().unwrap_or(sect_end);
+                let off = (addr - sect.vmaddr) as usize;
+                let limit_off = (limit - sect.vmaddr) as usize;
+                let len = match dis.disassemble_insn_to_str(&DisassemblerInput {
+                    data: &bytes[off..limit_off],
+                    pc: addr,
+                    mode: CodeMode::X86Mode { bits: 64 },
+                    resolver: None,
+                }) {
+                    Some((Some(_), len)) if len > 0 => len as usize,
+                    _ => {
+                        addr = limit;
+                        continue;
+                    },
+                };
+                if let Some((field, kind)) = x86_64_rel32_field(&bytes[off..off+len]) {
+                    let rc = RelocContext {
+                        kind: kind,
+                        pointer_size: 8,
+                        base_addr: addr + field as u64,
+                        endian: end,
+                    };
+                    let target = rc.pack_unpack_insn(&sectdata[off+field..], None).unwrap();
+                    if exec::addr_to_seg_off_range(&self.eb.segments, target).is_none() {
+                        relocs.push((rc.base_addr, kind, target));
+                    }
+                }
+                addr = addr + len as u64;
+            }
+        }
+        relocs
+    }
+    // (name, address) for each stub or pointer in sections of the given type
+    fn indirect_name_list(&self, sect_type: u32) -> Vec<(&ByteStr, VMA)> {
         let mut res = Vec::new();
         let indirectsym = self.indirectsym.get();
         let indirectsym_count = indirectsym.len() / 4;
@@ -656,27 +727,30 @@ impl MachODscExtraction for MachO {
         let nlist_size = self.nlist_size;
         for sect in &self.eb.sections {
             let sp = &self.sect_private[sect.private];
-            if sp.flags & SECTION_TYPE != S_SYMBOL_STUBS { continue; }
-            let (ind_idx, stub_size) = (sp.reserved1 as usize, sp.reserved2 as usize);
-            let stub_count = sect.filesize / stub_size.ext();
+            if sp.flags & SECTION_TYPE != sect_type { continue; }
+            let ind_idx = sp.reserved1 as usize;
+            let item_size = if sect_type == S_SYMBOL_STUBS { sp.reserved2 as usize } else { self.eb.pointer_size };
+            if item_size == 0 { continue; }
+            let item_count = sect.filesize / item_size.ext();
             if ind_idx > indirectsym_count {
-                errln!("warning: stub_name_list: reserved1 ({}) > stub count ({}) for section {:?}",
-                       ind_idx, stub_count, sect.name);
+                errln!("warning: indirect_name_list: reserved1 ({}) > item count ({}) for section {:?}",
+                       ind_idx, item_count, sect.name);
                 continue;
             }
-            let stub_count = if stub_count > (indirectsym_count - ind_idx).ext() {
-                errln!("warning: stub_name_list: reserved1 ({}) + stub count ({}) goes off end of indirect table \
-                        for section {:?}", ind_idx, stub_count, sect.name);
+            let item_count = if item_count > (indirectsym_count - ind_idx).ext() {
+                errln!("warning: indirect_name_list: reserved1 ({}) + item count ({}) goes off end of indirect table \
+                        for section {:?}", ind_idx, item_count, sect.name);
                 indirectsym_count - ind_idx
-            } else { stub_count as usize };
-            let mut stub_addr = sect.vmaddr;
-            for indirect_buf in indirectsym[ind_idx * 4 .. (ind_idx + stub_count) * 4].chunks(4) {
+            } else { item_count as usize };
+            let mut item_addr = sect.vmaddr;
+            for indirect_buf in indirectsym[ind_idx * 4 .. (ind_idx + item_count) * 4].chunks(4) {
                 let sym_idx: u32 = util::copy_from_slice(indirect_buf, end);
                 let off = (sym_idx as usize).saturating_mul(nlist_size);
-                let sa = stub_addr;
-                stub_addr = stub_addr.wrapping_add(stub_size.ext());
+                let sa = item_addr;
+                item_addr = item_addr.wrapping_add(item_size.ext());
+                if sym_idx & (INDIRECT_SYMBOL_LOCAL | INDIRECT_SYMBOL_ABS) != 0 { continue; }
                 let nlist_buf = some_or!(symtab.slice_opt(off, off + nlist_size), {
-                    errln!("warning: stub_name_list: bad symbol table index {}", sym_idx);
+                    errln!("warning: indirect_name_list: bad symbol table index {}", sym_idx);
                     continue;
                 });
                 let nl = copy_nlist_from_slice(nlist_buf, end);
@@ -694,20 +768,43 @@ impl MachODscExtraction for MachO {
         { // <-
 
         let mut my_stubs_by_name: HashMap<&ByteStr, VMA, _> = util::new_fnv_hashmap();
-        for (name, stub_addr) in self.stub_name_list() {
+        for (name, stub_addr) in self.indirect_name_list(S_SYMBOL_STUBS) {
             my_stubs_by_name.insert(name, stub_addr);
         }
+        let mut my_ptrs_by_name: HashMap<&ByteStr, VMA, _> = util::new_fnv_hashmap();
+        for (name, ptr_addr) in self.indirect_name_list(S_NON_LAZY_SYMBOL_POINTERS) {
+            my_ptrs_by_name.insert(name, ptr_addr);
+        }
 
         let guess = self.guess_text_relocs(&my_stubs_by_name);
         if guess.len() == 0 { return; }
 
-        let mut target_cache: HashMap<VMA, Option<VMA>, _> = util::new_fnv_hashmap();
+        let mut target_cache: HashMap<(VMA, bool), Option<VMA>, _> = util::new_fnv_hashmap();
         let bmap: Lazy<_> = Lazy::new();
         let this: &MachO = self;
         for (source, kind, target) in guess {
-            let new_target = target_cache.entry(target).or_insert_with(|| {
-                let (target, sme) = some_or!(resolve_arm64_trampolines(dc, ic, target, source, self.eb.endian),
-                                             return None);
+            // for GOT references, point at our own pointer for the symbol instead of a stub
+            let is_got = kind == RelocKind::GotPcRel;
+            let mine = if is_got { &my_ptrs_by_name } else { &my_stubs_by_name };
+            let new_target = target_cache.entry((target, is_got)).or_insert_with(|| {
+                let (target, sme) = if is_got {
+                    let ptr = some_or!(dc.eb.get_sane(target, pointer_size as u64), {
+                        errln!("warning: fix_text_relocs_from_cache: GOT entry {} (ref'd by {}) is unmapped", target, source);
+                        return None;
+                    });
+                    let target = VMA(dc.eb.ptr_from_slice(ptr));
+                    (target, some_or!(ic.lookup_addr(target), {
+                        errln!("warning: fix_text_relocs_from_cache: GOT entry (ref'd by {}) points to {}, which is not in any image", source, target);
+                        return None;
+                    }))
+                } else if this.eb.arch == arch::AArch64 {
+                    some_or!(resolve_arm64_trampolines(dc, ic, target, source, end), return None)
+                } else {
+                    (target, some_or!(ic.lookup_addr(target), {
+                        errln!("warning: fix_text_relocs_from_cache: addr {} (ref'd by {}) is not in any image", target, source);
+                        return None;
+                    }))
+                };
                 let ice = &ic.cache[sme.image_idx];
                 if let Err(ref e) = ice.mo {
                     errln!("warning: fix_text_relocs_from_cache: addr {} (ref'd by {}) points to bad image ({})", target, source, e);
@@ -725,7 +822,7 @@ impl MachODscExtraction for MachO {
                 for idx in min_idx..max_idx+1 {
                     let sym_name = &syms[idx].name;
                     // todo data relocs
-                    if let Some(&res) = my_stubs_by_name.get(&**sym_name) {
+                    if let Some(&res) = mine.get(&**sym_name) {
                         return Some(res);
                     }
                 }
@@ -737,14 +834,14 @@ impl MachODscExtraction for MachO {
                 for idx in min_idx..max_idx+1 {
                     let sym_name = &syms[idx].name;
                     if let Some(orig_name) = bmap.get(&**sym_name) {
-                        if let Some(&res) = my_stubs_by_name.get(orig_name) {
+                        if let Some(&res) = mine.get(orig_name) {
                             return Some(res);
                         }
                     }
                 }
                 // we fail
-                errln!("warning: fix_text_relocs_from_cache: couldn't find stub for symbol (addr {} ref'd by {}), name possibilities: {{",
-                       target, source);
+                errln!("warning: fix_text_relocs_from_cache: couldn't find {} for symbol (addr {} ref'd by {}), name possibilities: {{",
+                       if is_got { "pointer" } else { "stub" }, target, source);
                 for idx in min_idx..max_idx+1 {
                     let sym_name = &syms[idx].name;
                     println!("  {}", sym_name);
@@ -765,7 +862,7 @@ impl MachODscExtraction for MachO {
                     endian: end,
                 };
 
-                let cell_ptr = self.eb.get_sane(source, 4).unwrap();
+                let cell_ptr = self.eb.get_sane(source, rc.size() as u64).unwrap();
                 rc.pack_unpack_insn(cell_ptr, Some(new_target)).unwrap();
                 //println!("patching {} -> {:x} newt={}", source, insn, new_target);
             }
@@ -840,7 +937,7 @@ impl MachODscExtraction for MachO {
     fn reconstruct_rebase(&self, dc: &DyldCache) -> Vec<u8> {
         let mut output: Vec<u8> = Vec::new();
         // not optimally compressed but whatever
-        if let Some(ref slide_info) = dc.slide_info {
+        if !dc.slide_info.is_empty() {
             let mut w = UlebWriter::new(&mut output);
             let mut cur_seg_idx: Option<u8> = None;
             let mut cur_offset: u64 = 0; // dontcare initializer
@@ -850,7 +947,7 @@ impl MachODscExtraction for MachO {
                     break;
                 }
                 let seg_idx = seg_idx as u8;
-                slide_info.iter(&dc.eb, Some((segment.vmaddr, segment.vmsize)), &mut |vma| {
+                dc.iter_rebases(Some((segment.vmaddr, segment.vmsize)), &mut |vma| {
                     let offset = vma - segment.vmaddr;
                     if cur_seg_idx != Some(seg_idx) {
                         if cur_seg_idx.is_some() {
@@ -926,17 +1023,72 @@ fn ice_get_addr_syms(this: &ImageCacheEntry) -> &Vec<exec::Symbol<'static>> {
     any.downcast_ref().unwrap()
 }
 
-fn decode_stub(stub: &[ReadCell<u8>], stub_addr: VMA, end: Endian, arch: Arch) -> Option<VMA> {
+// Where in this (already decoded) instruction is a rel32 we know how to
+// retarget: call/jmp/jcc or a rip-relative operand (Rel32), or call/jmp/mov
+// through a rip-relative pointer (GotPcRel).
+fn x86_64_rel32_field(insn: &[u8]) -> Option<(usize, RelocKind)> {
+    let mut i = 0;
+    let mut rex_w = false;
+    while i < insn.len() {
+        match insn[i] {
+            0x66 | 0xf2 | 0xf3 => (),
+            0x40...0x4f => rex_w = insn[i] & 8 != 0,
+            _ => break,
+        }
+        i += 1;
+    }
+    let op = *try_opt!(insn.get(i));
+    let next = insn.get(i + 1).cloned().unwrap_or(0);
+    let (field, kind) = match (op, next) {
+        (0xe8, _) | (0xe9, _) => (i + 1, RelocKind::Rel32),
+        (0x0f, 0x80...0x8f) => (i + 2, RelocKind::Rel32),
+        _ => {
+            let modrm_pos = match (op, next) {
+                (0x0f, 0x38) | (0x0f, 0x3a) => i + 3,
+                (0x0f, _) => i + 2,
+                _ => i + 1,
+            };
+            let modrm = *try_opt!(insn.get(modrm_pos));
+            if modrm & 0xc7 != 0x05 { return None; }
+            let reg = modrm >> 3 & 7;
+            let kind = match op {
+                // call/jmp *
+                0xff if reg == 2 || reg == 4 => RelocKind::GotPcRel,
+                0x8b if rex_w => RelocKind::GotPcRel,
+                _ => RelocKind::Rel32,
+            };
+            (modrm_pos + 1, kind)
+        },
+    };
+    // An immediate after the displacement would make it relative to the end
+    // of the instruction rather than the field.  This also rules out opcodes
+    // without a ModRM byte, none of which are 5 bytes after the opcode.
+    if field + 4 != insn.len() { return None; }
+    Some((field, kind))
+}
+
+// Returns the address of the pointer the stub jumps through.
+pub fn decode_stub(stub: &[ReadCell<u8>], stub_addr: VMA, end: Endian, arch: Arch) -> Option<VMA> {
     match arch {
         arch::X86 | arch::X86_64 => {
             assert_eq!(end, LittleEndian);
             if stub.len() != 6 { return None; }
             let bytes: [u8; 2] = util::copy_from_slice(&stub[0..2], LittleEndian);
             if bytes != [0xff, 0x25] { return None; }
-            let rel: i32 = util::copy_from_slice(&stub[2..], LittleEndian);
-            let mut res = stub_addr.wrapping_add(rel as u64);
-            if arch == arch::X86 { res = res.trunc32(); }
-            Some(res)
+            if arch == arch::X86 {
+                // jmp *abs32
+                let addr: u32 = util::copy_from_slice(&stub[2..], LittleEndian);
+                Some(VMA(addr.ext()))
+            } else {
+                // jmp *rel32(%rip)
+                let rc = RelocContext {
+                    kind: RelocKind::GotPcRel,
+                    pointer_size: 8,
+                    base_addr: stub_addr + 2,
+                    endian: LittleEndian,
+                };
+                rc.unpack_insn(&stub[2..]).ok()
+            }
         },
         arch::ARM => {
             match stub.len() {
@@ -979,6 +1131,26 @@ fn decode_stub(stub: &[ReadCell<u8>], stub_addr: VMA, end: Endian, arch: Arch) -
     }
 }
 
+// (stub address, pointer address) for every stub in __stubs-like sections.
+pub fn stub_targets(mo: &MachO) -> Vec<(VMA, VMA)> {
+    let mut res = Vec::new();
+    for sect in &mo.eb.sections {
+        if sect.private == !0 { continue; }
+        let sp = &mo.sect_private[sect.private];
+        if sp.flags & SECTION_TYPE != S_SYMBOL_STUBS || sp.reserved2 == 0 { continue; }
+        let item_size = sp.reserved2 as usize;
+        let data = mo.eb.read(sect.vmaddr, sect.vmsize);
+        for (i, stub) in data.get().chunks(item_size).enumerate() {
+            if stub.len() < item_size { break; }
+            let addr = sect.vmaddr + (i * item_size) as u64;
+            if let Some(target) = decode_stub(stub, addr, mo.eb.endian, mo.eb.arch) {
+                res.push((addr, target));
+            }
+        }
+    }
+    res
+}
+
 pub fn extract_as_necessary(mo: &mut MachO, dc: Option<&DyldCache>, image_cache: Option<&ImageCache>, minimal_processing: bool) -> exec::ExecResult<()> {
     let _sw = stopwatch("extract_as_necessary");
     if mo.text_fileoff() != 0 && !minimal_processing {
@@ -1009,3 +1181,42 @@ pub fn extract_as_necessary(mo: &mut MachO, dc: Option<&DyldCache>, image_cache:
     mo.rewhole();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (field offset, kind, target) for an instruction at 0x1000
+    fn rel32_target(insn: &[u8]) -> Option<(usize, RelocKind, VMA)> {
+        let (field, kind) = try_opt!(x86_64_rel32_field(insn));
+        let cells: Vec<Cell<u8>> = insn[field..].iter().map(|&b| Cell::new(b)).collect();
+        let rc = RelocContext { kind: kind, pointer_size: 8, base_addr: VMA(0x1000 + field as u64), endian: LittleEndian };
+        Some((field, kind, rc.pack_unpack_insn(&cells, None).unwrap()))
+    }
+
+    #[test]
+    fn test_x86_64_rel32_field() {
+        let rel = |field| Some((field, RelocKind::Rel32, VMA(0x2000)));
+        let got = |field| Some((field, RelocKind::GotPcRel, VMA(0x2000)));
+        // call, jmp, jne
+        assert_eq!(rel32_target(&[0xe8, 0xfb, 0x0f, 0x00, 0x00]), rel(1));
+        assert_eq!(rel32_target(&[0xe9, 0xfb, 0x0f, 0x00, 0x00]), rel(1));
+        assert_eq!(rel32_target(&[0x0f, 0x85, 0xfa, 0x0f, 0x00, 0x00]), rel(2));
+        // call *, jmp *, mov rax, [rip + x]
+        assert_eq!(rel32_target(&[0xff, 0x15, 0xfa, 0x0f, 0x00, 0x00]), got(2));
+        assert_eq!(rel32_target(&[0xff, 0x25, 0xfa, 0x0f, 0x00, 0x00]), got(2));
+        assert_eq!(rel32_target(&[0x48, 0x8b, 0x05, 0xf9, 0x0f, 0x00, 0x00]), got(3));
+        // plain rip-relative operands: mov eax, [rip + x]; lea rax, [rip + x]; movsd xmm0, [rip + x]
+        assert_eq!(rel32_target(&[0x8b, 0x05, 0xfa, 0x0f, 0x00, 0x00]), rel(2));
+        assert_eq!(rel32_target(&[0x48, 0x8d, 0x05, 0xf9, 0x0f, 0x00, 0x00]), rel(3));
+        assert_eq!(rel32_target(&[0xf2, 0x0f, 0x10, 0x05, 0xf8, 0x0f, 0x00, 0x00]), rel(4));
+        // an immediate after the displacement: cmp byte ptr [rip + x], 0x20; mov dword ptr [rip + x], 1
+        assert_eq!(rel32_target(&[0x80, 0x3d, 0xf9, 0x0f, 0x00, 0x00, 0x20]), None);
+        assert_eq!(rel32_target(&[0xc7, 0x05, 0xf6, 0x0f, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]), None);
+        // not rip-relative: mov eax, 5; call rax; jmp short; lea rax, [rbx + 0x10]
+        assert_eq!(rel32_target(&[0xb8, 0x05, 0x00, 0x00, 0x00]), None);
+        assert_eq!(rel32_target(&[0xff, 0xd0]), None);
+        assert_eq!(rel32_target(&[0xeb, 0x10]), None);
+        assert_eq!(rel32_target(&[0x48, 0x8d, 0x43, 0x10]), None);
+    }
+}
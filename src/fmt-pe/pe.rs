@@ -0,0 +1,538 @@
+#![allow(non_camel_case_types)]
+#[macro_use]
+extern crate macros;
+extern crate util;
+extern crate exec;
+extern crate fmt_pe_bind as pe_bind;
+
+use std::mem::size_of;
+use std::borrow::Cow;
+use std::any::Any;
+use std::cmp::{min, max};
+use std::ascii::AsciiExt;
+
+use exec::arch::Arch;
+use util::{Mem, SliceExt, ByteStr, ByteString, Lazy, ReadCell, CheckAdd, LittleEndian};
+use exec::{ExecResult, ErrorKind, Segment, VMA, Prot, Symbol, SymbolValue, SymbolSource, SourceLib, DepLib, Reloc, RelocKind, RelocTarget, ReadVMA};
+use pe_bind::*;
+
+pub struct PeBasics {
+    pub is64: bool,
+    pub machine: u16,
+    pub arch: Arch,
+    pub characteristics: u16,
+    pub num_sections: u16,
+    pub opt_off: usize, // file offset of the optional header
+    pub opt_size: usize,
+}
+
+pub struct Pe {
+    pub eb: exec::ExecBase,
+    pub basics: PeBasics,
+    pub image_base: VMA,
+    pub entry: Option<VMA>,
+    pub data_dirs: Vec<IMAGE_DATA_DIRECTORY>,
+    pub sect_hdrs: Vec<IMAGE_SECTION_HEADER>,
+    imports_cache: Lazy<Imports>,
+}
+
+struct Imports {
+    dep_libs: Vec<DepLib<'static>>,
+    delay_start: usize, // dep_libs from here on are delay-loaded
+    syms: Vec<Symbol<'static>>,
+    slots: Vec<VMA>, // IAT entry for each of syms
+}
+
+fn machine_to_arch(machine: u16) -> Arch {
+    match machine as u32 {
+        IMAGE_FILE_MACHINE_I386 => Arch::X86,
+        IMAGE_FILE_MACHINE_AMD64 => Arch::X86_64,
+        IMAGE_FILE_MACHINE_ARM | IMAGE_FILE_MACHINE_THUMB | IMAGE_FILE_MACHINE_ARMNT => Arch::ARM,
+        IMAGE_FILE_MACHINE_ARM64 => Arch::AArch64,
+        IMAGE_FILE_MACHINE_POWERPC | IMAGE_FILE_MACHINE_POWERPCFP => Arch::PowerPC,
+        IMAGE_FILE_MACHINE_R3000 | IMAGE_FILE_MACHINE_R4000 | IMAGE_FILE_MACHINE_R10000 |
+        IMAGE_FILE_MACHINE_WCEMIPSV2 | IMAGE_FILE_MACHINE_MIPS16 | IMAGE_FILE_MACHINE_MIPSFPU |
+        IMAGE_FILE_MACHINE_MIPSFPU16 => Arch::Mips,
+        _ => Arch::UnknownArch,
+    }
+}
+
+fn machine_to_str(machine: u16) -> &'static str {
+    match machine as u32 {
+        IMAGE_FILE_MACHINE_I386 => "i386",
+        IMAGE_FILE_MACHINE_AMD64 => "x86_64",
+        IMAGE_FILE_MACHINE_ARM => "arm",
+        IMAGE_FILE_MACHINE_THUMB => "thumb",
+        IMAGE_FILE_MACHINE_ARMNT => "armnt",
+        IMAGE_FILE_MACHINE_ARM64 => "arm64",
+        IMAGE_FILE_MACHINE_POWERPC | IMAGE_FILE_MACHINE_POWERPCFP => "powerpc",
+        IMAGE_FILE_MACHINE_IA64 => "ia64",
+        IMAGE_FILE_MACHINE_EBC => "ebc",
+        IMAGE_FILE_MACHINE_SH3 | IMAGE_FILE_MACHINE_SH4 => "sh",
+        IMAGE_FILE_MACHINE_RISCV32 | IMAGE_FILE_MACHINE_RISCV64 => "riscv",
+        IMAGE_FILE_MACHINE_R3000 | IMAGE_FILE_MACHINE_R4000 | IMAGE_FILE_MACHINE_R10000 |
+        IMAGE_FILE_MACHINE_WCEMIPSV2 | IMAGE_FILE_MACHINE_MIPS16 | IMAGE_FILE_MACHINE_MIPSFPU |
+        IMAGE_FILE_MACHINE_MIPSFPU16 => "mips",
+        _ => "unknown-machine",
+    }
+}
+
+fn check_pe_basics(buf: &[ReadCell<u8>]) -> Result<PeBasics, &'static str> {
+    let dos_size = size_of::<IMAGE_DOS_HEADER>();
+    let dos: IMAGE_DOS_HEADER = util::copy_from_slice(some_or!(buf.slice_opt(0, dos_size), { return Err("too short"); }), LittleEndian);
+    if dos.e_magic as u32 != IMAGE_DOS_SIGNATURE {
+        return Err("bad DOS magic");
+    }
+    let pe_off = dos.e_lfanew as usize;
+    let fh_off = some_or!(pe_off.check_add(4), { return Err("bad e_lfanew"); });
+    let fh_size = size_of::<IMAGE_FILE_HEADER>();
+    let sig: u32 = util::copy_from_slice(some_or!(buf.slice_opt(pe_off, fh_off), { return Err("e_lfanew out of range"); }), LittleEndian);
+    if sig != IMAGE_NT_SIGNATURE {
+        return Err("bad PE signature (plain DOS executable?)");
+    }
+    let fh: IMAGE_FILE_HEADER = util::copy_from_slice(some_or!(buf.slice_opt(fh_off, fh_off + fh_size), { return Err("truncated file header"); }), LittleEndian);
+    let opt_off = fh_off + fh_size;
+    let opt_size = fh.SizeOfOptionalHeader as usize;
+    if opt_size < 2 {
+        return Err("no optional header (object file?)");
+    }
+    let magic: u16 = util::copy_from_slice(some_or!(buf.slice_opt(opt_off, opt_off + 2), { return Err("truncated optional header"); }), LittleEndian);
+    let is64 = match magic as u32 {
+        IMAGE_NT_OPTIONAL_HDR32_MAGIC => false,
+        IMAGE_NT_OPTIONAL_HDR64_MAGIC => true,
+        _ => return Err("unknown optional header magic"),
+    };
+    Ok(PeBasics {
+        is64: is64,
+        machine: fh.Machine,
+        arch: machine_to_arch(fh.Machine),
+        characteristics: fh.Characteristics,
+        num_sections: fh.NumberOfSections,
+        opt_off: opt_off,
+        opt_size: opt_size,
+    })
+}
+
+fn sect_prot(characteristics: u32) -> Prot {
+    Prot {
+        r: characteristics & IMAGE_SCN_MEM_READ != 0,
+        w: characteristics & IMAGE_SCN_MEM_WRITE != 0,
+        x: characteristics & IMAGE_SCN_MEM_EXECUTE != 0,
+    }
+}
+
+impl Pe {
+    fn new(buf: Mem<u8>) -> ExecResult<Self> {
+        let mut res = {
+            let b = buf.get();
+            let basics = try!(check_pe_basics(b).map_err(|a| exec::err_only(ErrorKind::BadData, a)));
+            let opt_buf = some_or!(b.slice_opt(basics.opt_off, basics.opt_off + basics.opt_size), {
+                return exec::err(ErrorKind::BadData, "optional header out of range");
+            });
+            // the fields we care about are at the same place in both
+            let (image_base, entry_rva, size_of_headers, num_dirs, dirs_off) = branch!(if (basics.is64) {
+                type IMAGE_OPTIONAL_HEADERX = IMAGE_OPTIONAL_HEADER64;
+            } else {
+                type IMAGE_OPTIONAL_HEADERX = IMAGE_OPTIONAL_HEADER32;
+            } then {
+                let size = size_of::<IMAGE_OPTIONAL_HEADERX>();
+                let oh: IMAGE_OPTIONAL_HEADERX = util::copy_from_slice(some_or!(opt_buf.slice_opt(0, size), {
+                    return exec::err(ErrorKind::BadData, "optional header too small");
+                }), LittleEndian);
+                (oh.ImageBase as u64, oh.AddressOfEntryPoint, oh.SizeOfHeaders, oh.NumberOfRvaAndSizes, size)
+            });
+            let image_base = VMA(image_base);
+            let dir_size = size_of::<IMAGE_DATA_DIRECTORY>();
+            let avail_dirs = (opt_buf.len() - dirs_off) / dir_size;
+            if num_dirs as usize > avail_dirs {
+                errln!("warning: NumberOfRvaAndSizes ({}) doesn't fit in optional header; only reading {}", num_dirs, avail_dirs);
+            }
+            let data_dirs: Vec<IMAGE_DATA_DIRECTORY> = opt_buf[dirs_off..].chunks(dir_size)
+                .take(min(num_dirs as usize, avail_dirs))
+                .map(|d| util::copy_from_slice(d, LittleEndian))
+                .collect();
+
+            let sh_off = basics.opt_off + basics.opt_size;
+            let sh_size = size_of::<IMAGE_SECTION_HEADER>();
+            let mut sect_hdrs = Vec::new();
+            for i in 0..basics.num_sections as usize {
+                let off = sh_off + i * sh_size;
+                let sh_data = some_or!(b.slice_opt(off, off + sh_size), {
+                    errln!("warning: section table truncated at section {}", i);
+                    break;
+                });
+                let sh: IMAGE_SECTION_HEADER = util::copy_from_slice(sh_data, LittleEndian);
+                sect_hdrs.push(sh);
+            }
+
+            let mut segs = Vec::new();
+            let mut sects = Vec::new();
+            // the headers get mapped too, and some directories live there
+            let headers_size = min(size_of_headers as usize, b.len());
+            segs.push(Segment {
+                vmaddr: image_base,
+                vmsize: headers_size as u64,
+                fileoff: 0,
+                filesize: headers_size as u64,
+                name: None,
+                prot: Prot { r: true, w: false, x: false },
+                data: buf.slice(0, headers_size),
+                seg_idx: None,
+                private: 0,
+            });
+            for (i, sh) in sect_hdrs.iter().enumerate() {
+                let name = util::from_cstr(&sh.Name as &[u8]).to_owned();
+                // VirtualSize is 0 in some old linkers' output
+                let vmsize = if sh.VirtualSize == 0 { sh.SizeOfRawData } else { sh.VirtualSize } as u64;
+                let mut filesize = min(sh.SizeOfRawData as u64, vmsize);
+                let fileoff = if filesize == 0 { 0 } else { sh.PointerToRawData as u64 };
+                if fileoff > b.len() as u64 {
+                    errln!("warning: section {} data out of range", name);
+                    filesize = 0;
+                } else if filesize > b.len() as u64 - fileoff {
+                    errln!("warning: section {} data truncated", name);
+                    filesize = b.len() as u64 - fileoff;
+                }
+                let seg = Segment {
+                    vmaddr: image_base + sh.VirtualAddress as u64,
+                    vmsize: vmsize,
+                    fileoff: fileoff,
+                    filesize: filesize,
+                    name: Some(name),
+                    prot: sect_prot(sh.Characteristics),
+                    data: buf.slice(fileoff as usize, (fileoff + filesize) as usize),
+                    seg_idx: None,
+                    private: i,
+                };
+                sects.push(Segment { seg_idx: Some(segs.len()), ..seg.clone() });
+                segs.push(seg);
+            }
+            let eb = exec::ExecBase {
+                arch: basics.arch,
+                pointer_size: if basics.is64 { 8 } else { 4 },
+                endian: LittleEndian,
+                segments: segs,
+                sections: sects,
+                whole_buf: None,
+            };
+            Pe {
+                eb: eb,
+                basics: basics,
+                image_base: image_base,
+                entry: if entry_rva != 0 { Some(image_base + entry_rva as u64) } else { None },
+                data_dirs: data_dirs,
+                sect_hdrs: sect_hdrs,
+                imports_cache: Lazy::new(),
+            }
+        };
+        res.eb.whole_buf = Some(buf);
+        Ok(res)
+    }
+
+    pub fn desc(&self) -> String {
+        let kind = if self.basics.characteristics as u32 & IMAGE_FILE_DLL != 0 { "DLL" } else { "executable" };
+        format!("PE{} {} {}", if self.basics.is64 { "32+" } else { "32" }, kind, machine_to_str(self.basics.machine))
+    }
+
+    // (address, size) of a data directory, if present
+    pub fn data_dir(&self, idx: u32) -> Option<(VMA, u64)> {
+        let dir = some_or!(self.data_dirs.get(idx as usize), { return None; });
+        if dir.VirtualAddress == 0 || dir.Size == 0 { return None; }
+        Some((self.image_base + dir.VirtualAddress as u64, dir.Size as u64))
+    }
+
+    fn read_u32(&self, addr: VMA) -> Option<u32> {
+        self.eb.get_sane(addr, 4).map(|s| util::copy_from_slice(s, LittleEndian))
+    }
+    fn read_u16(&self, addr: VMA) -> Option<u16> {
+        self.eb.get_sane(addr, 2).map(|s| util::copy_from_slice(s, LittleEndian))
+    }
+    fn read_ptr(&self, addr: VMA) -> Option<u64> {
+        self.eb.get_sane(addr, self.eb.pointer_size as u64).map(|s| self.eb.ptr_from_slice(s))
+    }
+    fn read_str(&self, addr: VMA) -> Option<ByteString> {
+        self.eb.read_cstr_sane(addr).map(|s| s.to_owned())
+    }
+
+    fn get_export_list(&self, only: Option<&ByteStr>) -> ExecResult<Vec<Symbol<'static>>> {
+        let mut out = Vec::new();
+        let (dir_addr, dir_size) = some_or!(self.data_dir(IMAGE_DIRECTORY_ENTRY_EXPORT), { return Ok(out); });
+        let ed_size = size_of::<IMAGE_EXPORT_DIRECTORY>();
+        let ed: IMAGE_EXPORT_DIRECTORY = util::copy_from_slice(some_or!(self.eb.get_sane(dir_addr, ed_size as u64), {
+            return exec::err(ErrorKind::BadData, "export directory out of range");
+        }), LittleEndian);
+        let base = self.image_base;
+        // don't trust NumberOfFunctions with an allocation until the table it describes is there
+        if self.eb.get_sane(base + ed.AddressOfFunctions as u64, ed.NumberOfFunctions as u64 * 4).is_none() {
+            return exec::err(ErrorKind::BadData, format!("export address table ({} entries) out of range", ed.NumberOfFunctions));
+        }
+        // names, by function index
+        let mut names: Vec<Option<ByteString>> = vec![None; ed.NumberOfFunctions as usize];
+        for i in 0..ed.NumberOfNames as u64 {
+            let name_rva = some_or!(self.read_u32(base + ed.AddressOfNames as u64 + i * 4), {
+                errln!("warning: export name table out of range");
+                break;
+            });
+            let idx = some_or!(self.read_u16(base + ed.AddressOfNameOrdinals as u64 + i * 2), {
+                errln!("warning: export ordinal table out of range");
+                break;
+            }) as usize;
+            let name = some_or!(self.read_str(base + name_rva as u64), {
+                errln!("warning: export name {} out of range", i);
+                continue;
+            });
+            match names.get_mut(idx) {
+                Some(slot) => *slot = Some(name),
+                None => errln!("warning: export '{}' has out-of-range index {}", name, idx),
+            }
+        }
+        let deps = &self.get_imports().dep_libs;
+        for (i, name) in names.into_iter().enumerate() {
+            let ordinal = ed.Base.wrapping_add(i as u32);
+            let name = name.unwrap_or_else(|| ByteString::from_string(format!("#{}", ordinal)));
+            if let Some(only) = only {
+                if &*name != only { continue; }
+            }
+            let rva = some_or!(self.read_u32(base + ed.AddressOfFunctions as u64 + (i as u64) * 4), {
+                errln!("warning: export address table out of range");
+                break;
+            });
+            if rva == 0 { continue; }
+            let addr = base + rva as u64;
+            let val = if addr.wrapping_sub(dir_addr) < dir_size {
+                // forwarder, like "NTDLL.RtlAllocateHeap"
+                let fwd = some_or!(self.read_str(addr), {
+                    errln!("warning: forwarder string for export '{}' out of range", name);
+                    continue;
+                });
+                let dot = fwd.iter().rposition(|&c| c == b'.').unwrap_or(0);
+                let (lib, target) = (&fwd[..dot], &fwd[min(dot + 1, fwd.len())..]);
+                let source = deps.iter().position(|dl| {
+                    let path: &[u8] = &dl.path;
+                    let path = if path.len() >= 4 && path[path.len() - 4..].eq_ignore_ascii_case(b".dll") { &path[..path.len() - 4] } else { path };
+                    path.eq_ignore_ascii_case(lib)
+                }).map_or(SourceLib::None, |idx| SourceLib::Ordinal(idx as u32));
+                SymbolValue::ReExport(Cow::Owned(target.to_owned()), source)
+            } else {
+                SymbolValue::Addr(addr)
+            };
+            out.push(Symbol {
+                name: Cow::Owned(name),
+                is_public: true,
+                is_weak: false,
+                val: val,
+                size: None,
+                private: ordinal as usize,
+            });
+        }
+        Ok(out)
+    }
+
+    // walk one import lookup table, pushing a symbol for each entry
+    fn push_thunk_imports(&self, lookup: VMA, iat: VMA, lib_idx: usize, rva_based: bool, out: &mut Imports) {
+        let psize = self.eb.pointer_size as u64;
+        let ordinal_flag = 1u64 << (psize * 8 - 1);
+        for i in 0.. {
+            let thunk = some_or!(self.read_ptr(lookup + i * psize), {
+                errln!("warning: import lookup table runs out of range");
+                break;
+            });
+            if thunk == 0 { break; }
+            let name = if thunk & ordinal_flag != 0 {
+                ByteString::from_string(format!("#{}", thunk & 0xffff))
+            } else {
+                // IMAGE_IMPORT_BY_NAME: skip the hint
+                let hint_name = if rva_based { self.image_base + (thunk & 0x7fffffff) } else { VMA(thunk) };
+                some_or!(self.read_str(hint_name + 2), {
+                    errln!("warning: import name out of range");
+                    continue;
+                })
+            };
+            out.syms.push(Symbol {
+                name: Cow::Owned(name),
+                is_public: true,
+                is_weak: false,
+                val: SymbolValue::Undefined(SourceLib::Ordinal(lib_idx as u32)),
+                size: None,
+                private: out.syms.len(),
+            });
+            out.slots.push(iat + i * psize);
+        }
+    }
+
+    fn get_imports(&self) -> &Imports {
+        self.imports_cache.get(|| {
+            let mut out = Imports { dep_libs: Vec::new(), delay_start: 0, syms: Vec::new(), slots: Vec::new() };
+            let base = self.image_base;
+            if let Some((dir_addr, dir_size)) = self.data_dir(IMAGE_DIRECTORY_ENTRY_IMPORT) {
+                let so = size_of::<IMAGE_IMPORT_DESCRIPTOR>() as u64;
+                let mut addr = dir_addr;
+                // null-terminated; the size is more of a suggestion
+                loop {
+                    let desc: IMAGE_IMPORT_DESCRIPTOR = util::copy_from_slice(some_or!(self.eb.get_sane(addr, so), {
+                        errln!("warning: import directory runs out of range (size {})", dir_size);
+                        break;
+                    }), LittleEndian);
+                    addr = addr + so;
+                    if desc.Name == 0 && desc.FirstThunk == 0 { break; }
+                    let name = self.read_str(base + desc.Name as u64).unwrap_or_else(|| {
+                        errln!("warning: import DLL name out of range");
+                        ByteString::from_str("<err>")
+                    });
+                    let lib_idx = out.dep_libs.len();
+                    out.dep_libs.push(DepLib { path: Cow::Owned(name), private: lib_idx });
+                    // if there's no separate lookup table, the IAT doubles as one (unless it's bound...)
+                    let lookup = if desc.OriginalFirstThunk != 0 { desc.OriginalFirstThunk } else { desc.FirstThunk };
+                    self.push_thunk_imports(base + lookup as u64, base + desc.FirstThunk as u64, lib_idx, true, &mut out);
+                }
+            }
+            out.delay_start = out.dep_libs.len();
+            if let Some((dir_addr, _)) = self.data_dir(IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT) {
+                let so = size_of::<IMAGE_DELAYLOAD_DESCRIPTOR>() as u64;
+                let mut addr = dir_addr;
+                loop {
+                    let desc: IMAGE_DELAYLOAD_DESCRIPTOR = util::copy_from_slice(some_or!(self.eb.get_sane(addr, so), {
+                        errln!("warning: delay import directory runs out of range");
+                        break;
+                    }), LittleEndian);
+                    addr = addr + so;
+                    if desc.DllNameRVA == 0 { break; }
+                    // old (VC6) ones use VAs
+                    let rva_based = desc.Attributes & IMAGE_DELAYLOAD_RVA_BASED != 0;
+                    let to_addr = |x: u32| if rva_based { base + x as u64 } else { VMA(x as u64) };
+                    let name = self.read_str(to_addr(desc.DllNameRVA)).unwrap_or_else(|| {
+                        errln!("warning: delay import DLL name out of range");
+                        ByteString::from_str("<err>")
+                    });
+                    let lib_idx = out.dep_libs.len();
+                    out.dep_libs.push(DepLib { path: Cow::Owned(name), private: lib_idx });
+                    self.push_thunk_imports(to_addr(desc.ImportNameTableRVA), to_addr(desc.ImportAddressTableRVA), lib_idx, rva_based, &mut out);
+                }
+            }
+            out
+        })
+    }
+}
+
+impl exec::Exec for Pe {
+    fn get_exec_base<'a>(&'a self) -> &'a exec::ExecBase {
+        &self.eb
+    }
+    fn as_any(&self) -> &Any { self as &Any }
+
+    fn get_symbol_list(&self, source: SymbolSource, specific: Option<&Any>) -> Vec<Symbol> {
+        assert!(specific.is_none());
+        let mut out = Vec::new();
+        if source != SymbolSource::Exported {
+            out.extend(self.get_imports().syms.iter().cloned());
+        }
+        if source != SymbolSource::Imported {
+            match self.get_export_list(None) {
+                Ok(exports) => out.extend(exports),
+                Err(e) => errln!("warning: {}", e.message),
+            }
+        }
+        out
+    }
+
+    fn lookup_export(&self, name: &ByteStr, specific: Option<&Any>) -> Vec<Symbol> {
+        assert!(specific.is_none());
+        self.get_export_list(Some(name)).unwrap_or_else(|e| {
+            errln!("warning: {}", e.message);
+            Vec::new()
+        })
+    }
+
+    fn get_reloc_list<'a>(&'a self, specific: Option<&'a Any>) -> Vec<Reloc<'a>> {
+        assert!(specific.is_none());
+        let mut out = Vec::new();
+        let imports = self.get_imports();
+        for (sym, &slot) in imports.syms.iter().zip(&imports.slots) {
+            out.push(Reloc {
+                address: slot,
+                kind: RelocKind::Pointer,
+                base: None,
+                target: RelocTarget::Import(sym),
+            });
+        }
+        let (dir_addr, dir_size) = some_or!(self.data_dir(IMAGE_DIRECTORY_ENTRY_BASERELOC), { return out; });
+        let dir = self.eb.read(dir_addr, dir_size);
+        let dir = dir.get();
+        let is64 = self.basics.is64;
+        let hdr_size = size_of::<IMAGE_BASE_RELOCATION>();
+        let mut off = 0;
+        let mut unknown = 0;
+        while let Some(hdr_data) = dir.slice_opt(off, off + hdr_size) {
+            let block: IMAGE_BASE_RELOCATION = util::copy_from_slice(hdr_data, LittleEndian);
+            let block_size = block.SizeOfBlock as usize;
+            if block_size < hdr_size || block_size > dir.len() - off {
+                errln!("warning: bad base relocation block size {} at offset {}", block_size, off);
+                break;
+            }
+            let page = self.image_base + block.VirtualAddress as u64;
+            for ent in dir[off + hdr_size..off + block_size].chunks(2) {
+                if ent.len() < 2 { break; }
+                let ent: u16 = util::copy_from_slice(ent, LittleEndian);
+                let kind = match (ent >> 12) as u32 {
+                    IMAGE_REL_BASED_ABSOLUTE => continue, // padding
+                    IMAGE_REL_BASED_HIGHLOW => if is64 { RelocKind::_32Bit } else { RelocKind::Pointer },
+                    IMAGE_REL_BASED_DIR64 => if is64 { RelocKind::Pointer } else { RelocKind::_64Bit },
+                    _ => { unknown += 1; continue; },
+                };
+                out.push(Reloc {
+                    address: page + (ent & 0xfff) as u64,
+                    kind: kind,
+                    base: None,
+                    target: RelocTarget::ThisImageSlide,
+                });
+            }
+            off += max(block_size, hdr_size);
+        }
+        if unknown > 0 {
+            errln!("warning: skipped {} base relocs of unknown type (machine {})", unknown, machine_to_str(self.basics.machine));
+        }
+        out
+    }
+
+    fn get_dep_libs(&self) -> Cow<[DepLib]> {
+        (&*self.get_imports().dep_libs).into()
+    }
+    fn describe_dep_lib(&self, dl: &DepLib) -> String {
+        let mut out = dl.path.lossy().into_owned();
+        if dl.private >= self.get_imports().delay_start {
+            out.push_str(" (delay-load)");
+        }
+        out
+    }
+}
+
+pub struct PeProber;
+
+impl exec::ExecProber for PeProber {
+    fn name(&self) -> &str {
+        "pe"
+    }
+    fn create(&self, _eps: &Vec<&'static exec::ExecProber>, buf: Mem<u8>, args: Vec<String>) -> exec::ExecResult<(Box<exec::Exec>, Vec<String>)> {
+        let m = try!(exec::usage_to_invalid_args(util::do_getopts_or_usage(&*args, "pe ...", 0, std::usize::MAX, &mut vec!(
+            // ...
+        ))));
+        let free = m.free;
+        Pe::new(buf).map(|res| (Box::new(res) as Box<exec::Exec>, free))
+    }
+    fn probe(&self, _eps: &Vec<&'static exec::ExecProber>, buf: Mem<u8>) -> Vec<exec::ProbeResult> {
+        match check_pe_basics(buf.get()) {
+            Err(_msg) => vec!(),
+            Ok(basics) => {
+                vec![exec::ProbeResult {
+                    desc: format!("PE{} {} {}",
+                                  if basics.is64 { "32+" } else { "32" },
+                                  if basics.characteristics as u32 & IMAGE_FILE_DLL != 0 { "DLL" } else { "executable" },
+                                  machine_to_str(basics.machine)),
+                    likely: true,
+                    arch: basics.arch,
+                    cmd: vec!["pe".to_string()],
+                }]
+            }
+        }
+    }
+}
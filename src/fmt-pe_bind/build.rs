@@ -0,0 +1,4 @@
+extern crate build_help;
+fn main() {
+    build_help::do_bind("pe_bind.h", &["externals", "pe"]);
+}
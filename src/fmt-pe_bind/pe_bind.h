@@ -0,0 +1 @@
+#include "pe.h"
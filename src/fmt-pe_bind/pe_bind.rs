@@ -0,0 +1,7 @@
+#![allow(non_camel_case_types, non_upper_case_globals, non_snake_case)]
+#[macro_use]
+extern crate macros;
+extern crate util;
+use util::Swap;
+
+include!(concat!(env!("OUT_DIR"), "/out.rs"));
@@ -1,6 +1,11 @@
+#[macro_use]
+extern crate macros;
 extern crate exec;
 extern crate util;
+extern crate bsdlike_getopts as getopts;
 use exec::arch;
+use exec::arch::ArchAndOptions;
+use exec::{ErrorKind, VMA, Prot};
 
 pub struct RawBinary {
     eb: exec::ExecBase,
@@ -14,31 +19,126 @@ impl exec::Exec for RawBinary {
     fn as_any(&self) -> &std::any::Any { self as &std::any::Any }
 }
 
+// offset:size@vma[:prot], e.g. 0x100:0x4000@0x8000000:r-x
+#[derive(Debug, Clone)]
+pub struct SegSpec {
+    pub offset: u64,
+    pub size: u64,
+    pub vmaddr: VMA,
+    pub prot: Prot,
+}
+
+impl SegSpec {
+    pub fn parse(spec: &str) -> Result<SegSpec, String> {
+        let bad = || format!("bad segment spec '{}' (expected offset:size@vma[:prot])", spec);
+        let at = some_or!(spec.find('@'), { return Err(bad()); });
+        let (file_part, vm_part) = (&spec[..at], &spec[at + 1..]);
+        let mut fp = file_part.splitn(2, ':');
+        let offset: u64 = some_or!(fp.next().and_then(util::stoi), { return Err(bad()); });
+        let size: u64 = some_or!(fp.next().and_then(util::stoi), { return Err(bad()); });
+        let mut vp = vm_part.splitn(2, ':');
+        let vmaddr: u64 = some_or!(vp.next().and_then(util::stoi), { return Err(bad()); });
+        let prot = match vp.next() {
+            None => exec::prot_all,
+            Some(p) => try!(parse_prot(p).ok_or_else(|| format!("bad protection '{}' in segment spec '{}'", p, spec))),
+        };
+        Ok(SegSpec { offset: offset, size: size, vmaddr: VMA(vmaddr), prot: prot })
+    }
+}
+
+fn parse_prot(s: &str) -> Option<Prot> {
+    let mut prot = Prot { r: false, w: false, x: false };
+    for c in s.chars() {
+        match c {
+            'r' => prot.r = true,
+            'w' => prot.w = true,
+            'x' => prot.x = true,
+            '-' => (),
+            _ => return None,
+        }
+    }
+    Some(prot)
+}
+
+fn invalid<T, S: Into<String>>(s: S) -> exec::ExecResult<T> {
+    exec::err(ErrorKind::InvalidArgs, s.into())
+}
+
 impl RawBinary {
-    pub fn new(buf: util::Mem<u8>, _args: Vec<String>) -> RawBinary {
-        let len = buf.get().len();
-        // todo: parse args
-        let seg = exec::Segment {
-            vmaddr: exec::VMA(0),
-            vmsize: len as u64,
-            fileoff: 0u64,
-            filesize: len as u64,
-            name: None,
-            prot: exec::prot_all,
-            data: Some(buf.clone()),
-            seg_idx: None,
-            private: 0,
+    pub fn new(buf: util::Mem<u8>, args: Vec<String>) -> exec::ExecResult<(RawBinary, Vec<String>)> {
+        let m = try!(exec::usage_to_invalid_args(util::do_getopts_or_usage(&*args, "raw [--arch 'ARCH [ARCHOPTS]'] [--base ADDR | --seg SPEC...] ...", 0, std::usize::MAX, &mut vec!(
+            getopts::optopt("a", "arch", "architecture, plus any options (e.g. 'arm -E little')", "arch"),
+            getopts::optopt("E", "endian", "endianness (little/big/L/B); default depends on arch", "endian"),
+            getopts::optopt("", "pointer-size", "pointer size in bytes; default depends on arch", "size"),
+            getopts::optopt("b", "base", "load the whole file at this address (default 0)", "addr"),
+            getopts::optmulti("s", "seg", "map part of the file: offset:size@vma[:prot]; can be repeated", "spec"),
+        ))));
+        let arch_opts = match m.opt_str("arch") {
+            Some(spec) => {
+                let spec_args: Vec<String> = spec.split_whitespace().map(|s| s.to_owned()).collect();
+                try!(ArchAndOptions::new(&spec_args).or_else(|e| invalid(format!("--arch: {}", e))))
+            },
+            None => ArchAndOptions::default(),
         };
-        RawBinary {
+        let endian = match m.opt_str("endian").as_ref().map(|s| &**s) {
+            Some("L") | Some("l") | Some("little") => util::LittleEndian,
+            Some("B") | Some("b") | Some("big") => util::BigEndian,
+            Some(x) => return invalid(format!("bad endian spec {}", x)),
+            None => arch_opts.default_endian(),
+        };
+        let pointer_size = match m.opt_str("pointer-size") {
+            Some(s) => match util::stoi::<usize>(&*s) {
+                Some(4) => 4,
+                Some(8) => 8,
+                _ => return invalid(format!("bad pointer size {} (must be 4 or 8)", s)),
+            },
+            None => arch_opts.default_pointer_size(),
+        };
+        let base = match m.opt_str("base") {
+            Some(s) => Some(VMA(some_or!(util::stoi(&*s), { return invalid(format!("bad base address {}", s)); }))),
+            None => None,
+        };
+        let mut specs = Vec::new();
+        for s in m.opt_strs("seg") {
+            specs.push(try!(SegSpec::parse(&*s).or_else(invalid)));
+        }
+        if base.is_some() && !specs.is_empty() {
+            return invalid("--base and --seg are mutually exclusive");
+        }
+        let len = buf.get().len() as u64;
+        if specs.is_empty() {
+            specs.push(SegSpec { offset: 0, size: len, vmaddr: base.unwrap_or(VMA(0)), prot: exec::prot_all });
+        }
+        let mut segs = Vec::new();
+        for spec in specs {
+            if spec.offset > len {
+                return invalid(format!("segment at {} starts past end of file (size {:#x})", spec.vmaddr, len));
+            }
+            // past-EOF parts act like bss
+            let filesize = std::cmp::min(spec.size, len - spec.offset);
+            segs.push(exec::Segment {
+                vmaddr: spec.vmaddr,
+                vmsize: spec.size,
+                fileoff: spec.offset,
+                filesize: filesize,
+                name: None,
+                prot: spec.prot,
+                data: buf.slice(spec.offset as usize, (spec.offset + filesize) as usize),
+                seg_idx: None,
+                private: 0,
+            });
+        }
+        let raw = RawBinary {
             eb: exec::ExecBase {
-                arch: arch::UnknownArch,
-                pointer_size: 0,
-                endian: util::BigEndian,
-                segments: vec!(seg.clone()),
-                sections: vec!(seg.clone()),
+                arch: arch_opts.arch(),
+                pointer_size: pointer_size,
+                endian: endian,
+                sections: segs.clone(),
+                segments: segs,
                 whole_buf: Some(buf),
             }
-        }
+        };
+        Ok((raw, m.free))
     }
 }
 
@@ -58,10 +158,7 @@ impl exec::ExecProber for RawProber {
         })
     }
     fn create(&self, _eps: &Vec<&'static exec::ExecProber>, buf: util::Mem<u8>, args: Vec<String>) -> exec::ExecResult<(Box<exec::Exec>, Vec<String>)> {
-        let m = try!(exec::usage_to_invalid_args(util::do_getopts_or_usage(&*args, "raw ...", 0, std::usize::MAX, &mut vec!(
-            // ...
-        ))));
-        Ok((Box::new(RawBinary::new(buf, args)) as Box<exec::Exec>, m.free))
+        let (raw, free) = try!(RawBinary::new(buf, args));
+        Ok((Box::new(raw) as Box<exec::Exec>, free))
     }
 }
-
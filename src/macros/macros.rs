@@ -58,6 +58,10 @@ macro_rules! some_or {($opt:expr, $els:stmt) => {
     if let Some(xxx) = $opt { xxx } else { $els }
 }}
 #[macro_export]
+macro_rules! try_opt {($opt:expr) => {
+    some_or!($opt, { return None; })
+}}
+#[macro_export]
 macro_rules! ok_or {($res:expr, $evar:pat, $els:stmt) => {
     match $res {
         Ok(xxx) => xxx,
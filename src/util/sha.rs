@@ -0,0 +1,247 @@
+// SHA-1 and SHA-2, for checking and making code signatures.  Not fast, but
+// there's nothing else in the tree that needs hashing.
+use std::slice;
+use ::ROSlicePtr;
+
+static K256: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+static K512: [u64; 80] = [
+    0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc,
+    0x3956c25bf348b538, 0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
+    0xd807aa98a3030242, 0x12835b0145706fbe, 0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235, 0xc19bf174cf692694,
+    0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+    0x983e5152ee66dfab, 0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2, 0xd5a79147930aa725, 0x06ca6351e003826f, 0x142929670a0e6e70,
+    0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed, 0x53380d139d95b3df,
+    0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+    0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30,
+    0xd192e819d6ef5218, 0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8, 0x1e376c085141ab53, 0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+    0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b,
+    0xca273eceea26619c, 0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178,
+    0x06f067aa72176fba, 0x0a637dc5a2c898a6, 0x113f9804bef90dae, 0x1b710b35131c471b,
+    0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc, 0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817,
+];
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ShaKind {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl ShaKind {
+    pub fn digest_len(self) -> usize {
+        match self {
+            ShaKind::Sha1 => 20,
+            ShaKind::Sha256 => 32,
+            ShaKind::Sha384 => 48,
+            ShaKind::Sha512 => 64,
+        }
+    }
+    fn block_len(self) -> usize {
+        match self {
+            ShaKind::Sha1 | ShaKind::Sha256 => 64,
+            ShaKind::Sha384 | ShaKind::Sha512 => 128,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum State {
+    Small([u32; 8]), // SHA-1 only uses 5
+    Large([u64; 8]),
+}
+
+#[derive(Clone)]
+pub struct Sha {
+    kind: ShaKind,
+    state: State,
+    buf: Vec<u8>,
+    total: u64,
+}
+
+fn be32(b: &[u8]) -> u32 {
+    (b[0] as u32) << 24 | (b[1] as u32) << 16 | (b[2] as u32) << 8 | b[3] as u32
+}
+
+fn be64(b: &[u8]) -> u64 {
+    (be32(&b[..4]) as u64) << 32 | be32(&b[4..8]) as u64
+}
+
+fn compress_sha1(h: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 80];
+    for i in 0..16 { w[i] = be32(&block[i*4..]); }
+    for i in 16..80 { w[i] = (w[i-3] ^ w[i-8] ^ w[i-14] ^ w[i-16]).rotate_left(1); }
+    let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+    for i in 0..80 {
+        let (f, k) = match i {
+            0...19 => ((b & c) | (!b & d), 0x5a827999),
+            20...39 => (b ^ c ^ d, 0x6ed9eba1),
+            40...59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+            _ => (b ^ c ^ d, 0xca62c1d6),
+        };
+        let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(w[i]);
+        e = d; d = c; c = b.rotate_left(30); b = a; a = t;
+    }
+    for (x, y) in h.iter_mut().zip(&[a, b, c, d, e]) { *x = x.wrapping_add(*y); }
+}
+
+fn compress_sha256(h: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+    for i in 0..16 { w[i] = be32(&block[i*4..]); }
+    for i in 16..64 {
+        let s0 = w[i-15].rotate_right(7) ^ w[i-15].rotate_right(18) ^ (w[i-15] >> 3);
+        let s1 = w[i-2].rotate_right(17) ^ w[i-2].rotate_right(19) ^ (w[i-2] >> 10);
+        w[i] = w[i-16].wrapping_add(s0).wrapping_add(w[i-7]).wrapping_add(s1);
+    }
+    let mut v = *h;
+    for i in 0..64 {
+        let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+        let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+        let t1 = v[7].wrapping_add(s1).wrapping_add(ch).wrapping_add(K256[i]).wrapping_add(w[i]);
+        let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+        let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+        let t2 = s0.wrapping_add(maj);
+        v = [t1.wrapping_add(t2), v[0], v[1], v[2], v[3].wrapping_add(t1), v[4], v[5], v[6]];
+    }
+    for (x, y) in h.iter_mut().zip(&v) { *x = x.wrapping_add(*y); }
+}
+
+fn compress_sha512(h: &mut [u64; 8], block: &[u8]) {
+    let mut w = [0u64; 80];
+    for i in 0..16 { w[i] = be64(&block[i*8..]); }
+    for i in 16..80 {
+        let s0 = w[i-15].rotate_right(1) ^ w[i-15].rotate_right(8) ^ (w[i-15] >> 7);
+        let s1 = w[i-2].rotate_right(19) ^ w[i-2].rotate_right(61) ^ (w[i-2] >> 6);
+        w[i] = w[i-16].wrapping_add(s0).wrapping_add(w[i-7]).wrapping_add(s1);
+    }
+    let mut v = *h;
+    for i in 0..80 {
+        let s1 = v[4].rotate_right(14) ^ v[4].rotate_right(18) ^ v[4].rotate_right(41);
+        let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+        let t1 = v[7].wrapping_add(s1).wrapping_add(ch).wrapping_add(K512[i]).wrapping_add(w[i]);
+        let s0 = v[0].rotate_right(28) ^ v[0].rotate_right(34) ^ v[0].rotate_right(39);
+        let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+        let t2 = s0.wrapping_add(maj);
+        v = [t1.wrapping_add(t2), v[0], v[1], v[2], v[3].wrapping_add(t1), v[4], v[5], v[6]];
+    }
+    for (x, y) in h.iter_mut().zip(&v) { *x = x.wrapping_add(*y); }
+}
+
+impl Sha {
+    pub fn new(kind: ShaKind) -> Sha {
+        let state = match kind {
+            ShaKind::Sha1 => State::Small([0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0, 0, 0, 0]),
+            ShaKind::Sha256 => State::Small([0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+                                             0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19]),
+            ShaKind::Sha384 => State::Large([0xcbbb9d5dc1059ed8, 0x629a292a367cd507, 0x9159015a3070dd17, 0x152fecd8f70e5939,
+                                             0x67332667ffc00b31, 0x8eb44a8768581511, 0xdb0c2e0d64f98fa7, 0x47b5481dbefa4fa4]),
+            ShaKind::Sha512 => State::Large([0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+                                             0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179]),
+        };
+        Sha { kind: kind, state: state, buf: Vec::new(), total: 0 }
+    }
+
+    fn compress(&mut self, block: &[u8]) {
+        match (self.kind, &mut self.state) {
+            (ShaKind::Sha1, &mut State::Small(ref mut h)) => compress_sha1(h, block),
+            (_, &mut State::Small(ref mut h)) => compress_sha256(h, block),
+            (_, &mut State::Large(ref mut h)) => compress_sha512(h, block),
+        }
+    }
+
+    pub fn update<S: ?Sized + ROSlicePtr<u8>>(&mut self, data: &S) {
+        let mut data: &[u8] = unsafe { slice::from_raw_parts(data.as_ptr(), data.len()) };
+        let block_len = self.kind.block_len();
+        self.total = self.total.wrapping_add(data.len() as u64);
+        if !self.buf.is_empty() {
+            let take = ::std::cmp::min(block_len - self.buf.len(), data.len());
+            self.buf.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.buf.len() < block_len { return; }
+            let block = ::std::mem::replace(&mut self.buf, Vec::new());
+            self.compress(&block);
+        }
+        while data.len() >= block_len {
+            self.compress(&data[..block_len]);
+            data = &data[block_len..];
+        }
+        self.buf.extend_from_slice(data);
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let block_len = self.kind.block_len();
+        // the length field is 8 or 16 bytes, but we never need more than 64 bits
+        let len_len = block_len / 8;
+        let bits = self.total.wrapping_mul(8);
+        let mut pad = vec![0x80u8];
+        while (self.buf.len() + pad.len() + len_len) % block_len != 0 {
+            pad.push(0);
+        }
+        for _ in 8..len_len { pad.push(0); }
+        for i in (0..8).rev() { pad.push((bits >> (i * 8)) as u8); }
+        self.update(&pad[..]);
+        assert!(self.buf.is_empty());
+        let mut out = Vec::with_capacity(64);
+        match self.state {
+            State::Small(h) => for &x in &h { for i in (0..4).rev() { out.push((x >> (i * 8)) as u8); } },
+            State::Large(h) => for &x in &h { for i in (0..8).rev() { out.push((x >> (i * 8)) as u8); } },
+        }
+        out.truncate(self.kind.digest_len());
+        out
+    }
+}
+
+pub fn sha_digest<S: ?Sized + ROSlicePtr<u8>>(kind: ShaKind, data: &S) -> Vec<u8> {
+    let mut sha = Sha::new(kind);
+    sha.update(data);
+    sha.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(data: &[u8]) -> String {
+        data.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_sha_abc() {
+        assert_eq!(hex(&sha_digest(ShaKind::Sha1, &b"abc"[..])),
+                   "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hex(&sha_digest(ShaKind::Sha256, &b"abc"[..])),
+                   "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(hex(&sha_digest(ShaKind::Sha384, &b"abc"[..])),
+                   "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed\
+                    8086072ba1e7cc2358baeca134c825a7");
+    }
+
+    #[test]
+    fn test_sha_split_updates() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        for &kind in &[ShaKind::Sha1, ShaKind::Sha256, ShaKind::Sha512] {
+            let mut sha = Sha::new(kind);
+            for chunk in data.chunks(37) { sha.update(chunk); }
+            assert_eq!(sha.finish(), sha_digest(kind, &data[..]));
+        }
+        assert_eq!(hex(&sha_digest(ShaKind::Sha1, &b""[..])),
+                   "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    }
+}
@@ -54,6 +54,9 @@ pub use trivial_hasher::*;
 mod small_vector;
 pub use small_vector::SmallVector;
 
+mod sha;
+pub use sha::{Sha, ShaKind, sha_digest};
+
 pub struct ReadCell<T: Copy> {
     pub value: UnsafeCell<T>
 }
@@ -240,6 +243,11 @@ pub fn downgrade<T: Copy>(a: &[Cell<T>]) -> &[ReadCell<T>] {
     unsafe { transmute(a) }
 }
 
+// fine since ReadCell never hands out a way to write
+pub fn as_read_cells<T: Copy>(a: &[T]) -> &[ReadCell<T>] {
+    unsafe { transmute(a) }
+}
+
 // These two impls cannot conflict with the generic read-only slice impl because of the Copy bound.
 // ...But rustc thinks they do, hence the dummy parameter.  In the future, hack around this with
 // specialization instead.
@@ -791,6 +799,22 @@ enum MemoryContainer {
     Empty,
     MemoryMap(Mmap),
     BoxedSlice(Box<[u8]>),
+    #[cfg(unix)]
+    RawMap(RawMap),
+}
+
+// an mmap region we made ourselves, for when memmap's API isn't enough
+#[cfg(unix)]
+struct RawMap {
+    ptr: *mut u8,
+    len: usize,
+}
+
+#[cfg(unix)]
+impl Drop for RawMap {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len); }
+    }
 }
 
 #[derive(Clone)]
@@ -930,6 +954,63 @@ pub fn memmap(fil: &File) -> io::Result<Mem<u8>> {
     Ok(Mem::with_mm(try!(Mmap::open(fil, memmap::Protection::ReadCopy))))
 }
 
+// Map several files back to back into one contiguous (copy-on-write) region, each starting at a
+// multiple of align (which must be a multiple of the page size).  Returns the offset of each file.
+// Gaps between files read as zero.
+#[cfg(unix)]
+pub fn memmap_concat(fils: &[&File], align: usize) -> io::Result<(Mem<u8>, Vec<usize>)> {
+    use std::os::unix::io::AsRawFd;
+    let too_big = || io::Error::new(io::ErrorKind::Other, "memmap_concat: files too big");
+    let mut offs = Vec::with_capacity(fils.len());
+    let mut lens = Vec::with_capacity(fils.len());
+    let mut total: usize = 0;
+    for fil in fils {
+        let len = try!(fil.metadata()).len();
+        let len: usize = try!(len.narrow().ok_or_else(&too_big));
+        offs.push(total);
+        lens.push(len);
+        total = try!(total.checked_add(len).and_then(|t| t.checked_add(align - 1)).ok_or_else(&too_big)) & !(align - 1);
+    }
+    if total == 0 {
+        return Ok((Mem::empty(), offs));
+    }
+    unsafe {
+        let prot = libc::PROT_READ | libc::PROT_WRITE;
+        let base = libc::mmap(ptr::null_mut(), total, prot, libc::MAP_PRIVATE | libc::MAP_ANON, -1, 0);
+        if base == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        // unmaps everything if we bail out
+        let region = RawMap { ptr: base as *mut u8, len: total };
+        for ((fil, &off), &len) in fils.iter().zip(&offs).zip(&lens) {
+            if len == 0 { continue; }
+            let addr = region.ptr.offset(off as isize) as *mut libc::c_void;
+            let res = libc::mmap(addr, len, prot, libc::MAP_PRIVATE | libc::MAP_FIXED, fil.as_raw_fd(), 0);
+            if res == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        let ptr = region.ptr as *const u8;
+        Ok((Mem { mc: Arc::new(MemoryContainer::RawMap(region)), ptr: ptr, len: total }, offs))
+    }
+}
+
+// No MAP_FIXED here, so just read everything in.
+#[cfg(not(unix))]
+pub fn memmap_concat(fils: &[&File], align: usize) -> io::Result<(Mem<u8>, Vec<usize>)> {
+    use std::io::Read;
+    let too_big = || io::Error::new(io::ErrorKind::Other, "memmap_concat: files too big");
+    let mut offs = Vec::with_capacity(fils.len());
+    let mut buf: Vec<u8> = Vec::new();
+    for mut fil in fils.iter().cloned() {
+        offs.push(buf.len());
+        try!(fil.read_to_end(&mut buf));
+        let total = try!(buf.len().checked_add(align - 1).ok_or_else(&too_big)) & !(align - 1);
+        buf.resize(total, 0);
+    }
+    Ok((Mem::with_vec(buf), offs))
+}
+
 pub fn do_getopts(args: &[String], min_expected_free: usize, max_expected_free: usize, optgrps: &mut Vec<getopts::OptGroup>) -> Option<getopts::Matches> {
     if let Ok(m) = getopts::getopts(args, &optgrps) {
         if m.free.len() >= min_expected_free &&
@@ -1161,6 +1242,19 @@ impl<T> Lazy<T> {
     }
 }
 unsafe impl<T> Sync for Lazy<T> {}
+impl<T> Default for Lazy<T> {
+    fn default() -> Self { Lazy::new() }
+}
+impl<T: Clone> Clone for Lazy<T> {
+    fn clone(&self) -> Self {
+        let res = Lazy::new();
+        if self.is_valid.load(Ordering::Acquire) {
+            let val: &T = unsafe { &*self.val.get() };
+            res.get(|| val.clone());
+        }
+        res
+    }
+}
 impl<T> Drop for Lazy<T> {
     fn drop(&mut self) {
         if self.is_valid.load(Ordering::Acquire) {
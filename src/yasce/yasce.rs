@@ -7,7 +7,7 @@ use std::os::unix::fs::symlink;
 use std::ffi::{OsStr, OsString};
 use std::path::{Path, PathBuf, Component};
 use std::fs::File;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::sync::Arc;
 use std::sync::mpsc::channel;
 
@@ -25,8 +25,11 @@ extern crate threadpool;
 use threadpool::ThreadPool;
 extern crate num_cpus;
 
+// None means don't sign; Some(None) means sign without entitlements
+type AdhocSign = Option<Option<Vec<u8>>>;
+
 fn extract_one(dc: &DyldCache, ii: &ImageInfo, outpath: &Path, image_cache: Option<&ImageCache>,
-               minimal_processing: bool) {
+               minimal_processing: bool, adhoc_sign: &AdhocSign) {
     let mut macho = match dc.load_single_image(ii, /*fix_data*/ true) {
         Ok(m) => m,
         Err(e) => { errln!("for '{}', parse Mach-O fail: {}", ii.path, e); return },
@@ -35,6 +38,12 @@ fn extract_one(dc: &DyldCache, ii: &ImageInfo, outpath: &Path, image_cache: Opti
         Ok(()) => (),
         Err(e) => { errln!("for '{}', extract fail: {}", ii.path, e); return },
     }
+    if let Some(ref entitlements) = *adhoc_sign {
+        match macho.adhoc_sign(ii.path.unix_basename(), entitlements.as_ref().map(|e| &e[..])) {
+            Ok(()) => (),
+            Err(e) => { errln!("for '{}', adhoc sign fail: {}", ii.path, e); return },
+        }
+    }
     let mut fp = File::create(outpath).unwrap();
     let buf = unsafe { macho.eb.whole_buf.as_ref().unwrap().get_plain_slice() };
     fp.write_all(buf).unwrap();
@@ -52,7 +61,7 @@ fn bstr_to_path(a: &ByteStr) -> Result<&Path, std::str::Utf8Error> {
 fn usage() -> ! {
     println!(
     "Yet Another Shared Cache Extractor
-    Usage: yasce <cache> [basename | full path | --all | blank to list files] [-o dir/file] [-v] [-m/--minimal-processing]"
+    Usage: yasce <cache> [basename | full path | --all | blank to list files] [-o dir/file] [-v] [-m/--minimal-processing] [--adhoc-sign [--entitlements plist]]"
     );
     util::exit();
 }
@@ -65,11 +74,15 @@ fn main() {
     let mut verbose = false;
     let mut extract_all = false;
     let mut minimal_processing = false;
+    let mut adhoc_sign = false;
+    let mut entitlements_path = None;
     let dash_o = OsStr::new("-o");
     let dash_v = OsStr::new("-v");
     let dash_m = OsStr::new("-m");
     let dash_dash_all = OsStr::new("--all");
     let dash_dash_minimal_processing = OsStr::new("--minimal-processing");
+    let dash_dash_adhoc_sign = OsStr::new("--adhoc-sign");
+    let dash_dash_entitlements = OsStr::new("--entitlements");
     let dash_dash = OsStr::new("--");
     while let Some(arg) = args_it.next() {
         if arg == dash_o {
@@ -84,6 +97,10 @@ fn main() {
             extract_all = true;
         } else if arg == dash_m || arg == dash_dash_minimal_processing {
             minimal_processing = true;
+        } else if arg == dash_dash_adhoc_sign {
+            adhoc_sign = true;
+        } else if arg == dash_dash_entitlements {
+            entitlements_path = Some(args_it.next().unwrap_or_else(|| usage()));
         } else if arg == dash_dash {
             while let Some(arg) = args_it.next() {
                 base_args.push(arg);
@@ -95,15 +112,24 @@ fn main() {
     let argc = base_args.len();
     if argc != 2 && argc != 3 { usage(); }
     let dc_path = &base_args[1];
+    if entitlements_path.is_some() && !adhoc_sign {
+        errln!("--entitlements requires --adhoc-sign");
+        usage();
+    }
+    let adhoc_sign: Arc<AdhocSign> = Arc::new(if adhoc_sign {
+        Some(entitlements_path.map(|path| {
+            let mut buf = Vec::new();
+            File::open(&path).and_then(|mut fp| fp.read_to_end(&mut buf)).unwrap_or_else(|e| {
+                errln!("read {:?} failed: {}", path, e);
+                util::exit();
+            });
+            buf
+        }))
+    } else { None });
     let filename = base_args.get(2);
 
-    let fp = File::open(&Path::new(&dc_path)).unwrap_or_else(|e| {
-        errln!("open {:?} failed: {}", dc_path, e);
-        util::exit();
-    });
-
-    let dc_buf = util::memmap(&fp).unwrap();
-    let dc = DyldCache::new(dc_buf, false, true).unwrap_or_else(|e| {
+    // picks up any sub-caches and .symbols file too
+    let dc = DyldCache::open(&Path::new(&dc_path), false, true).unwrap_or_else(|e| {
         errln!("parse dyld cache format fail: {}", e);
         util::exit();
     });
@@ -128,7 +154,7 @@ fn main() {
                     return;
                 }
                 extract_one(&dc, ii, get_output_path(ii, &output_name),
-                            (*image_cache).as_ref(), minimal_processing);
+                            (*image_cache).as_ref(), minimal_processing, &adhoc_sign);
                 which_extracted = Some(&ii.path);
             }
         }
@@ -145,7 +171,7 @@ fn main() {
                     continue;
                 }
                 extract_one(&dc, ii, get_output_path(ii, &output_name),
-                            (*image_cache).as_ref(), minimal_processing);
+                            (*image_cache).as_ref(), minimal_processing, &adhoc_sign);
                 which_extracted = Some(&ii.path);
             }
         }
@@ -215,17 +241,18 @@ fn main() {
                 let xdc_ = xdc.clone();
                 let tx_ = tx.clone();
                 let image_cache_ = image_cache.clone();
+                let adhoc_sign_ = adhoc_sign.clone();
                 wait_count += 1;
                 pool.execute(move || {
                     let ii = &xdc_.image_info[i];
                     extract_one(&xdc_, ii, &output_path,
-                                (*image_cache_).as_ref(), minimal_processing);
+                                (*image_cache_).as_ref(), minimal_processing, &adhoc_sign_);
                     tx_.send(()).unwrap();
                 });
             } else {
                 println!("-> {}", ii.path);
                 extract_one(&xdc, ii, &output_path,
-                            (*image_cache).as_ref(), minimal_processing);
+                            (*image_cache).as_ref(), minimal_processing, &adhoc_sign);
             }
         }
         if let Some((_, _, ref rx)) = stuff {